@@ -424,7 +424,13 @@ impl<I: FixedWidthUnsignedInteger, const N: usize> Num<I, N> {
 
 impl<const N: usize> Num<i32, N> {
     #[must_use]
-    /// Returns the square root of a number, it is calcuated a digit at a time.
+    /// Returns the square root of a number, it is calcuated a digit at a time. The result is
+    /// rounded down to the nearest number that can be represented.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number is negative, see [`checked_sqrt`](Num::checked_sqrt) for a version
+    /// which doesn't.
     /// ```
     /// # use agb_fixnum::*;
     /// let n: Num<i32, 8> = num!(16.);
@@ -433,27 +439,63 @@ impl<const N: usize> Num<i32, N> {
     /// assert_eq!(n.sqrt(), num!(1.5));
     /// ```
     pub fn sqrt(self) -> Self {
-        assert_eq!(N % 2, 0, "N must be even to be able to square root");
-        assert!(self.0 >= 0, "sqrt is only valid for positive numbers");
-        let mut d = 1 << 30;
-        let mut x = self.0;
-        let mut c = 0;
+        self.checked_sqrt()
+            .expect("sqrt is only valid for positive numbers")
+    }
 
-        while d > self.0 {
-            d >>= 2;
+    #[must_use]
+    /// Returns the square root of a number, or `None` if the number is negative. The result is
+    /// rounded down to the nearest number that can be represented.
+    /// ```
+    /// # use agb_fixnum::*;
+    /// let n: Num<i32, 8> = num!(6.25);
+    /// assert_eq!(n.checked_sqrt(), Some(num!(2.5)));
+    /// let n: Num<i32, 8> = num!(-1.);
+    /// assert_eq!(n.checked_sqrt(), None);
+    /// ```
+    pub fn checked_sqrt(self) -> Option<Self> {
+        if self.0 < 0 {
+            return None;
         }
 
-        while d != 0 {
-            if x >= c + d {
-                x -= c + d;
-                c = (c >> 1) + d;
-            } else {
-                c >>= 1;
-            }
-            d >>= 2;
+        // sqrt(raw / 2^N) * 2^N = sqrt(raw * 2^N), which always fits back in an i32
+        Some(Self(isqrt_u64((self.0 as u64) << N) as i32))
+    }
+}
+
+/// Returns the square root of an integer, rounded down.
+/// ```
+/// # use agb_fixnum::*;
+/// assert_eq!(isqrt(49), 7);
+/// assert_eq!(isqrt(48), 6);
+/// assert_eq!(isqrt(u32::MAX), 65535);
+/// ```
+#[must_use]
+pub fn isqrt(n: u32) -> u32 {
+    isqrt_u64(u64::from(n)) as u32
+}
+
+// Works out the square root a binary digit at a time
+fn isqrt_u64(n: u64) -> u64 {
+    let mut remainder = n;
+    let mut root = 0;
+    let mut bit = 1 << 62;
+
+    while bit > n {
+        bit >>= 2;
+    }
+
+    while bit != 0 {
+        if remainder >= root + bit {
+            remainder -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
         }
-        Self(c << (N / 2))
+        bit >>= 2;
     }
+
+    root
 }
 
 impl<I: FixedWidthSignedInteger, const N: usize> Num<I, N> {
@@ -470,8 +512,60 @@ impl<I: FixedWidthSignedInteger, const N: usize> Num<I, N> {
         Num(self.0.fixed_abs())
     }
 
-    /// Calculates the cosine of a fixed point number with the domain of [0, 1].
-    /// Uses a [fifth order polynomial](https://github.com/tarcieri/micromath/blob/24584465b48ff4e87cffb709c7848664db896b4f/src/float/cos.rs#L226).
+    #[must_use]
+    /// Linearly interpolates between `self` at a `t` of 0 and `other` at a `t` of 1. A `t`
+    /// outside of 0 to 1 isn't clamped, so it extrapolates past either end. Use
+    /// [`clamp`](Ord::clamp) on `t` first if you don't want that.
+    /// ```
+    /// # use agb_fixnum::*;
+    /// let a: Num<i32, 8> = num!(2.);
+    /// let b: Num<i32, 8> = num!(-4.);
+    /// assert_eq!(a.lerp(b, num!(0.25)), num!(0.5));
+    /// assert_eq!(a.lerp(b, num!(1.)), b);
+    /// assert_eq!(a.lerp(b, num!(1.5)), num!(-7.));
+    /// ```
+    pub fn lerp(self, other: Self, t: Self) -> Self {
+        self + (other - self) * t
+    }
+
+    #[must_use]
+    /// Moves towards `target` by at most `max_delta`, never going past it. Once `target` is
+    /// within `max_delta` this returns exactly `target`, so repeatedly moving towards something
+    /// always ends up there. `max_delta` shouldn't be negative.
+    /// ```
+    /// # use agb_fixnum::*;
+    /// let health: Num<i32, 8> = num!(10.);
+    /// let health = health.move_towards(num!(7.), num!(2.5));
+    /// assert_eq!(health, num!(7.5));
+    /// assert_eq!(health.move_towards(num!(7.), num!(2.5)), num!(7.));
+    /// ```
+    pub fn move_towards(self, target: Self, max_delta: Self) -> Self {
+        if target > self + max_delta {
+            self + max_delta
+        } else if target < self - max_delta {
+            self - max_delta
+        } else {
+            target
+        }
+    }
+}
+
+// sin(i / 256 revolutions) for the first quarter of a revolution, where 1 is 1 << 15
+static SIN_QUARTER_WAVE: [u16; 65] = [
+    0, 804, 1608, 2411, 3212, 4011, 4808, 5602, 6393, 7180, 7962, 8740, 9512, 10279, 11039, 11793,
+    12540, 13279, 14010, 14733, 15447, 16151, 16846, 17531, 18205, 18868, 19520, 20160, 20788,
+    21403, 22006, 22595, 23170, 23732, 24279, 24812, 25330, 25833, 26320, 26791, 27246, 27684,
+    28106, 28511, 28899, 29269, 29622, 29957, 30274, 30572, 30853, 31114, 31357, 31581, 31786,
+    31972, 32138, 32286, 32413, 32522, 32610, 32679, 32729, 32758, 32768,
+];
+
+impl<const N: usize> Num<i32, N> {
+    /// Calculates the cosine of an angle in revolutions, so 0 to 1 is a whole turn from 0 to 2π
+    /// radians. Angles outside of that range wrap around. This uses a table of a quarter of a
+    /// sine wave with linear interpolation between its entries, and is within one
+    /// least significant bit of the true value when `N` is 8.
+    ///
+    /// With y pointing down the screen, increasing the angle turns clockwise from the x axis.
     /// ```
     /// # use agb_fixnum::*;
     /// let n: Num<i32, 8> = num!(0.);   // 0 radians
@@ -487,21 +581,11 @@ impl<I: FixedWidthSignedInteger, const N: usize> Num<I, N> {
     /// ```
     #[must_use]
     pub fn cos(self) -> Self {
-        let one: Self = I::one().into();
-        let mut x = self;
-        let four: I = 4.into();
-        let two: I = 2.into();
-        let sixteen: I = 16.into();
-        let nine: I = 9.into();
-        let forty: I = 40.into();
-
-        x -= one / four + (x + one / four).floor();
-        x *= (x.abs() - one / two) * sixteen;
-        x += x * (x.abs() - one) * (nine / forty);
-        x
+        Self::sin_of_phase(self.phase().wrapping_add(1 << 14))
     }
 
-    /// Calculates the sine of a number with domain of [0, 1].
+    /// Calculates the sine of an angle in revolutions, see [`cos`](Num::cos) for how the angle
+    /// is measured and how accurate this is.
     /// ```
     /// # use agb_fixnum::*;
     /// let n: Num<i32, 8> = num!(0.);   // 0 radians
@@ -517,12 +601,76 @@ impl<I: FixedWidthSignedInteger, const N: usize> Num<I, N> {
     /// ```
     #[must_use]
     pub fn sin(self) -> Self {
-        let one: Self = I::one().into();
-        let four: I = 4.into();
-        (self - one / four).cos()
+        Self::sin_of_phase(self.phase())
+    }
+
+    // An angle from a fraction of a revolution with 16 bits of precision, rounded to the nearest
+    // angle this can represent and wrapped to a single revolution
+    fn from_phase(phase: u32) -> Self {
+        if N >= 16 {
+            Self((phase << (N - 16)) as i32)
+        } else {
+            let rounded = (phase + (1 << (15 - N))) >> (16 - N);
+            Self((rounded & ((1 << N) - 1)) as i32)
+        }
+    }
+
+    // The angle as a fraction of a revolution with 16 bits of precision, wrapped to a single
+    // revolution
+    fn phase(self) -> u16 {
+        let phase = if N >= 16 {
+            self.0 >> (N - 16)
+        } else {
+            self.0.wrapping_shl((16 - N) as u32)
+        };
+
+        phase as u16
+    }
+
+    fn sin_of_phase(phase: u16) -> Self {
+        const QUARTER: u16 = 1 << 14;
+
+        let quadrant = phase / QUARTER;
+        let in_quadrant = phase % QUARTER;
+
+        // The second and fourth quarters are the first and third mirrored, and the second half
+        // is the first half negated. The magnitude is rounded before negating so that
+        // sin(-x) == -sin(x) exactly.
+        let magnitude = if quadrant & 1 == 0 {
+            quarter_sine(in_quadrant)
+        } else {
+            quarter_sine(QUARTER - in_quadrant)
+        };
+
+        let magnitude = if N >= 15 {
+            magnitude << (N - 15)
+        } else {
+            (magnitude + (1 << (14 - N))) >> (15 - N)
+        };
+
+        if quadrant >= 2 {
+            Self(-magnitude)
+        } else {
+            Self(magnitude)
+        }
     }
 }
 
+// sin of an angle from 0 to a quarter of a revolution, in 1 / (1 << 16) revolutions, where the
+// result has 15 fractional bits
+fn quarter_sine(angle: u16) -> i32 {
+    let index = usize::from(angle >> 8);
+    let fraction = i32::from(angle & 0xff);
+
+    let start = i32::from(SIN_QUARTER_WAVE[index]);
+    if fraction == 0 {
+        return start;
+    }
+
+    let end = i32::from(SIN_QUARTER_WAVE[index + 1]);
+    start + (((end - start) * fraction + 128) >> 8)
+}
+
 impl<I: FixedWidthUnsignedInteger, const N: usize> Display for Num<I, N> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let mut integral = self.0 >> N;
@@ -685,16 +833,57 @@ impl<I: FixedWidthUnsignedInteger, const N: usize> Vector2D<Num<I, N>> {
     }
 }
 
+impl<I: FixedWidthSignedInteger, const N: usize> Vector2D<Num<I, N>> {
+    #[must_use]
+    /// Linearly interpolates each component between `self` at a `t` of 0 and `other` at a `t`
+    /// of 1, see [Num::lerp]. A `t` outside of 0 to 1 extrapolates past either end.
+    /// ```
+    /// # use agb_fixnum::*;
+    /// let a: Vector2D<Num<i32, 8>> = (num!(0.), num!(10.)).into();
+    /// let b: Vector2D<Num<i32, 8>> = (num!(8.), num!(-2.)).into();
+    /// assert_eq!(a.lerp(b, num!(0.25)), (num!(2.), num!(7.)).into());
+    /// ```
+    pub fn lerp(self, other: Self, t: Num<I, N>) -> Self {
+        Vector2D {
+            x: self.x.lerp(other.x, t),
+            y: self.y.lerp(other.y, t),
+        }
+    }
+}
+
 impl<const N: usize> Vector2D<Num<i32, N>> {
     #[must_use]
-    /// Calculates the magnitude squared, ie (x*x + y*y)
+    /// Calculates the magnitude squared, ie (x*x + y*y). This is worked out with 64 bits so the
+    /// squares themselves can't overflow, but the result still has to fit in a `Num<i32, N>`,
+    /// so the magnitude must be less than `2^((31 - N) / 2)`. That is a magnitude of up to 2896
+    /// with 8 fractional bits, or 181 with 16.
+    ///
+    /// # Panics
+    ///
+    /// Panics in debug builds if the result doesn't fit. Use [magnitude](Vector2D::magnitude)
+    /// for longer vectors.
     /// ```
     /// # use agb_fixnum::*;
     /// let v1: Vector2D<Num<i32, 8>> = (num!(3.), num!(4.)).into();
     /// assert_eq!(v1.magnitude_squared(), 25.into());
     /// ```
     pub fn magnitude_squared(self) -> Num<i32, N> {
-        self.x * self.x + self.y * self.y
+        let magnitude_squared = self.raw_magnitude_squared() >> N;
+        debug_assert!(
+            magnitude_squared <= i32::MAX as u64,
+            "magnitude squared of {:?} overflows",
+            self
+        );
+
+        Num::from_raw(magnitude_squared as i32)
+    }
+
+    // The magnitude squared with 2N fractional bits, which can't overflow
+    fn raw_magnitude_squared(self) -> u64 {
+        let x = u64::from(self.x.to_raw().unsigned_abs());
+        let y = u64::from(self.y.to_raw().unsigned_abs());
+
+        x * x + y * y
     }
 
     #[must_use]
@@ -709,20 +898,27 @@ impl<const N: usize> Vector2D<Num<i32, N>> {
     }
 
     #[must_use]
-    /// Calculates the magnitude by square root
+    /// Calculates the magnitude by square root. The square root is taken of the magnitude
+    /// squared with all of its fractional bits, so the result is the true magnitude rounded down
+    /// to the nearest number that can be represented. This doesn't overflow as long as the
+    /// magnitude itself fits in a `Num<i32, N>`, which any vector with components of up to half
+    /// the maximum value does.
     /// ```
     /// # use agb_fixnum::*;
     /// let v1: Vector2D<Num<i32, 8>> = (num!(3.), num!(4.)).into();
     /// assert_eq!(v1.magnitude(), 5.into());
+    /// let v2: Vector2D<Num<i32, 8>> = (num!(30000.), num!(40000.)).into();
+    /// assert_eq!(v2.magnitude(), 50000.into());
     /// ```
     pub fn magnitude(self) -> Num<i32, N> {
-        self.magnitude_squared().sqrt()
+        Num::from_raw(isqrt_u64(self.raw_magnitude_squared()) as i32)
     }
 
     /// Calculates the magnitude of a vector using the [alpha max plus beta min
     /// algorithm](https://en.wikipedia.org/wiki/Alpha_max_plus_beta_min_algorithm)
-    /// this has a maximum error of less than 4% of the true magnitude, probably
-    /// depending on the size of your fixed point approximation
+    /// without a square root. The result is within 4% of the true magnitude, plus
+    /// one least significant bit of rounding. Like [magnitude](Vector2D::magnitude)
+    /// this doesn't overflow for components of up to half the maximum value.
     /// ```
     /// # use agb_fixnum::*;
     /// let v1: Vector2D<Num<i32, 8>> = (num!(3.), num!(4.)).into();
@@ -730,22 +926,88 @@ impl<const N: usize> Vector2D<Num<i32, N>> {
     /// ```
     #[must_use]
     pub fn fast_magnitude(self) -> Num<i32, N> {
-        let max = core::cmp::max(self.x.abs(), self.y.abs());
-        let min = core::cmp::min(self.x.abs(), self.y.abs());
+        // 0.960433870103 and 0.397824734759 with 30 fractional bits, so they don't lose
+        // precision when N is small
+        const ALPHA: u64 = 1_031_258_016;
+        const BETA: u64 = 427_161_056;
+
+        let x = u64::from(self.x.to_raw().unsigned_abs());
+        let y = u64::from(self.y.to_raw().unsigned_abs());
+        let (max, min) = if x > y { (x, y) } else { (y, x) };
 
-        max * num!(0.960433870103) + min * num!(0.397824734759)
+        Num::from_raw(((max * ALPHA + min * BETA + (1 << 29)) >> 30) as i32)
     }
 
     #[must_use]
     /// Normalises the vector to magnitude of one by performing a square root,
-    /// due to fixed point imprecision this magnitude may not be exactly one
+    /// due to fixed point imprecision this magnitude may not be exactly one. Each
+    /// component is within one least significant bit of the true value. The zero
+    /// vector stays as the zero vector, see [try_normalise](Vector2D::try_normalise)
+    /// to handle it separately.
     /// ```
     /// # use agb_fixnum::*;
     /// let v1: Vector2D<Num<i32, 8>> = (num!(4.), num!(4.)).into();
-    /// assert_eq!(v1.normalise().magnitude(), 1.into());
+    /// assert!((v1.normalise().magnitude() - 1).abs() < num!(0.02));
+    /// let v2: Vector2D<Num<i32, 8>> = (num!(0.), num!(-3.)).into();
+    /// assert_eq!(v2.normalise(), (num!(0.), num!(-1.)).into());
     /// ```
     pub fn normalise(self) -> Self {
-        self / self.magnitude()
+        self.normalised_times(1.into())
+    }
+
+    #[must_use]
+    /// Normalises the vector to magnitude of one like [normalise](Vector2D::normalise),
+    /// or returns `None` for the zero vector which has no direction.
+    /// ```
+    /// # use agb_fixnum::*;
+    /// let v1: Vector2D<Num<i32, 8>> = (num!(-2.), num!(0.)).into();
+    /// assert_eq!(v1.try_normalise(), Some((num!(-1.), num!(0.)).into()));
+    /// let v2: Vector2D<Num<i32, 8>> = (num!(0.), num!(0.)).into();
+    /// assert_eq!(v2.try_normalise(), None);
+    /// ```
+    pub fn try_normalise(self) -> Option<Self> {
+        if self.x == 0.into() && self.y == 0.into() {
+            None
+        } else {
+            Some(self.normalise())
+        }
+    }
+
+    #[must_use]
+    /// A vector in the same direction with a magnitude of `scalar`, so the same as
+    /// `self.normalise() * scalar` but without losing precision in between. With 8
+    /// fractional bits a normalised vector is only accurate to about 1/256, which
+    /// is out by a whole pixel per frame at a speed of 256. Each component of this
+    /// is within one least significant bit of the true value instead. The zero
+    /// vector stays as the zero vector.
+    /// ```
+    /// # use agb_fixnum::*;
+    /// let to_player: Vector2D<Num<i32, 8>> = (num!(30.), num!(-40.)).into();
+    /// assert_eq!(to_player.normalised_times(num!(2.5)), (num!(1.5), num!(-2.)).into());
+    /// ```
+    pub fn normalised_times(self, scalar: Num<i32, N>) -> Self {
+        let magnitude_squared = self.raw_magnitude_squared();
+        if magnitude_squared == 0 {
+            return self;
+        }
+
+        // Scale the magnitude up as far as it will go, so short vectors keep their precision.
+        // The components scaled up by the same amount then fit in 32 bits.
+        let scale = magnitude_squared.leading_zeros() / 2;
+        let magnitude = isqrt_u64(magnitude_squared << (2 * scale)) as i64;
+
+        let component = |c: Num<i32, N>| {
+            let numerator = (i64::from(c.to_raw()) << scale) * i64::from(scalar.to_raw());
+            let rounding = if numerator < 0 {
+                -magnitude / 2
+            } else {
+                magnitude / 2
+            };
+
+            Num::from_raw(((numerator + rounding) / magnitude) as i32)
+        };
+
+        Vector2D::new(component(self.x), component(self.y))
     }
 
     #[must_use]
@@ -753,11 +1015,36 @@ impl<const N: usize> Vector2D<Num<i32, N>> {
     /// ```
     /// # use agb_fixnum::*;
     /// let v1: Vector2D<Num<i32, 8>> = (num!(4.), num!(4.)).into();
-    /// assert_eq!(v1.fast_normalise().magnitude(), 1.into());
+    /// assert!((v1.fast_normalise().magnitude() - 1).abs() < num!(0.05));
     /// ```
     pub fn fast_normalise(self) -> Self {
         self / self.fast_magnitude()
     }
+
+    #[must_use]
+    /// Moves towards `target` in a straight line by a distance of at most `max_delta`, never
+    /// going past it. The distance is limited by the magnitude of the move rather than on
+    /// each axis, so moving diagonally is no faster than moving along an axis. Once `target`
+    /// is within `max_delta` this returns exactly `target`, so repeatedly moving towards
+    /// something always ends up there. `max_delta` shouldn't be negative.
+    /// ```
+    /// # use agb_fixnum::*;
+    /// let camera: Vector2D<Num<i32, 8>> = (num!(0.), num!(0.)).into();
+    /// let player: Vector2D<Num<i32, 8>> = (num!(6.), num!(8.)).into();
+    /// let camera = camera.move_towards(player, num!(5.));
+    /// assert_eq!(camera, (num!(3.), num!(4.)).into());
+    /// assert_eq!(camera.move_towards(player, num!(5.)), player);
+    /// ```
+    pub fn move_towards(self, target: Self, max_delta: Num<i32, N>) -> Self {
+        let difference = target - self;
+
+        let max_delta_raw = u64::from(max_delta.to_raw().unsigned_abs());
+        if difference.raw_magnitude_squared() <= max_delta_raw * max_delta_raw {
+            target
+        } else {
+            self + difference.normalised_times(max_delta)
+        }
+    }
 }
 
 impl<T: Number, P: Number + Into<T>> From<(P, P)> for Vector2D<T> {
@@ -778,20 +1065,80 @@ impl<T: Number> Vector2D<T> {
     }
 }
 
-impl<I: FixedWidthSignedInteger, const N: usize> Vector2D<Num<I, N>> {
-    /// Creates a unit vector from an angle, noting that the domain of the angle
-    /// is [0, 1], see [Num::cos] and [Num::sin].
+impl<const N: usize> Vector2D<Num<i32, N>> {
+    /// Creates a unit vector from an angle in revolutions, see [Num::cos] and [Num::sin].
     /// ```
     /// # use agb_fixnum::*;
     /// let v: Vector2D<Num<i32, 8>> = Vector2D::new_from_angle(num!(0.0));
     /// assert_eq!(v, (num!(1.0), num!(0.0)).into());
     /// ```
-    pub fn new_from_angle(angle: Num<I, N>) -> Self {
+    pub fn new_from_angle(angle: Num<i32, N>) -> Self {
         Vector2D {
             x: angle.cos(),
             y: angle.sin(),
         }
     }
+
+    #[must_use]
+    /// The angle of the vector from the x axis in revolutions, from 0 up to but not including 1,
+    /// so this is the inverse of [`new_from_angle`](Vector2D::new_from_angle) and works like
+    /// `atan2(y, x)`. The zero vector has an angle of 0.
+    ///
+    /// This uses a table of arctangents with linear interpolation between its entries, and is
+    /// within one least significant bit of the true angle when `N` is 8.
+    /// ```
+    /// # use agb_fixnum::*;
+    /// let v: Vector2D<Num<i32, 8>> = (num!(0.), num!(3.)).into();
+    /// assert_eq!(v.angle(), num!(0.25));
+    /// let v: Vector2D<Num<i32, 8>> = (num!(-2.), num!(-2.)).into();
+    /// assert_eq!(v.angle(), num!(0.625));
+    /// ```
+    pub fn angle(self) -> Num<i32, N> {
+        const QUARTER: u32 = 1 << 14;
+        const HALF: u32 = 1 << 15;
+
+        let (x, y) = (self.x.to_raw(), self.y.to_raw());
+        if x == 0 && y == 0 {
+            return 0.into();
+        }
+
+        // Reduce to the first octant, where the tangent is between 0 and 1
+        let (ax, ay) = (u64::from(x.unsigned_abs()), u64::from(y.unsigned_abs()));
+        let angle = if ax >= ay {
+            octant_arctan(((ay << 16) / ax) as u32)
+        } else {
+            QUARTER - octant_arctan(((ax << 16) / ay) as u32)
+        };
+
+        let angle = if x < 0 { HALF - angle } else { angle };
+        let angle = if y < 0 {
+            (2 * HALF - angle) % (2 * HALF)
+        } else {
+            angle
+        };
+
+        Num::from_phase(angle)
+    }
+}
+
+// atan(i / 32) for the first eighth of a revolution, in 1 / (1 << 16) revolutions
+static ARCTAN_OCTANT: [u16; 33] = [
+    0, 326, 651, 975, 1297, 1617, 1933, 2246, 2555, 2860, 3159, 3453, 3742, 4025, 4302, 4572, 4836,
+    5094, 5344, 5589, 5826, 6058, 6282, 6500, 6712, 6917, 7117, 7310, 7498, 7679, 7856, 8026, 8192,
+];
+
+// atan of a tangent from 0 to 1 with 16 fractional bits, in 1 / (1 << 16) revolutions
+fn octant_arctan(tangent: u32) -> u32 {
+    let index = (tangent >> 11) as usize;
+    let fraction = tangent & 0x7ff;
+
+    let start = u32::from(ARCTAN_OCTANT[index]);
+    if fraction == 0 {
+        return start;
+    }
+
+    let end = u32::from(ARCTAN_OCTANT[index + 1]);
+    start + (((end - start) * fraction + 0x400) >> 11)
 }
 
 impl<I: FixedWidthUnsignedInteger, const N: usize> From<Vector2D<I>> for Vector2D<Num<I, N>> {
@@ -846,7 +1193,8 @@ impl<T: Number> Rect<T> {
             && point.y <= self.position.y + self.size.y
     }
 
-    /// Returns true if the other rectangle touches or overlaps the first.
+    /// Returns true if the other rectangle touches or overlaps the first. This is the same as
+    /// [overlaps](Rect::overlaps), so rectangles which only share an edge don't count.
     /// ```
     /// # use agb_fixnum::*;
     /// let r = Rect::new(Vector2D::new(1,1), Vector2D::new(3,3));
@@ -860,15 +1208,13 @@ impl<T: Number> Rect<T> {
     /// assert!(!r.touches(r2));
     /// ```
     pub fn touches(&self, other: Rect<T>) -> bool {
-        self.position.x < other.position.x + other.size.x
-            && self.position.x + self.size.x > other.position.x
-            && self.position.y < other.position.y + other.size.y
-            && self.position.y + self.size.y > other.position.y
+        self.overlaps(&other)
     }
 
     #[must_use]
     /// Returns the rectangle that is the region that the two rectangles have in
-    /// common, or [None] if they don't overlap
+    /// common, or [None] if they don't overlap. This is the same as
+    /// [intersection](Rect::intersection).
     /// ```
     /// # use agb_fixnum::*;
     /// let r = Rect::new(Vector2D::new(1,1), Vector2D::new(3,3));
@@ -885,10 +1231,173 @@ impl<T: Number> Rect<T> {
     /// assert_eq!(r.overlapping_rect(r2), None);
     /// ```
     pub fn overlapping_rect(&self, other: Rect<T>) -> Option<Self> {
-        if !self.touches(other.clone()) {
-            return None;
+        self.intersection(&other)
+    }
+
+    /// Returns true if the two rectangles have some area in common.
+    ///
+    /// For overlapping, a rectangle covers from its position up to but not including its
+    /// position plus its size, the same as the points given by [iter](Rect::iter). So
+    /// rectangles which only share an edge or a corner don't overlap, and a rectangle with
+    /// a width or height of zero doesn't overlap anything. Note that
+    /// [contains_point](Rect::contains_point) does include the far edges.
+    /// ```
+    /// # use agb_fixnum::*;
+    /// let r = Rect::new(Vector2D::new(1,1), Vector2D::new(3,3));
+    ///
+    /// assert!(r.overlaps(&Rect::new(Vector2D::new(3,3), Vector2D::new(5,5))));
+    /// assert!(r.overlaps(&Rect::new(Vector2D::new(2,-5), Vector2D::new(1,20))));
+    ///
+    /// // sharing the edge at x = 4
+    /// assert!(!r.overlaps(&Rect::new(Vector2D::new(4,1), Vector2D::new(3,3))));
+    /// // sharing the corner at (1, 1)
+    /// assert!(!r.overlaps(&Rect::new(Vector2D::new(-1,-1), Vector2D::new(2,2))));
+    /// ```
+    pub fn overlaps(&self, other: &Rect<T>) -> bool {
+        let (top_left, bottom_right) = self.overlapping_corners(other);
+
+        top_left.x < bottom_right.x && top_left.y < bottom_right.y
+    }
+
+    #[must_use]
+    /// Returns the region that the two rectangles have in common, or [None] if they don't
+    /// [overlap](Rect::overlaps). Rectangles which only share an edge have no intersection.
+    /// ```
+    /// # use agb_fixnum::*;
+    /// let r = Rect::new(Vector2D::new(-4,-4), Vector2D::new(6,6));
+    /// let r2 = Rect::new(Vector2D::new(0,-10), Vector2D::new(10,8));
+    ///
+    /// assert_eq!(r.intersection(&r2), Some(Rect::new(Vector2D::new(0,-4), Vector2D::new(2,2))));
+    ///
+    /// let r3 = Rect::new(Vector2D::new(2,-4), Vector2D::new(6,6));
+    /// assert_eq!(r.intersection(&r3), None);
+    /// ```
+    pub fn intersection(&self, other: &Rect<T>) -> Option<Self> {
+        let (top_left, bottom_right) = self.overlapping_corners(other);
+
+        if top_left.x < bottom_right.x && top_left.y < bottom_right.y {
+            Some(Rect::new(top_left, bottom_right - top_left))
+        } else {
+            None
+        }
+    }
+
+    #[must_use]
+    /// Returns the part of this rectangle which is inside `bounds`, or [None] if none of it
+    /// is. This is the same as the [intersection](Rect::intersection) of the two, and is
+    /// useful for restricting drawing or loading to the screen or a level.
+    /// ```
+    /// # use agb_fixnum::*;
+    /// let screen = Rect::new(Vector2D::new(0,0), Vector2D::new(240,160));
+    ///
+    /// let sprite = Rect::new(Vector2D::new(-8,150), Vector2D::new(16,16));
+    /// assert_eq!(sprite.clip_to(&screen), Some(Rect::new(Vector2D::new(0,150), Vector2D::new(8,10))));
+    ///
+    /// let off_screen = Rect::new(Vector2D::new(240,0), Vector2D::new(16,16));
+    /// assert_eq!(off_screen.clip_to(&screen), None);
+    /// ```
+    pub fn clip_to(&self, bounds: &Rect<T>) -> Option<Self> {
+        self.intersection(bounds)
+    }
+
+    /// Returns true if the rectangle has a width or height of zero or less, so covers no area.
+    /// ```
+    /// # use agb_fixnum::*;
+    /// assert!(Rect::new(Vector2D::new(3,-2), Vector2D::new(0,5)).is_empty());
+    /// assert!(!Rect::new(Vector2D::new(3,-2), Vector2D::new(1,5)).is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.position.x + self.size.x <= self.position.x
+            || self.position.y + self.size.y <= self.position.y
+    }
+
+    #[must_use]
+    /// Returns the smallest rectangle which contains both rectangles, for example to build up
+    /// the region that changed over several updates. Empty rectangles don't add anything to
+    /// the union, so starting from an empty rectangle and taking the union with each region in
+    /// turn gives the smallest rectangle containing all of them. If both are empty then this
+    /// returns the first.
+    /// ```
+    /// # use agb_fixnum::*;
+    /// let r = Rect::new(Vector2D::new(-4,-4), Vector2D::new(2,2));
+    /// let r2 = Rect::new(Vector2D::new(3,1), Vector2D::new(1,5));
+    /// assert_eq!(r.union(&r2), Rect::new(Vector2D::new(-4,-4), Vector2D::new(8,10)));
+    ///
+    /// let empty = Rect::new(Vector2D::new(100,100), Vector2D::new(0,0));
+    /// assert_eq!(empty.union(&r), r);
+    /// ```
+    pub fn union(&self, other: &Rect<T>) -> Self {
+        if other.is_empty() {
+            return self.clone();
+        }
+        if self.is_empty() {
+            return other.clone();
+        }
+
+        let top_left = Vector2D::new(
+            core::cmp::min(self.position.x, other.position.x),
+            core::cmp::min(self.position.y, other.position.y),
+        );
+        let bottom_right = Vector2D::new(
+            core::cmp::max(
+                self.position.x + self.size.x,
+                other.position.x + other.size.x,
+            ),
+            core::cmp::max(
+                self.position.y + self.size.y,
+                other.position.y + other.size.y,
+            ),
+        );
+
+        Rect::new(top_left, bottom_right - top_left)
+    }
+
+    /// Returns true if every part of `other` is inside this rectangle, which includes
+    /// rectangles which go right up to its edges. An empty rectangle covers nothing, so it
+    /// is contained by every rectangle wherever it is, and an empty rectangle contains only
+    /// other empty rectangles.
+    /// ```
+    /// # use agb_fixnum::*;
+    /// let r = Rect::new(Vector2D::new(-10,-10), Vector2D::new(20,20));
+    ///
+    /// assert!(r.contains_rect(&Rect::new(Vector2D::new(-10,0), Vector2D::new(20,10))));
+    /// assert!(!r.contains_rect(&Rect::new(Vector2D::new(-11,0), Vector2D::new(5,5))));
+    /// assert!(r.contains_rect(&Rect::new(Vector2D::new(50,50), Vector2D::new(0,0))));
+    /// ```
+    pub fn contains_rect(&self, other: &Rect<T>) -> bool {
+        if other.is_empty() {
+            return true;
         }
 
+        other.position.x >= self.position.x
+            && other.position.y >= self.position.y
+            && other.position.x + other.size.x <= self.position.x + self.size.x
+            && other.position.y + other.size.y <= self.position.y + self.size.y
+    }
+
+    #[must_use]
+    /// Returns the rectangle grown by `margin` on every side, for example to make a camera
+    /// dead zone or a region to load around the screen. A negative margin shrinks the
+    /// rectangle, and shrinking it by at least half of its width or height makes it
+    /// [empty](Rect::is_empty). For rectangles of unsigned integers the position has to be
+    /// at least `margin`.
+    /// ```
+    /// # use agb_fixnum::*;
+    /// let r = Rect::new(Vector2D::new(-2,3), Vector2D::new(4,4));
+    /// assert_eq!(r.expand(3), Rect::new(Vector2D::new(-5,0), Vector2D::new(10,10)));
+    /// assert_eq!(r.expand(-1), Rect::new(Vector2D::new(-1,4), Vector2D::new(2,2)));
+    /// assert!(r.expand(-2).is_empty());
+    /// ```
+    pub fn expand(&self, margin: T) -> Self {
+        Rect::new(
+            self.position - Vector2D::new(margin, margin),
+            self.size + Vector2D::new(margin + margin, margin + margin),
+        )
+    }
+
+    // The top left and bottom right of the region covered by both rectangles, which is empty
+    // if either coordinate of the top left isn't less than the bottom right
+    fn overlapping_corners(&self, other: &Rect<T>) -> (Vector2D<T>, Vector2D<T>) {
         fn max<E: Number>(x: E, y: E) -> E {
             if x > y {
                 x
@@ -904,12 +1413,11 @@ impl<T: Number> Rect<T> {
             }
         }
 
-        let top_left: Vector2D<T> = (
+        let top_left = Vector2D::new(
             max(self.position.x, other.position.x),
             max(self.position.y, other.position.y),
-        )
-            .into();
-        let bottom_right: Vector2D<T> = (
+        );
+        let bottom_right = Vector2D::new(
             min(
                 self.position.x + self.size.x,
                 other.position.x + other.size.x,
@@ -918,10 +1426,9 @@ impl<T: Number> Rect<T> {
                 self.position.y + self.size.y,
                 other.position.y + other.size.y,
             ),
-        )
-            .into();
+        );
 
-        Some(Rect::new(top_left, bottom_right - top_left))
+        (top_left, bottom_right)
     }
 }
 
@@ -1047,6 +1554,73 @@ mod tests {
         }
     }
 
+    #[test]
+    fn sqrt_rounds_down_either_side_of_perfect_squares() {
+        fn check<const N: usize>(n: Num<i32, N>) {
+            let root = i64::from(n.sqrt().to_raw());
+            let scaled = i64::from(n.to_raw()) << N;
+
+            assert!(
+                root * root <= scaled && (root + 1) * (root + 1) > scaled,
+                "sqrt({}) with N = {} gave {}",
+                n,
+                N,
+                n.sqrt()
+            );
+        }
+
+        for x in 1..181 {
+            let square = Num::<i32, 8>::new(x * x).to_raw();
+            for raw in [square - 1, square, square + 1] {
+                check(Num::<i32, 8>::from_raw(raw));
+            }
+        }
+
+        for raw in 0..4096 {
+            check(Num::<i32, 7>::from_raw(raw));
+            check(Num::<i32, 12>::from_raw(raw));
+        }
+
+        check(Num::<i32, 8>::from_raw(i32::MAX));
+        check(Num::<i32, 16>::from_raw(i32::MAX));
+        check(Num::<i32, 31>::from_raw(i32::MAX));
+    }
+
+    #[test]
+    fn sqrt_of_the_maximum_value() {
+        let max = Num::<i32, 8>::from_raw(i32::MAX);
+        // sqrt(2^23 - 1 / 256) is a little under 2896.31
+        assert_eq!(max.sqrt().floor(), 2896);
+        assert_eq!(max.sqrt().to_raw(), 741455);
+    }
+
+    #[test]
+    fn checked_sqrt_of_negative_numbers_is_none() {
+        assert_eq!(Num::<i32, 8>::from_raw(-1).checked_sqrt(), None);
+        assert_eq!(Num::<i32, 8>::new(-4).checked_sqrt(), None);
+        assert_eq!(Num::<i32, 8>::from_raw(i32::MIN).checked_sqrt(), None);
+        assert_eq!(Num::<i32, 8>::new(0).checked_sqrt(), Some(0.into()));
+    }
+
+    #[test]
+    #[should_panic]
+    fn sqrt_of_a_negative_number_panics() {
+        let _ = Num::<i32, 8>::new(-1).sqrt();
+    }
+
+    #[test]
+    fn isqrt_of_integers() {
+        for x in 1..=65535 {
+            let square = x * x;
+            assert_eq!(isqrt(square), x, "isqrt({})", square);
+            assert_eq!(isqrt(square - 1), x - 1, "isqrt({})", square - 1);
+            assert_eq!(isqrt(square + 1), x, "isqrt({})", square + 1);
+        }
+
+        assert_eq!(isqrt(0), 0);
+        assert_eq!(isqrt(u32::MAX), 65535);
+    }
+
     #[test]
     fn test_macro_conversion() {
         fn test_positive<A: FixedWidthUnsignedInteger, const B: usize>() {
@@ -1229,6 +1803,237 @@ mod tests {
         assert!((n.magnitude() - 5).abs() < num!(0.1));
     }
 
+    #[test]
+    fn magnitude_is_the_true_magnitude_rounded_down() {
+        extern crate std;
+
+        for x in (-2000..2000).step_by(37) {
+            for y in (-2000..2000).step_by(41) {
+                let v = Vector2D::new(Num::<i32, 8>::from_raw(x), Num::from_raw(y * 113));
+                let expected = f64::from(v.x.to_raw()).hypot(f64::from(v.y.to_raw()));
+
+                let error = expected - f64::from(v.magnitude().to_raw());
+                assert!(
+                    (0. ..1.).contains(&error),
+                    "magnitude of {:?} is out by {} bits",
+                    v,
+                    error
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn magnitude_works_when_the_square_would_overflow() {
+        let v: Vector2D<Num<i32, 8>> = (3000, -4000).into();
+        assert_eq!(v.magnitude(), 5000.into());
+
+        let v: Vector2D<Num<i32, 16>> = (-6000, 8000).into();
+        assert_eq!(v.magnitude(), 10000.into());
+
+        let v: Vector2D<Num<i32, 8>> =
+            (Num::from_raw(i32::MAX / 2), Num::from_raw(i32::MAX / 2)).into();
+        assert_eq!(v.magnitude().floor(), 5931641);
+    }
+
+    #[test]
+    fn magnitude_squared_uses_all_of_the_range() {
+        let v: Vector2D<Num<i32, 8>> = (2000, -2000).into();
+        assert_eq!(v.magnitude_squared(), 8_000_000.into());
+
+        let v: Vector2D<Num<i32, 8>> = (num!(0.5), num!(-1.5)).into();
+        assert_eq!(v.magnitude_squared(), num!(2.5));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic]
+    fn magnitude_squared_panics_when_it_overflows() {
+        let v: Vector2D<Num<i32, 8>> = (3000, 3000).into();
+        let _ = v.magnitude_squared();
+    }
+
+    #[test]
+    fn normalised_times_is_within_one_bit_of_the_real_value() {
+        extern crate std;
+
+        fn check<const N: usize>(v: Vector2D<Num<i32, N>>, scalar: Num<i32, N>) {
+            let (x, y) = (f64::from(v.x.to_raw()), f64::from(v.y.to_raw()));
+            let factor = f64::from(scalar.to_raw()) / x.hypot(y);
+
+            let result = v.normalised_times(scalar);
+            let x_error = f64::from(result.x.to_raw()) - x * factor;
+            let y_error = f64::from(result.y.to_raw()) - y * factor;
+
+            assert!(
+                x_error.abs() <= 1. && y_error.abs() <= 1.,
+                "{:?} normalised and multiplied by {} gave {:?}",
+                v,
+                scalar,
+                result
+            );
+        }
+
+        for x in -30..30 {
+            for y in -30..30 {
+                if x == 0 && y == 0 {
+                    continue;
+                }
+
+                // tiny, ordinary and long vectors
+                for length in [1, 77, 2000 << 8] {
+                    let v = Vector2D::new(
+                        Num::<i32, 8>::from_raw(x * length),
+                        Num::from_raw(y * length),
+                    );
+                    check(v, 1.into());
+                    check(v, num!(3.7));
+                    check(v, num!(-250.5));
+                }
+
+                let v = Vector2D::new(Num::<i32, 16>::from_raw(x * 1234), Num::from_raw(y));
+                check(v, 1.into());
+                check(v, num!(12.25));
+            }
+        }
+    }
+
+    #[test]
+    fn normalising_the_zero_vector() {
+        let zero: Vector2D<Num<i32, 8>> = (0, 0).into();
+
+        assert_eq!(zero.normalise(), zero);
+        assert_eq!(zero.normalised_times(num!(5.)), zero);
+        assert_eq!(zero.try_normalise(), None);
+
+        let v: Vector2D<Num<i32, 8>> = (Num::from_raw(1), Num::from_raw(0)).into();
+        assert_eq!(v.try_normalise(), Some((1, 0).into()));
+    }
+
+    #[test]
+    fn normalised_times_keeps_precision_that_normalise_then_multiply_loses() {
+        let v: Vector2D<Num<i32, 8>> = (1, 2).into();
+        let speed: Num<i32, 8> = 200.into();
+
+        // sqrt(5) = 2.2360679..., so the true answer is (89.4427..., 178.8854...)
+        assert_eq!(
+            v.normalised_times(speed),
+            (Num::from_raw(22897), Num::from_raw(45795)).into()
+        );
+        assert_ne!(v.normalise() * speed, v.normalised_times(speed));
+    }
+
+    #[test]
+    fn lerp_hits_both_ends_exactly_and_extrapolates() {
+        let values: [Num<i32, 8>; 5] = [num!(-100.5), num!(-1.), num!(0.), num!(0.3), num!(77.25)];
+
+        for a in values {
+            for b in values {
+                assert_eq!(a.lerp(b, 0.into()), a);
+                assert_eq!(a.lerp(b, 1.into()), b);
+                assert_eq!(a.lerp(b, 2.into()), b + (b - a));
+                assert_eq!(a.lerp(b, (-1).into()), a - (b - a));
+            }
+        }
+
+        let a: Vector2D<Num<i32, 8>> = (num!(-3.), num!(5.)).into();
+        let b: Vector2D<Num<i32, 8>> = (num!(1.), num!(-7.)).into();
+        assert_eq!(a.lerp(b, 0.into()), a);
+        assert_eq!(a.lerp(b, 1.into()), b);
+        assert_eq!(a.lerp(b, num!(0.5)), (num!(-1.), num!(-1.)).into());
+        assert_eq!(a.lerp(b, num!(-0.5)), (num!(-5.), num!(11.)).into());
+    }
+
+    #[test]
+    fn move_towards_stops_exactly_at_the_target() {
+        let deltas: [Num<i32, 8>; 4] = [Num::from_raw(1), num!(0.3), num!(1.), num!(7.7)];
+        let targets: [Num<i32, 8>; 4] = [num!(-20.1), num!(0.), num!(3.), num!(19.9)];
+
+        for max_delta in deltas {
+            for target in targets {
+                let mut current: Num<i32, 8> = num!(2.9);
+                let mut distance = (target - current).abs();
+
+                while current != target {
+                    current = current.move_towards(target, max_delta);
+
+                    let new_distance = (target - current).abs();
+                    assert!(
+                        distance - new_distance == max_delta || current == target,
+                        "moving by {} towards {} went to {}",
+                        max_delta,
+                        target,
+                        current
+                    );
+                    distance = new_distance;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn vector_move_towards_limits_the_distance_moved_and_stops_at_the_target() {
+        let start: Vector2D<Num<i32, 8>> = (num!(-5.5), num!(3.)).into();
+
+        for (x, y) in [(0, 0), (10, 0), (-7, 30), (1, 1), (-40, -40), (3, -100)] {
+            let target: Vector2D<Num<i32, 8>> = (x, y).into();
+
+            for max_delta in [Num::from_raw(3), num!(0.5), num!(2.), num!(13.3)] {
+                let mut current = start;
+                let mut steps = 0;
+
+                while current != target {
+                    let next = current.move_towards(target, max_delta);
+                    let moved = (next - current).magnitude();
+                    assert!(
+                        moved <= max_delta + Num::from_raw(1),
+                        "moved {} from {:?} towards {:?} with a limit of {}",
+                        moved,
+                        current,
+                        target,
+                        max_delta
+                    );
+                    assert!((target - next).magnitude() < (target - current).magnitude());
+
+                    current = next;
+                    steps += 1;
+                    assert!(steps < 100_000, "never got to {:?}", target);
+                }
+            }
+        }
+
+        let diagonal: Vector2D<Num<i32, 8>> = (100, 100).into();
+        let moved = Vector2D::new(0.into(), 0.into()).move_towards(diagonal, 1.into());
+        assert!((moved.magnitude() - 1).abs() <= Num::from_raw(1));
+    }
+
+    #[test]
+    fn fast_magnitude_is_within_four_percent() {
+        extern crate std;
+
+        fn check<const N: usize>() {
+            for x in -50..50 {
+                for y in -50..50 {
+                    let v: Vector2D<Num<i32, N>> = (x, y).into();
+                    let expected = f64::from(x).hypot(f64::from(y));
+                    let fast = f64::from(v.fast_magnitude().to_raw()) / f64::from(1 << N);
+
+                    assert!(
+                        (fast - expected).abs() <= expected * 0.04 + 1. / f64::from(1 << N),
+                        "fast magnitude of ({}, {}) with N = {} is {}",
+                        x,
+                        y,
+                        N,
+                        fast
+                    );
+                }
+            }
+        }
+
+        check::<8>();
+        check::<16>();
+    }
+
     #[test]
     fn test_vector_changing() {
         let v1: Vector2D<FixedNum<8>> = Vector2D::new(1.into(), 2.into());
@@ -1257,4 +2062,316 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn rects_sharing_an_edge_or_corner_do_not_overlap() {
+        let r: Rect<i32> = Rect::new((-5, -5).into(), (10, 10).into());
+
+        let neighbours = [
+            Rect::new((5, -5).into(), (3, 10).into()),
+            Rect::new((-8, -5).into(), (3, 10).into()),
+            Rect::new((-5, 5).into(), (10, 3).into()),
+            Rect::new((-5, -8).into(), (10, 3).into()),
+            Rect::new((5, 5).into(), (2, 2).into()),
+            Rect::new((-7, -7).into(), (2, 2).into()),
+        ];
+        for other in neighbours {
+            assert!(!r.overlaps(&other), "{:?} overlaps {:?}", r, other);
+            assert!(!other.overlaps(&r), "{:?} overlaps {:?}", other, r);
+            assert_eq!(r.intersection(&other), None);
+            assert_eq!(other.clip_to(&r), None);
+        }
+
+        // moving each of them one further in overlaps by a single row or column
+        let one_in = [
+            Rect::new((4, -5).into(), (3, 10).into()),
+            Rect::new((-7, -5).into(), (3, 10).into()),
+            Rect::new((-5, 4).into(), (10, 3).into()),
+            Rect::new((-5, -7).into(), (10, 3).into()),
+            Rect::new((4, 4).into(), (2, 2).into()),
+            Rect::new((-6, -6).into(), (2, 2).into()),
+        ];
+        for other in one_in {
+            assert!(r.overlaps(&other), "{:?} doesn't overlap {:?}", r, other);
+            assert!(other.overlaps(&r), "{:?} doesn't overlap {:?}", other, r);
+
+            let intersection = r.intersection(&other).unwrap();
+            assert_eq!(Some(intersection.clone()), other.intersection(&r));
+            assert!(intersection.size.x == 1 || intersection.size.y == 1);
+        }
+    }
+
+    #[test]
+    fn empty_rects_never_overlap() {
+        let r: Rect<i32> = Rect::new((0, 0).into(), (10, 10).into());
+
+        for empty in [
+            Rect::new((5, 5).into(), (0, 3).into()),
+            Rect::new((5, 5).into(), (3, 0).into()),
+            Rect::new((5, 5).into(), (0, 0).into()),
+        ] {
+            assert!(!r.overlaps(&empty));
+            assert!(!empty.overlaps(&r));
+            assert!(!empty.overlaps(&empty));
+            assert_eq!(empty.clip_to(&r), None);
+        }
+    }
+
+    #[test]
+    fn rect_clipping_works_for_fixed_point_rects() {
+        let bounds: Rect<Num<i32, 8>> = Rect::new((0, 0).into(), (num!(10.5), 8.into()).into());
+        let r: Rect<Num<i32, 8>> = Rect::new((num!(-1.25), num!(7.5)).into(), (3, 3).into());
+
+        assert!(r.overlaps(&bounds));
+        assert_eq!(
+            r.clip_to(&bounds),
+            Some(Rect::new(
+                (num!(0.), num!(7.5)).into(),
+                (num!(1.75), num!(0.5)).into()
+            ))
+        );
+
+        let beside: Rect<Num<i32, 8>> = Rect::new((num!(10.5), 0.into()).into(), (1, 1).into());
+        assert!(!beside.overlaps(&bounds));
+
+        let just_in: Rect<Num<i32, 8>> = Rect::new(
+            (num!(10.5) - Num::from_raw(1), 0.into()).into(),
+            (1, 1).into(),
+        );
+        assert_eq!(
+            just_in.clip_to(&bounds),
+            Some(Rect::new(
+                (just_in.position.x, 0.into()).into(),
+                (Num::from_raw(1), 1.into()).into()
+            ))
+        );
+    }
+
+    #[test]
+    fn union_of_rects_with_negative_coordinates() {
+        let a: Rect<i32> = Rect::new((-20, -5).into(), (5, 5).into());
+        let b: Rect<i32> = Rect::new((-3, -30).into(), (2, 40).into());
+
+        let union = a.union(&b);
+        assert_eq!(union, Rect::new((-20, -30).into(), (19, 40).into()));
+        assert_eq!(b.union(&a), union);
+        assert!(union.contains_rect(&a));
+        assert!(union.contains_rect(&b));
+
+        // a rect inside another doesn't change it
+        let inside: Rect<i32> = Rect::new((-15, -4).into(), (2, 2).into());
+        assert_eq!(union.union(&inside), union);
+    }
+
+    #[test]
+    fn empty_rects_do_not_change_a_union() {
+        let r: Rect<i32> = Rect::new((-7, -7).into(), (3, 4).into());
+
+        let empties: [Rect<i32>; 4] = [
+            Rect::new((0, 0).into(), (0, 0).into()),
+            Rect::new((-100, 50).into(), (0, 10).into()),
+            Rect::new((100, -50).into(), (10, 0).into()),
+            Rect::new((5, 5).into(), (-3, -3).into()),
+        ];
+
+        for empty in &empties {
+            assert!(empty.is_empty());
+            assert_eq!(r.union(empty), r);
+            assert_eq!(empty.union(&r), r);
+        }
+
+        // building a dirty region up from nothing
+        let mut dirty = empties[0].clone();
+        for region in [
+            Rect::new((-3, 2).into(), (1, 1).into()),
+            Rect::new((4, -6).into(), (2, 2).into()),
+        ] {
+            dirty = dirty.union(&region);
+        }
+        assert_eq!(dirty, Rect::new((-3, -6).into(), (9, 9).into()));
+    }
+
+    #[test]
+    fn rects_contain_rects_up_to_their_edges() {
+        let r: Rect<i32> = Rect::new((-8, -8).into(), (8, 8).into());
+
+        assert!(r.contains_rect(&r));
+        assert!(r.contains_rect(&Rect::new((-8, -8).into(), (1, 1).into())));
+        assert!(r.contains_rect(&Rect::new((-1, -1).into(), (1, 1).into())));
+        assert!(!r.contains_rect(&Rect::new((-1, -1).into(), (2, 1).into())));
+        assert!(!r.contains_rect(&Rect::new((-9, -4).into(), (2, 2).into())));
+        assert!(!r.contains_rect(&Rect::new((-10, -10).into(), (20, 20).into())));
+
+        let empty: Rect<i32> = Rect::new((-4, -4).into(), (0, 0).into());
+        assert!(r.contains_rect(&empty));
+        assert!(r.contains_rect(&Rect::new((40, -40).into(), (0, 3).into())));
+        assert!(empty.contains_rect(&empty));
+        assert!(!empty.contains_rect(&r));
+    }
+
+    #[test]
+    fn expanding_and_shrinking_rects() {
+        let r: Rect<i32> = Rect::new((-3, -6).into(), (6, 10).into());
+
+        assert_eq!(r.expand(0), r);
+        assert_eq!(r.expand(2), Rect::new((-5, -8).into(), (10, 14).into()));
+        assert_eq!(r.expand(2).expand(-2), r);
+        assert!(r.expand(2).contains_rect(&r));
+        assert!(!r.expand(-1).is_empty());
+        assert!(r.expand(-3).is_empty());
+        assert!(r.expand(-4).is_empty());
+
+        let r: Rect<Num<i32, 8>> = Rect::new((num!(-0.5), num!(1.25)).into(), (1, 1).into());
+        assert_eq!(
+            r.expand(num!(0.25)),
+            Rect::new(
+                (num!(-0.75), num!(1.)).into(),
+                (num!(1.5), num!(1.5)).into()
+            )
+        );
+    }
+
+    #[test]
+    fn sin_and_cos_are_exact_at_the_cardinal_angles() {
+        fn check<const N: usize>() {
+            let quarter = Num::<i32, N>::new(1) / 4;
+            let expected = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+            for turns in -8..8 {
+                let angle = quarter * turns;
+                let (cos, sin) = expected[turns.rem_euclid(4) as usize];
+                assert_eq!(angle.cos(), cos.into(), "cos({}) with N = {}", angle, N);
+                assert_eq!(angle.sin(), sin.into(), "sin({}) with N = {}", angle, N);
+            }
+        }
+
+        check::<4>();
+        check::<8>();
+        check::<12>();
+        check::<16>();
+        check::<20>();
+    }
+
+    #[test]
+    fn sin_and_cos_symmetries_hold_exactly() {
+        let half: Num<i32, 12> = num!(0.5);
+        let quarter: Num<i32, 12> = num!(0.25);
+
+        for raw in -(1 << 13)..(1 << 13) {
+            let x = Num::<i32, 12>::from_raw(raw);
+
+            assert_eq!((-x).sin(), -x.sin(), "sin(-x) at {}", x);
+            assert_eq!((-x).cos(), x.cos(), "cos(-x) at {}", x);
+            assert_eq!((half - x).sin(), x.sin(), "sin(0.5 - x) at {}", x);
+            assert_eq!((x + half).sin(), -x.sin(), "sin(x + 0.5) at {}", x);
+            assert_eq!((x + quarter).sin(), x.cos(), "sin(x + 0.25) at {}", x);
+            assert_eq!((x + 1).cos(), x.cos(), "cos(x + 1) at {}", x);
+        }
+    }
+
+    #[test]
+    fn sin_and_cos_are_within_one_bit_of_the_real_values() {
+        extern crate std;
+
+        fn check<const N: usize>(step: i32) {
+            let one = f64::from(1 << N);
+
+            for raw in (0..(1 << N)).step_by(step as usize) {
+                let angle = Num::<i32, N>::from_raw(raw);
+                let radians = f64::from(raw) / one * core::f64::consts::TAU;
+
+                let sin_error = f64::from(angle.sin().to_raw()) / one - radians.sin();
+                let cos_error = f64::from(angle.cos().to_raw()) / one - radians.cos();
+
+                assert!(
+                    sin_error.abs() <= 1. / 256. && cos_error.abs() <= 1. / 256.,
+                    "sin and cos of {} are out by {} and {}",
+                    angle,
+                    sin_error,
+                    cos_error
+                );
+            }
+        }
+
+        check::<8>(1);
+        check::<16>(7);
+    }
+
+    #[test]
+    fn angle_is_exact_along_the_axes_and_diagonals() {
+        fn check<const N: usize>() {
+            let expected = [
+                ((1, 0), num!(0.)),
+                ((1, 1), num!(0.125)),
+                ((0, 1), num!(0.25)),
+                ((-1, 1), num!(0.375)),
+                ((-1, 0), num!(0.5)),
+                ((-1, -1), num!(0.625)),
+                ((0, -1), num!(0.75)),
+                ((1, -1), num!(0.875)),
+            ];
+
+            for ((x, y), angle) in expected {
+                for length in [1, 3, 100] {
+                    let v: Vector2D<Num<i32, N>> = (x * length, y * length).into();
+                    assert_eq!(v.angle(), angle, "angle of {:?} with N = {}", v, N);
+                }
+            }
+
+            let zero: Vector2D<Num<i32, N>> = (0, 0).into();
+            assert_eq!(zero.angle(), 0.into());
+        }
+
+        check::<4>();
+        check::<8>();
+        check::<12>();
+        check::<16>();
+        check::<20>();
+    }
+
+    #[test]
+    fn angle_round_trips_with_sin_and_cos() {
+        for raw in 0..256 {
+            let angle = Num::<i32, 8>::from_raw(raw);
+            let round_trip = Vector2D::new_from_angle(angle).angle();
+
+            let difference = (round_trip - angle).to_raw().rem_euclid(256);
+            assert!(
+                difference <= 1 || difference >= 255,
+                "angle {} came back as {}",
+                angle,
+                round_trip
+            );
+        }
+    }
+
+    #[test]
+    fn angle_is_within_one_bit_of_the_real_value() {
+        extern crate std;
+
+        for x in -20..=20 {
+            for y in -20..=20 {
+                if x == 0 && y == 0 {
+                    continue;
+                }
+
+                let v: Vector2D<Num<i32, 8>> = (x, y).into();
+                let expected = f64::from(y)
+                    .atan2(f64::from(x))
+                    .rem_euclid(core::f64::consts::TAU)
+                    / core::f64::consts::TAU;
+
+                let error = f64::from(v.angle().to_raw()) / 256. - expected;
+                // an angle just below a whole revolution can round up to 0
+                let error = error - error.round();
+                assert!(
+                    error.abs() <= 1. / 256.,
+                    "angle of ({}, {}) is out by {}",
+                    x,
+                    y,
+                    error
+                );
+            }
+        }
+    }
 }
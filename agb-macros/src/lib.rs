@@ -3,7 +3,7 @@ use proc_macro::TokenStream;
 
 use proc_macro2::Span;
 use quote::{quote, ToTokens};
-use syn::{FnArg, Ident, ItemFn, Pat, ReturnType, Token, Type, Visibility};
+use syn::{FnArg, Ident, Item, ItemFn, Pat, ReturnType, Token, Type, Visibility};
 
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
@@ -101,6 +101,63 @@ pub fn entry(args: TokenStream, input: TokenStream) -> TokenStream {
     .into()
 }
 
+#[proc_macro_attribute]
+pub fn iwram(args: TokenStream, input: TokenStream) -> TokenStream {
+    assert!(
+        args.to_string() == "",
+        "Must pass no args to #[agb::iwram] macro"
+    );
+
+    let item: Item =
+        syn::parse(input).expect("#[agb::iwram] must be applied to a static or a function");
+
+    match item {
+        Item::Static(item) => {
+            let section = section_name(".iwram", &item.ident, &item);
+            quote!(
+                #[link_section = #section]
+                #item
+            )
+        }
+        Item::Fn(item) => {
+            // Code in iwram is compiled as ARM rather than thumb, since iwram's 32 bit bus means
+            // ARM instructions are fetched as quickly as thumb ones. It mustn't be inlined into
+            // its callers either, or it would end up back in rom.
+            let section = section_name(".text_iwram", &item.sig.ident, &item);
+            quote!(
+                #[link_section = #section]
+                #[instruction_set(arm::a32)]
+                #[inline(never)]
+                #item
+            )
+        }
+        _ => panic!("#[agb::iwram] must be applied to a static or a function"),
+    }
+    .into()
+}
+
+#[proc_macro_attribute]
+pub fn ewram(args: TokenStream, input: TokenStream) -> TokenStream {
+    assert!(
+        args.to_string() == "",
+        "Must pass no args to #[agb::ewram] macro"
+    );
+
+    let item: Item = syn::parse(input).expect("#[agb::ewram] must be applied to a static");
+
+    match item {
+        Item::Static(item) => {
+            let section = section_name(".ewram", &item.ident, &item);
+            quote!(
+                #[link_section = #section]
+                #item
+            )
+        }
+        _ => panic!("#[agb::ewram] must be applied to a static"),
+    }
+    .into()
+}
+
 #[proc_macro]
 pub fn num(input: TokenStream) -> TokenStream {
     let f = syn::parse_macro_input!(input as syn::LitFloat);
@@ -119,6 +176,12 @@ fn hashed_ident<T: Hash>(f: &T) -> Ident {
     Ident::new(&format!("_agb_main_func_{}", hash), Span::call_site())
 }
 
+// Each item gets its own section, named after it so that it is easy to find in the map file, and
+// with a hash so that items with the same name in different modules don't share a section.
+fn section_name<T: Hash>(prefix: &str, ident: &Ident, item: &T) -> String {
+    format!("{}.{}.{}", prefix, ident, calculate_hash(item))
+}
+
 fn calculate_hash<T: Hash>(t: &T) -> u64 {
     let mut s = DefaultHasher::new();
     t.hash(&mut s);
@@ -0,0 +1,58 @@
+#![no_std]
+#![no_main]
+
+use agb::display::{self, bitmap4::Page};
+
+const BALL_SIZE: i32 = 8;
+
+fn draw_ball(bitmap: &mut display::bitmap4::Bitmap4, (x, y): (i32, i32), colour: u8, page: Page) {
+    for j in 0..BALL_SIZE {
+        for i in 0..BALL_SIZE {
+            // leave out the corners to make it look a bit more like a ball
+            let is_corner = (i == 0 || i == BALL_SIZE - 1) && (j == 0 || j == BALL_SIZE - 1);
+            if !is_corner {
+                bitmap.draw_point_page(x + i, y + j, colour, page);
+            }
+        }
+    }
+}
+
+#[agb::entry]
+fn main(mut gba: agb::Gba) -> ! {
+    let mut bitmap = gba.display.video.bitmap4();
+    let vblank = agb::interrupt::VBlank::get();
+
+    bitmap.set_palette_entry(0, 0x0000);
+    bitmap.set_palette_entry(1, 0x03FF);
+
+    let mut pos = (20, 40);
+    let mut velocity = (2, 1);
+
+    // where the ball was last drawn on each page, so it can be rubbed out
+    // rather than clearing the whole page every frame
+    let mut drawn_at: [Option<(i32, i32)>; 2] = [None, None];
+
+    loop {
+        pos.0 += velocity.0;
+        pos.1 += velocity.1;
+
+        if pos.0 <= 0 || pos.0 >= display::WIDTH - BALL_SIZE {
+            velocity.0 = -velocity.0;
+            pos.0 = pos.0.clamp(0, display::WIDTH - BALL_SIZE);
+        }
+        if pos.1 <= 0 || pos.1 >= display::HEIGHT - BALL_SIZE {
+            velocity.1 = -velocity.1;
+            pos.1 = pos.1.clamp(0, display::HEIGHT - BALL_SIZE);
+        }
+
+        let page = bitmap.drawing_page();
+        if let Some(old) = drawn_at[page as usize] {
+            draw_ball(&mut bitmap, old, 0, page);
+        }
+        draw_ball(&mut bitmap, pos, 1, page);
+        drawn_at[page as usize] = Some(pos);
+
+        vblank.wait_for_vblank();
+        bitmap.flip_page();
+    }
+}
@@ -0,0 +1,42 @@
+#![no_std]
+#![no_main]
+
+use agb::display::bitmap5::Bitmap5;
+use agb::fixnum::Rect;
+
+#[agb::entry]
+fn main(mut gba: agb::Gba) -> ! {
+    let mut bitmap = gba.display.video.bitmap5();
+    let vblank = agb::interrupt::VBlank::get();
+
+    bitmap.set_border_colour(0x2108);
+    let mut input = agb::input::ButtonController::new();
+
+    let mut frame = 0;
+
+    loop {
+        let page = bitmap.drawing_page();
+        bitmap.fill_page(
+            Rect::new((0, 0).into(), (Bitmap5::WIDTH, Bitmap5::HEIGHT).into()),
+            0,
+            page,
+        );
+
+        // a stripe of colour which moves across the page each frame
+        let x = frame % Bitmap5::WIDTH;
+        bitmap.fill_page(
+            Rect::new((x, 0).into(), (8, Bitmap5::HEIGHT).into()),
+            0x001F,
+            page,
+        );
+
+        input.update();
+        if input.is_just_pressed(agb::input::Button::A) {
+            bitmap.stretch_to_screen();
+        }
+
+        vblank.wait_for_vblank();
+        bitmap.flip_page();
+        frame += 2;
+    }
+}
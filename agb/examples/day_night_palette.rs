@@ -0,0 +1,80 @@
+#![no_std]
+#![no_main]
+
+use agb::{
+    display::{
+        palette16::Palette16,
+        tiled::{RegularBackgroundSize, TileFormat, TileSet, TileSetting},
+        Priority,
+    },
+    fixnum::Rect,
+    include_gfx,
+};
+
+include_gfx!("examples/water_tiles.toml");
+
+const DAY_BANK: u8 = 0;
+const NIGHT_BANK: u8 = 1;
+
+// Halves each of the red, green and blue components and adds a little blue back in
+fn night_colour(colour: u16) -> u16 {
+    let r = (colour & 0x1f) / 2;
+    let g = ((colour >> 5) & 0x1f) / 2;
+    let b = (((colour >> 10) & 0x1f) / 2 + 4).min(0x1f);
+
+    r | (g << 5) | (b << 10)
+}
+
+#[agb::entry]
+fn main(mut gba: agb::Gba) -> ! {
+    let (gfx, mut vram) = gba.display.video.tiled0();
+    let vblank = agb::interrupt::VBlank::get();
+
+    let tileset = TileSet::new(water_tiles::water_tiles.tiles, TileFormat::FourBpp);
+
+    let day = &water_tiles::water_tiles.palettes[0];
+    let mut night = Palette16::new([0; 16]);
+    for i in 0..16 {
+        night.update_colour(i, night_colour(day.colour(i)));
+    }
+
+    vram.set_background_palette(DAY_BANK, day);
+    vram.set_background_palette(NIGHT_BANK, &night);
+
+    let mut bg = gfx.background(Priority::P0, RegularBackgroundSize::Background32x32);
+
+    for y in 0..20u16 {
+        for x in 0..30u16 {
+            bg.set_tile(
+                &mut vram,
+                (x, y).into(),
+                &tileset,
+                TileSetting::new((x + y) % 8, false, false, DAY_BANK),
+            );
+        }
+    }
+
+    bg.commit(&mut vram);
+    bg.show();
+
+    let whole_map = Rect::new((0u16, 0u16).into(), (32u16, 32u16).into());
+    let mut is_night = false;
+    let mut frame = 0;
+
+    loop {
+        frame += 1;
+
+        if frame % 120 == 0 {
+            if is_night {
+                bg.remap_palette(&whole_map, NIGHT_BANK, DAY_BANK);
+            } else {
+                bg.remap_palette(&whole_map, DAY_BANK, NIGHT_BANK);
+            }
+
+            is_night = !is_night;
+        }
+
+        vblank.wait_for_vblank();
+        bg.commit(&mut vram);
+    }
+}
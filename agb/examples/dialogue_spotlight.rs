@@ -0,0 +1,63 @@
+#![no_std]
+#![no_main]
+
+use agb::display::blend::{BlendMode, Layer};
+use agb::display::{example_logo, tiled::RegularBackgroundSize, window::WinIn};
+use agb::display::{HEIGHT, WIDTH};
+use agb::fixnum::{num, Num, Rect};
+use agb::interrupt::VBlank;
+
+// Darkens everything except a dialogue box which slides up from the bottom of the screen.
+#[agb::entry]
+fn main(mut gba: agb::Gba) -> ! {
+    let (gfx, mut vram) = gba.display.video.tiled0();
+
+    let mut map = gfx.background(
+        agb::display::Priority::P0,
+        RegularBackgroundSize::Background32x32,
+    );
+
+    example_logo::display_logo(&mut map, &mut vram);
+
+    let mut window = gba.display.window.get();
+    window
+        .win_in(WinIn::Win0)
+        .set_background_enable(map.background(), true)
+        .set_blend_enable(false)
+        .enable();
+
+    window
+        .win_out()
+        .set_background_enable(map.background(), true)
+        .set_blend_enable(true)
+        .enable();
+
+    let mut blend = gba.display.blend.get();
+    blend
+        .set_background_enable(Layer::Top, map.background(), true)
+        .set_backdrop_enable(Layer::Top, true)
+        .set_fade(num!(0.5))
+        .set_blend_mode(BlendMode::FadeToBlack);
+
+    let dialogue_height = 48;
+    let mut dialogue_top: Num<i32, 8> = HEIGHT.into();
+    let dialogue_speed: Num<i32, 8> = num!(0.75);
+
+    let vblank = VBlank::get();
+
+    loop {
+        // starts off screen below the bottom edge, and the window clips it until it is in place
+        if dialogue_top > (HEIGHT - dialogue_height - 8).into() {
+            dialogue_top -= dialogue_speed;
+        }
+
+        window.win_in(WinIn::Win0).set_position(&Rect::new(
+            (8, dialogue_top.floor()).into(),
+            (WIDTH - 16, dialogue_height).into(),
+        ));
+
+        vblank.wait_for_vblank();
+        window.commit();
+        blend.commit();
+    }
+}
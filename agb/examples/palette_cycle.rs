@@ -0,0 +1,61 @@
+#![no_std]
+#![no_main]
+
+use agb::{
+    display::{
+        tiled::{RegularBackgroundSize, TileFormat, TileSet, TileSetting},
+        Priority,
+    },
+    include_gfx,
+};
+
+include_gfx!("examples/water_tiles.toml");
+
+const STATIC_BANK: u8 = 0;
+const FLOWING_BANK: u8 = 1;
+
+// How many frames each step of the cycle is shown for
+const FRAMES_PER_STEP: usize = 8;
+
+#[agb::entry]
+fn main(mut gba: agb::Gba) -> ! {
+    let (gfx, mut vram) = gba.display.video.tiled0();
+    let vblank = agb::interrupt::VBlank::get();
+
+    let tileset = TileSet::new(water_tiles::water_tiles.tiles, TileFormat::FourBpp);
+
+    // the same palette in two banks, so the left of the screen stays still while the right
+    // hand side flows
+    let palette = &water_tiles::water_tiles.palettes[0];
+    vram.set_background_palette(STATIC_BANK, palette);
+    vram.set_background_palette(FLOWING_BANK, palette);
+
+    let mut bg = gfx.background(Priority::P0, RegularBackgroundSize::Background32x32);
+
+    for y in 0..20u16 {
+        for x in 0..30u16 {
+            let bank = if x < 15 { STATIC_BANK } else { FLOWING_BANK };
+
+            bg.set_tile(
+                &mut vram,
+                (x, y).into(),
+                &tileset,
+                TileSetting::new((x + y) % 8, false, false, bank),
+            );
+        }
+    }
+
+    bg.commit(&mut vram);
+    bg.show();
+
+    let mut frame = 0;
+
+    loop {
+        vblank.wait_for_vblank();
+
+        // colour 0 is transparent, so cycle all the others
+        vram.cycle_background_palette(FLOWING_BANK, 1..16, frame / FRAMES_PER_STEP);
+
+        frame += 1;
+    }
+}
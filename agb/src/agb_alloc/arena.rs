@@ -0,0 +1,199 @@
+//! The arena is a bump allocator over its own region at the end of EWRAM. Each call to
+//! [`with_arena`] remembers where the next allocation would go and rewinds back there once the
+//! closure returns, so temporary allocations never fragment the heap.
+
+use core::alloc::Layout;
+use core::cell::Cell;
+use core::ptr::NonNull;
+
+use bare_metal::Mutex;
+
+use super::bump_allocator::{BumpAllocator, StartEnd};
+use super::EWRAM_END;
+use crate::interrupt::free;
+
+static ARENA: BumpAllocator = BumpAllocator::new(StartEnd {
+    start: arena_start,
+    end: || EWRAM_END,
+});
+
+// How many calls to with_arena are currently running
+static DEPTH: Mutex<Cell<usize>> = Mutex::new(Cell::new(0));
+
+/// Scratch space for temporary allocations, such as collision lists or text layout buffers which
+/// are only needed for part of a frame. Get one with [`with_arena`].
+///
+/// Allocating is just moving a pointer along, and everything is freed at once when the call to
+/// `with_arena` returns. Destructors of values allocated in the arena are never run.
+pub struct Arena {
+    depth: usize,
+}
+
+/// Calls `f` with an [`Arena`] for temporary allocations, all of which are freed when `f`
+/// returns. The borrow checker makes sure that nothing allocated in the arena outlives the call.
+///
+/// Calls can be nested, in which case the inner arena frees only what was allocated from it. The
+/// arena is 8kB by default, which can be changed by passing
+/// `-Clink-arg=--defsym=EWRAM_ARENA_SIZE=<bytes>` to the linker. The space for it is taken from
+/// the end of the EWRAM heap.
+///
+/// ```rust,no_run
+/// # #![no_std]
+/// # #![no_main]
+/// # fn foo() {
+/// use agb::agb_alloc::with_arena;
+///
+/// let total = with_arena(|arena| {
+///     let nearby = arena.alloc_slice_fill(16, 0u16);
+///     for (i, enemy) in nearby.iter_mut().enumerate() {
+///         *enemy = i as u16 * 3;
+///     }
+///
+///     nearby.iter().sum::<u16>()
+/// });
+/// # }
+/// ```
+pub fn with_arena<R>(f: impl FnOnce(&Arena) -> R) -> R {
+    let (position, depth) = free(|cs| {
+        let depth = DEPTH.borrow(cs).get() + 1;
+        DEPTH.borrow(cs).set(depth);
+
+        (ARENA.position(cs), depth)
+    });
+
+    let result = f(&Arena { depth });
+
+    free(|cs| {
+        DEPTH.borrow(cs).set(depth - 1);
+        unsafe { ARENA.rewind(position, cs) };
+    });
+
+    result
+}
+
+// Each allocation is new memory, so handing out mutable references from a shared one is fine
+#[allow(clippy::mut_from_ref)]
+impl Arena {
+    /// Moves `value` into the arena.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there isn't enough space left in the arena, or if this is called inside a nested
+    /// call to [`with_arena`], since the allocation would be freed when the inner one returns.
+    pub fn alloc<T>(&self, value: T) -> &mut T {
+        let ptr = self.alloc_layout(Layout::new::<T>()).cast::<T>().as_ptr();
+
+        unsafe {
+            ptr.write(value);
+            &mut *ptr
+        }
+    }
+
+    /// Copies `values` into the arena.
+    ///
+    /// # Panics
+    ///
+    /// Panics in the same cases as [`alloc`](Arena::alloc).
+    pub fn alloc_slice_copy<T: Copy>(&self, values: &[T]) -> &mut [T] {
+        let slice = self.alloc_slice_uninit::<T>(values.len());
+
+        unsafe {
+            slice.copy_from_nonoverlapping(values.as_ptr(), values.len());
+            core::slice::from_raw_parts_mut(slice, values.len())
+        }
+    }
+
+    /// Allocates a slice of `len` copies of `value` in the arena.
+    ///
+    /// # Panics
+    ///
+    /// Panics in the same cases as [`alloc`](Arena::alloc).
+    pub fn alloc_slice_fill<T: Copy>(&self, len: usize, value: T) -> &mut [T] {
+        let slice = self.alloc_slice_uninit::<T>(len);
+
+        unsafe {
+            for i in 0..len {
+                slice.add(i).write(value);
+            }
+
+            core::slice::from_raw_parts_mut(slice, len)
+        }
+    }
+
+    /// The number of bytes left in the arena.
+    #[must_use]
+    pub fn remaining(&self) -> usize {
+        free(|cs| ARENA.remaining(cs))
+    }
+
+    fn alloc_slice_uninit<T>(&self, len: usize) -> *mut T {
+        let layout = Layout::array::<T>(len).expect("too large allocation");
+        self.alloc_layout(layout).cast().as_ptr()
+    }
+
+    fn alloc_layout(&self, layout: Layout) -> NonNull<u8> {
+        free(|cs| {
+            assert_eq!(
+                DEPTH.borrow(cs).get(),
+                self.depth,
+                "can't allocate from an arena inside a nested call to with_arena"
+            );
+
+            ARENA.alloc_critical(layout, cs)
+        })
+        .unwrap_or_else(|| {
+            panic!(
+                "the arena is out of space, failed to allocate {} bytes",
+                layout.size()
+            )
+        })
+    }
+}
+
+pub(super) fn arena_start() -> usize {
+    extern "C" {
+        static __ewram_arena_start: usize;
+    }
+
+    (unsafe { &__ewram_arena_start }) as *const _ as usize
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_case]
+    fn arena_allocations_are_freed_when_the_closure_returns(_gba: &mut crate::Gba) {
+        let first = with_arena(|arena| {
+            let a = arena.alloc(5u32);
+            let b = arena.alloc_slice_copy(&[1u16, 2, 3]);
+
+            assert_eq!(*a, 5);
+            assert_eq!(b, &[1, 2, 3]);
+            assert!((arena_start()..EWRAM_END).contains(&(a as *mut u32 as usize)));
+
+            a as *mut u32 as usize
+        });
+
+        let second = with_arena(|arena| arena.alloc(7u32) as *mut u32 as usize);
+
+        assert_eq!(first, second);
+    }
+
+    #[test_case]
+    fn nested_arenas_only_free_their_own_allocations(_gba: &mut crate::Gba) {
+        with_arena(|outer| {
+            let kept = outer.alloc_slice_fill(10, 0x55u8);
+            let remaining = outer.remaining();
+
+            with_arena(|inner| {
+                let temporary = inner.alloc_slice_fill(100, 0xaau8);
+                assert!(temporary.iter().all(|&value| value == 0xaa));
+                assert!(inner.remaining() < remaining);
+            });
+
+            assert_eq!(outer.remaining(), remaining);
+            assert!(kept.iter().all(|&value| value == 0x55));
+        });
+    }
+}
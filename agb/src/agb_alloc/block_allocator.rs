@@ -2,8 +2,13 @@
 //! requesting new blocks using a bump allocator. Freed blocks are inserted into
 //! the linked list in order of pointer. Blocks are then merged after every
 //! free.
+//!
+//! The allocator doesn't need to know where each allocation starts, but can optionally be given
+//! an [`AllocationStarts`] bitmap to record it in so that [`BlockAllocator::debug_dump`] can list
+//! each allocation.
 
 use core::alloc::{Allocator, GlobalAlloc, Layout};
+use core::fmt::Arguments;
 
 use core::cell::RefCell;
 use core::convert::TryInto;
@@ -13,7 +18,7 @@ use crate::interrupt::free;
 use bare_metal::{CriticalSection, Mutex};
 
 use super::bump_allocator::{BumpAllocator, StartEnd};
-use super::SendNonNull;
+use super::{trace, AllocatorStats, SendNonNull};
 
 struct Block {
     size: usize,
@@ -39,8 +44,58 @@ impl Block {
     }
 }
 
+// Every allocation and free block is a multiple of this many bytes, and starts at a multiple of it
+// from the start of the heap
+const GRANULE: usize = 8;
+
+/// A bitmap with a bit for every 8 bytes of an allocator's memory, which is set where an
+/// allocation starts.
+pub(crate) struct AllocationStarts {
+    words: SendNonNull<u32>,
+    len: usize,
+}
+
+impl AllocationStarts {
+    /// Uses the `len` words at `words` as the bitmap, which covers `len * 32 * 8` bytes.
+    ///
+    /// # Safety
+    ///
+    /// The words must be zeroed, and only used by the allocator this is given to.
+    pub const unsafe fn new(words: *mut u32, len: usize) -> Self {
+        AllocationStarts {
+            words: SendNonNull(NonNull::new_unchecked(words)),
+            len,
+        }
+    }
+
+    fn set(&mut self, granule: usize, value: bool) {
+        assert!(
+            granule < self.len * 32,
+            "the allocation bitmap is too small for the allocator"
+        );
+
+        unsafe {
+            let word = &mut *self.words.as_ptr().add(granule / 32);
+            if value {
+                *word |= 1 << (granule % 32);
+            } else {
+                *word &= !(1 << (granule % 32));
+            }
+        }
+    }
+
+    fn get(&self, granule: usize) -> bool {
+        granule < self.len * 32
+            && unsafe { *self.words.as_ptr().add(granule / 32) } & (1 << (granule % 32)) != 0
+    }
+}
+
 struct BlockAllocatorState {
     first_free_block: Option<SendNonNull<Block>>,
+    // kept up to date on every allocation so that stats don't need to find every allocation
+    bytes_allocated: usize,
+    allocations: usize,
+    allocation_starts: Option<AllocationStarts>,
 }
 
 pub struct BlockAllocator {
@@ -50,10 +105,29 @@ pub struct BlockAllocator {
 
 impl BlockAllocator {
     pub(crate) const unsafe fn new(start: StartEnd) -> Self {
+        Self::new_with_starts(start, None)
+    }
+
+    /// Creates an allocator which records where each allocation starts in `allocation_starts`, so
+    /// that [`debug_dump`](Self::debug_dump) can list them.
+    pub(crate) const unsafe fn new_tracking_allocations(
+        start: StartEnd,
+        allocation_starts: AllocationStarts,
+    ) -> Self {
+        Self::new_with_starts(start, Some(allocation_starts))
+    }
+
+    const unsafe fn new_with_starts(
+        start: StartEnd,
+        allocation_starts: Option<AllocationStarts>,
+    ) -> Self {
         Self {
             inner_allocator: BumpAllocator::new(start),
             state: Mutex::new(RefCell::new(BlockAllocatorState {
                 first_free_block: None,
+                bytes_allocated: 0,
+                allocations: 0,
+                allocation_starts,
             })),
         }
     }
@@ -76,6 +150,91 @@ impl BlockAllocator {
         })
     }
 
+    /// How much of the allocator's memory is in use
+    pub fn stats(&self) -> AllocatorStats {
+        free(|key| self.stats_critical(&self.state.borrow(key).borrow(), key))
+    }
+
+    fn stats_critical(&self, state: &BlockAllocatorState, key: CriticalSection) -> AllocatorStats {
+        {
+            let remaining = self.inner_allocator.remaining(key);
+            let mut bytes_free = remaining;
+            let mut largest_free_block = remaining;
+
+            let mut current_block = state.first_free_block;
+            while let Some(curr) = current_block {
+                let curr_block = unsafe { curr.as_ref() };
+
+                bytes_free += curr_block.size;
+                largest_free_block = largest_free_block.max(curr_block.size);
+
+                current_block = curr_block.next;
+            }
+
+            AllocatorStats {
+                bytes_allocated: state.bytes_allocated,
+                bytes_free,
+                largest_free_block,
+                allocations: state.allocations,
+            }
+        }
+    }
+
+    /// Writes the layout of the allocator's memory to `log`, one line at a time: a summary
+    /// followed by the offset from the start of the allocator and size of each allocation and
+    /// free block in address order, and the unused space at the end. This doesn't allocate, so
+    /// can be used when an allocation fails.
+    ///
+    /// Allocations which are next to each other are only listed separately if the allocator
+    /// records where each allocation starts. Otherwise they are listed as a single run.
+    pub fn debug_dump(&self, name: &str, mut log: impl FnMut(Arguments)) {
+        free(|key| {
+            let state = self.state.borrow(key).borrow();
+            let stats = self.stats_critical(&state, key);
+
+            log(format_args!(
+                "{}: {} bytes in {} allocations, {} bytes free, largest free block {} bytes, {}% fragmented",
+                name,
+                stats.bytes_allocated,
+                stats.allocations,
+                stats.bytes_free,
+                stats.largest_free_block,
+                stats.fragmentation_percent()
+            ));
+
+            let heap_start = self.inner_allocator.start(key);
+            let mut position = heap_start;
+
+            let mut current_block = state.first_free_block;
+            while let Some(curr) = current_block {
+                let curr_block = unsafe { curr.as_ref() };
+                let block_start = curr.as_ptr() as usize;
+
+                dump_allocations(&state, heap_start, position, block_start, &mut log);
+                log(format_args!(
+                    "  {:#07x} free {}",
+                    block_start - heap_start,
+                    curr_block.size
+                ));
+
+                position = block_start + curr_block.size;
+                current_block = curr_block.next;
+            }
+
+            let end = self.inner_allocator.position(key);
+            dump_allocations(&state, heap_start, position, end, &mut log);
+            log(format_args!(
+                "  {:#07x} unused {}",
+                end - heap_start,
+                self.inner_allocator.remaining(key)
+            ));
+        });
+    }
+
+    fn heap_start(&self) -> usize {
+        free(|key| self.inner_allocator.start(key))
+    }
+
     /// Requests a brand new block from the inner bump allocator
     fn new_block(&self, layout: Layout, cs: CriticalSection) -> Option<NonNull<u8>> {
         let overall_layout = Block::either_layout(layout);
@@ -86,7 +245,12 @@ impl BlockAllocator {
     unsafe fn normalise(&self) {
         free(|key| {
             let mut state = self.state.borrow(key).borrow_mut();
+            self.normalise_critical(&mut state, key);
+        });
+    }
 
+    unsafe fn normalise_critical(&self, state: &mut BlockAllocatorState, key: CriticalSection) {
+        {
             let mut list_ptr = &mut state.first_free_block;
 
             while let Some(mut curr) = list_ptr {
@@ -110,19 +274,71 @@ impl BlockAllocator {
                 }
                 list_ptr = &mut curr.as_mut().next;
             }
-        });
+        }
+
+        // If the last free block is right before the space the bump allocator hasn't handed out
+        // yet, give it back so that it can be allocated as part of that space
+        let mut list_ptr = &mut state.first_free_block;
+        while let Some(mut curr) = *list_ptr {
+            let curr_block = curr.as_mut();
+            if curr_block.next.is_none() {
+                let start = curr.cast::<u8>();
+                let end = start.as_ptr() as usize + curr_block.size;
+                if self.inner_allocator.release_end(start, end, key) {
+                    *list_ptr = None;
+                }
+
+                break;
+            }
+
+            list_ptr = &mut curr_block.next;
+        }
     }
 
     pub unsafe fn alloc(&self, layout: Layout) -> Option<NonNull<u8>> {
         // find a block that this current request fits in
         let full_layout = Block::either_layout(layout);
 
+        free(|key| {
+            let mut state = self.state.borrow(key).borrow_mut();
+
+            let mut allocation = self.alloc_critical(&mut state, layout, key);
+            if allocation.is_none() {
+                // blocks freed by dealloc_no_normalise may not have been merged yet
+                self.normalise_critical(&mut state, key);
+                allocation = self.alloc_critical(&mut state, layout, key);
+            }
+
+            if let Some(ptr) = allocation {
+                state.bytes_allocated += full_layout.size();
+                state.allocations += 1;
+
+                let offset = ptr.as_ptr() as usize - self.inner_allocator.start(key);
+                if let Some(starts) = &mut state.allocation_starts {
+                    debug_assert_eq!(offset & (GRANULE - 1), 0, "the heap must be 8 byte aligned");
+                    starts.set(offset / GRANULE, true);
+                }
+            }
+
+            trace::alloc(self.inner_allocator.start(key), layout, allocation);
+
+            allocation
+        })
+    }
+
+    unsafe fn alloc_critical(
+        &self,
+        state: &mut BlockAllocatorState,
+        layout: Layout,
+        key: CriticalSection,
+    ) -> Option<NonNull<u8>> {
+        let full_layout = Block::either_layout(layout);
+
         let (block_after_layout, block_after_layout_offset) = full_layout
             .extend(Layout::new::<Block>().align_to(8).unwrap().pad_to_align())
             .unwrap();
 
-        free(|key| {
-            let mut state = self.state.borrow(key).borrow_mut();
+        {
             let mut current_block = state.first_free_block;
             let mut list_ptr = &mut state.first_free_block;
             // This iterates the free list until it either finds a block that
@@ -154,7 +370,7 @@ impl BlockAllocator {
             }
 
             self.new_block(layout, key)
-        })
+        }
     }
 
     pub unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
@@ -162,8 +378,148 @@ impl BlockAllocator {
         self.normalise();
     }
 
+    /// Grows the allocation at `ptr` to fit `new_layout`. If the free block straight after it is
+    /// big enough, the allocation is extended into it and stays where it is. Otherwise a new
+    /// allocation is made, the contents are copied across and the old one is freed. Returns `None`
+    /// without changing anything if there is no space for the new allocation.
+    pub unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Option<NonNull<u8>> {
+        let old_size = Block::either_layout(old_layout).size();
+        let new_size = Block::either_layout(new_layout).size();
+
+        if ptr.as_ptr() as usize & (new_layout.align() - 1) == 0
+            && (new_size <= old_size || self.extend_in_place(ptr, old_size, new_size))
+        {
+            trace::resize(self.heap_start(), ptr, old_layout, new_layout.size());
+            return Some(ptr);
+        }
+
+        self.reallocate(ptr, old_layout, new_layout)
+    }
+
+    /// Shrinks the allocation at `ptr` to fit `new_layout`, freeing the end of it. The allocation
+    /// only moves if it isn't aligned for `new_layout`, in which case this returns `None` if
+    /// there is no space for the new allocation.
+    pub unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Option<NonNull<u8>> {
+        if ptr.as_ptr() as usize & (new_layout.align() - 1) != 0 {
+            return self.reallocate(ptr, old_layout, new_layout);
+        }
+
+        let old_size = Block::either_layout(old_layout).size();
+        let new_size = Block::either_layout(new_layout).size();
+
+        // both sizes are multiples of 8, which is big enough to hold a block header
+        if new_size < old_size {
+            free(|key| self.state.borrow(key).borrow_mut().bytes_allocated -= old_size - new_size);
+
+            self.insert_free_block(ptr.as_ptr().add(new_size), old_size - new_size);
+            self.normalise();
+        }
+
+        trace::resize(self.heap_start(), ptr, old_layout, new_layout.size());
+        Some(ptr)
+    }
+
+    /// Extends the allocation of `old_size` bytes at `ptr` to `new_size` bytes using the free
+    /// block right after it, returning whether there was one big enough.
+    unsafe fn extend_in_place(&self, ptr: NonNull<u8>, old_size: usize, new_size: usize) -> bool {
+        let end = ptr.as_ptr().add(old_size);
+        let needed = new_size - old_size;
+        let block_header_size = Layout::new::<Block>()
+            .align_to(8)
+            .unwrap()
+            .pad_to_align()
+            .size();
+
+        free(|key| {
+            let mut state = self.state.borrow(key).borrow_mut();
+            let mut list_ptr = &mut state.first_free_block;
+
+            // The free list is in order of address, so stop once past the end of the allocation
+            while let Some(mut curr) = *list_ptr {
+                let curr_ptr = curr.as_ptr().cast::<u8>();
+                if curr_ptr > end {
+                    return false;
+                }
+
+                if curr_ptr == end {
+                    let curr_block = curr.as_mut();
+
+                    if curr_block.size == needed {
+                        *list_ptr = curr_block.next;
+                    } else if curr_block.size >= needed + block_header_size {
+                        let split_ptr = curr_ptr.add(needed).cast();
+                        *split_ptr = Block {
+                            size: curr_block.size - needed,
+                            next: curr_block.next,
+                        };
+                        *list_ptr = NonNull::new(split_ptr).map(SendNonNull);
+                    } else {
+                        return false;
+                    }
+
+                    state.bytes_allocated += needed;
+                    return true;
+                }
+
+                list_ptr = &mut curr.as_mut().next;
+            }
+
+            false
+        })
+    }
+
+    /// Moves an allocation to a new one fitting `new_layout`, copying as much as fits
+    unsafe fn reallocate(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Option<NonNull<u8>> {
+        let new_ptr = self.alloc(new_layout)?;
+
+        core::ptr::copy_nonoverlapping(
+            ptr.as_ptr(),
+            new_ptr.as_ptr(),
+            old_layout.size().min(new_layout.size()),
+        );
+        self.dealloc(ptr.as_ptr(), old_layout);
+
+        Some(new_ptr)
+    }
+
+    /// Frees the allocation without merging it with the free blocks either side. Useful when
+    /// freeing lots of allocations at once, as the blocks get merged by the next call to
+    /// [`dealloc`](Self::dealloc) or by an allocation which wouldn't fit otherwise.
     pub unsafe fn dealloc_no_normalise(&self, ptr: *mut u8, layout: Layout) {
-        let new_layout = Block::either_layout(layout).pad_to_align();
+        let size = Block::either_layout(layout).pad_to_align().size();
+        free(|key| {
+            let mut state = self.state.borrow(key).borrow_mut();
+            state.bytes_allocated -= size;
+            state.allocations -= 1;
+
+            let granule = (ptr as usize - self.inner_allocator.start(key)) / GRANULE;
+            if let Some(starts) = &mut state.allocation_starts {
+                starts.set(granule, false);
+            }
+
+            trace::dealloc(self.inner_allocator.start(key), ptr, layout);
+        });
+
+        self.insert_free_block(ptr, size);
+    }
+
+    /// Adds `size` bytes at `ptr` to the free list
+    unsafe fn insert_free_block(&self, ptr: *mut u8, size: usize) {
         free(|key| {
             let mut state = self.state.borrow(key).borrow_mut();
 
@@ -179,7 +535,7 @@ impl BlockAllocator {
                     Some(mut current_block) => {
                         if current_block.as_ptr().cast() > ptr {
                             let new_block_content = Block {
-                                size: new_layout.size(),
+                                size,
                                 next: Some(current_block),
                             };
                             *ptr.cast() = new_block_content;
@@ -190,10 +546,7 @@ impl BlockAllocator {
                     }
                     None => {
                         // reached the end of the list without finding a place to insert the value
-                        let new_block_content = Block {
-                            size: new_layout.size(),
-                            next: None,
-                        };
+                        let new_block_content = Block { size, next: None };
                         *ptr.cast() = new_block_content;
                         *list_ptr = NonNull::new(ptr.cast()).map(SendNonNull);
                         break;
@@ -204,6 +557,55 @@ impl BlockAllocator {
     }
 }
 
+// Logs the allocations between `start` and `end`, which has no free blocks in it
+fn dump_allocations(
+    state: &BlockAllocatorState,
+    heap_start: usize,
+    start: usize,
+    end: usize,
+    log: &mut impl FnMut(Arguments),
+) {
+    if start == end {
+        return;
+    }
+
+    let starts = match &state.allocation_starts {
+        Some(starts) => starts,
+        None => {
+            log(format_args!(
+                "  {:#07x} allocated {} (one or more allocations)",
+                start - heap_start,
+                end - start
+            ));
+            return;
+        }
+    };
+
+    let granule = |address: usize| (address - heap_start) / GRANULE;
+    let mut log_region = |region_start: usize, region_end: usize, allocated: bool| {
+        log(format_args!(
+            "  {:#07x} {} {}",
+            region_start - heap_start,
+            // the bump allocator leaves gaps to align allocations
+            if allocated { "allocated" } else { "padding" },
+            region_end - region_start
+        ));
+    };
+
+    let mut region_start = start;
+    let mut allocated = starts.get(granule(start));
+
+    for position in (start + GRANULE..end).step_by(GRANULE) {
+        if starts.get(granule(position)) {
+            log_region(region_start, position, allocated);
+            region_start = position;
+            allocated = true;
+        }
+    }
+
+    log_region(region_start, end, allocated);
+}
+
 unsafe impl GlobalAlloc for BlockAllocator {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
         match self.alloc(layout) {
@@ -215,6 +617,19 @@ unsafe impl GlobalAlloc for BlockAllocator {
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
         self.dealloc(ptr, layout);
     }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        let ptr = NonNull::new_unchecked(ptr);
+
+        let new_ptr = if new_size > layout.size() {
+            self.grow(ptr, layout, new_layout)
+        } else {
+            self.shrink(ptr, layout, new_layout)
+        };
+
+        new_ptr.map_or(core::ptr::null_mut(), NonNull::as_ptr)
+    }
 }
 
 unsafe impl Allocator for BlockAllocator {
@@ -233,4 +648,348 @@ unsafe impl Allocator for BlockAllocator {
     unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
         self.dealloc(ptr.as_ptr(), layout);
     }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, core::alloc::AllocError> {
+        match BlockAllocator::grow(self, ptr, old_layout, new_layout) {
+            None => Err(core::alloc::AllocError),
+            Some(p) => Ok(NonNull::slice_from_raw_parts(p, new_layout.size())),
+        }
+    }
+
+    unsafe fn grow_zeroed(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, core::alloc::AllocError> {
+        let new = Allocator::grow(self, ptr, old_layout, new_layout)?;
+
+        new.cast::<u8>()
+            .as_ptr()
+            .add(old_layout.size())
+            .write_bytes(0, new_layout.size() - old_layout.size());
+
+        Ok(new)
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, core::alloc::AllocError> {
+        match BlockAllocator::shrink(self, ptr, old_layout, new_layout) {
+            None => Err(core::alloc::AllocError),
+            Some(p) => Ok(NonNull::slice_from_raw_parts(p, new_layout.size())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::string::{String, ToString};
+    use alloc::vec::Vec;
+
+    const HEAP_SIZE: usize = 1024;
+
+    #[repr(align(64))]
+    struct Heap([u8; HEAP_SIZE]);
+
+    static mut HEAP: Heap = Heap([0; HEAP_SIZE]);
+
+    // taking the address of a static mut only needs to be unsafe on older compilers
+    #[allow(unused_unsafe)]
+    fn heap_start() -> usize {
+        unsafe { core::ptr::addr_of!(HEAP) as usize }
+    }
+
+    fn test_allocator() -> BlockAllocator {
+        unsafe {
+            BlockAllocator::new(StartEnd {
+                start: heap_start,
+                end: || heap_start() + HEAP_SIZE,
+            })
+        }
+    }
+
+    fn layout(size: usize) -> Layout {
+        Layout::from_size_align(size, 4).unwrap()
+    }
+
+    static mut STARTS: [u32; HEAP_SIZE / GRANULE / 32] = [0; HEAP_SIZE / GRANULE / 32];
+
+    fn tracking_allocator() -> BlockAllocator {
+        unsafe {
+            (*core::ptr::addr_of_mut!(STARTS)).fill(0);
+
+            BlockAllocator::new_tracking_allocations(
+                StartEnd {
+                    start: heap_start,
+                    end: || heap_start() + HEAP_SIZE,
+                },
+                AllocationStarts::new(core::ptr::addr_of_mut!(STARTS).cast(), HEAP_SIZE / 256),
+            )
+        }
+    }
+
+    fn dump(allocator: &BlockAllocator) -> Vec<String> {
+        let mut lines = Vec::new();
+        allocator.debug_dump("test", |line| lines.push(line.to_string()));
+        lines
+    }
+
+    #[test_case]
+    fn growing_extends_into_the_free_block_after(_gba: &mut crate::Gba) {
+        let allocator = test_allocator();
+
+        unsafe {
+            let a = allocator.alloc(layout(16)).unwrap();
+            let b = allocator.alloc(layout(64)).unwrap();
+            let _c = allocator.alloc(layout(16)).unwrap();
+
+            allocator.dealloc(b.as_ptr(), layout(64));
+
+            // only part of the free block is used, so the rest is split off
+            assert_eq!(allocator.grow(a, layout(16), layout(32)), Some(a));
+            assert_eq!(allocator.number_of_blocks(), 1);
+            assert_eq!(
+                allocator.alloc(layout(48)).unwrap().as_ptr(),
+                a.as_ptr().add(32)
+            );
+            assert_eq!(allocator.number_of_blocks(), 0);
+        }
+    }
+
+    #[test_case]
+    fn growing_into_the_whole_free_block_removes_it(_gba: &mut crate::Gba) {
+        let allocator = test_allocator();
+
+        unsafe {
+            let a = allocator.alloc(layout(16)).unwrap();
+            let b = allocator.alloc(layout(16)).unwrap();
+            let _c = allocator.alloc(layout(16)).unwrap();
+
+            allocator.dealloc(b.as_ptr(), layout(16));
+
+            assert_eq!(allocator.grow(a, layout(16), layout(32)), Some(a));
+            assert_eq!(allocator.number_of_blocks(), 0);
+
+            // growing within the padding of the block doesn't need any more space
+            assert_eq!(allocator.grow(a, layout(32), layout(30)), Some(a));
+        }
+    }
+
+    #[test_case]
+    fn growing_moves_the_allocation_when_there_is_no_space(_gba: &mut crate::Gba) {
+        let allocator = test_allocator();
+
+        unsafe {
+            let a = allocator.alloc(layout(16)).unwrap();
+            let _b = allocator.alloc(layout(16)).unwrap();
+
+            a.as_ptr().cast::<u32>().write(0x1234_5678);
+
+            let moved = allocator.grow(a, layout(16), layout(64)).unwrap();
+            assert_ne!(moved, a);
+            assert_eq!(moved.as_ptr().cast::<u32>().read(), 0x1234_5678);
+
+            // the old allocation was freed
+            assert_eq!(allocator.number_of_blocks(), 1);
+
+            // and if there is no space at all, nothing changes
+            assert_eq!(allocator.grow(moved, layout(64), layout(HEAP_SIZE)), None);
+            assert_eq!(moved.as_ptr().cast::<u32>().read(), 0x1234_5678);
+        }
+    }
+
+    #[test_case]
+    fn growing_to_a_bigger_alignment_moves_misaligned_allocations(_gba: &mut crate::Gba) {
+        let allocator = test_allocator();
+
+        unsafe {
+            let _a = allocator.alloc(layout(16)).unwrap();
+            let b = allocator.alloc(layout(16)).unwrap();
+            assert_ne!(b.as_ptr() as usize % 64, 0);
+
+            b.as_ptr().write(42);
+
+            let aligned = Layout::from_size_align(32, 64).unwrap();
+            let moved = allocator.grow(b, layout(16), aligned).unwrap();
+            assert_eq!(moved.as_ptr() as usize % 64, 0);
+            assert_eq!(moved.as_ptr().read(), 42);
+        }
+    }
+
+    #[test_case]
+    fn stats_track_allocations(_gba: &mut crate::Gba) {
+        let allocator = test_allocator();
+
+        unsafe {
+            let a = allocator.alloc(layout(16)).unwrap();
+            let b = allocator.alloc(layout(60)).unwrap();
+            let _c = allocator.alloc(layout(16)).unwrap();
+
+            let stats = allocator.stats();
+            assert_eq!(stats.allocations, 3);
+            assert_eq!(stats.bytes_allocated, 16 + 64 + 16);
+            assert_eq!(stats.bytes_free, HEAP_SIZE - 96);
+            assert_eq!(stats.largest_free_block, HEAP_SIZE - 96);
+
+            allocator.dealloc(b.as_ptr(), layout(60));
+            allocator.grow(a, layout(16), layout(32)).unwrap();
+
+            let stats = allocator.stats();
+            assert_eq!(stats.allocations, 2);
+            assert_eq!(stats.bytes_allocated, 32 + 16);
+            assert_eq!(stats.bytes_free, HEAP_SIZE - 48);
+        }
+    }
+
+    #[test_case]
+    fn blocks_freed_without_normalising_are_merged_when_needed(_gba: &mut crate::Gba) {
+        let allocator = test_allocator();
+
+        unsafe {
+            let blocks: Vec<_> = (0..4)
+                .map(|_| allocator.alloc(layout(HEAP_SIZE / 4)).unwrap())
+                .collect();
+
+            allocator.dealloc_no_normalise(blocks[1].as_ptr(), layout(HEAP_SIZE / 4));
+            allocator.dealloc_no_normalise(blocks[2].as_ptr(), layout(HEAP_SIZE / 4));
+            assert_eq!(allocator.number_of_blocks(), 2);
+
+            assert_eq!(allocator.alloc(layout(HEAP_SIZE / 2)), Some(blocks[1]));
+        }
+    }
+
+    #[test_case]
+    fn freeing_everything_allows_allocating_the_whole_heap(_gba: &mut crate::Gba) {
+        let allocator = test_allocator();
+        let mut rng = crate::rng::RandomNumberGenerator::new();
+        // the sizes of 4bpp sprites from 8x8 to 32x32, where the whole heap fits a 64x32 sprite
+        let sizes = [32, 64, 128, 256, 512];
+        let mut allocations: Vec<(NonNull<u8>, Layout)> = Vec::new();
+
+        unsafe {
+            for i in 0..5000 {
+                let random = rng.gen() as u32 as usize;
+
+                if allocations.is_empty() || random % 3 != 0 {
+                    let layout = layout(sizes[random % sizes.len()]);
+                    match allocator.alloc(layout) {
+                        Some(ptr) => allocations.push((ptr, layout)),
+                        // only fails if there isn't a big enough gap anywhere
+                        None => assert!(allocator.stats().largest_free_block < layout.size()),
+                    }
+                } else {
+                    let (ptr, layout) = allocations.swap_remove(random % allocations.len());
+                    // sprites are freed with dealloc, background tiles with dealloc_no_normalise
+                    if i % 2 == 0 {
+                        allocator.dealloc(ptr.as_ptr(), layout);
+                    } else {
+                        allocator.dealloc_no_normalise(ptr.as_ptr(), layout);
+                    }
+                }
+
+                if i % 100 == 99 {
+                    for (ptr, layout) in allocations.drain(..) {
+                        allocator.dealloc(ptr.as_ptr(), layout);
+                    }
+
+                    let everything = allocator
+                        .alloc(layout(HEAP_SIZE))
+                        .expect("the heap should be in one piece once everything is freed");
+                    allocator.dealloc(everything.as_ptr(), layout(HEAP_SIZE));
+                }
+            }
+        }
+    }
+
+    #[test_case]
+    fn shrinking_frees_the_end_of_the_allocation(_gba: &mut crate::Gba) {
+        let allocator = test_allocator();
+
+        unsafe {
+            let a = allocator.alloc(layout(64)).unwrap();
+            let _b = allocator.alloc(layout(16)).unwrap();
+
+            assert_eq!(allocator.shrink(a, layout(64), layout(16)), Some(a));
+            assert_eq!(allocator.number_of_blocks(), 1);
+
+            assert_eq!(
+                allocator.alloc(layout(48)).unwrap().as_ptr(),
+                a.as_ptr().add(16)
+            );
+        }
+    }
+
+    #[test_case]
+    fn debug_dump_lists_every_allocation_and_free_block(_gba: &mut crate::Gba) {
+        let allocator = tracking_allocator();
+
+        unsafe {
+            let _a = allocator.alloc(layout(16)).unwrap();
+            let b = allocator.alloc(layout(32)).unwrap();
+            let c = allocator.alloc(layout(8)).unwrap();
+            let _d = allocator.alloc(layout(64)).unwrap();
+            let _e = allocator.alloc(layout(24)).unwrap();
+
+            allocator.dealloc(b.as_ptr(), layout(32));
+            allocator.dealloc(c.as_ptr(), layout(8));
+
+            assert_eq!(
+                dump(&allocator),
+                [
+                    "test: 104 bytes in 3 allocations, 920 bytes free, largest free block 880 bytes, 5% fragmented",
+                    "  0x00000 allocated 16",
+                    "  0x00010 free 40",
+                    "  0x00038 allocated 64",
+                    "  0x00078 allocated 24",
+                    "  0x00090 unused 880",
+                ]
+            );
+        }
+    }
+
+    #[test_case]
+    fn debug_dump_without_tracking_lists_runs_of_allocations(_gba: &mut crate::Gba) {
+        let allocator = test_allocator();
+
+        unsafe {
+            let a = allocator.alloc(layout(16)).unwrap();
+            let _b = allocator.alloc(layout(16)).unwrap();
+            let _c = allocator.alloc(layout(16)).unwrap();
+
+            allocator.dealloc(a.as_ptr(), layout(16));
+
+            assert_eq!(
+                dump(&allocator),
+                [
+                    "test: 32 bytes in 2 allocations, 992 bytes free, largest free block 976 bytes, 2% fragmented",
+                    "  0x00000 free 16",
+                    "  0x00010 allocated 32 (one or more allocations)",
+                    "  0x00030 unused 976",
+                ]
+            );
+        }
+    }
+
+    #[test_case]
+    fn fragmentation_is_the_free_memory_outside_the_largest_block(_gba: &mut crate::Gba) {
+        let stats = |bytes_free, largest_free_block| AllocatorStats {
+            bytes_free,
+            largest_free_block,
+            ..AllocatorStats::default()
+        };
+
+        assert_eq!(stats(0, 0).fragmentation_percent(), 0);
+        assert_eq!(stats(100, 100).fragmentation_percent(), 0);
+        assert_eq!(stats(100, 25).fragmentation_percent(), 75);
+    }
 }
@@ -43,7 +43,7 @@ impl BumpAllocator {
         let resulting_ptr = ptr + amount_to_add;
         let new_current_ptr = resulting_ptr + layout.size();
 
-        if new_current_ptr as usize >= (self.start_end.borrow(cs).end)() {
+        if new_current_ptr as usize > (self.start_end.borrow(cs).end)() {
             return None;
         }
 
@@ -51,6 +51,45 @@ impl BumpAllocator {
 
         NonNull::new(resulting_ptr as *mut _)
     }
+
+    /// If the most recent allocation ended at `end`, moves the start of the unallocated space back
+    /// to `start` so that the memory from there can be handed out again. Returns whether it did.
+    pub fn release_end(&self, start: NonNull<u8>, end: usize, cs: CriticalSection) -> bool {
+        let mut current_ptr = self.current_ptr.borrow(cs).borrow_mut();
+
+        if !current_ptr.is_some_and(|current| current.as_ptr() as usize == end) {
+            return false;
+        }
+
+        *current_ptr = Some(SendNonNull(start));
+        true
+    }
+
+    /// The address of the start of the memory this allocates from
+    pub fn start(&self, cs: CriticalSection) -> usize {
+        (self.start_end.borrow(cs).start)()
+    }
+
+    /// The number of bytes which haven't been handed out yet
+    pub fn remaining(&self, cs: CriticalSection) -> usize {
+        (self.start_end.borrow(cs).end)().saturating_sub(self.position(cs))
+    }
+
+    /// The address the next allocation will start from, for passing to [`rewind`](Self::rewind)
+    pub fn position(&self, cs: CriticalSection) -> usize {
+        match *self.current_ptr.borrow(cs).borrow() {
+            Some(c) => c.as_ptr() as usize,
+            None => (self.start_end.borrow(cs).start)(),
+        }
+    }
+
+    /// Frees everything allocated since [`position`](Self::position) returned `position`. Nothing
+    /// allocated since then may be used afterwards.
+    pub unsafe fn rewind(&self, position: usize, cs: CriticalSection) {
+        *self.current_ptr.borrow(cs).borrow_mut() =
+            NonNull::new(position as *mut u8).map(SendNonNull);
+    }
+
     pub fn alloc_safe(&self, layout: Layout) -> Option<NonNull<u8>> {
         free(|key| self.alloc_critical(layout, key))
     }
@@ -0,0 +1,129 @@
+use core::alloc::Layout;
+use core::ops::{Deref, DerefMut};
+use core::ptr::NonNull;
+
+use alloc::alloc::{alloc_zeroed, dealloc, handle_alloc_error};
+
+// 32 bit DMA transfers and CpuFastSet both need word aligned addresses
+const DMA_ALIGNMENT: usize = 4;
+
+/// A fixed length buffer on the heap which is always aligned to at least 4 bytes, so it can be
+/// used as the source or destination of 32 bit DMA transfers and `CpuFastSet`. Unlike a `Vec` it
+/// can't grow, so its address never changes while it exists, which is needed for repeating
+/// transfers such as [`HBlankDma`](crate::dma::HBlankDma).
+///
+/// ```rust,no_run
+/// # #![no_std]
+/// # #![no_main]
+/// # fn foo() {
+/// use agb::agb_alloc::DmaBuffer;
+///
+/// let mut colours = DmaBuffer::new(256, 0u16);
+/// colours[5] = 0x7fff;
+/// assert_eq!(colours.as_ptr() as usize % 4, 0);
+/// # }
+/// ```
+pub struct DmaBuffer<T: Copy> {
+    ptr: NonNull<T>,
+    len: usize,
+    layout: Layout,
+}
+
+impl<T: Copy> DmaBuffer<T> {
+    /// A buffer of `len` copies of `value`.
+    #[must_use]
+    pub fn new(len: usize, value: T) -> Self {
+        let mut buffer = Self::zeroed(len, DMA_ALIGNMENT);
+        buffer.fill(value);
+        buffer
+    }
+
+    /// A buffer containing a copy of `values`.
+    #[must_use]
+    pub fn from_slice(values: &[T]) -> Self {
+        let mut buffer = Self::zeroed(values.len(), DMA_ALIGNMENT);
+        buffer.copy_from_slice(values);
+        buffer
+    }
+
+    // Every bit pattern must be valid for T before the buffer can be read. The buffers made by
+    // the public functions are always filled immediately.
+    fn zeroed(len: usize, align: usize) -> Self {
+        let layout = Layout::array::<T>(len)
+            .and_then(|layout| layout.align_to(align.max(DMA_ALIGNMENT)))
+            .expect("too large allocation");
+
+        if layout.size() == 0 {
+            return Self {
+                ptr: NonNull::dangling(),
+                len,
+                layout,
+            };
+        }
+
+        let ptr = unsafe { alloc_zeroed(layout) };
+        let ptr = NonNull::new(ptr.cast()).unwrap_or_else(|| handle_alloc_error(layout));
+
+        Self { ptr, len, layout }
+    }
+}
+
+/// Allocates `len` zeroed bytes aligned to `align` bytes, which is increased to 4 if it is less
+/// than that so that the buffer can always be used by DMA.
+///
+/// # Panics
+///
+/// Panics if `align` isn't a power of 2.
+#[must_use]
+pub fn alloc_aligned(len: usize, align: usize) -> DmaBuffer<u8> {
+    assert!(align.is_power_of_two(), "alignment must be a power of 2");
+
+    DmaBuffer::zeroed(len, align)
+}
+
+impl<T: Copy> Deref for DmaBuffer<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe { core::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T: Copy> DerefMut for DmaBuffer<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        unsafe { core::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T: Copy> Drop for DmaBuffer<T> {
+    fn drop(&mut self) {
+        if self.layout.size() != 0 {
+            unsafe { dealloc(self.ptr.as_ptr().cast(), self.layout) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test_case]
+    fn buffers_are_word_aligned(_gba: &mut crate::Gba) {
+        // odd sized allocations first, so that the heap isn't word aligned by accident
+        let _bytes: Vec<Vec<u8>> = (1..8).map(|len| alloc::vec![0; len]).collect();
+
+        let halfwords = DmaBuffer::from_slice(&[1u16, 2, 3]);
+        assert_eq!(halfwords.as_ptr() as usize % 4, 0);
+        assert_eq!(&*halfwords, &[1, 2, 3]);
+
+        let bytes = alloc_aligned(5, 1);
+        assert_eq!(bytes.as_ptr() as usize % 4, 0);
+        assert_eq!(&*bytes, &[0; 5]);
+
+        let page = alloc_aligned(16, 256);
+        assert_eq!(page.as_ptr() as usize % 256, 0);
+
+        assert!(DmaBuffer::new(0, 0u32).is_empty());
+    }
+}
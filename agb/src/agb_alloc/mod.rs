@@ -1,9 +1,22 @@
 use core::alloc::{Allocator, Layout};
+use core::cell::Cell;
 use core::ops::{Deref, DerefMut};
 use core::ptr::NonNull;
 
+use bare_metal::Mutex;
+
+use crate::display::{object, palette_manager, tiled::vram_manager};
+use crate::interrupt::free;
+
+mod arena;
 pub(crate) mod block_allocator;
 pub(crate) mod bump_allocator;
+mod dma_buffer;
+pub(crate) mod trace;
+
+pub use arena::{with_arena, Arena};
+pub use dma_buffer::{alloc_aligned, DmaBuffer};
+pub use trace::{set_trace_tag, trace_frame};
 
 use block_allocator::BlockAllocator;
 
@@ -33,13 +46,12 @@ impl<T> DerefMut for SendNonNull<T> {
 }
 
 const EWRAM_END: usize = 0x0204_0000;
-const IWRAM_END: usize = 0x0300_8000;
 
 #[global_allocator]
 static GLOBAL_ALLOC: BlockAllocator = unsafe {
     BlockAllocator::new(StartEnd {
         start: data_end,
-        end: || EWRAM_END,
+        end: arena::arena_start,
     })
 };
 
@@ -53,6 +65,40 @@ macro_rules! impl_zst_allocator {
             unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
                 $name_of_static.deallocate(ptr, layout)
             }
+
+            // forwarded so that allocations can grow and shrink in place, which the default
+            // implementations never do
+            unsafe fn grow(
+                &self,
+                ptr: NonNull<u8>,
+                old_layout: Layout,
+                new_layout: Layout,
+            ) -> Result<NonNull<[u8]>, core::alloc::AllocError> {
+                <BlockAllocator as Allocator>::grow(&$name_of_static, ptr, old_layout, new_layout)
+            }
+
+            unsafe fn grow_zeroed(
+                &self,
+                ptr: NonNull<u8>,
+                old_layout: Layout,
+                new_layout: Layout,
+            ) -> Result<NonNull<[u8]>, core::alloc::AllocError> {
+                <BlockAllocator as Allocator>::grow_zeroed(
+                    &$name_of_static,
+                    ptr,
+                    old_layout,
+                    new_layout,
+                )
+            }
+
+            unsafe fn shrink(
+                &self,
+                ptr: NonNull<u8>,
+                old_layout: Layout,
+                new_layout: Layout,
+            ) -> Result<NonNull<[u8]>, core::alloc::AllocError> {
+                <BlockAllocator as Allocator>::shrink(&$name_of_static, ptr, old_layout, new_layout)
+            }
         }
     };
 }
@@ -87,6 +133,13 @@ impl_zst_allocator!(ExternalAllocator, GLOBAL_ALLOC);
 /// allocator trait, so is meant to be used in specifying where certain
 /// structures should be allocated.
 ///
+/// IWRAM is much faster than EWRAM, but there is only 32kB of it which is
+/// shared with the stack and any code or statics placed there. So the heap is a
+/// fixed size region after the statics, 8kB by default. This can be changed by
+/// passing `-Clink-arg=--defsym=IWRAM_HEAP_SIZE=<bytes>` to the linker, and the
+/// space reserved for the stack with `IWRAM_STACK_SIZE` (also 8kB by default).
+/// Linking fails if the heap would overlap the stack.
+///
 /// ```rust,no_run
 /// #![feature(allocator_api)]
 /// # #![no_std]
@@ -110,11 +163,131 @@ impl_zst_allocator!(InternalAllocator, __IWRAM_ALLOC);
 
 static __IWRAM_ALLOC: BlockAllocator = unsafe {
     BlockAllocator::new(StartEnd {
-        start: iwram_data_end,
-        end: || IWRAM_END,
+        start: iwram_heap_start,
+        end: iwram_heap_end,
     })
 };
 
+/// Returns the allocator for the fast IWRAM heap, for use with allocator aware
+/// collections such as [`Box::new_in`](alloc::boxed::Box::new_in) or
+/// [`Vec::new_in`](alloc::vec::Vec::new_in). Allocations from this can be used
+/// alongside those from the global EWRAM heap. See [`InternalAllocator`] for
+/// how big the heap is.
+///
+/// ```rust,no_run
+/// #![feature(allocator_api)]
+/// # #![no_std]
+/// # #![no_main]
+/// # extern crate alloc;
+/// # use alloc::vec::Vec;
+/// # fn foo(gba: &mut agb::Gba) {
+/// let mut open_list = Vec::with_capacity_in(64, agb::agb_alloc::iwram_allocator());
+/// open_list.push((5, 3));
+/// # }
+/// ```
+#[must_use]
+pub fn iwram_allocator() -> InternalAllocator {
+    InternalAllocator
+}
+
+/// How much of an allocator is in use. All sizes are in bytes, and include the padding added to
+/// each allocation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AllocatorStats {
+    /// The total size of every allocation which hasn't been freed
+    pub bytes_allocated: usize,
+    /// The total size of the memory which can still be allocated
+    pub bytes_free: usize,
+    /// The size of the largest allocation which could currently succeed
+    pub largest_free_block: usize,
+    /// The number of allocations which haven't been freed
+    pub allocations: usize,
+}
+
+impl AllocatorStats {
+    /// How much of the free memory can't be used by an allocation of the size of all of it, as a
+    /// percentage. This is 0 when the free memory is all in one piece, and gets close to 100 as it
+    /// gets split into many small pieces.
+    #[must_use]
+    pub fn fragmentation_percent(&self) -> usize {
+        (self.largest_free_block * 100)
+            .checked_div(self.bytes_free)
+            .map_or(0, |largest_percent| 100 - largest_percent)
+    }
+}
+
+/// The usage of each of agb's allocators, as returned by [`stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HeapStats {
+    /// The global allocator and [`ExternalAllocator`]
+    pub ewram: AllocatorStats,
+    /// [`InternalAllocator`]
+    pub iwram: AllocatorStats,
+    /// Sprite tile data, allocated by the
+    /// [`ObjectController`](crate::display::object::ObjectController)
+    pub sprite_vram: AllocatorStats,
+    /// Background tile data, allocated by the
+    /// [`VRamManager`](crate::display::tiled::VRamManager)
+    pub background_vram: AllocatorStats,
+    /// The 16 sprite palette banks, each of which is 32 bytes
+    pub sprite_palettes: AllocatorStats,
+    /// The 16 background palette banks, each of which is 32 bytes
+    pub background_palettes: AllocatorStats,
+}
+
+/// Returns how much of each allocator is in use. This walks the lists of free memory, so is
+/// cheap enough to call once a frame for debugging but isn't free.
+///
+/// ```rust,no_run
+/// # #![no_std]
+/// # #![no_main]
+/// # fn foo() {
+/// let stats = agb::agb_alloc::stats();
+/// agb::println!(
+///     "{} bytes in {} allocations, largest free block {}",
+///     stats.ewram.bytes_allocated,
+///     stats.ewram.allocations,
+///     stats.ewram.largest_free_block
+/// );
+/// # }
+/// ```
+#[must_use]
+pub fn stats() -> HeapStats {
+    HeapStats {
+        ewram: GLOBAL_ALLOC.stats(),
+        iwram: __IWRAM_ALLOC.stats(),
+        sprite_vram: object::sprite_vram_stats(),
+        background_vram: vram_manager::tile_vram_stats(),
+        sprite_palettes: palette_manager::stats(palette_manager::PaletteKind::Sprite),
+        background_palettes: palette_manager::stats(palette_manager::PaletteKind::Background),
+    }
+}
+
+type OutOfMemoryHook = fn(Layout, &HeapStats);
+
+static OUT_OF_MEMORY_HOOK: Mutex<Cell<Option<OutOfMemoryHook>>> = Mutex::new(Cell::new(None));
+
+/// Sets a function to be called when the global allocator runs out of memory, just before the
+/// game panics. It is given the layout which couldn't be allocated and the current [`stats`], so
+/// can be used to log or display what was using the memory. The hook must not allocate.
+///
+/// ```rust,no_run
+/// # #![no_std]
+/// # #![no_main]
+/// # fn foo() {
+/// agb::agb_alloc::set_out_of_memory_hook(|layout, stats| {
+///     agb::println!(
+///         "failed to allocate {} bytes, largest free block is {}",
+///         layout.size(),
+///         stats.ewram.largest_free_block
+///     );
+/// });
+/// # }
+/// ```
+pub fn set_out_of_memory_hook(hook: fn(Layout, &HeapStats)) {
+    free(|cs| OUT_OF_MEMORY_HOOK.borrow(cs).set(Some(hook)));
+}
+
 #[cfg(any(test, feature = "testing"))]
 pub(crate) unsafe fn number_of_blocks() -> u32 {
     GLOBAL_ALLOC.number_of_blocks()
@@ -122,6 +295,10 @@ pub(crate) unsafe fn number_of_blocks() -> u32 {
 
 #[alloc_error_handler]
 fn alloc_error(layout: Layout) -> ! {
+    if let Some(hook) = free(|cs| OUT_OF_MEMORY_HOOK.borrow(cs).get()) {
+        hook(layout, &stats());
+    }
+
     panic!(
         "Failed to allocate size {} with alignment {}",
         layout.size(),
@@ -129,14 +306,22 @@ fn alloc_error(layout: Layout) -> ! {
     );
 }
 
-fn iwram_data_end() -> usize {
+fn iwram_heap_start() -> usize {
     extern "C" {
-        static __iwram_end: usize;
+        static __iwram_heap_start: usize;
     }
 
     // TODO: This seems completely wrong, but without the &, rust generates
     // a double dereference :/. Maybe a bug in nightly?
-    (unsafe { &__iwram_end }) as *const _ as usize
+    (unsafe { &__iwram_heap_start }) as *const _ as usize
+}
+
+fn iwram_heap_end() -> usize {
+    extern "C" {
+        static __iwram_heap_end: usize;
+    }
+
+    (unsafe { &__iwram_heap_end }) as *const _ as usize
 }
 
 fn data_end() -> usize {
@@ -236,7 +421,7 @@ mod test {
 
     #[test_case]
     fn should_return_data_end_somewhere_in_iwram(_gba: &mut crate::Gba) {
-        let data_end = iwram_data_end();
+        let data_end = iwram_heap_start();
 
         assert!(
             (0x0300_0000..0x0300_8000).contains(&data_end),
@@ -245,6 +430,54 @@ mod test {
         );
     }
 
+    #[test_case]
+    fn the_iwram_heap_is_below_the_stack(_gba: &mut crate::Gba) {
+        let stack_variable = 0;
+        let stack_address = &stack_variable as *const i32 as usize;
+
+        assert!(iwram_heap_start() < iwram_heap_end());
+        assert!(
+            iwram_heap_end() <= stack_address,
+            "iwram heap ends at {:#010X}, which is above the stack at {:#010X}",
+            iwram_heap_end(),
+            stack_address
+        );
+    }
+
+    #[test_case]
+    fn allocating_from_both_heaps_at_once(_gba: &mut crate::Gba) {
+        let mut in_ewram = Vec::new();
+        let mut in_iwram = Vec::new_in(iwram_allocator());
+
+        for i in 0..100 {
+            in_ewram.push(i);
+            in_iwram.push(i * 2);
+        }
+
+        let ewram_address = in_ewram.as_ptr() as usize;
+        let iwram_address = in_iwram.as_ptr() as usize;
+        assert!((EWRAM_START..EWRAM_END).contains(&ewram_address));
+        assert!((iwram_heap_start()..iwram_heap_end()).contains(&iwram_address));
+
+        assert_eq!(
+            in_ewram.iter().sum::<i32>() * 2,
+            in_iwram.iter().sum::<i32>()
+        );
+    }
+
+    #[test_case]
+    fn stats_count_allocations(_gba: &mut crate::Gba) {
+        let before = stats().ewram;
+        let a = Box::new([0u8; 100]);
+
+        let after = stats().ewram;
+        assert_eq!(after.allocations, before.allocations + 1);
+        assert_eq!(after.bytes_allocated, before.bytes_allocated + 104);
+
+        drop(a);
+        assert_eq!(stats().ewram, before);
+    }
+
     #[test_case]
     fn allocate_to_iwram_works(_gba: &mut crate::Gba) {
         let a = Box::new_in(1, InternalAllocator);
@@ -256,4 +489,25 @@ mod test {
             p
         );
     }
+
+    #[test_case]
+    fn vectors_grow_in_place_into_the_free_space_after_them(_gba: &mut crate::Gba) {
+        let mut v: Vec<u32, _> = Vec::with_capacity_in(4, InternalAllocator);
+        let after = Box::new_in([0u32; 16], InternalAllocator);
+        let _end = Box::new_in(0u32, InternalAllocator);
+
+        assert_eq!(
+            &*after as *const _ as usize,
+            v.as_ptr() as usize + 16,
+            "the allocations should be next to each other"
+        );
+        drop(after);
+
+        let start = v.as_ptr();
+        v.extend_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+
+        assert!(v.capacity() >= 8);
+        assert_eq!(v.as_ptr(), start);
+        assert_eq!(v, [1, 2, 3, 4, 5, 6, 7, 8]);
+    }
 }
@@ -0,0 +1,132 @@
+//! With the `alloc_trace` feature enabled, every allocation and deallocation made by the heaps,
+//! the video ram allocators and the palette banks is written to the mgba log at the debug level,
+//! along with the tag set by [`set_trace_tag`] and frame markers from [`trace_frame`]. The log
+//! can then be split by frame and diffed to find what is allocated but never freed.
+//!
+//! Logging writes straight to mgba's debug output so never allocates. Without the feature every
+//! function here does nothing and is optimised away.
+
+use core::alloc::Layout;
+use core::cell::Cell;
+use core::fmt::Arguments;
+use core::ptr::NonNull;
+
+use bare_metal::Mutex;
+
+use crate::interrupt::free;
+use crate::mgba::{DebugLevel, Mgba};
+
+static TAG: Mutex<Cell<&'static str>> = Mutex::new(Cell::new(""));
+
+/// Writes a marker for the start of frame `frame` to the allocation trace, so the allocations
+/// made in each frame can be told apart. Does nothing unless the `alloc_trace` feature is
+/// enabled.
+pub fn trace_frame(frame: u32) {
+    if cfg!(feature = "alloc_trace") {
+        log(format_args!("frame {}", frame));
+    }
+}
+
+/// Sets the tag written with every traced allocation from now on, such as the name of the part
+/// of the game which is running, to tell where allocations come from. Does nothing unless the
+/// `alloc_trace` feature is enabled.
+pub fn set_trace_tag(tag: &'static str) {
+    if cfg!(feature = "alloc_trace") {
+        free(|cs| TAG.borrow(cs).set(tag));
+    }
+}
+
+pub(crate) fn alloc(heap_start: usize, layout: Layout, result: Option<NonNull<u8>>) {
+    if cfg!(feature = "alloc_trace") {
+        match result {
+            Some(ptr) => log(format_args!(
+                "alloc {} size={} align={} ptr={:p} tag={}",
+                heap_name(heap_start),
+                layout.size(),
+                layout.align(),
+                ptr,
+                tag()
+            )),
+            None => log(format_args!(
+                "alloc {} size={} align={} failed tag={}",
+                heap_name(heap_start),
+                layout.size(),
+                layout.align(),
+                tag()
+            )),
+        }
+    }
+}
+
+pub(crate) fn dealloc(heap_start: usize, ptr: *mut u8, layout: Layout) {
+    if cfg!(feature = "alloc_trace") {
+        log(format_args!(
+            "dealloc {} size={} align={} ptr={:p} tag={}",
+            heap_name(heap_start),
+            layout.size(),
+            layout.align(),
+            ptr,
+            tag()
+        ));
+    }
+}
+
+// Allocations which grow or shrink without moving. Those that move are traced as an alloc and a
+// dealloc.
+pub(crate) fn resize(heap_start: usize, ptr: NonNull<u8>, old_layout: Layout, new_size: usize) {
+    if cfg!(feature = "alloc_trace") {
+        log(format_args!(
+            "resize {} size={} new_size={} align={} ptr={:p} tag={}",
+            heap_name(heap_start),
+            old_layout.size(),
+            new_size,
+            old_layout.align(),
+            ptr,
+            tag()
+        ));
+    }
+}
+
+pub(crate) fn palette(event: &str, sprite: bool, bank: Option<u8>) {
+    if cfg!(feature = "alloc_trace") {
+        let kind = if sprite { "sprite" } else { "background" };
+        match bank {
+            Some(bank) => log(format_args!(
+                "{} {} palette bank={} tag={}",
+                event,
+                kind,
+                bank,
+                tag()
+            )),
+            None => log(format_args!(
+                "{} {} palette failed tag={}",
+                event,
+                kind,
+                tag()
+            )),
+        }
+    }
+}
+
+fn heap_name(heap_start: usize) -> &'static str {
+    match heap_start {
+        0x0200_0000..=0x0203_ffff => "ewram",
+        0x0300_0000..=0x0300_7fff => "iwram",
+        0x0600_0000..=0x0600_ffff => "background_vram",
+        0x0601_0000..=0x0601_7fff => "sprite_vram",
+        _ => "unknown",
+    }
+}
+
+fn tag() -> &'static str {
+    free(|cs| TAG.borrow(cs).get())
+}
+
+fn log(output: Arguments) {
+    // keep each line together if an interrupt also allocates
+    free(|_| {
+        if let Some(mut mgba) = Mgba::new() {
+            let _ = mgba.print(output, DebugLevel::Debug);
+        }
+    });
+}
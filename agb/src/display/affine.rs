@@ -0,0 +1,186 @@
+#![deny(missing_docs)]
+//! Affine transformations of backgrounds.
+//!
+//! The GBA displays an affine background by working out, for each pixel of the
+//! screen, which pixel of the background should be shown there. This is done
+//! using a 2x2 matrix (`pa`, `pb`, `pc` and `pd`) and the position in the
+//! background shown at the top left of the screen. Working these out by hand for
+//! a rotation or scale is fiddly, since the matrix is the inverse of the
+//! transformation you actually want and the position has to be recalculated
+//! whenever the matrix changes. [`AffineParameters::from_transform`] does this
+//! for you.
+//!
+//! Currently the only affine background is the page of
+//! [`Bitmap5`][super::bitmap5::Bitmap5], using
+//! [`Bitmap5::set_affine_parameters`][super::bitmap5::Bitmap5::set_affine_parameters].
+//! Parts of the screen which the transformation maps to outside of the page
+//! show the backdrop colour.
+
+use core::convert::TryInto;
+
+use crate::fixnum::{Num, Vector2D};
+
+/// The values of the affine registers of a background.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AffineParameters {
+    /// How far through the background to move for each pixel right on the screen horizontally
+    pub pa: Num<i16, 8>,
+    /// How far through the background to move for each pixel down the screen horizontally
+    pub pb: Num<i16, 8>,
+    /// How far through the background to move for each pixel right on the screen vertically
+    pub pc: Num<i16, 8>,
+    /// How far through the background to move for each pixel down the screen vertically
+    pub pd: Num<i16, 8>,
+    /// The position in the background shown at the top left of the screen
+    pub position: Vector2D<Num<i32, 8>>,
+}
+
+impl AffineParameters {
+    /// Works out the parameters which display `origin`, a position in the background, at
+    /// `screen_anchor` on the screen, with the background scaled by `scale` and rotated by
+    /// `rotation` around that point.
+    ///
+    /// A `scale` of 2 makes the background appear twice as big, and `rotation` is in
+    /// revolutions clockwise, so 0.25 is a quarter turn.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either component of `scale` is 0, or so small that the background would need
+    /// to be shrunk by more than a factor of 128.
+    #[must_use]
+    pub fn from_transform(
+        origin: Vector2D<Num<i32, 8>>,
+        scale: Vector2D<Num<i32, 8>>,
+        rotation: Num<i32, 8>,
+        screen_anchor: Vector2D<i32>,
+    ) -> Self {
+        assert!(
+            scale.x != 0.into() && scale.y != 0.into(),
+            "can't scale a background to nothing"
+        );
+
+        let cos = rotation.cos();
+        let sin = rotation.sin();
+
+        // The hardware needs the inverse of the transformation, which maps the screen on to the
+        // background: unrotate, then unscale.
+        let pa = cos / scale.x;
+        let pb = sin / scale.x;
+        let pc = -sin / scale.y;
+        let pd = cos / scale.y;
+
+        // and then the top left of the screen is wherever the anchor is moved away from the origin
+        let anchor: Vector2D<Num<i32, 8>> = screen_anchor.change_base();
+        let position = Vector2D::new(
+            origin.x - (pa * anchor.x + pb * anchor.y),
+            origin.y - (pc * anchor.x + pd * anchor.y),
+        );
+
+        Self {
+            pa: matrix_entry(pa),
+            pb: matrix_entry(pb),
+            pc: matrix_entry(pc),
+            pd: matrix_entry(pd),
+            position,
+        }
+    }
+}
+
+impl Default for AffineParameters {
+    /// Parameters which display the background at its normal size with its top left at the
+    /// top left of the screen.
+    fn default() -> Self {
+        Self {
+            pa: 1.into(),
+            pb: 0.into(),
+            pc: 0.into(),
+            pd: 1.into(),
+            position: (0, 0).into(),
+        }
+    }
+}
+
+fn matrix_entry(value: Num<i32, 8>) -> Num<i16, 8> {
+    Num::from_raw(
+        value
+            .to_raw()
+            .try_into()
+            .expect("the background is scaled down too much"),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixnum::num;
+
+    #[test_case]
+    fn no_transform_is_the_identity(_gba: &mut crate::Gba) {
+        let parameters =
+            AffineParameters::from_transform((0, 0).into(), (1, 1).into(), 0.into(), (0, 0).into());
+
+        assert_eq!(parameters, AffineParameters::default());
+    }
+
+    #[test_case]
+    fn scaling_about_the_centre_of_the_screen(_gba: &mut crate::Gba) {
+        // the middle of a 160x128 mode 5 page, doubled in size in the middle of the screen
+        let parameters = AffineParameters::from_transform(
+            (80, 64).into(),
+            (2, 2).into(),
+            0.into(),
+            (120, 80).into(),
+        );
+
+        assert_eq!(parameters.pa, num!(0.5));
+        assert_eq!(parameters.pb, 0.into());
+        assert_eq!(parameters.pc, 0.into());
+        assert_eq!(parameters.pd, num!(0.5));
+        assert_eq!(parameters.position, (20, 24).into());
+    }
+
+    #[test_case]
+    fn quarter_turn_about_the_centre_of_the_screen(_gba: &mut crate::Gba) {
+        let parameters = AffineParameters::from_transform(
+            (0, 0).into(),
+            (1, 1).into(),
+            num!(0.25),
+            (120, 80).into(),
+        );
+
+        assert_eq!(parameters.pa, 0.into());
+        assert_eq!(parameters.pb, 1.into());
+        assert_eq!(parameters.pc, (-1).into());
+        assert_eq!(parameters.pd, 0.into());
+        assert_eq!(parameters.position, (-80, 120).into());
+    }
+
+    #[test_case]
+    fn the_anchor_shows_the_origin(_gba: &mut crate::Gba) {
+        let origin: Vector2D<Num<i32, 8>> = (num!(37.5), num!(-12.25)).into();
+        let anchor: Vector2D<i32> = (100, 30).into();
+
+        for &rotation in &[num!(0.), num!(0.125), num!(0.3), num!(0.5), num!(0.9)] {
+            for &scale in &[num!(0.5), num!(1.), num!(3.25)] {
+                let parameters = AffineParameters::from_transform(
+                    origin,
+                    (scale, scale * 2).into(),
+                    rotation,
+                    anchor,
+                );
+
+                let anchor: Vector2D<Num<i32, 8>> = anchor.change_base();
+                let shown = Vector2D::new(
+                    parameters.position.x
+                        + parameters.pa.change_base() * anchor.x
+                        + parameters.pb.change_base() * anchor.y,
+                    parameters.position.y
+                        + parameters.pc.change_base() * anchor.x
+                        + parameters.pd.change_base() * anchor.y,
+                );
+
+                assert_eq!(shown, origin);
+            }
+        }
+    }
+}
@@ -0,0 +1,158 @@
+#![deny(missing_docs)]
+//! Changing the backdrop colour on every line of the screen.
+//!
+//! The backdrop is the colour shown wherever no background or sprite is drawn,
+//! and is the first colour in the background palette. [`BackdropGradient`]
+//! uses the hblank DMA to change it after every line, which gives a sky
+//! gradient without using any tiles.
+//! ```no_run
+//! # #![no_main]
+//! # #![no_std]
+//! use agb::display::{backdrop::BackdropGradient, palette16::colour555};
+//!
+//! # fn gradient(mut gba: agb::Gba) {
+//! let vblank = agb::interrupt::VBlank::get();
+//!
+//! // from light blue at the top of the screen to orange at the bottom
+//! let mut gradient = BackdropGradient::between(colour555(12, 20, 31), colour555(31, 16, 4));
+//!
+//! loop {
+//!     vblank.wait_for_vblank();
+//!     gradient.commit();
+//! }
+//! # }
+//! ```
+//!
+//! Only one hblank effect can be used at a time, so this can't be used at the
+//! same time as a [per line scroll](super::tiled::RegularMap::set_scroll_per_line)
+//! or an [`HBlankDma`](crate::dma::HBlankDma).
+
+use crate::{
+    dma::{HBlankDma, HblankDmaUser},
+    memory_mapped::MemoryMapped,
+};
+
+use super::palette_fade::lerp_colour;
+
+const BACKDROP_COLOUR: MemoryMapped<u16> = unsafe { MemoryMapped::new(0x0500_0000) };
+const SCREEN_HEIGHT: usize = 160;
+
+/// Sets the backdrop colour of each line of the screen. The original backdrop
+/// colour is restored when this is dropped.
+pub struct BackdropGradient {
+    dma: HBlankDma,
+    original: u16,
+}
+
+impl BackdropGradient {
+    /// Uses `colours` as the backdrop colour of each line, starting at the top
+    /// of the screen. This takes effect from the next call to
+    /// [`commit`](BackdropGradient::commit).
+    ///
+    /// # Panics
+    ///
+    /// Panics if another hblank effect, such as a per line scroll, is already
+    /// being used.
+    #[must_use]
+    pub fn new(colours: &[u16; 160]) -> Self {
+        let mut dma = HBlankDma::claim(HblankDmaUser::BackdropGradient);
+        unsafe { dma.start(BACKDROP_COLOUR.as_ptr() as usize, colours) };
+
+        BackdropGradient {
+            dma,
+            original: BACKDROP_COLOUR.get(),
+        }
+    }
+
+    /// A gradient which goes smoothly from `top` on the first line of the
+    /// screen to `bottom` on the last one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another hblank effect is already being used, like [`new`](BackdropGradient::new).
+    #[must_use]
+    pub fn between(top: u16, bottom: u16) -> Self {
+        let mut colours = [0; SCREEN_HEIGHT];
+        for (line, colour) in colours.iter_mut().enumerate() {
+            let fraction = line as i32 * (1 << 8) / (SCREEN_HEIGHT as i32 - 1);
+            *colour = lerp_colour(top, bottom, fraction);
+        }
+
+        Self::new(&colours)
+    }
+
+    /// Changes the colour of each line from the next call to
+    /// [`commit`](BackdropGradient::commit). This can be called every frame
+    /// without tearing.
+    pub fn set_colours(&mut self, colours: &[u16; 160]) {
+        self.dma.set_table(colours);
+    }
+
+    /// Starts showing the latest colours. Call this during vblank every frame,
+    /// which also sets the colour of the first line so that it is never left
+    /// over from the end of the previous frame.
+    pub fn commit(&mut self) {
+        self.dma.commit();
+    }
+}
+
+impl Drop for BackdropGradient {
+    fn drop(&mut self) {
+        self.dma.stop();
+        BACKDROP_COLOUR.set(self.original);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::display::palette16::colour555;
+
+    #[test_case]
+    fn backdrop_gradient_interpolates_between_colours(_gba: &mut crate::Gba) {
+        let top = colour555(0, 8, 31);
+        let bottom = colour555(31, 0, 0);
+
+        let mut gradient = BackdropGradient::between(top, bottom);
+        gradient.commit();
+
+        let table = gradient.dma.front_table();
+        assert_eq!(table[0], top);
+        assert_eq!(table[SCREEN_HEIGHT - 1], bottom);
+        assert_eq!(table[SCREEN_HEIGHT], top);
+    }
+
+    #[test_case]
+    fn backdrop_gradient_restores_the_backdrop_and_releases_the_dma(gba: &mut crate::Gba) {
+        let dma0_control: MemoryMapped<u16> = unsafe { MemoryMapped::new(0x0400_00BA) };
+
+        BACKDROP_COLOUR.set(colour555(1, 2, 3));
+
+        let mut colours = [0; 160];
+        for (line, colour) in colours.iter_mut().enumerate() {
+            *colour = line as u16;
+        }
+
+        let mut gradient = BackdropGradient::new(&colours);
+        gradient.commit();
+
+        assert_ne!(dma0_control.get() & (1 << 15), 0);
+        assert_eq!(BACKDROP_COLOUR.get(), 0);
+
+        drop(gradient);
+
+        assert_eq!(dma0_control.get() & (1 << 15), 0);
+        assert_eq!(BACKDROP_COLOUR.get(), colour555(1, 2, 3));
+
+        // a per line scroll can use the hblank DMA once the gradient is dropped
+        let (gfx, _vram) = gba.display.video.tiled0();
+        let mut map = gfx.background(
+            crate::display::Priority::P0,
+            crate::display::tiled::RegularBackgroundSize::Background32x32,
+        );
+        map.set_scroll_per_line(&[0; 160]);
+        drop(map);
+
+        BACKDROP_COLOUR.set(0);
+    }
+}
@@ -1,7 +1,11 @@
-use crate::memory_mapped::MemoryMapped2DArray;
+use crate::{
+    fixnum::{Rect, Vector2D},
+    memory_mapped::MemoryMapped2DArray,
+};
 
 use super::{
-    set_graphics_mode, set_graphics_settings, DisplayMode, GraphicsSettings, HEIGHT, WIDTH,
+    draw::{copy_run16, fill_run16, sealed::DrawTarget, Draw},
+    enter_bitmap_mode, leave_bitmap_mode, DisplayMode, HEIGHT, WIDTH,
 };
 
 use core::convert::TryInto;
@@ -9,21 +13,137 @@ use core::convert::TryInto;
 const BITMAP_MODE_3: MemoryMapped2DArray<u16, { WIDTH as usize }, { HEIGHT as usize }> =
     unsafe { MemoryMapped2DArray::new(0x600_0000) };
 
+/// The 240x160 framebuffer of mode 3, where each pixel is a 15 bit colour.
+///
+/// The framebuffer overlaps the first half of the object tile memory, so
+/// objects can be displayed alongside the bitmap but only sprite tiles stored
+/// from `0x0601_4000` onwards will be shown.
+///
+/// The previous display mode and background layers are restored when this is
+/// dropped.
 #[non_exhaustive]
-pub struct Bitmap3 {}
+pub struct Bitmap3 {
+    previous_display_control: u16,
+}
 
 impl Bitmap3 {
     pub(crate) unsafe fn new() -> Self {
-        set_graphics_mode(DisplayMode::Bitmap3);
-        set_graphics_settings(GraphicsSettings::LAYER_BG2);
-        Bitmap3 {}
+        Bitmap3 {
+            previous_display_control: enter_bitmap_mode(DisplayMode::Bitmap3),
+        }
+    }
+
+    /// Sets the pixel at (x, y) to colour and panics if (x, y) is out of the
+    /// bounds of the screen.
+    pub fn set_pixel(&mut self, x: i32, y: i32, colour: u16) {
+        let x = x.try_into().unwrap();
+        let y = y.try_into().unwrap();
+        BITMAP_MODE_3.set(x, y, colour);
+    }
+
+    /// Gets the colour of the pixel at (x, y) and panics if (x, y) is out of
+    /// the bounds of the screen.
+    #[must_use]
+    pub fn pixel(&self, x: i32, y: i32) -> u16 {
+        let x = x.try_into().unwrap();
+        let y = y.try_into().unwrap();
+        BITMAP_MODE_3.get(x, y)
     }
 
     /// Draws point to screen at (x, y) coordinates with colour and panics if
     /// (x, y) is out of the bounds of the screen.
     pub fn draw_point(&mut self, x: i32, y: i32, colour: u16) {
-        let x = x.try_into().unwrap();
-        let y = y.try_into().unwrap();
+        self.set_pixel(x, y, colour);
+    }
+
+    /// Fills the given rectangle with colour. Any part of the rectangle which
+    /// is outside of the screen is ignored.
+    pub fn fill(&mut self, rect: Rect<i32>, colour: u16) {
+        self.fill_rect(rect, colour);
+    }
+
+    /// The whole framebuffer in row major order, useful as the destination of
+    /// a DMA copy. Video ram can only be written 16 or 32 bits at a time.
+    pub fn framebuffer_mut(&mut self) -> &mut [u16] {
+        unsafe {
+            core::slice::from_raw_parts_mut(
+                BITMAP_MODE_3.as_ptr(),
+                WIDTH as usize * HEIGHT as usize,
+            )
+        }
+    }
+}
+
+impl DrawTarget for Bitmap3 {
+    type Colour = u16;
+
+    fn size(&self) -> Vector2D<i32> {
+        (WIDTH, HEIGHT).into()
+    }
+
+    fn write_pixel(&mut self, x: usize, y: usize, colour: u16) {
         BITMAP_MODE_3.set(x, y, colour);
     }
+
+    fn fill_run(&mut self, x: usize, y: usize, length: usize, colour: u16) {
+        unsafe { fill_run16(pixel_address(x, y), length, colour) };
+    }
+
+    fn copy_run(&mut self, x: usize, y: usize, colours: &[u16]) {
+        unsafe { copy_run16(pixel_address(x, y), colours) };
+    }
+}
+
+fn pixel_address(x: usize, y: usize) -> *mut u16 {
+    unsafe { BITMAP_MODE_3.as_ptr().add(x + y * WIDTH as usize) }
+}
+
+impl Drop for Bitmap3 {
+    fn drop(&mut self) {
+        leave_bitmap_mode(self.previous_display_control);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::display::{GraphicsSettings, BITMAP_DISPLAY_CONTROL_MASK, DISPLAY_CONTROL};
+
+    #[test_case]
+    fn fill_is_clipped_to_the_screen(gba: &mut crate::Gba) {
+        let mut bitmap = gba.display.video.bitmap3();
+
+        bitmap.fill(Rect::new((0, 0).into(), (WIDTH, HEIGHT).into()), 0);
+        bitmap.fill(Rect::new((230, 150).into(), (20, 20).into()), 0x7FFF);
+
+        assert_eq!(bitmap.pixel(229, 159), 0);
+        assert_eq!(bitmap.pixel(230, 150), 0x7FFF);
+        assert_eq!(bitmap.pixel(239, 159), 0x7FFF);
+        assert_eq!(bitmap.framebuffer_mut()[239 + 159 * 240], 0x7FFF);
+
+        bitmap.fill(Rect::new((-10, -10).into(), (5, 5).into()), 0x001F);
+        assert_eq!(bitmap.pixel(0, 0), 0);
+    }
+
+    #[test_case]
+    fn dropping_restores_display_mode_and_keeps_objects(gba: &mut crate::Gba) {
+        let previous = DISPLAY_CONTROL.get();
+        DISPLAY_CONTROL.set(previous | GraphicsSettings::LAYER_OBJ.bits());
+
+        let bitmap = gba.display.video.bitmap3();
+        let during = DISPLAY_CONTROL.get();
+        assert_eq!(during & 0b111, 3);
+        assert_ne!(during & GraphicsSettings::LAYER_OBJ.bits(), 0);
+        assert_ne!(during & GraphicsSettings::LAYER_BG2.bits(), 0);
+
+        drop(bitmap);
+        let after = DISPLAY_CONTROL.get();
+        assert_eq!(
+            after & BITMAP_DISPLAY_CONTROL_MASK,
+            previous & BITMAP_DISPLAY_CONTROL_MASK
+        );
+        assert_ne!(after & GraphicsSettings::LAYER_OBJ.bits(), 0);
+
+        DISPLAY_CONTROL.set(previous);
+    }
 }
@@ -1,10 +1,15 @@
-use crate::memory_mapped::{MemoryMapped1DArray, MemoryMapped2DArray};
+use crate::{
+    fixnum::Vector2D,
+    memory_mapped::{MemoryMapped1DArray, MemoryMapped2DArray},
+};
 
 use super::{
-    set_graphics_mode, set_graphics_settings, DisplayMode, GraphicsSettings, DISPLAY_CONTROL,
-    HEIGHT, WIDTH,
+    draw::sealed::DrawTarget, enter_bitmap_mode, leave_bitmap_mode, DisplayMode, GraphicsSettings,
+    VramBytes, DISPLAY_CONTROL, HEIGHT, WIDTH,
 };
 
+use core::convert::TryInto;
+
 const BITMAP_PAGE_FRONT_MODE_4: MemoryMapped2DArray<
     u16,
     { (WIDTH / 2) as usize },
@@ -18,56 +23,95 @@ const BITMAP_PAGE_BACK_MODE_4: MemoryMapped2DArray<
 const PALETTE_BACKGROUND: MemoryMapped1DArray<u16, 256> =
     unsafe { MemoryMapped1DArray::new(0x0500_0000) };
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Page {
     Front = 0,
     Back = 1,
 }
 
+impl Page {
+    pub(super) fn other(self) -> Self {
+        match self {
+            Page::Front => Page::Back,
+            Page::Back => Page::Front,
+        }
+    }
+}
+
+/// Two 240x160 pages where each pixel is an index into the 256 colour
+/// background palette. One page is displayed while the other is drawn to, and
+/// [`Bitmap4::flip_page`] swaps them, which avoids tearing.
+///
+/// The back page overlaps the first half of the object tile memory, so objects
+/// can be displayed alongside the bitmap but only sprite tiles stored from
+/// `0x0601_4000` onwards will be shown.
+///
+/// The previous display mode and background layers are restored when this is
+/// dropped.
 #[non_exhaustive]
-pub struct Bitmap4 {}
+pub struct Bitmap4 {
+    previous_display_control: u16,
+}
 
 impl Bitmap4 {
     pub(crate) unsafe fn new() -> Self {
-        set_graphics_mode(DisplayMode::Bitmap4);
-        set_graphics_settings(GraphicsSettings::LAYER_BG2);
-        Bitmap4 {}
+        Bitmap4 {
+            previous_display_control: enter_bitmap_mode(DisplayMode::Bitmap4),
+        }
     }
 
     /// Draws point on specified page at (x, y) coordinates with colour index
     /// whose colour is specified in the background palette. Panics if (x, y) is
     /// out of the bounds of the screen.
     pub fn draw_point_page(&mut self, x: i32, y: i32, colour: u8, page: Page) {
-        let addr = match page {
-            Page::Front => BITMAP_PAGE_FRONT_MODE_4,
-            Page::Back => BITMAP_PAGE_BACK_MODE_4,
-        };
+        assert!(x < WIDTH, "x position {} is off the screen", x);
+        let x: usize = x.try_into().unwrap();
+        let y: usize = y.try_into().unwrap();
 
-        let x_in_screen = (x / 2) as usize;
-        let y_in_screen = y as usize;
+        row(page, y).set_byte(x, colour);
+    }
 
-        let c = addr.get(x_in_screen, y_in_screen);
-        if x & 0b1 != 0 {
-            addr.set(x_in_screen, y_in_screen, c | u16::from(colour) << 8);
-        } else {
-            addr.set(x_in_screen, y_in_screen, c | u16::from(colour));
-        }
+    /// Gets the colour index of the pixel at (x, y) on the specified page.
+    /// Panics if (x, y) is out of the bounds of the screen.
+    #[must_use]
+    pub fn pixel_page(&self, x: i32, y: i32, page: Page) -> u8 {
+        assert!(x < WIDTH, "x position {} is off the screen", x);
+        let x: usize = x.try_into().unwrap();
+        let y: usize = y.try_into().unwrap();
+
+        row(page, y).byte(x)
     }
 
     /// Draws point on the non-current page at (x, y) coordinates with colour
     /// index whose colour is specified in the background palette. Panics if (x,
     /// y) is out of the bounds of the screen.
     pub fn draw_point(&mut self, x: i32, y: i32, colour: u8) {
-        let disp = DISPLAY_CONTROL.get();
+        self.set_pixel(x, y, colour);
+    }
 
-        // get other page
-        let page = if disp & GraphicsSettings::PAGE_SELECT.bits() != 0 {
-            Page::Front
-        } else {
+    /// Sets the pixel at (x, y) on the [drawing page][Bitmap4::drawing_page]
+    /// to the colour index. Panics if (x, y) is out of the bounds of the
+    /// screen.
+    pub fn set_pixel(&mut self, x: i32, y: i32, colour: u8) {
+        let page = self.drawing_page();
+        self.draw_point_page(x, y, colour, page);
+    }
+
+    /// The page which is currently being displayed.
+    #[must_use]
+    pub fn displayed_page(&self) -> Page {
+        if DISPLAY_CONTROL.get() & GraphicsSettings::PAGE_SELECT.bits() != 0 {
             Page::Back
-        };
+        } else {
+            Page::Front
+        }
+    }
 
-        self.draw_point_page(x, y, colour, page);
+    /// The page which isn't being displayed, so is safe to draw to without
+    /// tearing.
+    #[must_use]
+    pub fn drawing_page(&self) -> Page {
+        self.displayed_page().other()
     }
 
     /// Sets the colour of colour index in the background palette.
@@ -75,11 +119,98 @@ impl Bitmap4 {
         PALETTE_BACKGROUND.set(entry as usize, colour);
     }
 
+    /// Gets the colour of colour index in the background palette.
+    #[must_use]
+    pub fn palette_entry(&self, entry: u8) -> u16 {
+        PALETTE_BACKGROUND.get(entry as usize)
+    }
+
+    /// Copies the whole 256 colour background palette.
+    pub fn set_palette(&mut self, palette: &[u16; 256]) {
+        PALETTE_BACKGROUND.write_slice(0, palette);
+    }
+
     /// Flips page, changing the Gameboy advance to draw the contents of the
-    /// other page
+    /// other page. This should be called during vblank to avoid tearing.
     pub fn flip_page(&mut self) {
         let disp = DISPLAY_CONTROL.get();
         let swapped = disp ^ GraphicsSettings::PAGE_SELECT.bits();
         DISPLAY_CONTROL.set(swapped);
     }
 }
+
+impl DrawTarget for Bitmap4 {
+    type Colour = u8;
+
+    fn size(&self) -> Vector2D<i32> {
+        (WIDTH, HEIGHT).into()
+    }
+
+    fn write_pixel(&mut self, x: usize, y: usize, colour: u8) {
+        row(self.drawing_page(), y).set_byte(x, colour);
+    }
+
+    fn fill_run(&mut self, x: usize, y: usize, length: usize, colour: u8) {
+        row(self.drawing_page(), y).fill_bytes(x, length, colour);
+    }
+
+    fn copy_run(&mut self, x: usize, y: usize, colours: &[u8]) {
+        row(self.drawing_page(), y).write_bytes(x, colours);
+    }
+}
+
+fn page_address(
+    page: Page,
+) -> MemoryMapped2DArray<u16, { (WIDTH / 2) as usize }, { HEIGHT as usize }> {
+    match page {
+        Page::Front => BITMAP_PAGE_FRONT_MODE_4,
+        Page::Back => BITMAP_PAGE_BACK_MODE_4,
+    }
+}
+
+// Video ram can't be written a byte at a time, so the pixels are written through VramBytes which
+// keeps the other pixel in the same halfword
+fn row(page: Page, y: usize) -> VramBytes {
+    assert!(y < HEIGHT as usize, "y position {} is off the screen", y);
+    unsafe {
+        VramBytes::new(
+            page_address(page).as_ptr().add(y * (WIDTH / 2) as usize) as usize,
+            WIDTH as usize,
+        )
+    }
+}
+
+impl Drop for Bitmap4 {
+    fn drop(&mut self) {
+        leave_bitmap_mode(self.previous_display_control);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_case]
+    fn set_pixel_keeps_the_neighbouring_pixel(gba: &mut crate::Gba) {
+        let mut bitmap = gba.display.video.bitmap4();
+        let page = bitmap.drawing_page();
+
+        bitmap.draw_point_page(10, 20, 0xAB, page);
+        bitmap.draw_point_page(11, 20, 0xCD, page);
+        bitmap.draw_point_page(10, 20, 0x12, page);
+
+        assert_eq!(page_address(page).get(5, 20), 0xCD12);
+    }
+
+    #[test_case]
+    fn flipping_swaps_the_drawing_page(gba: &mut crate::Gba) {
+        let mut bitmap = gba.display.video.bitmap4();
+
+        assert_eq!(bitmap.displayed_page(), Page::Front);
+        assert_eq!(bitmap.drawing_page(), Page::Back);
+
+        bitmap.flip_page();
+        assert_eq!(bitmap.displayed_page(), Page::Back);
+        assert_eq!(bitmap.drawing_page(), Page::Front);
+    }
+}
@@ -0,0 +1,275 @@
+use crate::{
+    fixnum::{Num, Rect, Vector2D},
+    memory_mapped::{MemoryMapped, MemoryMapped1DArray, MemoryMapped2DArray},
+};
+
+pub use super::bitmap4::Page;
+use super::{
+    affine::AffineParameters,
+    draw::{copy_run16, fill_run16, sealed::DrawTarget},
+    enter_bitmap_mode, leave_bitmap_mode, DisplayMode, GraphicsSettings, DISPLAY_CONTROL,
+};
+
+use core::convert::TryInto;
+
+const MODE_5_WIDTH: usize = 160;
+const MODE_5_HEIGHT: usize = 128;
+
+const BITMAP_PAGE_FRONT_MODE_5: MemoryMapped2DArray<u16, MODE_5_WIDTH, MODE_5_HEIGHT> =
+    unsafe { MemoryMapped2DArray::new(0x600_0000) };
+const BITMAP_PAGE_BACK_MODE_5: MemoryMapped2DArray<u16, MODE_5_WIDTH, MODE_5_HEIGHT> =
+    unsafe { MemoryMapped2DArray::new(0x600_A000) };
+const PALETTE_BACKGROUND: MemoryMapped1DArray<u16, 256> =
+    unsafe { MemoryMapped1DArray::new(0x0500_0000) };
+
+const BG2_AFFINE_MATRIX: MemoryMapped1DArray<i16, 4> =
+    unsafe { MemoryMapped1DArray::new(0x0400_0020) };
+const BG2_REFERENCE_X: MemoryMapped<i32> = unsafe { MemoryMapped::new(0x0400_0028) };
+const BG2_REFERENCE_Y: MemoryMapped<i32> = unsafe { MemoryMapped::new(0x0400_002C) };
+
+/// Two 160x128 pages where each pixel is a 15 bit colour. One page is
+/// displayed while the other is drawn to, and [`Bitmap5::flip_page`] swaps
+/// them.
+///
+/// The pages don't cover the whole screen, and the border around them shows
+/// the colour set by [`Bitmap5::set_border_colour`]. Alternatively, the page
+/// can be scaled up using the affine transform of background 2, for example
+/// using [`Bitmap5::stretch_to_screen`].
+///
+/// The back page overlaps the first half of the object tile memory, so objects
+/// can be displayed alongside the bitmap but only sprite tiles stored from
+/// `0x0601_4000` onwards will be shown.
+///
+/// The previous display mode and background layers are restored when this is
+/// dropped.
+#[non_exhaustive]
+pub struct Bitmap5 {
+    previous_display_control: u16,
+}
+
+impl Bitmap5 {
+    /// Width of each page in pixels
+    pub const WIDTH: i32 = MODE_5_WIDTH as i32;
+    /// Height of each page in pixels
+    pub const HEIGHT: i32 = MODE_5_HEIGHT as i32;
+
+    pub(crate) unsafe fn new() -> Self {
+        let bitmap = Bitmap5 {
+            previous_display_control: enter_bitmap_mode(DisplayMode::Bitmap5),
+        };
+        reset_transform();
+
+        bitmap
+    }
+
+    /// Sets the pixel at (x, y) on the specified page to colour and panics if
+    /// (x, y) is outside of the page.
+    pub fn set_pixel_page(&mut self, x: i32, y: i32, colour: u16, page: Page) {
+        let x = x.try_into().unwrap();
+        let y = y.try_into().unwrap();
+        page_address(page).set(x, y, colour);
+    }
+
+    /// Sets the pixel at (x, y) on the [drawing page][Bitmap5::drawing_page]
+    /// to colour and panics if (x, y) is outside of the page.
+    pub fn set_pixel(&mut self, x: i32, y: i32, colour: u16) {
+        let page = self.drawing_page();
+        self.set_pixel_page(x, y, colour, page);
+    }
+
+    /// Gets the colour of the pixel at (x, y) on the specified page and panics
+    /// if (x, y) is outside of the page.
+    #[must_use]
+    pub fn pixel_page(&self, x: i32, y: i32, page: Page) -> u16 {
+        let x = x.try_into().unwrap();
+        let y = y.try_into().unwrap();
+        page_address(page).get(x, y)
+    }
+
+    /// Fills the given rectangle of the specified page with colour. Any part
+    /// of the rectangle which is outside of the page is ignored.
+    pub fn fill_page(&mut self, rect: Rect<i32>, colour: u16, page: Page) {
+        let bounds = Rect::new((0, 0).into(), (Self::WIDTH, Self::HEIGHT).into());
+        let rect = match bounds.overlapping_rect(rect) {
+            Some(rect) => rect,
+            None => return,
+        };
+
+        for y in rect.position.y..(rect.position.y + rect.size.y) {
+            unsafe {
+                fill_run16(
+                    pixel_address(page, rect.position.x as usize, y as usize),
+                    rect.size.x as usize,
+                    colour,
+                );
+            }
+        }
+    }
+
+    /// Fills the given rectangle of the [drawing page][Bitmap5::drawing_page]
+    /// with colour. Any part of the rectangle which is outside of the page is
+    /// ignored.
+    pub fn fill(&mut self, rect: Rect<i32>, colour: u16) {
+        let page = self.drawing_page();
+        self.fill_page(rect, colour, page);
+    }
+
+    /// The page which is currently being displayed.
+    #[must_use]
+    pub fn displayed_page(&self) -> Page {
+        if DISPLAY_CONTROL.get() & GraphicsSettings::PAGE_SELECT.bits() != 0 {
+            Page::Back
+        } else {
+            Page::Front
+        }
+    }
+
+    /// The page which isn't being displayed, so is safe to draw to without
+    /// tearing.
+    #[must_use]
+    pub fn drawing_page(&self) -> Page {
+        self.displayed_page().other()
+    }
+
+    /// Flips page, changing the Gameboy advance to draw the contents of the
+    /// other page. This should be called during vblank to avoid tearing.
+    pub fn flip_page(&mut self) {
+        let disp = DISPLAY_CONTROL.get();
+        let swapped = disp ^ GraphicsSettings::PAGE_SELECT.bits();
+        DISPLAY_CONTROL.set(swapped);
+    }
+
+    /// Sets the colour of the part of the screen not covered by the page. This
+    /// is the backdrop colour, so is shared with all the other display modes.
+    pub fn set_border_colour(&mut self, colour: u16) {
+        PALETTE_BACKGROUND.set(0, colour);
+    }
+
+    /// Sets the affine matrix of background 2, which maps each screen pixel
+    /// to the pixel of the page displayed there. `pa` and `pd` are the
+    /// horizontal and vertical step through the page for each pixel across
+    /// and down the screen, so values less than 1 scale the page up.
+    pub fn set_transform(
+        &mut self,
+        pa: Num<i16, 8>,
+        pb: Num<i16, 8>,
+        pc: Num<i16, 8>,
+        pd: Num<i16, 8>,
+    ) {
+        BG2_AFFINE_MATRIX.set(0, pa.to_raw());
+        BG2_AFFINE_MATRIX.set(1, pb.to_raw());
+        BG2_AFFINE_MATRIX.set(2, pc.to_raw());
+        BG2_AFFINE_MATRIX.set(3, pd.to_raw());
+    }
+
+    /// Sets the position in the page which is displayed at the top left of
+    /// the screen.
+    pub fn set_origin(&mut self, origin: Vector2D<Num<i32, 8>>) {
+        BG2_REFERENCE_X.set(origin.x.to_raw());
+        BG2_REFERENCE_Y.set(origin.y.to_raw());
+    }
+
+    /// Sets both the affine matrix and the origin of background 2, for example
+    /// to rotate and scale the page using
+    /// [`AffineParameters::from_transform`].
+    pub fn set_affine_parameters(&mut self, parameters: &AffineParameters) {
+        self.set_transform(parameters.pa, parameters.pb, parameters.pc, parameters.pd);
+        self.set_origin(parameters.position);
+    }
+
+    /// Scales the page up so that it covers the whole screen.
+    pub fn stretch_to_screen(&mut self) {
+        let zero = Num::new(0);
+        self.set_transform(
+            Num::from_raw((Self::WIDTH * 256 / super::WIDTH) as i16),
+            zero,
+            zero,
+            Num::from_raw((Self::HEIGHT * 256 / super::HEIGHT) as i16),
+        );
+        self.set_origin((0, 0).into());
+    }
+}
+
+fn page_address(page: Page) -> MemoryMapped2DArray<u16, MODE_5_WIDTH, MODE_5_HEIGHT> {
+    match page {
+        Page::Front => BITMAP_PAGE_FRONT_MODE_5,
+        Page::Back => BITMAP_PAGE_BACK_MODE_5,
+    }
+}
+
+fn pixel_address(page: Page, x: usize, y: usize) -> *mut u16 {
+    unsafe { page_address(page).as_ptr().add(x + y * MODE_5_WIDTH) }
+}
+
+impl DrawTarget for Bitmap5 {
+    type Colour = u16;
+
+    fn size(&self) -> Vector2D<i32> {
+        (Self::WIDTH, Self::HEIGHT).into()
+    }
+
+    fn write_pixel(&mut self, x: usize, y: usize, colour: u16) {
+        page_address(self.drawing_page()).set(x, y, colour);
+    }
+
+    fn fill_run(&mut self, x: usize, y: usize, length: usize, colour: u16) {
+        unsafe { fill_run16(pixel_address(self.drawing_page(), x, y), length, colour) };
+    }
+
+    fn copy_run(&mut self, x: usize, y: usize, colours: &[u16]) {
+        unsafe { copy_run16(pixel_address(self.drawing_page(), x, y), colours) };
+    }
+}
+
+fn reset_transform() {
+    BG2_AFFINE_MATRIX.set(0, 1 << 8);
+    BG2_AFFINE_MATRIX.set(1, 0);
+    BG2_AFFINE_MATRIX.set(2, 0);
+    BG2_AFFINE_MATRIX.set(3, 1 << 8);
+    BG2_REFERENCE_X.set(0);
+    BG2_REFERENCE_Y.set(0);
+}
+
+impl Drop for Bitmap5 {
+    fn drop(&mut self) {
+        reset_transform();
+        leave_bitmap_mode(self.previous_display_control);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_case]
+    fn fill_is_clipped_to_the_page(gba: &mut crate::Gba) {
+        let mut bitmap = gba.display.video.bitmap5();
+        let page = bitmap.drawing_page();
+
+        bitmap.fill_page(
+            Rect::new((0, 0).into(), (Bitmap5::WIDTH, Bitmap5::HEIGHT).into()),
+            0,
+            page,
+        );
+        bitmap.fill_page(
+            Rect::new((150, 120).into(), (100, 100).into()),
+            0x7C00,
+            page,
+        );
+
+        assert_eq!(bitmap.pixel_page(149, 127, page), 0);
+        assert_eq!(bitmap.pixel_page(150, 120, page), 0x7C00);
+        assert_eq!(bitmap.pixel_page(159, 127, page), 0x7C00);
+    }
+
+    #[test_case]
+    fn border_colour_is_the_backdrop(gba: &mut crate::Gba) {
+        let mut bitmap = gba.display.video.bitmap5();
+        let previous = PALETTE_BACKGROUND.get(0);
+
+        bitmap.set_border_colour(0x03E0);
+        assert_eq!(PALETTE_BACKGROUND.get(0), 0x03E0);
+
+        PALETTE_BACKGROUND.set(0, previous);
+    }
+}
@@ -17,7 +17,7 @@
 
 use crate::{fixnum::Num, memory_mapped::set_bits};
 
-use super::tiled::BackgroundID;
+use super::tiled::{BackgroundID, RegularMap};
 
 /// The layers, top layer will be blended into the bottom layer
 #[derive(Clone, Copy, Debug)]
@@ -172,11 +172,12 @@ impl Blend {
         self
     }
 
-    /// Set the weight for the blend on a particular layer.
+    /// Set the weight for the blend on a particular layer. The hardware works
+    /// in steps of 1/16, and weights above 1 are treated as 1.
     pub fn set_blend_weight(&mut self, layer: Layer, value: Num<u8, 4>) -> &mut Self {
         self.blend_weights = set_bits(
             self.blend_weights,
-            value.to_raw() as u16,
+            hardware_weight(value),
             5,
             (layer as usize) * 8,
         );
@@ -184,9 +185,10 @@ impl Blend {
         self
     }
 
-    /// Set the fade of brighten or darken
+    /// Set the fade of brighten or darken. The hardware works in steps of
+    /// 1/16, and fades above 1 are treated as 1.
     pub fn set_fade(&mut self, value: Num<u8, 4>) -> &mut Self {
-        self.fade_weight = value.to_raw() as u16;
+        self.fade_weight = hardware_weight(value);
 
         self
     }
@@ -209,8 +211,189 @@ impl Blend {
     }
 }
 
+/// A cross-fade between two backgrounds, created by [Blend::cross_fade].
+/// While this exists it has control of the blend registers, and the blend
+/// state from before the cross-fade is put back and committed when it is
+/// dropped.
+pub struct CrossFade<'blend> {
+    blend: &'blend mut Blend,
+    // the targets, blend weights and fade weight from before the cross-fade
+    previous: (u16, u16, u16),
+    outgoing: u8,
+    frame: u16,
+    frames: u16,
+}
+
+impl Blend {
+    /// Fades from `outgoing` to `incoming` over `frames` frames, by alpha
+    /// blending `outgoing` on to `incoming`. Both backgrounds should already be
+    /// visible, and `outgoing` must be in front of `incoming` so must have the
+    /// lower priority number.
+    ///
+    /// Call [CrossFade::update] once a frame after waiting for vblank, and
+    /// [CrossFade::finish] once it is done to hide `outgoing`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `outgoing` isn't in front of `incoming`, or if `frames` is 0.
+    pub fn cross_fade(
+        &mut self,
+        outgoing: &RegularMap,
+        incoming: &RegularMap,
+        frames: u16,
+    ) -> CrossFade<'_> {
+        assert!(
+            (outgoing.priority() as u8) < (incoming.priority() as u8),
+            "the outgoing background must be in front of the incoming one"
+        );
+        assert!(frames > 0, "a cross-fade must last at least one frame");
+
+        let previous = (self.targets, self.blend_weights, self.fade_weight);
+
+        self.reset()
+            .set_background_enable(Layer::Top, BackgroundID(outgoing.background_id()), true)
+            .set_background_enable(Layer::Bottom, BackgroundID(incoming.background_id()), true)
+            .set_blend_mode(BlendMode::Normal);
+
+        let mut cross_fade = CrossFade {
+            blend: self,
+            previous,
+            outgoing: outgoing.background_id(),
+            frame: 0,
+            frames,
+        };
+        cross_fade.set_weights();
+
+        cross_fade
+    }
+}
+
+impl CrossFade<'_> {
+    /// Moves the cross-fade on by a frame and commits it, so should be called
+    /// just after waiting for vblank. Returns `true` once only the incoming
+    /// background can be seen.
+    pub fn update(&mut self) -> bool {
+        if self.frame < self.frames {
+            self.frame += 1;
+        }
+
+        self.set_weights();
+
+        self.is_done()
+    }
+
+    /// Whether the cross-fade has reached the incoming background
+    #[must_use]
+    pub fn is_done(&self) -> bool {
+        self.frame == self.frames
+    }
+
+    /// Hides the outgoing background and puts back the blend state from before
+    /// the cross-fade. This can be called before the cross-fade is done to stop
+    /// it early.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `outgoing` isn't the background which was faded out.
+    pub fn finish(self, outgoing: &mut RegularMap) {
+        assert_eq!(
+            outgoing.background_id(),
+            self.outgoing,
+            "finishing a cross-fade with the wrong background"
+        );
+
+        outgoing.hide();
+    }
+
+    fn set_weights(&mut self) {
+        let outgoing_weight = 16 * u32::from(self.frames - self.frame) / u32::from(self.frames);
+        let outgoing_weight = outgoing_weight as u8;
+
+        self.blend
+            .set_blend_weight(Layer::Top, Num::from_raw(outgoing_weight))
+            .set_blend_weight(Layer::Bottom, Num::from_raw(16 - outgoing_weight))
+            .commit();
+    }
+}
+
+impl Drop for CrossFade<'_> {
+    fn drop(&mut self) {
+        let (targets, blend_weights, fade_weight) = self.previous;
+
+        self.blend.targets = targets;
+        self.blend.blend_weights = blend_weights;
+        self.blend.fade_weight = fade_weight;
+        self.blend.commit();
+    }
+}
+
+// The hardware only uses the bottom 5 bits, and any value above 16 behaves as 16
+fn hardware_weight(value: Num<u8, 4>) -> u16 {
+    (value.to_raw() as u16).min(16)
+}
+
 impl Drop for Blend {
     fn drop(&mut self) {
         self.reset().commit();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixnum::num;
+
+    #[test_case]
+    fn weights_are_clamped_to_one(gba: &mut crate::Gba) {
+        let mut blend = gba.display.blend.get();
+
+        blend
+            .set_blend_weight(Layer::Top, num!(0.5))
+            .set_blend_weight(Layer::Bottom, num!(3.0))
+            .set_fade(num!(15.5));
+
+        assert_eq!(blend.blend_weights, 8 | (16 << 8));
+        assert_eq!(blend.fade_weight, 16);
+    }
+
+    #[test_case]
+    fn cross_fades_put_the_blend_state_back(gba: &mut crate::Gba) {
+        use crate::display::{tiled::RegularBackgroundSize, Priority, DISPLAY_CONTROL};
+
+        let (gfx, _vram) = gba.display.video.tiled0();
+        let mut outgoing = gfx.background(Priority::P0, RegularBackgroundSize::Background32x32);
+        let incoming = gfx.background(Priority::P1, RegularBackgroundSize::Background32x32);
+
+        let mut blend = gba.display.blend.get();
+        blend
+            .set_blend_mode(BlendMode::FadeToBlack)
+            .set_object_enable(Layer::Top, true)
+            .set_fade(num!(0.25));
+        let targets = blend.targets;
+
+        let mut cross_fade = blend.cross_fade(&outgoing, &incoming, 4);
+        assert_eq!(cross_fade.blend.blend_weights, 16);
+
+        let mut weights = [0; 4];
+        for weight in &mut weights {
+            cross_fade.update();
+            *weight = cross_fade.blend.blend_weights;
+        }
+
+        assert_eq!(
+            weights,
+            [12 | (4 << 8), 8 | (8 << 8), 4 | (12 << 8), 16 << 8]
+        );
+        assert!(cross_fade.is_done());
+
+        cross_fade.finish(&mut outgoing);
+
+        assert_eq!(blend.targets, targets);
+        assert_eq!(blend.blend_weights, 0);
+        assert_eq!(blend.fade_weight, 4);
+        assert_eq!(
+            DISPLAY_CONTROL.get() & (1 << (8 + outgoing.background_id())),
+            0
+        );
+    }
+}
@@ -0,0 +1,370 @@
+//! Drawing shapes and images onto the bitmap modes.
+//!
+//! [`Draw`] is implemented by [`Bitmap3`][super::bitmap3::Bitmap3],
+//! [`Bitmap4`][super::bitmap4::Bitmap4] and [`Bitmap5`][super::bitmap5::Bitmap5].
+//! The paged modes draw onto the page which isn't currently being displayed.
+//! Everything is clipped to the framebuffer, so shapes may be partially or
+//! entirely off the screen.
+//!
+//! ```no_run
+//! # #![no_main]
+//! # #![no_std]
+//! use agb::display::draw::Draw;
+//! use agb::fixnum::Rect;
+//!
+//! # fn draw(mut gba: agb::Gba) {
+//! let mut bitmap = gba.display.video.bitmap3();
+//! bitmap.fill_rect(Rect::new((10, 10).into(), (50, 20).into()), 0x001F);
+//! bitmap.draw_line((0, 0).into(), (239, 159).into(), 0x7FFF);
+//! # }
+//! ```
+
+use crate::{
+    dma::{dma_copy16, dma_fill32},
+    fixnum::{Rect, Vector2D},
+};
+
+pub(crate) mod sealed {
+    use crate::fixnum::Vector2D;
+
+    /// The operations each bitmap mode has to provide for [`Draw`][super::Draw].
+    /// Positions and lengths given to these are always within the framebuffer.
+    pub trait DrawTarget {
+        type Colour: Copy;
+
+        fn size(&self) -> Vector2D<i32>;
+        fn write_pixel(&mut self, x: usize, y: usize, colour: Self::Colour);
+        fn fill_run(&mut self, x: usize, y: usize, length: usize, colour: Self::Colour);
+        fn copy_run(&mut self, x: usize, y: usize, colours: &[Self::Colour]);
+    }
+}
+
+use sealed::DrawTarget;
+
+/// Drawing primitives shared by the bitmap modes. `Colour` is a 15 bit colour
+/// for modes 3 and 5, and a palette index for mode 4.
+pub trait Draw: DrawTarget {
+    /// Sets a single pixel, doing nothing if it is outside the framebuffer.
+    fn draw_pixel(&mut self, position: Vector2D<i32>, colour: Self::Colour) {
+        let size = self.size();
+        if position.x >= 0 && position.y >= 0 && position.x < size.x && position.y < size.y {
+            self.write_pixel(position.x as usize, position.y as usize, colour);
+        }
+    }
+
+    /// Draws a line between the two points, including both ends.
+    fn draw_line(&mut self, start: Vector2D<i32>, end: Vector2D<i32>, colour: Self::Colour) {
+        if start.y == end.y {
+            let left = start.x.min(end.x);
+            let width = (start.x - end.x).abs() + 1;
+            self.fill_rect(Rect::new((left, start.y).into(), (width, 1).into()), colour);
+            return;
+        }
+
+        // Bresenham's line algorithm
+        let dx = (end.x - start.x).abs();
+        let dy = -(end.y - start.y).abs();
+        let step_x = if start.x < end.x { 1 } else { -1 };
+        let step_y = if start.y < end.y { 1 } else { -1 };
+
+        let mut error = dx + dy;
+        let mut position = start;
+
+        loop {
+            self.draw_pixel(position, colour);
+            if position == end {
+                break;
+            }
+
+            let doubled_error = 2 * error;
+            if doubled_error >= dy {
+                error += dy;
+                position.x += step_x;
+            }
+            if doubled_error <= dx {
+                error += dx;
+                position.y += step_y;
+            }
+        }
+    }
+
+    /// Draws the outline of the rectangle, one pixel thick.
+    fn draw_rect(&mut self, rect: Rect<i32>, colour: Self::Colour) {
+        if rect.size.x <= 0 || rect.size.y <= 0 {
+            return;
+        }
+
+        let Rect { position, size } = rect;
+        self.fill_rect(Rect::new(position, (size.x, 1).into()), colour);
+        self.fill_rect(
+            Rect::new(
+                (position.x, position.y + size.y - 1).into(),
+                (size.x, 1).into(),
+            ),
+            colour,
+        );
+        self.fill_rect(Rect::new(position, (1, size.y).into()), colour);
+        self.fill_rect(
+            Rect::new(
+                (position.x + size.x - 1, position.y).into(),
+                (1, size.y).into(),
+            ),
+            colour,
+        );
+    }
+
+    /// Fills the rectangle with colour.
+    fn fill_rect(&mut self, rect: Rect<i32>, colour: Self::Colour) {
+        let rect = match clip(&rect, self.size()) {
+            Some(rect) => rect,
+            None => return,
+        };
+
+        for y in rect.position.y..(rect.position.y + rect.size.y) {
+            self.fill_run(
+                rect.position.x as usize,
+                y as usize,
+                rect.size.x as usize,
+                colour,
+            );
+        }
+    }
+
+    /// Draws the outline of a circle, one pixel thick.
+    fn draw_circle(&mut self, centre: Vector2D<i32>, radius: i32, colour: Self::Colour) {
+        if radius < 0 {
+            return;
+        }
+
+        // midpoint circle algorithm, drawing all 8 octants at once
+        let mut x = radius;
+        let mut y = 0;
+        let mut error = 1 - radius;
+
+        while x >= y {
+            for &(dx, dy) in &[
+                (x, y),
+                (y, x),
+                (-y, x),
+                (-x, y),
+                (-x, -y),
+                (-y, -x),
+                (y, -x),
+                (x, -y),
+            ] {
+                self.draw_pixel((centre.x + dx, centre.y + dy).into(), colour);
+            }
+
+            y += 1;
+            if error < 0 {
+                error += 2 * y + 1;
+            } else {
+                x -= 1;
+                error += 2 * (y - x) + 1;
+            }
+        }
+    }
+
+    /// Copies the `source_rect` part of an image to `destination`. The image
+    /// is stored in row major order and is `source_width` pixels wide.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `source_width` is not positive.
+    fn blit(
+        &mut self,
+        source: &[Self::Colour],
+        source_width: i32,
+        source_rect: Rect<i32>,
+        destination: Vector2D<i32>,
+    ) {
+        assert!(source_width > 0, "source width must be positive");
+        let source_height = source.len() as i32 / source_width;
+
+        let clipped_source = match clip(&source_rect, (source_width, source_height).into()) {
+            Some(rect) => rect,
+            None => return,
+        };
+
+        // the part of the source rectangle outside the image would have been drawn before the
+        // rest of it, so skip over where it would have gone
+        let destination = destination + (clipped_source.position - source_rect.position);
+        let destination_rect = Rect::new(destination, clipped_source.size);
+        let clipped = match clip(&destination_rect, self.size()) {
+            Some(rect) => rect,
+            None => return,
+        };
+
+        let source_offset = clipped_source.position + (clipped.position - destination);
+        for row in 0..clipped.size.y {
+            let start = ((source_offset.y + row) * source_width + source_offset.x) as usize;
+            self.copy_run(
+                clipped.position.x as usize,
+                (clipped.position.y + row) as usize,
+                &source[start..start + clipped.size.x as usize],
+            );
+        }
+    }
+}
+
+impl<T: DrawTarget> Draw for T {}
+
+fn clip(rect: &Rect<i32>, size: Vector2D<i32>) -> Option<Rect<i32>> {
+    rect.clip_to(&Rect::new((0, 0).into(), size))
+}
+
+/// Fills a run of 16 bit video ram, writing two pixels at a time where
+/// possible.
+pub(crate) unsafe fn fill_run16(dest: *mut u16, length: usize, colour: u16) {
+    let mut dest = dest;
+    let mut length = length;
+
+    if length > 0 && (dest as usize) & 0b10 != 0 {
+        dest.write_volatile(colour);
+        dest = dest.add(1);
+        length -= 1;
+    }
+
+    if length >= 2 {
+        let doubled = u32::from(colour) | (u32::from(colour) << 16);
+        dma_fill32(doubled, dest.cast(), length / 2);
+    }
+
+    if length & 1 != 0 {
+        dest.add(length - 1).write_volatile(colour);
+    }
+}
+
+/// Copies a run of pixels to 16 bit video ram.
+pub(crate) unsafe fn copy_run16(dest: *mut u16, colours: &[u16]) {
+    dma_copy16(colours.as_ptr(), dest, colours.len());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::display::{HEIGHT, WIDTH};
+
+    #[test_case]
+    fn lines_include_both_ends_and_are_clipped(gba: &mut crate::Gba) {
+        let mut bitmap = gba.display.video.bitmap3();
+        bitmap.fill_rect(Rect::new((0, 0).into(), (WIDTH, HEIGHT).into()), 0);
+
+        bitmap.draw_line((10, 10).into(), (20, 15).into(), 0x7FFF);
+        assert_eq!(bitmap.pixel(10, 10), 0x7FFF);
+        assert_eq!(bitmap.pixel(20, 15), 0x7FFF);
+        assert_eq!(bitmap.pixel(21, 15), 0);
+
+        bitmap.draw_line((-50, -50).into(), (5, 5).into(), 0x001F);
+        assert_eq!(bitmap.pixel(0, 0), 0x001F);
+        assert_eq!(bitmap.pixel(5, 5), 0x001F);
+
+        bitmap.draw_line((230, 100).into(), (300, 100).into(), 0x03E0);
+        assert_eq!(bitmap.pixel(239, 100), 0x03E0);
+        assert_eq!(bitmap.pixel(229, 100), 0);
+    }
+
+    #[test_case]
+    fn circles_and_rectangle_outlines(gba: &mut crate::Gba) {
+        let mut bitmap = gba.display.video.bitmap3();
+        bitmap.fill_rect(Rect::new((0, 0).into(), (WIDTH, HEIGHT).into()), 0);
+
+        bitmap.draw_circle((50, 50).into(), 10, 0x7FFF);
+        assert_eq!(bitmap.pixel(60, 50), 0x7FFF);
+        assert_eq!(bitmap.pixel(50, 40), 0x7FFF);
+        assert_eq!(bitmap.pixel(50, 50), 0);
+
+        bitmap.draw_rect(Rect::new((100, 100).into(), (5, 4).into()), 0x001F);
+        assert_eq!(bitmap.pixel(100, 100), 0x001F);
+        assert_eq!(bitmap.pixel(104, 103), 0x001F);
+        assert_eq!(bitmap.pixel(102, 101), 0);
+    }
+
+    #[test_case]
+    fn eight_bit_runs_keep_neighbouring_pixels(gba: &mut crate::Gba) {
+        let mut bitmap = gba.display.video.bitmap4();
+        let page = bitmap.drawing_page();
+
+        bitmap.fill_rect(Rect::new((0, 0).into(), (WIDTH, 1).into()), 1);
+        bitmap.fill_rect(Rect::new((3, 0).into(), (6, 1).into()), 2);
+
+        let colours: [u8; 10] = [1, 1, 1, 2, 2, 2, 2, 2, 2, 1];
+        for (x, &colour) in colours.iter().enumerate() {
+            assert_eq!(bitmap.pixel_page(x as i32, 0, page), colour);
+        }
+
+        bitmap.blit(
+            &[7, 8, 9],
+            3,
+            Rect::new((0, 0).into(), (3, 1).into()),
+            (5, 0).into(),
+        );
+        assert_eq!(bitmap.pixel_page(4, 0, page), 2);
+        assert_eq!(bitmap.pixel_page(5, 0, page), 7);
+        assert_eq!(bitmap.pixel_page(7, 0, page), 9);
+        assert_eq!(bitmap.pixel_page(8, 0, page), 2);
+    }
+
+    #[test_case]
+    fn blit_is_clipped(gba: &mut crate::Gba) {
+        let mut bitmap = gba.display.video.bitmap3();
+        bitmap.fill_rect(Rect::new((0, 0).into(), (WIDTH, HEIGHT).into()), 0);
+
+        let image: [u16; 6] = [1, 2, 3, 4, 5, 6];
+        bitmap.blit(
+            &image,
+            3,
+            Rect::new((0, 0).into(), (3, 2).into()),
+            (-1, 158).into(),
+        );
+
+        assert_eq!(bitmap.pixel(0, 158), 2);
+        assert_eq!(bitmap.pixel(1, 158), 3);
+        assert_eq!(bitmap.pixel(0, 159), 5);
+        assert_eq!(bitmap.pixel(2, 158), 0);
+    }
+
+    #[test_case]
+    fn blit_of_a_source_rect_outside_the_image_keeps_its_position(gba: &mut crate::Gba) {
+        let mut bitmap = gba.display.video.bitmap3();
+        bitmap.fill_rect(Rect::new((0, 0).into(), (WIDTH, HEIGHT).into()), 0);
+
+        let image: [u16; 6] = [1, 2, 3, 4, 5, 6];
+        bitmap.blit(
+            &image,
+            3,
+            Rect::new((-1, -1).into(), (3, 2).into()),
+            (10, 10).into(),
+        );
+
+        assert_eq!(bitmap.pixel(11, 11), 1);
+        assert_eq!(bitmap.pixel(12, 11), 2);
+        assert_eq!(bitmap.pixel(10, 10), 0);
+        assert_eq!(bitmap.pixel(10, 11), 0);
+        assert_eq!(bitmap.pixel(11, 10), 0);
+        assert_eq!(bitmap.pixel(13, 11), 0);
+        assert_eq!(bitmap.pixel(11, 12), 0);
+    }
+
+    #[test_case]
+    fn filling_the_screen_fits_in_a_frame(gba: &mut crate::Gba) {
+        let mut bitmap = gba.display.video.bitmap3();
+
+        let mut timers = gba.timers.timers();
+        let timer = &mut timers.timer2;
+        timer
+            .set_divider(crate::timer::Divider::Divider64)
+            .set_overflow_amount(0xFFFF)
+            .set_enabled(true);
+
+        let start = timer.value();
+        bitmap.fill_rect(Rect::new((0, 0).into(), (WIDTH, HEIGHT).into()), 0x1234);
+        let end = timer.value();
+        timer.set_enabled(false);
+
+        // a frame is 280896 cycles
+        let cycles = u32::from(end.wrapping_sub(start)) * 64;
+        assert!(cycles < 280_896 / 2, "filling took {} cycles", cycles);
+        assert_eq!(bitmap.pixel(239, 159), 0x1234);
+    }
+}
@@ -1,3 +1,4 @@
+use alloc::{string::String, vec::Vec};
 use core::fmt::{Error, Write};
 
 use crate::fixnum::Vector2D;
@@ -66,46 +67,112 @@ impl Font {
         vram_manager: &'a mut VRamManager,
     ) -> TextRenderer<'a> {
         TextRenderer {
-            current_x_pos: 0,
-            current_y_pos: 0,
+            cursor: Cursor::default(),
             font: self,
             tile_pos,
             vram_manager,
             bg,
-            background_colour,
-            foreground_colour,
-            tiles: Default::default(),
+            tiles: TextTiles::new(foreground_colour, background_colour),
+            unfinished_word: String::new(),
+        }
+    }
+
+    /// Reveals `text` a few letters at a time, for dialogue which appears as if it is being
+    /// typed. See [`TextReveal`].
+    pub fn reveal_text<'a>(
+        &'a self,
+        text: &'a str,
+        tile_pos: Vector2D<u16>,
+        foreground_colour: u8,
+        background_colour: u8,
+    ) -> TextReveal<'a> {
+        TextReveal {
+            font: self,
+            text,
+            next: 0,
+            cursor: Cursor::default(),
+            tile_pos,
+            tiles: TextTiles::new(foreground_colour, background_colour),
+            new_tiles: Vec::new(),
+            letters_per_update: 1,
+            punctuation_pause: 0,
+            pause_remaining: 0,
         }
     }
 }
 
 pub struct TextRenderer<'a> {
-    current_x_pos: i32,
-    current_y_pos: i32,
+    cursor: Cursor,
     font: &'a Font,
     tile_pos: Vector2D<u16>,
     vram_manager: &'a mut VRamManager,
     bg: &'a mut RegularMap,
-    background_colour: u8,
-    foreground_colour: u8,
+    tiles: TextTiles<'a>,
+    // the end of the text written so far, which isn't drawn until the whole word is known so
+    // that it can be wrapped
+    unfinished_word: String,
+}
+
+// Where the next letter goes, in pixels from the start of the text
+#[derive(Clone, Copy)]
+struct Cursor {
+    x: i32,
+    y: i32,
+    wrap_width: Option<i32>,
+    // the letter before the next one, to tell where words start
+    previous: char,
+}
+
+impl Default for Cursor {
+    fn default() -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            wrap_width: None,
+            previous: ' ',
+        }
+    }
+}
+
+// The tiles which text has been drawn on to so far, by their position in tiles from the start
+// of the text
+struct TextTiles<'a> {
     tiles: HashMap<(i32, i32), DynamicTile<'a>>,
+    foreground_colour: u8,
+    background_colour: u8,
+}
+
+/// The tiles of text which has been committed to a map, so that it can be
+/// cleared later.
+pub struct RenderedText {
+    positions: Vec<Vector2D<u16>>,
+}
+
+impl RenderedText {
+    /// Empties every tile which the text was drawn on, including the parts of
+    /// those tiles which were background. The tiles used by the text are
+    /// returned to the [`VRamManager`] on its next [`gc`][VRamManager::gc].
+    pub fn clear(self, bg: &mut RegularMap, vram_manager: &mut VRamManager) {
+        for pos in self.positions {
+            bg.clear_tile(vram_manager, pos);
+        }
+    }
 }
 
 impl<'a> Write for TextRenderer<'a> {
     fn write_str(&mut self, text: &str) -> Result<(), Error> {
-        for c in text.chars() {
-            if c == '\n' {
-                self.current_y_pos += self.font.line_height;
-                self.current_x_pos = 0;
-                continue;
-            }
+        // formatting splits text into several writes, which can split words, so the last word
+        // waits for the rest of it
+        let mut text = core::mem::take(&mut self.unfinished_word) + text;
+        let finished = text
+            .char_indices()
+            .rfind(|(_, c)| c.is_whitespace())
+            .map_or(0, |(i, c)| i + c.len_utf8());
 
-            let letter = self.font.letter(c);
+        self.render(&text[..finished]);
 
-            self.render_letter(letter);
-
-            self.current_x_pos += i32::from(letter.advance_width);
-        }
+        text.drain(..finished);
+        self.unfinished_word = text;
 
         Ok(())
     }
@@ -115,16 +182,90 @@ fn div_ceil(quotient: i32, divisor: i32) -> i32 {
     (quotient + divisor - 1) / divisor
 }
 
-impl<'a> TextRenderer<'a> {
-    fn render_letter(&mut self, letter: &FontLetter) {
-        let vram_manager = &mut self.vram_manager;
+impl Cursor {
+    fn new_line(&mut self, font: &Font) {
+        self.y += font.line_height;
+        self.x = 0;
+    }
+
+    fn text_width(font: &Font, text: Option<&str>) -> i32 {
+        text.unwrap_or("")
+            .chars()
+            .map(|c| i32::from(font.letter(c).advance_width))
+            .sum()
+    }
+
+    // Moves on past `c`, which is at byte `i` of `text`, returning the letter to draw and where
+    // to draw it if there is anything to draw. Any word starting at `c` must be complete in
+    // `text`.
+    fn advance<'f>(
+        &mut self,
+        font: &'f Font,
+        text: &str,
+        i: usize,
+        c: char,
+    ) -> Option<(&'f FontLetter, Vector2D<i32>)> {
+        let starts_word = self.previous.is_whitespace() && !c.is_whitespace();
+        self.previous = c;
+
+        if c == '\n' {
+            self.new_line(font);
+            return None;
+        }
+
+        let letter = font.letter(c);
+
+        if let Some(wrap_width) = self.wrap_width {
+            if starts_word {
+                // move whole words on to the next line where possible
+                let word_width =
+                    Self::text_width(font, text[i..].split(char::is_whitespace).next());
+                if self.x > 0 && self.x + word_width > wrap_width {
+                    self.new_line(font);
+                }
+            }
+
+            let letter_width = i32::from(letter.advance_width);
+            if self.x > 0 && self.x + letter_width > wrap_width {
+                self.new_line(font);
+            }
+
+            if self.x == 0 && c == ' ' {
+                return None;
+            }
+        }
+
+        let pos = (self.x, self.y).into();
+        self.x += i32::from(letter.advance_width);
+
+        Some((letter, pos))
+    }
+}
+
+impl<'a> TextTiles<'a> {
+    fn new(foreground_colour: u8, background_colour: u8) -> Self {
+        Self {
+            tiles: HashMap::default(),
+            foreground_colour,
+            background_colour,
+        }
+    }
+
+    // Draws `letter` with its origin at `pos`. The positions of any tiles which weren't drawn on
+    // before are added to `new_tiles`.
+    fn render_letter(
+        &mut self,
+        font: &Font,
+        letter: &FontLetter,
+        pos: Vector2D<i32>,
+        vram_manager: &mut VRamManager,
+        mut new_tiles: Option<&mut Vec<(i32, i32)>>,
+    ) {
         let foreground_colour = self.foreground_colour;
         let background_colour = self.background_colour;
 
-        let x_start = (self.current_x_pos + i32::from(letter.xmin)).max(0);
-        let y_start = self.current_y_pos + self.font.ascent
-            - i32::from(letter.height)
-            - i32::from(letter.ymin);
+        let x_start = (pos.x + i32::from(letter.xmin)).max(0);
+        let y_start = pos.y + font.ascent - i32::from(letter.height) - i32::from(letter.ymin);
 
         let x_tile_start = x_start / 8;
         let y_tile_start = y_start / 8;
@@ -171,6 +312,10 @@ impl<'a> TextRenderer<'a> {
 
                 if !zero {
                     let tile = self.tiles.entry((tile_x, tile_y)).or_insert_with(|| {
+                        if let Some(new_tiles) = new_tiles.as_deref_mut() {
+                            new_tiles.push((tile_x, tile_y));
+                        }
+
                         vram_manager.new_dynamic_tile().fill_with(background_colour)
                     });
 
@@ -182,28 +327,259 @@ impl<'a> TextRenderer<'a> {
         }
     }
 
-    pub fn commit(mut self) {
-        let tiles = core::mem::take(&mut self.tiles);
-
-        for ((x, y), tile) in tiles.into_iter() {
-            self.bg.set_tile(
-                self.vram_manager,
-                (self.tile_pos.x + x as u16, self.tile_pos.y + y as u16).into(),
+    // Puts the tiles at `positions` on to `bg` at `tile_pos`
+    fn place(
+        &self,
+        positions: &[(i32, i32)],
+        tile_pos: Vector2D<u16>,
+        bg: &mut RegularMap,
+        vram_manager: &mut VRamManager,
+    ) {
+        for &(x, y) in positions {
+            let tile = &self.tiles[&(x, y)];
+            bg.set_tile(
+                vram_manager,
+                (tile_pos.x + x as u16, tile_pos.y + y as u16).into(),
                 &tile.tile_set(),
                 TileSetting::from_raw(tile.tile_index()),
             );
-            self.vram_manager.remove_dynamic_tile(tile);
+        }
+    }
+
+    // Lets go of every tile, which stay in video ram for as long as a map uses them. Returns
+    // where the tiles were.
+    fn release(
+        &mut self,
+        tile_pos: Vector2D<u16>,
+        vram_manager: &mut VRamManager,
+    ) -> Vec<Vector2D<u16>> {
+        let tiles = core::mem::take(&mut self.tiles);
+        let mut positions = Vec::with_capacity(tiles.len());
+
+        for ((x, y), tile) in tiles.into_iter() {
+            vram_manager.remove_dynamic_tile(tile);
+            positions.push((tile_pos.x + x as u16, tile_pos.y + y as u16).into());
+        }
+
+        positions
+    }
+}
+
+impl<'a> TextRenderer<'a> {
+    /// Wraps text on to the next line rather than going beyond `width` tiles
+    /// from the start position. Text is wrapped between words where possible.
+    #[must_use]
+    pub fn wrap_at(mut self, width: u16) -> Self {
+        self.cursor.wrap_width = Some(i32::from(width) * 8);
+        self
+    }
+
+    /// Puts the rendered text on to the map, returning the tiles it covers so
+    /// that it can be cleared again using [`RenderedText::clear`].
+    pub fn commit(mut self) -> RenderedText {
+        let unfinished_word = core::mem::take(&mut self.unfinished_word);
+        self.render(&unfinished_word);
+
+        let positions: Vec<_> = self.tiles.tiles.keys().copied().collect();
+        self.tiles
+            .place(&positions, self.tile_pos, self.bg, self.vram_manager);
+
+        RenderedText {
+            positions: self.tiles.release(self.tile_pos, self.vram_manager),
+        }
+    }
+
+    fn render(&mut self, text: &str) {
+        for (i, c) in text.char_indices() {
+            if let Some((letter, pos)) = self.cursor.advance(self.font, text, i, c) {
+                self.tiles
+                    .render_letter(self.font, letter, pos, self.vram_manager, None);
+            }
         }
     }
 }
 
 impl<'a> Drop for TextRenderer<'a> {
     fn drop(&mut self) {
-        let tiles = core::mem::take(&mut self.tiles);
+        self.tiles.release(self.tile_pos, self.vram_manager);
+    }
+}
+
+/// What a [`TextReveal`] is doing after a call to [`TextReveal::update`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RevealStatus {
+    /// There is more of the current page to reveal
+    Revealing,
+    /// The whole page has been revealed. The next page is started by
+    /// [`TextReveal::next_page`], for example once the player presses a button.
+    EndOfPage,
+    /// All of the text has been revealed
+    Finished,
+}
+
+/// Text which appears a few letters at a time, like a typewriter, created by
+/// [`Font::reveal_text`]. Call [`update`](TextReveal::update) once a frame
+/// to draw the next few letters on to the map.
+///
+/// The text can be split into pages using [`TextReveal::PAGE_BREAK`]. Once a
+/// page is revealed, it stays on screen until
+/// [`next_page`](TextReveal::next_page) clears it and carries on with the
+/// next one.
+///
+/// Once you are done with the text, use [`finish`](TextReveal::finish) to
+/// get a [`RenderedText`] which can clear it from the map again. Dropping
+/// this without calling `finish` leaks the tiles used by the text, since they
+/// can only be given back to the [`VRamManager`], so debug builds panic if
+/// that happens.
+#[must_use = "the text's tiles are leaked unless `finish` is called"]
+pub struct TextReveal<'a> {
+    font: &'a Font,
+    text: &'a str,
+    // the byte in `text` of the next letter to reveal
+    next: usize,
+    cursor: Cursor,
+    tile_pos: Vector2D<u16>,
+    tiles: TextTiles<'a>,
+    new_tiles: Vec<(i32, i32)>,
+    letters_per_update: usize,
+    punctuation_pause: u16,
+    pause_remaining: u16,
+}
+
+impl<'a> TextReveal<'a> {
+    /// Put this in the text to wait for [`next_page`](TextReveal::next_page)
+    /// before clearing the text and carrying on.
+    pub const PAGE_BREAK: char = '\u{c}';
+
+    /// Wraps text on to the next line rather than going beyond `width` tiles
+    /// from the start position, in the same way as [`TextRenderer::wrap_at`].
+    pub fn wrap_at(mut self, width: u16) -> Self {
+        self.cursor.wrap_width = Some(i32::from(width) * 8);
+        self
+    }
+
+    /// Sets how many letters are drawn by each call to
+    /// [`update`](TextReveal::update). The default is 1.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `letters` is 0, since the text would never be revealed.
+    pub fn letters_per_update(mut self, letters: usize) -> Self {
+        assert!(
+            letters > 0,
+            "text must be revealed at least a letter at a time"
+        );
+        self.letters_per_update = letters;
+        self
+    }
+
+    /// Waits for `updates` calls to [`update`](TextReveal::update) after
+    /// revealing any of `.,!?;:`, so that there are pauses between
+    /// sentences. The default is not to pause.
+    pub fn pause_on_punctuation(mut self, updates: u16) -> Self {
+        self.punctuation_pause = updates;
+        self
+    }
+
+    /// Draws the next few letters of the current page on to `bg`.
+    pub fn update(&mut self, bg: &mut RegularMap, vram_manager: &mut VRamManager) -> RevealStatus {
+        if self.pause_remaining > 0 {
+            self.pause_remaining -= 1;
+            return RevealStatus::Revealing;
+        }
+
+        self.reveal(bg, vram_manager, Some(self.letters_per_update))
+    }
+
+    /// Draws the rest of the current page straight away, for example if the
+    /// player presses a button to skip the typing.
+    pub fn skip(&mut self, bg: &mut RegularMap, vram_manager: &mut VRamManager) -> RevealStatus {
+        self.pause_remaining = 0;
+        self.reveal(bg, vram_manager, None)
+    }
+
+    /// Clears the current page from `bg` and starts revealing the next one.
+    /// This does nothing unless the current page has been fully revealed.
+    pub fn next_page(&mut self, bg: &mut RegularMap, vram_manager: &mut VRamManager) {
+        if self.status() != RevealStatus::EndOfPage {
+            return;
+        }
+
+        self.next += Self::PAGE_BREAK.len_utf8();
+        self.cursor = Cursor {
+            wrap_width: self.cursor.wrap_width,
+            ..Cursor::default()
+        };
+
+        for pos in self.tiles.release(self.tile_pos, vram_manager) {
+            bg.clear_tile(vram_manager, pos);
+        }
+    }
+
+    /// Stops revealing text, returning the text revealed so far so that it
+    /// can be cleared from the map later.
+    pub fn finish(mut self, vram_manager: &mut VRamManager) -> RenderedText {
+        RenderedText {
+            positions: self.tiles.release(self.tile_pos, vram_manager),
+        }
+    }
+
+    fn status(&self) -> RevealStatus {
+        match self.text[self.next..].chars().next() {
+            None => RevealStatus::Finished,
+            Some(Self::PAGE_BREAK) => RevealStatus::EndOfPage,
+            Some(_) => RevealStatus::Revealing,
+        }
+    }
+
+    fn reveal(
+        &mut self,
+        bg: &mut RegularMap,
+        vram_manager: &mut VRamManager,
+        letters: Option<usize>,
+    ) -> RevealStatus {
+        let mut letters_drawn = 0;
+
+        while self.status() == RevealStatus::Revealing && Some(letters_drawn) != letters {
+            let c = self.text[self.next..].chars().next().unwrap();
+            let drawn = self.cursor.advance(self.font, self.text, self.next, c);
+
+            self.next += c.len_utf8();
+
+            if let Some((letter, pos)) = drawn {
+                self.tiles.render_letter(
+                    self.font,
+                    letter,
+                    pos,
+                    vram_manager,
+                    Some(&mut self.new_tiles),
+                );
+                letters_drawn += 1;
+            }
 
-        for (_, tile) in tiles.into_iter() {
-            self.vram_manager.remove_dynamic_tile(tile);
+            if letters.is_some() && self.punctuation_pause > 0 && ".,!?;:".contains(c) {
+                self.pause_remaining = self.punctuation_pause;
+                break;
+            }
         }
+
+        // tiles which were already on the map show new letters straight away, since
+        // drawing on a dynamic tile changes video ram directly
+        self.tiles
+            .place(&self.new_tiles, self.tile_pos, bg, vram_manager);
+        self.new_tiles.clear();
+
+        self.status()
+    }
+}
+
+impl<'a> Drop for TextReveal<'a> {
+    fn drop(&mut self) {
+        debug_assert!(
+            self.tiles.tiles.is_empty(),
+            "TextReveal dropped without calling finish, leaking {} tiles",
+            self.tiles.tiles.len()
+        );
     }
 }
 
@@ -253,4 +629,106 @@ mod tests {
 
         crate::test_runner::assert_image_output("examples/font/font-test-output.png");
     }
+
+    #[test_case]
+    fn wrapped_text_can_be_cleared(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+
+        let mut bg = gfx.background(
+            crate::display::Priority::P0,
+            crate::display::tiled::RegularBackgroundSize::Background32x32,
+        );
+
+        let mut writer = FONT
+            .render_text((2u16, 1u16).into(), 1, 2, &mut bg, &mut vram)
+            .wrap_at(6);
+        write!(&mut writer, "Some text which is too long for one line").unwrap();
+        let text = writer.commit();
+
+        assert!(text.positions.iter().all(|pos| pos.x >= 2 && pos.x < 2 + 6));
+        assert!(text.positions.iter().any(|pos| pos.y >= 4));
+
+        text.clear(&mut bg, &mut vram);
+        vram.gc();
+        assert_eq!(vram.stats().used_tile_slots, 0);
+    }
+
+    #[test_case]
+    fn text_split_across_writes_is_drawn_the_same_as_in_one_write(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+
+        let mut bg = gfx.background(
+            crate::display::Priority::P0,
+            crate::display::tiled::RegularBackgroundSize::Background32x32,
+        );
+
+        // the tile data of each tile drawn on, after drawing any unfinished word
+        fn drawn(writer: &mut TextRenderer<'_>) -> Vec<((i32, i32), Vec<u32>)> {
+            let unfinished_word = core::mem::take(&mut writer.unfinished_word);
+            writer.render(&unfinished_word);
+
+            let mut tiles: Vec<_> = writer
+                .tiles
+                .tiles
+                .iter()
+                .map(|(&pos, tile)| (pos, tile.tile_data.to_vec()))
+                .collect();
+            tiles.sort_by_key(|&(pos, _)| pos);
+            tiles
+        }
+
+        for &(first, second) in &[("hel", "lo world"), ("a hel", "lo world")] {
+            let mut writer = FONT
+                .render_text((1u16, 1u16).into(), 1, 2, &mut bg, &mut vram)
+                .wrap_at(4);
+            write!(&mut writer, "{}{}", first, second).unwrap();
+            let split = drawn(&mut writer);
+            drop(writer);
+
+            let mut writer = FONT
+                .render_text((1u16, 1u16).into(), 1, 2, &mut bg, &mut vram)
+                .wrap_at(4);
+            writer.write_str(&(String::from(first) + second)).unwrap();
+            let whole = drawn(&mut writer);
+            drop(writer);
+
+            assert_eq!(split, whole, "{:?} then {:?}", first, second);
+        }
+
+        vram.gc();
+        assert_eq!(vram.stats().used_tile_slots, 0);
+    }
+
+    #[test_case]
+    fn text_is_revealed_a_few_letters_at_a_time(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+
+        let mut bg = gfx.background(
+            crate::display::Priority::P0,
+            crate::display::tiled::RegularBackgroundSize::Background32x32,
+        );
+
+        let mut reveal = FONT
+            .reveal_text("Hello\u{c}World, again", (1u16, 1u16).into(), 1, 2)
+            .letters_per_update(2);
+
+        assert_eq!(reveal.update(&mut bg, &mut vram), RevealStatus::Revealing);
+        assert_eq!(reveal.update(&mut bg, &mut vram), RevealStatus::Revealing);
+        assert_eq!(reveal.update(&mut bg, &mut vram), RevealStatus::EndOfPage);
+        assert_eq!(reveal.update(&mut bg, &mut vram), RevealStatus::EndOfPage);
+
+        let first_page_tiles = vram.stats().used_tile_slots;
+        assert!(first_page_tiles > 0);
+
+        reveal.next_page(&mut bg, &mut vram);
+        vram.gc();
+        assert_eq!(vram.stats().used_tile_slots, 0);
+
+        assert_eq!(reveal.update(&mut bg, &mut vram), RevealStatus::Revealing);
+        assert_eq!(reveal.skip(&mut bg, &mut vram), RevealStatus::Finished);
+
+        reveal.finish(&mut vram).clear(&mut bg, &mut vram);
+        vram.gc();
+        assert_eq!(vram.stats().used_tile_slots, 0);
+    }
 }
@@ -4,12 +4,14 @@ use bitflags::bitflags;
 use modular_bitfield::BitfieldSpecifier;
 use video::Video;
 
-use self::{blend::Blend, object::ObjectController, window::Windows};
+use self::{blend::Blend, mosaic::Mosaic, object::ObjectController, window::Windows};
 
 /// Graphics mode 3. Bitmap mode that provides a 16-bit colour framebuffer.
 pub mod bitmap3;
 /// Graphics mode 4. Bitmap 4 provides two 8-bit paletted framebuffers with page switching.
 pub mod bitmap4;
+/// Graphics mode 5. Bitmap 5 provides two smaller 16-bit colour framebuffers with page switching.
+pub mod bitmap5;
 /// Test logo of agb.
 pub mod example_logo;
 /// Implements sprites.
@@ -23,11 +25,20 @@ pub mod tiled;
 /// Giving out graphics mode.
 pub mod video;
 
+pub mod affine;
+pub mod backdrop;
 pub mod blend;
+pub mod draw;
+pub mod mosaic;
+pub mod palette_fade;
+pub mod palette_manager;
 pub mod window;
 
 mod font;
-pub use font::{Font, FontLetter};
+pub use font::{Font, FontLetter, RenderedText, RevealStatus, TextReveal};
+
+mod vram_bytes;
+pub use vram_bytes::VramBytes;
 
 const DISPLAY_CONTROL: MemoryMapped<u16> = unsafe { MemoryMapped::new(0x0400_0000) };
 pub(crate) const DISPLAY_STATUS: MemoryMapped<u16> = unsafe { MemoryMapped::new(0x0400_0004) };
@@ -72,6 +83,7 @@ pub struct Display {
     pub object: ObjectDistribution,
     pub window: WindowDist,
     pub blend: BlendDist,
+    pub mosaic: MosaicDist,
 }
 
 #[non_exhaustive]
@@ -101,6 +113,15 @@ impl BlendDist {
     }
 }
 
+#[non_exhaustive]
+pub struct MosaicDist;
+
+impl MosaicDist {
+    pub fn get(&mut self) -> Mosaic {
+        Mosaic::new()
+    }
+}
+
 impl Display {
     pub(crate) const unsafe fn new() -> Self {
         Display {
@@ -108,6 +129,7 @@ impl Display {
             object: ObjectDistribution,
             window: WindowDist,
             blend: BlendDist,
+            mosaic: MosaicDist,
         }
     }
 }
@@ -123,13 +145,30 @@ unsafe fn set_graphics_mode(mode: DisplayMode) {
     DISPLAY_CONTROL.set(s);
 }
 
-unsafe fn set_graphics_settings(settings: GraphicsSettings) {
-    let current = DISPLAY_CONTROL.get();
-    // preserve display mode
-    let current = current & 0b111;
-    let s = settings.bits() | current;
+// The display mode, page and background layers are owned by a bitmap mode while
+// it exists and are put back when it is dropped. Everything else, such as the
+// object layer, is left alone so objects can be used alongside the bitmap.
+const BITMAP_DISPLAY_CONTROL_MASK: u16 = 0b111
+    | GraphicsSettings::PAGE_SELECT.bits()
+    | GraphicsSettings::LAYER_BG0.bits()
+    | GraphicsSettings::LAYER_BG1.bits()
+    | GraphicsSettings::LAYER_BG2.bits()
+    | GraphicsSettings::LAYER_BG3.bits();
+
+/// Switches to the given bitmap mode showing only background 2, returning the
+/// display control to pass to [leave_bitmap_mode] when the bitmap is dropped.
+unsafe fn enter_bitmap_mode(mode: DisplayMode) -> u16 {
+    let previous = DISPLAY_CONTROL.get();
+    DISPLAY_CONTROL
+        .set((previous & !BITMAP_DISPLAY_CONTROL_MASK) | GraphicsSettings::LAYER_BG2.bits());
+    set_graphics_mode(mode);
+
+    previous
+}
 
-    DISPLAY_CONTROL.set(s);
+fn leave_bitmap_mode(previous: u16) {
+    let current = DISPLAY_CONTROL.get() & !BITMAP_DISPLAY_CONTROL_MASK;
+    DISPLAY_CONTROL.set(current | (previous & BITMAP_DISPLAY_CONTROL_MASK));
 }
 
 #[allow(non_snake_case)]
@@ -141,7 +180,7 @@ pub fn busy_wait_for_vblank() {
     while VCOUNT.get() < 160 {}
 }
 
-#[derive(BitfieldSpecifier, Clone, Copy)]
+#[derive(BitfieldSpecifier, Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Priority {
     P0 = 0,
     P1 = 1,
@@ -0,0 +1,106 @@
+#![deny(missing_docs)]
+//! The mosaic effect of the GBA.
+//!
+//! Mosaic pixelates backgrounds and objects by repeating the top left pixel of
+//! each block of the given size. Backgrounds have to opt in to the effect using
+//! [`RegularMap::set_mosaic`][super::tiled::RegularMap::set_mosaic], and then
+//! the size of the blocks is shared between all backgrounds and is set here.
+//! You acquire the Mosaic struct through the [Display][super::Display] struct.
+//! ```no_run
+//! # #![no_main]
+//! # #![no_std]
+//! # fn mosaic(mut gba: agb::Gba) {
+//! let mut mosaic = gba.display.mosaic.get();
+//! // ...
+//! # }
+//! ```
+//! where `gba` is a mutable [Gba][crate::Gba] struct.
+
+use crate::memory_mapped::{set_bits, MemoryMapped};
+
+const MOSAIC: MemoryMapped<u16> = unsafe { MemoryMapped::new(0x0400_004C) };
+
+/// Manages the mosaic sizes of backgrounds and objects, which share a single
+/// register. Won't cause anything to change unless [Mosaic::commit] is called.
+pub struct Mosaic {
+    sizes: u16,
+}
+
+impl Mosaic {
+    pub(crate) fn new() -> Self {
+        let mosaic = Self { sizes: 0 };
+        mosaic.commit();
+
+        mosaic
+    }
+
+    /// Sets the size of the blocks for backgrounds with mosaic enabled, between
+    /// 1 and 16 pixels. A size of 1 in both directions has no visible effect.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either size is 0 or greater than 16.
+    pub fn set_background_size(&mut self, width: u8, height: u8) -> &mut Self {
+        self.sizes = set_bits(self.sizes, mosaic_bits(width, height), 8, 0);
+
+        self
+    }
+
+    /// Sets the size of the blocks for objects with mosaic enabled, between 1
+    /// and 16 pixels. A size of 1 in both directions has no visible effect.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either size is 0 or greater than 16.
+    pub fn set_object_size(&mut self, width: u8, height: u8) -> &mut Self {
+        self.sizes = set_bits(self.sizes, mosaic_bits(width, height), 8, 8);
+
+        self
+    }
+
+    /// Resets both background and object mosaic sizes to 1, which has no visible
+    /// effect.
+    pub fn reset(&mut self) -> &mut Self {
+        self.sizes = 0;
+
+        self
+    }
+
+    /// Commits the current state, should be called near after a call to wait
+    /// for next vblank.
+    pub fn commit(&self) {
+        MOSAIC.set(self.sizes);
+    }
+}
+
+fn mosaic_bits(width: u8, height: u8) -> u16 {
+    assert!(
+        (1..=16).contains(&width) && (1..=16).contains(&height),
+        "mosaic size must be between 1 and 16 pixels, got {}x{}",
+        width,
+        height
+    );
+
+    u16::from(width - 1) | (u16::from(height - 1) << 4)
+}
+
+impl Drop for Mosaic {
+    fn drop(&mut self) {
+        self.reset().commit();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test_case]
+    fn background_and_object_sizes_do_not_clobber_each_other(gba: &mut crate::Gba) {
+        let mut mosaic = gba.display.mosaic.get();
+
+        mosaic.set_object_size(3, 4);
+        mosaic.set_background_size(16, 2);
+        assert_eq!(mosaic.sizes, 0x321F);
+
+        mosaic.set_background_size(1, 1);
+        assert_eq!(mosaic.sizes, 0x3200);
+    }
+}
@@ -14,9 +14,11 @@ use modular_bitfield::{bitfield, BitfieldSpecifier};
 const BYTES_PER_TILE_4BPP: usize = 32;
 
 use super::palette16::Palette16;
+use super::palette_manager::{self, PaletteKind, ResidentPalette};
 use super::{Priority, DISPLAY_CONTROL};
-use crate::agb_alloc::block_allocator::BlockAllocator;
+use crate::agb_alloc::block_allocator::{AllocationStarts, BlockAllocator};
 use crate::agb_alloc::bump_allocator::StartEnd;
+use crate::agb_alloc::AllocatorStats;
 use crate::dma;
 use crate::fixnum::Vector2D;
 use crate::hash_map::HashMap;
@@ -93,21 +95,31 @@ unsafe fn get_object_controller(_r: ObjectControllerReference) -> ObjectControll
 /// "thread" is safe.
 type ObjectControllerReference<'a> = PhantomData<&'a UnsafeCell<()>>;
 
+const SPRITE_VRAM_SIZE: usize = 1024 * 8 * 4;
+
+// One bit for every 8 bytes of sprite vram, so that the sprites in vram can be listed by
+// ObjectController::debug_dump_allocators
+#[crate::ewram]
+static mut SPRITE_ALLOCATION_STARTS: [u32; SPRITE_VRAM_SIZE / 8 / 32] =
+    [0; SPRITE_VRAM_SIZE / 8 / 32];
+
 static SPRITE_ALLOCATOR: BlockAllocator = unsafe {
-    BlockAllocator::new(StartEnd {
-        start: || TILE_SPRITE,
-        end: || TILE_SPRITE + 1024 * 8 * 4,
-    })
+    BlockAllocator::new_tracking_allocations(
+        StartEnd {
+            start: || TILE_SPRITE,
+            end: || TILE_SPRITE + SPRITE_VRAM_SIZE,
+        },
+        AllocationStarts::new(
+            core::ptr::addr_of_mut!(SPRITE_ALLOCATION_STARTS).cast(),
+            SPRITE_VRAM_SIZE / 8 / 32,
+        ),
+    )
 };
 
-static PALETTE_ALLOCATOR: BlockAllocator = unsafe {
-    BlockAllocator::new(StartEnd {
-        start: || PALETTE_SPRITE,
-        end: || PALETTE_SPRITE + 0x200,
-    })
-};
+pub(crate) fn sprite_vram_stats() -> AllocatorStats {
+    SPRITE_ALLOCATOR.stats()
+}
 
-const PALETTE_SPRITE: usize = 0x0500_0200;
 const TILE_SPRITE: usize = 0x06010000;
 const OBJECT_ATTRIBUTE_MEMORY: usize = 0x0700_0000;
 
@@ -461,10 +473,18 @@ pub struct SpriteBorrow<'a> {
     phantom: ObjectControllerReference<'a>,
 }
 
+/// Returned by [`ObjectController::replace_palette`] when no sprite in vram uses the palette
+/// being replaced.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PaletteNotLoaded;
+
 #[derive(Clone, Copy)]
 struct Storage {
     location: u16,
     count: u16,
+    // How many of the borrows use each sprite palette bank. These can differ once the sprite's
+    // palette has been moved out of a bank by replace_palette.
+    palette_banks: [u16; 16],
 }
 
 impl Storage {
@@ -472,17 +492,9 @@ impl Storage {
         Self {
             location: (((d.as_ptr() as usize) - TILE_SPRITE) / BYTES_PER_TILE_4BPP) as u16,
             count: 1,
+            palette_banks: [0; 16],
         }
     }
-    fn from_palette_ptr(d: NonNull<u8>) -> Self {
-        Self {
-            location: ((d.as_ptr() as usize - PALETTE_SPRITE) / Palette16::layout().size()) as u16,
-            count: 1,
-        }
-    }
-    fn as_palette_ptr(self) -> *mut u8 {
-        (self.location as usize * Palette16::layout().size() + PALETTE_SPRITE) as *mut u8
-    }
     fn as_sprite_ptr(self) -> *mut u8 {
         (self.location as usize * BYTES_PER_TILE_4BPP + TILE_SPRITE) as *mut u8
     }
@@ -539,7 +551,6 @@ pub struct Object<'a> {
 }
 
 struct SpriteControllerInner {
-    palette: HashMap<PaletteId, Storage>,
     sprite: HashMap<SpriteId, Storage>,
 }
 
@@ -651,17 +662,74 @@ impl ObjectController {
         }
     }
 
+    /// Gives the sprite palette bank used by `old` the colours of `new` instead, so every object
+    /// whose sprite uses `old` changes at once without changing any of the objects. From then on
+    /// sprites using `new` share the bank, while sprites using `old` which are created later get a
+    /// bank of their own.
+    ///
+    /// Returns an error and does nothing if no sprite is using `old`.
+    pub fn replace_palette(
+        &self,
+        old: &'static Palette16,
+        new: &'static Palette16,
+    ) -> Result<(), PaletteNotLoaded> {
+        palette_manager::replace(PaletteKind::Sprite, old, new)
+            .map(|_| ())
+            .ok_or(PaletteNotLoaded)
+    }
+
+    /// Prints the usage of every palette bank which is in use to the mgba log, like
+    /// [`print_bank_usage`](palette_manager::print_bank_usage), along with the address of every
+    /// sprite in video ram using each sprite bank.
+    pub fn print_palette_usage(&self) {
+        let s = unsafe { get_object_controller(self.phantom) };
+
+        for usage in palette_manager::bank_usage() {
+            if usage.reference_count == 0 {
+                continue;
+            }
+
+            crate::println!("{}", usage);
+
+            if usage.sprite {
+                for sprite in s.sprite_controller.sprites_using_palette_bank(usage.bank) {
+                    crate::println!("    sprite {:p}", sprite);
+                }
+            }
+        }
+    }
+
+    /// Prints the layout of sprite video ram to the mgba log: the offset and size of every
+    /// sprite and free block in it and how fragmented it is, followed by how many sprite palette
+    /// banks are in use. This doesn't allocate, so is useful to call when a sprite can't be
+    /// allocated.
+    pub fn debug_dump_allocators(&self) {
+        SPRITE_ALLOCATOR.debug_dump("sprite vram", |line| crate::println!("{}", line));
+
+        let palettes = palette_manager::stats(PaletteKind::Sprite);
+        crate::println!(
+            "sprite palettes: {} of 16 banks in use",
+            palettes.allocations
+        );
+    }
+
+    /// Makes sure that `additional` more distinct sprites can be loaded into video ram without
+    /// the map tracking them having to grow, so that a loading screen can do the allocation
+    /// rather than it happening the first time lots of new sprites are shown.
+    pub fn reserve_sprites(&self, additional: usize) {
+        let mut s = unsafe { get_object_controller(self.phantom) };
+        s.sprite_controller.sprite.reserve(additional);
+    }
+
     pub(crate) fn new() -> Self {
         DISPLAY_CONTROL.set_bits(1, 1, 0x6);
         DISPLAY_CONTROL.set_bits(1, 1, 0xC);
         DISPLAY_CONTROL.set_bits(0, 1, 0x7);
 
-        for i in 0..128 {
-            unsafe {
-                (OBJECT_ATTRIBUTE_MEMORY as *mut u16)
-                    .add(i * 4)
-                    .write_volatile(HIDDEN_VALUE);
-            }
+        // Hides every object. This also sets the other attributes and the affine matrices, but
+        // those are all written before they are used.
+        unsafe {
+            dma::dma_fill16(HIDDEN_VALUE, OBJECT_ATTRIBUTE_MEMORY as *mut u16, 128 * 4);
         }
 
         unsafe { init_object_controller() };
@@ -993,20 +1061,6 @@ impl SpriteId {
     }
 }
 
-/// The palette id is a thin wrapper around the pointer to the palette in rom
-/// and is therefore a unique reference to a palette
-#[derive(Copy, Clone, PartialEq, Eq, Hash)]
-struct PaletteId(usize);
-
-impl Palette16 {
-    fn id(&'static self) -> PaletteId {
-        PaletteId(self as *const _ as usize)
-    }
-    const fn layout() -> Layout {
-        Layout::new::<Self>()
-    }
-}
-
 impl Sprite {
     fn id(&'static self) -> SpriteId {
         SpriteId(self as *const _ as usize)
@@ -1039,6 +1093,9 @@ impl SpriteControllerInner {
             storage.count += 1;
             let location = storage.location;
             let palette_location = self.palette(sprite.palette).unwrap();
+            if let Some(storage) = self.sprite.get_mut(&id) {
+                storage.palette_banks[palette_location as usize] += 1;
+            }
             Some(SpriteBorrow {
                 id,
                 palette_location,
@@ -1067,7 +1124,8 @@ impl SpriteControllerInner {
                 );
             }
 
-            let storage = Storage::from_sprite_ptr(dest);
+            let mut storage = Storage::from_sprite_ptr(dest);
+            storage.palette_banks[palette_location as usize] = 1;
             self.sprite.insert(id, storage);
 
             Some(SpriteBorrow {
@@ -1080,41 +1138,35 @@ impl SpriteControllerInner {
     }
 }
 
+// Enough for a few dozen distinct sprites, so that most games never need to resize the map
+const DEFAULT_SPRITE_CAPACITY: usize = 64;
+
 impl SpriteControllerInner {
     fn new() -> Self {
         Self {
-            palette: HashMap::default(),
-            sprite: HashMap::default(),
+            sprite: HashMap::with_capacity(DEFAULT_SPRITE_CAPACITY),
         }
     }
+    // Sprite palettes are allocated by the palette manager so that they can be shared with
+    // backgrounds and recoloured
     fn palette(&mut self, palette: &'static Palette16) -> Option<u16> {
-        let id = palette.id();
-        if let Some(storage) = self.palette.get_mut(&id) {
-            storage.count += 1;
-            Some(storage.location)
-        } else {
-            let dest = unsafe { PALETTE_ALLOCATOR.alloc(Palette16::layout())? };
-
-            unsafe {
-                dma::dma_copy16(
-                    palette.colours.as_ptr().cast(),
-                    dest.as_ptr().cast(),
-                    palette.colours.len(),
-                );
-            }
-
-            let storage = Storage::from_palette_ptr(dest);
-            self.palette.insert(id, storage);
+        let (bank, colours) = palette_manager::allocate(PaletteKind::Sprite, palette)?;
 
-            Some(storage.location)
+        if let Some(colours) = colours {
+            palette_manager::write_bank(PaletteKind::Sprite, bank.into(), &colours);
         }
+
+        Some(bank.into())
     }
 
-    fn return_sprite(&mut self, sprite: &'static Sprite) {
+    // The palette is released by bank rather than by its colours, as the bank may have been given
+    // a different palette by replace_palette
+    fn return_sprite(&mut self, sprite: &'static Sprite, palette_location: u16) {
         let storage = self.sprite.get_mut(&sprite.id());
 
         if let Some(storage) = storage {
             storage.count -= 1;
+            storage.palette_banks[palette_location as usize] -= 1;
 
             if storage.count == 0 {
                 unsafe { SPRITE_ALLOCATOR.dealloc(storage.as_sprite_ptr(), sprite.layout()) };
@@ -1122,39 +1174,66 @@ impl SpriteControllerInner {
             }
         }
 
-        self.return_palette(sprite.palette);
+        palette_manager::release(PaletteKind::Sprite, palette_location as u8);
     }
 
-    fn return_palette(&mut self, palette: &'static Palette16) {
-        let id = palette.id();
-
-        if let Some(storage) = self.palette.get_mut(&id) {
-            storage.count -= 1;
-
-            if storage.count == 0 {
-                unsafe { PALETTE_ALLOCATOR.dealloc(storage.as_palette_ptr(), Palette16::layout()) };
-                self.palette.remove(&id);
-            }
-        }
+    // Grouped by the banks the borrows were given rather than by where the sprite's palette is
+    // now, so sprites left on a bank by replace_palette are still found
+    fn sprites_using_palette_bank(&self, bank: u8) -> impl Iterator<Item = &'static Sprite> + '_ {
+        self.sprite
+            .iter()
+            .filter(move |(_, storage)| storage.palette_banks[bank as usize] > 0)
+            .map(|(id, _)| id.sprite())
     }
 }
 
 impl<'a> Drop for SpriteBorrow<'a> {
     fn drop(&mut self) {
         let mut s = unsafe { get_object_controller(self.phantom) };
-        s.sprite_controller.return_sprite(self.id.sprite());
+        s.sprite_controller
+            .return_sprite(self.id.sprite(), self.palette_location);
     }
 }
 
 impl<'a> SpriteBorrow<'a> {
+    /// Rotates the colours in `range` of this sprite's palette by `frame` places, in the same
+    /// way as [`VRamManager::cycle_background_palette`](super::tiled::VRamManager::cycle_background_palette).
+    /// This changes every sprite which uses the same palette. The colours are always rotated
+    /// starting from the sprite's original palette.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` goes past the end of the palette.
+    pub fn cycle_palette(&self, range: core::ops::Range<usize>, frame: usize) {
+        let cycled = self.id.sprite().palette.cycled(range.clone(), frame);
+
+        palette_manager::write_colours(
+            PaletteKind::Sprite,
+            self.palette_location.into(),
+            range.start,
+            &cycled.colours[range],
+        );
+    }
+
+    /// The copy of this sprite's palette in palette ram, whose colours can be changed for
+    /// effects such as damage flashes. This changes every sprite which uses the same palette,
+    /// and doesn't change the sprite's [`Palette16`].
+    #[must_use]
+    pub fn resident_palette(&self) -> ResidentPalette {
+        ResidentPalette::new(PaletteKind::Sprite, self.palette_location as u8)
+    }
+
     fn drop(self, s: &mut SpriteControllerInner) {
-        s.return_sprite(self.id.sprite());
+        s.return_sprite(self.id.sprite(), self.palette_location);
         core::mem::forget(self);
     }
 
     fn clone(&self, s: &mut SpriteControllerInner) -> Self {
-        s.sprite.entry(self.id).and_modify(|a| a.count += 1);
-        let _ = s.palette(self.id.sprite().palette).unwrap();
+        s.sprite.entry(self.id).and_modify(|a| {
+            a.count += 1;
+            a.palette_banks[self.palette_location as usize] += 1;
+        });
+        palette_manager::add_reference(PaletteKind::Sprite, self.palette_location as u8);
         Self {
             id: self.id,
             sprite_location: self.sprite_location,
@@ -1281,4 +1360,75 @@ mod tests {
 
         object.commit();
     }
+
+    #[test_case]
+    fn replacing_a_palette_changes_objects_using_it(gba: &mut crate::Gba) {
+        const GRAPHICS: &Graphics = include_aseprite!(
+            "../examples/the-purple-night/gfx/objects.aseprite",
+            "../examples/the-purple-night/gfx/boss.aseprite"
+        );
+        const BOSS: &Tag = GRAPHICS.tags().get("Boss");
+
+        static ENRAGED: Palette16 = Palette16::new([0x001f; 16]);
+        static UNUSED: Palette16 = Palette16::new([0x03e0; 16]);
+
+        let object = gba.display.object.get();
+
+        {
+            let sprite = BOSS.sprite(0);
+            let boss = object.object(object.sprite(sprite));
+            object.commit();
+
+            assert_eq!(
+                object.replace_palette(&UNUSED, &ENRAGED),
+                Err(PaletteNotLoaded)
+            );
+            assert_eq!(object.replace_palette(sprite.palette, &ENRAGED), Ok(()));
+            let bank = ResidentPalette::sprite(&ENRAGED).unwrap().bank();
+
+            // the boss is still on the bank, even though its sprite's palette no longer is
+            let s = unsafe { get_object_controller(PhantomData) };
+            assert!(s
+                .sprite_controller
+                .sprites_using_palette_bank(bank)
+                .any(|used| core::ptr::eq(used, sprite)));
+            drop(s);
+
+            drop(boss);
+            object.commit();
+        }
+
+        object.commit();
+        assert!(ResidentPalette::sprite(&ENRAGED).is_none());
+    }
+
+    #[test_case]
+    fn reserving_sprites_means_loading_them_never_resizes(gba: &mut crate::Gba) {
+        const GRAPHICS: &Graphics = include_aseprite!(
+            "../examples/the-purple-night/gfx/objects.aseprite",
+            "../examples/the-purple-night/gfx/boss.aseprite"
+        );
+
+        let object = gba.display.object.get();
+        let sprite_capacity = || unsafe {
+            get_object_controller(PhantomData)
+                .sprite_controller
+                .sprite
+                .capacity()
+        };
+
+        object.reserve_sprites(GRAPHICS.sprites().len());
+        let capacity = sprite_capacity();
+        assert!(capacity >= GRAPHICS.sprites().len());
+
+        // load every sprite which fits, like a level loading its enemies
+        let sprites: Vec<_> = GRAPHICS
+            .sprites()
+            .iter()
+            .map_while(|sprite| object.try_get_sprite(sprite))
+            .collect();
+
+        assert!(!sprites.is_empty());
+        assert_eq!(sprite_capacity(), capacity, "the sprite map was resized");
+    }
 }
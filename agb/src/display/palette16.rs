@@ -1,3 +1,8 @@
+use core::ops::Range;
+
+use super::palette_fade::lerp_colour;
+use crate::fixnum::Num;
+
 #[repr(C)]
 #[derive(Clone)]
 pub struct Palette16 {
@@ -10,6 +15,22 @@ impl Palette16 {
         Palette16 { colours }
     }
 
+    /// Creates a palette from 24 bit `(red, green, blue)` colours, converted in the same way as
+    /// images imported with `include_gfx!` so that the colours match.
+    #[must_use]
+    pub const fn from_rgb888(colours: &[(u8, u8, u8); 16]) -> Self {
+        let mut converted = [0; 16];
+
+        let mut i = 0;
+        while i < 16 {
+            let (r, g, b) = colours[i];
+            converted[i] = colour555(r >> 3, g >> 3, b >> 3);
+            i += 1;
+        }
+
+        Palette16 { colours: converted }
+    }
+
     // Clippy bug: claims that index is only used in recursion. I can't reproduce in
     // other examples, even just copy pasting this struct and impl into a blank project :/
     #[allow(clippy::only_used_in_recursion)]
@@ -21,4 +42,202 @@ impl Palette16 {
     pub fn colour(&self, index: usize) -> u16 {
         self.colours[index]
     }
+
+    /// This palette with the colours in `range` rotated `steps` places, so each of them moves
+    /// `steps` colours further through the range and those which go past the end wrap around to
+    /// the start. This is how palette cycling effects like flowing water are animated.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` goes past the end of the palette.
+    #[must_use]
+    pub fn cycled(&self, range: Range<usize>, steps: usize) -> Self {
+        let mut cycled = self.clone();
+
+        let colours = &mut cycled.colours[range];
+        if !colours.is_empty() {
+            colours.rotate_right(steps % colours.len());
+        }
+
+        cycled
+    }
+
+    /// The colours `t` of the way from each colour of `from` to the same colour of `to`, for
+    /// example to tint a scene between a day and a night version of its palettes. Each 5 bit
+    /// component is interpolated separately and rounded towards `from`, so a `t` of 0 gives
+    /// exactly `from` and 1 gives exactly `to`. Values of `t` outside of 0 to 1 are clamped.
+    #[must_use]
+    pub fn blend(from: &Palette16, to: &Palette16, t: Num<i32, 8>) -> [u16; 16] {
+        let t = t.to_raw().clamp(0, 1 << 8);
+
+        let mut colours = [0; 16];
+        for ((colour, &from), &to) in colours.iter_mut().zip(&from.colours).zip(&to.colours) {
+            *colour = lerp_colour(from, to, t);
+        }
+
+        colours
+    }
+
+    /// This palette with every colour converted to grey using [`greyscale`]
+    #[must_use]
+    pub fn greyscale(&self) -> Self {
+        self.map(greyscale)
+    }
+
+    /// This palette with every colour converted to a brown tint using [`sepia`]
+    #[must_use]
+    pub fn sepia(&self) -> Self {
+        self.map(sepia)
+    }
+
+    fn map(&self, f: impl Fn(u16) -> u16) -> Self {
+        let mut colours = self.colours;
+        for colour in &mut colours {
+            *colour = f(*colour);
+        }
+
+        Palette16 { colours }
+    }
+}
+
+// The brightness of a colour between 0 and 31, weighting green most and blue least like the eye
+// does. The weights add up to 256, so a grey colour has its own brightness and converting twice
+// gives the same colour as converting once.
+const fn luminance(colour: u16) -> u16 {
+    let r = colour & 31;
+    let g = (colour >> 5) & 31;
+    let b = (colour >> 10) & 31;
+
+    (r * 77 + g * 150 + b * 29 + 128) >> 8
+}
+
+/// `colour` converted to the grey with the same brightness.
+#[must_use]
+pub const fn greyscale(colour: u16) -> u16 {
+    let luminance = luminance(colour);
+    luminance | (luminance << 5) | (luminance << 10)
+}
+
+/// `colour` converted to a brown tint with the same brightness, like an old photograph.
+#[must_use]
+pub const fn sepia(colour: u16) -> u16 {
+    let luminance = luminance(colour);
+
+    let r = if luminance + 3 > 31 {
+        31
+    } else {
+        luminance + 3
+    };
+    let g = luminance;
+    let b = luminance * 3 / 4;
+
+    r | (g << 5) | (b << 10)
+}
+
+/// The colour used by the GBA with the given 5 bit red, green and blue components.
+///
+/// # Panics
+///
+/// Panics if any of the components are 32 or more.
+#[must_use]
+pub const fn colour555(r: u8, g: u8, b: u8) -> u16 {
+    assert!(
+        r < 32 && g < 32 && b < 32,
+        "colour components must be between 0 and 31"
+    );
+
+    (r as u16) | ((g as u16) << 5) | ((b as u16) << 10)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_case]
+    fn rgb888_colours_are_rounded_down_like_imported_ones(_gba: &mut crate::Gba) {
+        const PALETTE: Palette16 = Palette16::from_rgb888(&[
+            (255, 128, 7),
+            (0, 0, 0),
+            (8, 16, 248),
+            (0, 0, 0),
+            (0, 0, 0),
+            (0, 0, 0),
+            (0, 0, 0),
+            (0, 0, 0),
+            (0, 0, 0),
+            (0, 0, 0),
+            (0, 0, 0),
+            (0, 0, 0),
+            (0, 0, 0),
+            (0, 0, 0),
+            (0, 0, 0),
+            (255, 255, 255),
+        ]);
+
+        assert_eq!(PALETTE.colour(0), colour555(31, 16, 0));
+        assert_eq!(PALETTE.colour(1), 0);
+        assert_eq!(PALETTE.colour(2), 1 | (2 << 5) | (31 << 10));
+        assert_eq!(PALETTE.colour(15), 0x7fff);
+    }
+
+    #[test_case]
+    fn cycling_rotates_only_the_range(_gba: &mut crate::Gba) {
+        let mut colours = [0; 16];
+        for (i, colour) in colours.iter_mut().enumerate() {
+            *colour = i as u16;
+        }
+        let palette = Palette16::new(colours);
+
+        let cycled = palette.cycled(2..6, 1);
+        assert_eq!(cycled.colours[..8], [0, 1, 5, 2, 3, 4, 6, 7]);
+
+        // whole turns put the colours back where they started
+        assert_eq!(palette.cycled(2..6, 9).colours, cycled.colours);
+        assert_eq!(palette.cycled(2..6, 4).colours, palette.colours);
+        assert_eq!(palette.cycled(3..3, 5).colours, palette.colours);
+    }
+
+    #[test_case]
+    fn blending_is_exact_at_the_ends(_gba: &mut crate::Gba) {
+        use crate::fixnum::num;
+
+        let mut day = Palette16::new([colour555(0, 10, 31); 16]);
+        day.update_colour(1, colour555(31, 31, 31));
+        let mut night = Palette16::new([colour555(31, 20, 0); 16]);
+        night.update_colour(1, colour555(2, 4, 8));
+
+        assert_eq!(Palette16::blend(&day, &night, 0.into()), day.colours);
+        assert_eq!(Palette16::blend(&day, &night, 1.into()), night.colours);
+        assert_eq!(Palette16::blend(&day, &night, num!(-0.5)), day.colours);
+        assert_eq!(Palette16::blend(&day, &night, num!(3.)), night.colours);
+
+        // halfway components round towards the first palette
+        let halfway = Palette16::blend(&day, &night, num!(0.5));
+        assert_eq!(halfway[0], colour555(15, 15, 16));
+        assert_eq!(halfway[1], colour555(17, 18, 20));
+
+        let quarter = Palette16::blend(&day, &night, num!(0.25));
+        assert_eq!(quarter[0], colour555(7, 12, 24));
+    }
+
+    #[test_case]
+    fn greyscale_keeps_the_brightness_and_is_stable(_gba: &mut crate::Gba) {
+        assert_eq!(greyscale(0), 0);
+        assert_eq!(greyscale(0x7fff), 0x7fff);
+        assert_eq!(greyscale(colour555(31, 0, 0)), colour555(9, 9, 9));
+        assert_eq!(greyscale(colour555(0, 31, 0)), colour555(18, 18, 18));
+
+        for colour in (0..0x8000).step_by(97) {
+            let grey = greyscale(colour);
+            assert_eq!(greyscale(grey), grey);
+            assert_eq!(sepia(grey), sepia(colour));
+        }
+
+        assert_eq!(sepia(0x7fff), colour555(31, 31, 23));
+        assert_eq!(sepia(0), colour555(3, 0, 0));
+
+        let palette = Palette16::new([colour555(0, 31, 0); 16]);
+        assert_eq!(palette.greyscale().colour(3), colour555(18, 18, 18));
+        assert_eq!(palette.sepia().colour(15), colour555(21, 18, 13));
+    }
 }
@@ -0,0 +1,257 @@
+#![deny(missing_docs)]
+//! Fading the whole screen to a colour by changing the palettes.
+//!
+//! [`PaletteFade`] remembers the palettes which are in palette ram when it is
+//! created, and then writes every colour part of the way towards the target
+//! colour each frame. This works with any graphics mode which uses palettes,
+//! and unlike [blending][super::blend] doesn't need any layers to be set up.
+//!
+//! Banks allocated by the [`palette_manager`](super::palette_manager), such as
+//! sprite palettes and banks from
+//! [`VRamManager::palette`](super::tiled::VRamManager::palette), are faded by
+//! the palette manager instead. They always fade from the colours of the
+//! palette they currently hold, even if they were allocated, replaced or
+//! recoloured part way through the fade. The fade stays applied to them, even
+//! after the `PaletteFade` is dropped, until it is set back to 0 or
+//! [`restore`](PaletteFade::restore) is called.
+//! ```no_run
+//! # #![no_main]
+//! # #![no_std]
+//! use agb::{display::palette_fade::PaletteFade, fixnum::Num};
+//!
+//! # fn fade(mut gba: agb::Gba) {
+//! let vblank = agb::interrupt::VBlank::get();
+//!
+//! // fade everything, including sprites, to black over 30 frames
+//! let mut fade = PaletteFade::new(0, true);
+//! for frame in 0..=30 {
+//!     vblank.wait_for_vblank();
+//!     fade.set_fraction(Num::new(frame) / 30);
+//! }
+//! # }
+//! ```
+
+use alloc::vec::Vec;
+
+use alloc::boxed::Box;
+
+use super::palette_manager::{self, ColourTransform};
+use crate::{agb_alloc::DmaBuffer, dma::dma_copy16, fixnum::Num};
+
+const PALETTE_RAM: *mut u16 = 0x0500_0000 as *mut _;
+// The background palettes are followed immediately by the sprite palettes
+const BACKGROUND_COLOURS: usize = 256;
+const SPRITE_COLOURS: usize = 256;
+
+/// Fades palette ram between the colours it had when this was created and a
+/// single target colour.
+pub struct PaletteFade {
+    original: Vec<u16>,
+    staging: DmaBuffer<u16>,
+    target: u16,
+    sprites: bool,
+}
+
+impl PaletteFade {
+    /// Remembers the current background palettes, and the sprite palettes too
+    /// if `sprites` is set, ready to fade them towards `target_colour`.
+    #[must_use]
+    pub fn new(target_colour: u16, sprites: bool) -> Self {
+        let colours = if sprites {
+            BACKGROUND_COLOURS + SPRITE_COLOURS
+        } else {
+            BACKGROUND_COLOURS
+        };
+
+        let original: Vec<_> = (0..colours)
+            .map(|i| unsafe { PALETTE_RAM.add(i).read_volatile() })
+            .collect();
+
+        Self {
+            staging: DmaBuffer::from_slice(&original),
+            original,
+            target: target_colour,
+            sprites,
+        }
+    }
+
+    /// Writes every colour `fraction` of the way from its original colour to
+    /// the target colour, so 0 puts back the original colours and 1 makes
+    /// everything the target colour. Fractions outside of 0 to 1 are clamped.
+    /// This should be called during vblank.
+    pub fn set_fraction(&mut self, fraction: Num<i32, 8>) {
+        let fraction = fraction.to_raw().clamp(0, 1 << 8);
+        let target = self.target;
+
+        for (faded, &original) in self.staging.iter_mut().zip(&self.original) {
+            *faded = lerp_colour(original, target, fraction);
+        }
+
+        self.write_staging(if fraction == 0 {
+            None
+        } else {
+            Some(ColourTransform::Fade { target, fraction })
+        });
+    }
+
+    /// Writes every colour converted by `transform` from its original colour,
+    /// for example [`greyscale`](super::palette16::greyscale) for a flashback.
+    /// The target colour isn't used. Since the original colours are always the
+    /// ones converted, this can be called as many times as needed without the
+    /// colours drifting.
+    pub fn apply(&mut self, transform: impl Fn(u16) -> u16 + Send + 'static) {
+        for (transformed, &original) in self.staging.iter_mut().zip(&self.original) {
+            *transformed = transform(original);
+        }
+
+        self.write_staging(Some(ColourTransform::Convert(Box::new(transform))));
+    }
+
+    /// Puts back the colours which were in palette ram when this was created.
+    pub fn restore(&mut self) {
+        self.set_fraction(0.into());
+    }
+
+    // Copies the staged colours into palette ram, and then has the palette manager write the
+    // banks it tracks using `transform`, since they may hold different palettes by now
+    fn write_staging(&mut self, transform: Option<ColourTransform>) {
+        unsafe {
+            dma_copy16(self.staging.as_ptr(), PALETTE_RAM, self.staging.len());
+        }
+
+        palette_manager::set_transform(transform, self.sprites);
+    }
+}
+
+// Moves each 5 bit component of `from` towards `to` by `fraction` / 256
+pub(crate) fn lerp_colour(from: u16, to: u16, fraction: i32) -> u16 {
+    let mut colour = 0;
+
+    for shift in [0, 5, 10] {
+        let from = i32::from((from >> shift) & 31);
+        let to = i32::from((to >> shift) & 31);
+
+        let component = from + (to - from) * fraction / (1 << 8);
+        colour |= (component as u16) << shift;
+    }
+
+    colour
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::display::palette16::{colour555, greyscale, sepia, Palette16};
+    use crate::fixnum::num;
+
+    #[test_case]
+    fn fades_start_and_end_at_exact_colours(_gba: &mut crate::Gba) {
+        let colours = [
+            colour555(31, 0, 7),
+            colour555(1, 2, 3),
+            0,
+            colour555(16, 30, 31),
+        ];
+        for (i, &colour) in colours.iter().enumerate() {
+            unsafe { PALETTE_RAM.add(i * 16).write_volatile(colour) };
+        }
+        let sprite_colour = colour555(4, 4, 4);
+        unsafe { PALETTE_RAM.add(256 + 1).write_volatile(sprite_colour) };
+
+        let read = |i: usize| unsafe { PALETTE_RAM.add(i).read_volatile() };
+
+        let mut fade = PaletteFade::new(colour555(31, 31, 31), false);
+
+        fade.set_fraction(num!(0.5));
+        assert_eq!(read(0), colour555(31, 15, 19));
+        assert_eq!(read(16), colour555(16, 16, 17));
+        // sprite palettes weren't included
+        assert_eq!(read(256 + 1), sprite_colour);
+
+        fade.set_fraction(1.into());
+        assert!((0..256).all(|i| read(i) == colour555(31, 31, 31)));
+
+        fade.set_fraction(0.into());
+        for (i, &colour) in colours.iter().enumerate() {
+            assert_eq!(read(i * 16), colour);
+        }
+
+        let mut fade = PaletteFade::new(0, true);
+        fade.set_fraction(num!(2.));
+        assert_eq!(read(256 + 1), 0);
+
+        fade.set_fraction(num!(-1.));
+        assert_eq!(read(256 + 1), sprite_colour);
+        assert_eq!(read(48), colours[3]);
+
+        for i in 0..512 {
+            unsafe { PALETTE_RAM.add(i).write_volatile(0) };
+        }
+    }
+
+    #[test_case]
+    fn transforms_can_be_applied_and_restored(_gba: &mut crate::Gba) {
+        let colour = colour555(31, 10, 2);
+        let sprite_colour = colour555(2, 31, 9);
+        unsafe {
+            PALETTE_RAM.add(5).write_volatile(colour);
+            PALETTE_RAM.add(256 + 5).write_volatile(sprite_colour);
+        }
+
+        let read = |i: usize| unsafe { PALETTE_RAM.add(i).read_volatile() };
+
+        let mut snapshot = PaletteFade::new(0, true);
+
+        for _ in 0..3 {
+            snapshot.apply(greyscale);
+            assert_eq!(read(5), greyscale(colour));
+            assert_eq!(read(256 + 5), greyscale(sprite_colour));
+
+            snapshot.apply(sepia);
+            assert_eq!(read(5), sepia(colour));
+
+            snapshot.restore();
+            assert_eq!(read(5), colour);
+            assert_eq!(read(256 + 5), sprite_colour);
+        }
+
+        for i in 0..512 {
+            unsafe { PALETTE_RAM.add(i).write_volatile(0) };
+        }
+    }
+
+    #[test_case]
+    fn palettes_allocated_during_a_fade_are_faded(_gba: &mut crate::Gba) {
+        use crate::display::palette_manager::{allocate, release, write_bank, PaletteKind};
+
+        static BEFORE: Palette16 = Palette16::new([colour555(31, 31, 31); 16]);
+        static DURING: Palette16 = Palette16::new([colour555(0, 10, 31); 16]);
+
+        let read = |bank: u8| unsafe { PALETTE_RAM.add(bank as usize * 16).read_volatile() };
+
+        let (bank, colours) = allocate(PaletteKind::Background, &BEFORE).unwrap();
+        write_bank(PaletteKind::Background, bank.into(), &colours.unwrap());
+
+        let mut fade = PaletteFade::new(0, false);
+        fade.set_fraction(num!(0.5));
+        assert_eq!(read(bank), lerp_colour(BEFORE.colours[0], 0, 128));
+
+        // the bank is freed and given to a different palette part way through the fade
+        release(PaletteKind::Background, bank);
+        let (bank, colours) = allocate(PaletteKind::Background, &DURING).unwrap();
+        write_bank(PaletteKind::Background, bank.into(), &colours.unwrap());
+        assert_eq!(read(bank), lerp_colour(DURING.colours[0], 0, 128));
+
+        fade.set_fraction(num!(0.25));
+        assert_eq!(read(bank), lerp_colour(DURING.colours[0], 0, 64));
+
+        // restoring puts back the colours of the palette the bank holds now
+        fade.restore();
+        assert_eq!(read(bank), DURING.colours[0]);
+
+        release(PaletteKind::Background, bank);
+        for i in 0..512 {
+            unsafe { PALETTE_RAM.add(i).write_volatile(0) };
+        }
+    }
+}
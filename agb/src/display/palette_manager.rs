@@ -0,0 +1,601 @@
+#![deny(missing_docs)]
+//! The palettes shared between backgrounds and sprites.
+//!
+//! Background palette banks allocated by
+//! [`VRamManager::palette`][super::tiled::VRamManager::palette] and the
+//! palettes of sprites are both allocated here. Palettes with the same colours
+//! share a bank, however many copies of them there are, so the same asset used
+//! for a background and a sprite only needs one bank of each.
+//!
+//! A palette can be given different colours using [`set_colours`], for example
+//! to tint a character after they are hit. This changes every background and
+//! sprite using the palette at once, including any which start using it later,
+//! so they never disagree about what it looks like.
+//!
+//! To change only the copy of a sprite palette which is in palette ram right
+//! now, for example to flash a few colours of a boss, use a [`ResidentPalette`].
+//!
+//! A [`PaletteFade`](super::palette_fade::PaletteFade) is applied here too, so
+//! palettes which start being used part way through a fade are faded along
+//! with the rest.
+//!
+//! To find out which banks are in use, use [`bank_usage`] or
+//! [`print_bank_usage`].
+
+use alloc::{boxed::Box, vec::Vec};
+use core::{
+    cell::RefCell,
+    fmt::{self, Display},
+    hash::{Hash, Hasher},
+};
+
+use bare_metal::Mutex;
+use rustc_hash::FxHasher;
+
+use super::palette16::Palette16;
+use super::palette_fade::lerp_colour;
+use crate::agb_alloc::{trace, AllocatorStats};
+use crate::interrupt::free;
+use crate::memory_mapped::MemoryMapped1DArray;
+
+const BANKS: usize = 16;
+
+/// Which palette ram a bank is in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum PaletteKind {
+    Background = 0,
+    Sprite = 1,
+}
+
+impl PaletteKind {
+    fn palette_ram(self) -> MemoryMapped1DArray<u16, 256> {
+        let address = match self {
+            PaletteKind::Background => 0x0500_0000,
+            PaletteKind::Sprite => 0x0500_0200,
+        };
+
+        unsafe { MemoryMapped1DArray::new(address) }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Allocation {
+    // the original colours of the palette, which identify it
+    colours: [u16; 16],
+    count: u16,
+}
+
+/// A change made to the colours of every palette as they are copied into palette ram, set by a
+/// [`PaletteFade`](super::palette_fade::PaletteFade)
+pub(crate) enum ColourTransform {
+    /// Moves each colour `fraction` / 256 of the way to `target`
+    Fade { target: u16, fraction: i32 },
+    /// Converts each colour using the function
+    Convert(Box<dyn Fn(u16) -> u16 + Send>),
+}
+
+impl ColourTransform {
+    fn apply(&self, colour: u16) -> u16 {
+        match self {
+            ColourTransform::Fade { target, fraction } => lerp_colour(colour, *target, *fraction),
+            ColourTransform::Convert(convert) => convert(colour),
+        }
+    }
+}
+
+struct Palettes {
+    banks: [[Option<Allocation>; BANKS]; 2],
+    // the palettes given different colours by set_colours, by their original colours
+    recoloured: Vec<([u16; 16], [u16; 16])>,
+    // applied to the background palettes, and the sprite palettes too if set
+    transform: Option<(ColourTransform, bool)>,
+}
+
+impl Palettes {
+    fn current_colours(&self, colours: &[u16; 16]) -> [u16; 16] {
+        self.recoloured
+            .iter()
+            .find(|(original, _)| original == colours)
+            .map_or(*colours, |&(_, current)| current)
+    }
+
+    // The colours to put in palette ram for the palette with the original colours `colours`
+    fn shown_colours(&self, kind: PaletteKind, colours: &[u16; 16]) -> [u16; 16] {
+        let mut current = self.current_colours(colours);
+
+        match &self.transform {
+            Some((transform, sprites)) if kind == PaletteKind::Background || *sprites => {
+                for colour in &mut current {
+                    *colour = transform.apply(*colour);
+                }
+            }
+            _ => {}
+        }
+
+        current
+    }
+
+    // Writes the colours of the palette with the original colours `original` into every bank
+    // using it, or every bank in use if there is no `original`
+    fn write_everywhere(&self, original: Option<&[u16; 16]>) {
+        for &kind in &[PaletteKind::Background, PaletteKind::Sprite] {
+            for (bank, allocation) in self.banks[kind as usize].iter().enumerate() {
+                match allocation {
+                    Some(allocation) if original.is_none_or(|o| o == &allocation.colours) => {
+                        write_bank(kind, bank, &self.shown_colours(kind, &allocation.colours));
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+// This is global so that handles to banks, such as a PaletteBank or a sprite, can release their
+// bank when they are dropped
+static PALETTES: Mutex<RefCell<Palettes>> = Mutex::new(RefCell::new(Palettes {
+    banks: [[None; BANKS]; 2],
+    recoloured: Vec::new(),
+    transform: None,
+}));
+
+/// Allocates a bank for `palette`, sharing one with the same colours if there is one. If the
+/// bank is new, also returns the colours which need copying into it, which take any call to
+/// [`set_colours`] and any fade into account. Returns `None` if every bank is in use.
+pub(crate) fn allocate(kind: PaletteKind, palette: &Palette16) -> Option<(u8, Option<[u16; 16]>)> {
+    let allocation = free(|cs| {
+        let mut palettes = PALETTES.borrow(cs).borrow_mut();
+        let current = palettes.shown_colours(kind, &palette.colours);
+        let banks = &mut palettes.banks[kind as usize];
+
+        if let Some(bank) = banks.iter().position(
+            |allocation| matches!(allocation, Some(allocation) if allocation.colours == palette.colours),
+        ) {
+            banks[bank].as_mut().unwrap().count += 1;
+            return Some((bank as u8, None));
+        }
+
+        let bank = banks.iter().position(Option::is_none)?;
+        banks[bank] = Some(Allocation {
+            colours: palette.colours,
+            count: 1,
+        });
+
+        Some((bank as u8, Some(current)))
+    });
+
+    trace::palette(
+        "alloc",
+        kind == PaletteKind::Sprite,
+        allocation.map(|(bank, _)| bank),
+    );
+    allocation
+}
+
+/// Adds another reference to a bank which is already allocated
+pub(crate) fn add_reference(kind: PaletteKind, bank: u8) {
+    free(|cs| {
+        let mut palettes = PALETTES.borrow(cs).borrow_mut();
+        palettes.banks[kind as usize][bank as usize]
+            .as_mut()
+            .unwrap()
+            .count += 1;
+    });
+}
+
+/// Removes a reference to a bank, freeing it once nothing uses it
+pub(crate) fn release(kind: PaletteKind, bank: u8) {
+    free(|cs| {
+        let mut palettes = PALETTES.borrow(cs).borrow_mut();
+        let allocation = &mut palettes.banks[kind as usize][bank as usize];
+        let entry = allocation.as_mut().unwrap();
+
+        entry.count -= 1;
+        if entry.count == 0 {
+            *allocation = None;
+        }
+    });
+
+    trace::palette("release", kind == PaletteKind::Sprite, Some(bank));
+}
+
+/// The bank used by `palette`, if it has one
+pub(crate) fn bank_of(kind: PaletteKind, palette: &Palette16) -> Option<u8> {
+    free(|cs| {
+        let palettes = PALETTES.borrow(cs).borrow();
+        palettes.banks[kind as usize]
+            .iter()
+            .position(
+                |allocation| matches!(allocation, Some(allocation) if allocation.colours == palette.colours),
+            )
+            .map(|bank| bank as u8)
+    })
+}
+
+/// Gives the bank used by `old` the colours of `new`, keeping all the references to it. Returns
+/// the bank, or `None` if `old` doesn't have one.
+pub(crate) fn replace(kind: PaletteKind, old: &Palette16, new: &Palette16) -> Option<u8> {
+    free(|cs| {
+        let mut palettes = PALETTES.borrow(cs).borrow_mut();
+        let current = palettes.shown_colours(kind, &new.colours);
+
+        let bank = palettes.banks[kind as usize].iter().position(
+            |allocation| matches!(allocation, Some(allocation) if allocation.colours == old.colours),
+        )?;
+
+        palettes.banks[kind as usize][bank]
+            .as_mut()
+            .unwrap()
+            .colours = new.colours;
+        write_bank(kind, bank, &current);
+
+        Some(bank as u8)
+    })
+}
+
+/// Copies `colours` into a bank of palette ram
+pub(crate) fn write_bank(kind: PaletteKind, bank: usize, colours: &[u16; 16]) {
+    write_colours(kind, bank, 0, colours);
+}
+
+/// Copies `colours` into a bank of palette ram starting at colour `first`
+pub(crate) fn write_colours(kind: PaletteKind, bank: usize, first: usize, colours: &[u16]) {
+    assert!(
+        first + colours.len() <= 16,
+        "colours go past the end of the bank"
+    );
+
+    kind.palette_ram().write_slice(bank * 16 + first, colours);
+}
+
+/// Shows `palette`, and any other palette with the same colours, using the colours of `colours`
+/// instead. Every background palette bank and sprite using it changes straight away, as do any
+/// which start using it later, until [`reset_colours`] is called.
+pub fn set_colours(palette: &Palette16, colours: &Palette16) {
+    free(|cs| {
+        let mut palettes = PALETTES.borrow(cs).borrow_mut();
+
+        let original = palette.colours;
+        match palettes
+            .recoloured
+            .iter_mut()
+            .find(|(recoloured, _)| *recoloured == original)
+        {
+            Some((_, current)) => *current = colours.colours,
+            None => palettes.recoloured.push((original, colours.colours)),
+        }
+
+        palettes.write_everywhere(Some(&original));
+    });
+}
+
+/// Puts back the original colours of a palette given different colours by [`set_colours`].
+pub fn reset_colours(palette: &Palette16) {
+    free(|cs| {
+        let mut palettes = PALETTES.borrow(cs).borrow_mut();
+
+        let original = palette.colours;
+        palettes
+            .recoloured
+            .retain(|(recoloured, _)| *recoloured != original);
+
+        palettes.write_everywhere(Some(&original));
+    });
+}
+
+/// Changes the colours of every background palette, and every sprite palette too if `sprites`
+/// is set, by `transform` as they are copied into palette ram, including palettes which start
+/// being used later. Every bank in use is copied again straight away.
+pub(crate) fn set_transform(transform: Option<ColourTransform>, sprites: bool) {
+    free(|cs| {
+        let mut palettes = PALETTES.borrow(cs).borrow_mut();
+
+        palettes.transform = transform.map(|transform| (transform, sprites));
+        palettes.write_everywhere(None);
+    });
+}
+
+/// A sprite palette bank which is in palette ram, whose colours can be changed
+/// without changing the [`Palette16`] it came from. Get one with
+/// [`ResidentPalette::sprite`] or
+/// [`SpriteBorrow::resident_palette`](super::object::SpriteBorrow::resident_palette).
+///
+/// The bank stays allocated while this exists. Every sprite using the same
+/// palette shares the bank, so they all change together. Once nothing uses the
+/// bank any more the changes are lost, and the palette gets its original
+/// colours the next time it is used.
+pub struct ResidentPalette {
+    kind: PaletteKind,
+    bank: u8,
+}
+
+impl ResidentPalette {
+    pub(crate) fn new(kind: PaletteKind, bank: u8) -> Self {
+        add_reference(kind, bank);
+        ResidentPalette { kind, bank }
+    }
+
+    /// The bank used by `palette` if it is currently used by a sprite.
+    #[must_use]
+    pub fn sprite(palette: &Palette16) -> Option<Self> {
+        let bank = bank_of(PaletteKind::Sprite, palette)?;
+        Some(Self::new(PaletteKind::Sprite, bank))
+    }
+
+    /// The palette bank this refers to
+    #[must_use]
+    pub fn bank(&self) -> u8 {
+        self.bank
+    }
+
+    /// Sets colour `index` of the bank.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is 16 or more.
+    pub fn set_colour(&self, index: usize, colour: u16) {
+        assert!(index < 16, "colour index {} is out of range", index);
+        write_colours(self.kind, self.bank.into(), index, &[colour]);
+    }
+
+    /// Replaces every colour of the bank
+    pub fn write_all(&self, colours: &[u16; 16]) {
+        write_bank(self.kind, self.bank.into(), colours);
+    }
+}
+
+impl Drop for ResidentPalette {
+    fn drop(&mut self) {
+        release(self.kind, self.bank);
+    }
+}
+
+/// How a palette bank is being used, returned by [`bank_usage`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PaletteBankUsage {
+    /// Whether this is a sprite palette bank rather than a background one
+    pub sprite: bool,
+    /// The index of the bank
+    pub bank: u8,
+    /// The number of backgrounds, sprites and handles using the bank, or 0 if it is free
+    pub reference_count: u16,
+    /// A hash of the original colours of the palette in the bank, which is the same for every
+    /// copy of the palette. This is 0 if the bank is free.
+    pub colours_hash: u32,
+    /// Whether the palette has been given different colours with [`set_colours`]
+    pub recoloured: bool,
+}
+
+impl Display for PaletteBankUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = if self.sprite { "sprite" } else { "background" };
+
+        if self.reference_count == 0 {
+            return write!(f, "{} bank {}: free", kind, self.bank);
+        }
+
+        write!(
+            f,
+            "{} bank {}: {} references, colours hash {:#010x}",
+            kind, self.bank, self.reference_count, self.colours_hash
+        )?;
+
+        if self.recoloured {
+            write!(f, ", recoloured")?;
+        }
+
+        Ok(())
+    }
+}
+
+fn hash_colours(colours: &[u16; 16]) -> u32 {
+    let mut hasher = FxHasher::default();
+    colours.hash(&mut hasher);
+    hasher.finish() as u32
+}
+
+/// Lists how each of the 16 background palette banks and then each of the 16
+/// sprite palette banks are being used. Banks written directly, such as with
+/// [`VRamManager::set_background_palettes`](super::tiled::VRamManager::set_background_palettes),
+/// aren't tracked and show up as free.
+#[must_use]
+pub fn bank_usage() -> Vec<PaletteBankUsage> {
+    free(|cs| {
+        let palettes = PALETTES.borrow(cs).borrow();
+
+        let mut usage = Vec::with_capacity(2 * BANKS);
+        for &kind in &[PaletteKind::Background, PaletteKind::Sprite] {
+            for (bank, allocation) in palettes.banks[kind as usize].iter().enumerate() {
+                usage.push(PaletteBankUsage {
+                    sprite: kind == PaletteKind::Sprite,
+                    bank: bank as u8,
+                    reference_count: allocation.map_or(0, |allocation| allocation.count),
+                    colours_hash: allocation
+                        .map_or(0, |allocation| hash_colours(&allocation.colours)),
+                    recoloured: allocation.is_some_and(|allocation| {
+                        palettes
+                            .recoloured
+                            .iter()
+                            .any(|(original, _)| *original == allocation.colours)
+                    }),
+                });
+            }
+        }
+
+        usage
+    })
+}
+
+/// Prints the usage of every palette bank which is in use to the mgba log.
+/// [`ObjectController::print_palette_usage`](super::object::ObjectController::print_palette_usage)
+/// also lists which sprites use each sprite bank.
+pub fn print_bank_usage() {
+    for usage in bank_usage() {
+        if usage.reference_count > 0 {
+            crate::println!("{}", usage);
+        }
+    }
+}
+
+pub(crate) fn banks_in_use(kind: PaletteKind) -> usize {
+    free(|cs| {
+        PALETTES.borrow(cs).borrow().banks[kind as usize]
+            .iter()
+            .filter(|allocation| allocation.is_some())
+            .count()
+    })
+}
+
+/// How many of the palette banks of the given kind are in use, where each bank is an allocation of
+/// 16 colours
+pub(crate) fn stats(kind: PaletteKind) -> AllocatorStats {
+    let bank_size = 16 * core::mem::size_of::<u16>();
+    let in_use = banks_in_use(kind);
+
+    AllocatorStats {
+        bytes_allocated: in_use * bank_size,
+        bytes_free: (BANKS - in_use) * bank_size,
+        largest_free_block: if in_use < BANKS { bank_size } else { 0 },
+        allocations: in_use,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static PALETTE: Palette16 = Palette16::new([0x1234; 16]);
+    static SAME_COLOURS: Palette16 = Palette16::new([0x1234; 16]);
+
+    fn read_bank(kind: PaletteKind, bank: u8) -> u16 {
+        kind.palette_ram().get(bank as usize * 16)
+    }
+
+    #[test_case]
+    fn recolouring_changes_every_user_of_a_palette(_gba: &mut crate::Gba) {
+        let (background, colours) = allocate(PaletteKind::Background, &PALETTE).unwrap();
+        write_bank(
+            PaletteKind::Background,
+            background.into(),
+            &colours.unwrap(),
+        );
+
+        // palettes with the same colours share a bank
+        let (shared, colours) = allocate(PaletteKind::Background, &SAME_COLOURS).unwrap();
+        assert_eq!(shared, background);
+        assert!(colours.is_none());
+
+        let (sprite, colours) = allocate(PaletteKind::Sprite, &SAME_COLOURS).unwrap();
+        write_bank(PaletteKind::Sprite, sprite.into(), &colours.unwrap());
+
+        set_colours(&PALETTE, &Palette16::new([0x4321; 16]));
+        assert_eq!(read_bank(PaletteKind::Background, background), 0x4321);
+        assert_eq!(read_bank(PaletteKind::Sprite, sprite), 0x4321);
+
+        // palettes which start being used later get the new colours too
+        release(PaletteKind::Sprite, sprite);
+        let (sprite, colours) = allocate(PaletteKind::Sprite, &PALETTE).unwrap();
+        assert_eq!(colours, Some([0x4321; 16]));
+
+        reset_colours(&SAME_COLOURS);
+        assert_eq!(read_bank(PaletteKind::Background, background), 0x1234);
+
+        release(PaletteKind::Sprite, sprite);
+        release(PaletteKind::Background, background);
+        release(PaletteKind::Background, shared);
+
+        assert_eq!(banks_in_use(PaletteKind::Background), 0);
+        assert_eq!(banks_in_use(PaletteKind::Sprite), 0);
+    }
+
+    #[test_case]
+    fn replacing_a_palette_keeps_its_bank(_gba: &mut crate::Gba) {
+        let replacement = Palette16::new([0x0f0f; 16]);
+
+        assert!(replace(PaletteKind::Sprite, &PALETTE, &replacement).is_none());
+
+        let (bank, colours) = allocate(PaletteKind::Sprite, &PALETTE).unwrap();
+        write_bank(PaletteKind::Sprite, bank.into(), &colours.unwrap());
+        add_reference(PaletteKind::Sprite, bank);
+
+        assert_eq!(
+            replace(PaletteKind::Sprite, &PALETTE, &replacement),
+            Some(bank)
+        );
+        assert_eq!(read_bank(PaletteKind::Sprite, bank), 0x0f0f);
+
+        // the replacement now shares the bank, but the old palette doesn't
+        assert_eq!(bank_of(PaletteKind::Sprite, &replacement), Some(bank));
+        assert_eq!(bank_of(PaletteKind::Sprite, &PALETTE), None);
+
+        release(PaletteKind::Sprite, bank);
+        release(PaletteKind::Sprite, bank);
+        assert_eq!(banks_in_use(PaletteKind::Sprite), 0);
+    }
+
+    #[test_case]
+    fn bank_usage_lists_every_bank(_gba: &mut crate::Gba) {
+        let (background, _) = allocate(PaletteKind::Background, &PALETTE).unwrap();
+        add_reference(PaletteKind::Background, background);
+        let (sprite, _) = allocate(PaletteKind::Sprite, &SAME_COLOURS).unwrap();
+        set_colours(&PALETTE, &Palette16::new([0; 16]));
+
+        let usage = bank_usage();
+        assert_eq!(usage.len(), 32);
+
+        let background_usage = usage[background as usize];
+        let sprite_usage = usage[16 + sprite as usize];
+
+        assert!(!background_usage.sprite);
+        assert_eq!(background_usage.reference_count, 2);
+        assert!(background_usage.recoloured);
+
+        assert!(sprite_usage.sprite);
+        assert_eq!(sprite_usage.reference_count, 1);
+        assert_eq!(sprite_usage.colours_hash, background_usage.colours_hash);
+        assert_ne!(sprite_usage.colours_hash, 0);
+
+        assert_eq!(
+            usage
+                .iter()
+                .filter(|usage| usage.reference_count > 0)
+                .count(),
+            2
+        );
+
+        reset_colours(&PALETTE);
+        release(PaletteKind::Background, background);
+        release(PaletteKind::Background, background);
+        release(PaletteKind::Sprite, sprite);
+
+        assert_eq!(
+            alloc::format!("{}", bank_usage()[background as usize]),
+            alloc::format!("background bank {}: free", background)
+        );
+    }
+
+    #[test_case]
+    fn resident_palettes_change_palette_ram_only(_gba: &mut crate::Gba) {
+        assert!(ResidentPalette::sprite(&PALETTE).is_none());
+
+        let (bank, colours) = allocate(PaletteKind::Sprite, &PALETTE).unwrap();
+        write_bank(PaletteKind::Sprite, bank.into(), &colours.unwrap());
+
+        let resident = ResidentPalette::sprite(&PALETTE).unwrap();
+        assert_eq!(resident.bank(), bank);
+
+        resident.set_colour(0, 0x7fff);
+        assert_eq!(read_bank(PaletteKind::Sprite, bank), 0x7fff);
+        assert_eq!(PALETTE.colours[0], 0x1234);
+
+        // the bank is kept while the resident palette exists
+        release(PaletteKind::Sprite, bank);
+        assert_eq!(banks_in_use(PaletteKind::Sprite), 1);
+
+        resident.write_all(&[0x0421; 16]);
+        assert_eq!(read_bank(PaletteKind::Sprite, bank), 0x0421);
+
+        drop(resident);
+        assert_eq!(banks_in_use(PaletteKind::Sprite), 0);
+    }
+}
@@ -0,0 +1,182 @@
+use core::ops::Range;
+
+use alloc::vec::Vec;
+
+use super::{TileSet, VRamManager};
+
+struct AnimatedTileGroup<'a> {
+    tileset: &'a TileSet<'a>,
+    tiles: Range<u16>,
+    frames_per_step: u32,
+}
+
+/// Animates groups of background tiles, such as water or conveyor belts, by replacing the pixel
+/// data of the tiles in video ram. Every map using those tiles changes at once without any calls
+/// to `set_tile`.
+///
+/// Each group is a range of tiles in a tileset which cycle through each other, so for a group of
+/// tiles 40 to 43 the tile 40 shows tile 41, then 42, then 43 and back to 40, while tile 41 shows
+/// tile 42, 43, 40 and so on. Usually only the first tile of a group is used in maps.
+///
+/// Only tiles which are currently in video ram are updated, and a tile which is loaded after
+/// being animated is brought up to date on the next [`update`](AnimatedTiles::update). As with
+/// [`VRamManager::replace_tile`], tiles can't be animated while they share their slot with an
+/// identical tile from somewhere else, and tiles which are stored as a mirror image of a tile
+/// which was loaded before them aren't animated either.
+///
+/// ```rust,no_run
+/// # #![no_std]
+/// # #![no_main]
+/// # use agb::display::tiled::{AnimatedTiles, TileFormat, TileSet};
+/// # fn foo(mut gba: agb::Gba) {
+/// # let (gfx, mut vram) = gba.display.video.tiled0();
+/// # let tiles = [0u8; 64 * 32];
+/// let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+///
+/// let mut animated_tiles = AnimatedTiles::new();
+/// animated_tiles.add_group(&tileset, 40..44, 12);
+///
+/// let vblank = agb::interrupt::VBlank::get();
+/// let mut frame_count = 0;
+/// loop {
+///     animated_tiles.update(frame_count, &mut vram);
+///     frame_count += 1;
+///
+///     vblank.wait_for_vblank();
+/// }
+/// # }
+/// ```
+#[derive(Default)]
+pub struct AnimatedTiles<'a> {
+    groups: Vec<AnimatedTileGroup<'a>>,
+}
+
+impl<'a> AnimatedTiles<'a> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a group of tiles from `tileset` which cycle through each other, moving on to the next
+    /// tile every `frames_per_step` frames.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tiles` is empty, is past the end of the tileset, or `frames_per_step` is 0.
+    pub fn add_group(&mut self, tileset: &'a TileSet<'a>, tiles: Range<u16>, frames_per_step: u32) {
+        assert!(!tiles.is_empty(), "an animated tile group can't be empty");
+        assert!(
+            tiles.end <= tileset.tile_count(),
+            "tiles {:?} go past the end of the tileset, which has {} tiles",
+            tiles,
+            tileset.tile_count()
+        );
+        assert!(frames_per_step > 0, "frames_per_step must be at least 1");
+
+        self.groups.push(AnimatedTileGroup {
+            tileset,
+            tiles,
+            frames_per_step,
+        });
+    }
+
+    /// Updates every tile in video ram to show the correct frame of its group for `frame_count`.
+    /// Only tiles which don't already show the correct frame are copied, so this is cheap to call
+    /// every frame.
+    pub fn update(&mut self, frame_count: u32, vram: &mut VRamManager) {
+        for group in &self.groups {
+            let length = u32::from(group.tiles.end - group.tiles.start);
+            let step = frame_count / group.frames_per_step;
+
+            for (offset, tile) in group.tiles.clone().enumerate() {
+                let frame = group.tiles.start + ((offset as u32 + step) % length) as u16;
+
+                let current = match vram.resident_tile_data(group.tileset, tile) {
+                    Some(current) => current,
+                    None => continue,
+                };
+
+                if current != group.tileset.tile_data(frame) {
+                    vram.replace_tile(group.tileset, tile, group.tileset, frame);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::display::{
+        tiled::{RegularBackgroundSize, TileFormat, TileSetting},
+        Priority,
+    };
+
+    #[test_case]
+    fn resident_tiles_cycle_through_their_group(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+        let mut map = gfx.background(Priority::P0, RegularBackgroundSize::Background32x32);
+
+        let tile_size = 8 * 8 / 2;
+        let mut tiles = alloc::vec![0u8; 4 * tile_size];
+        for (i, tile) in tiles.chunks_mut(tile_size).enumerate() {
+            tile.fill(0x11 * (i as u8 + 1));
+        }
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        let mut animated_tiles = AnimatedTiles::new();
+        animated_tiles.add_group(&tileset, 0..4, 2);
+
+        map.set_tile(
+            &mut vram,
+            (0u16, 0u16).into(),
+            &tileset,
+            TileSetting::new(0, false, false, 0),
+        );
+
+        animated_tiles.update(0, &mut vram);
+        assert_eq!(
+            vram.resident_tile_data(&tileset, 0),
+            Some(tileset.tile_data(0))
+        );
+
+        animated_tiles.update(2, &mut vram);
+        assert_eq!(
+            vram.resident_tile_data(&tileset, 0),
+            Some(tileset.tile_data(1))
+        );
+        // tiles which aren't in video ram are skipped
+        assert_eq!(vram.resident_tile_data(&tileset, 1), None);
+
+        animated_tiles.update(5, &mut vram);
+        assert_eq!(
+            vram.resident_tile_data(&tileset, 0),
+            Some(tileset.tile_data(2))
+        );
+
+        // tile 1 is loaded part way through the animation and catches up on the next update
+        map.set_tile(
+            &mut vram,
+            (1u16, 0u16).into(),
+            &tileset,
+            TileSetting::new(1, false, false, 0),
+        );
+        assert_eq!(
+            vram.resident_tile_data(&tileset, 1),
+            Some(tileset.tile_data(1))
+        );
+
+        animated_tiles.update(5, &mut vram);
+        assert_eq!(
+            vram.resident_tile_data(&tileset, 0),
+            Some(tileset.tile_data(2))
+        );
+        assert_eq!(
+            vram.resident_tile_data(&tileset, 1),
+            Some(tileset.tile_data(3))
+        );
+
+        map.clear(&mut vram);
+        map.commit(&mut vram);
+    }
+}
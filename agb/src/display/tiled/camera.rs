@@ -0,0 +1,137 @@
+use crate::{
+    display,
+    fixnum::{Num, Rect, Vector2D},
+};
+
+/// The top left corner of the screen which centres it on `target`, without showing anything
+/// outside of `level_bounds`. Both are in pixels, and the result can be passed straight to
+/// [`InfiniteScrolledMap::set_pos_fixed`](super::InfiniteScrolledMap::set_pos_fixed).
+///
+/// If the level is smaller than the screen in either direction, the level is centred on the
+/// screen in that direction instead.
+#[must_use]
+pub fn camera_for(
+    target: Vector2D<Num<i32, 8>>,
+    level_bounds: &Rect<i32>,
+) -> Vector2D<Num<i32, 8>> {
+    let screen_centre: Vector2D<Num<i32, 8>> = (display::WIDTH / 2, display::HEIGHT / 2).into();
+    clamp_to_level(target - screen_centre, level_bounds)
+}
+
+/// Like [`camera_for`], but the camera only moves when `target` leaves `dead_zone`, so small
+/// movements of the player don't move the screen. `dead_zone` is in pixels relative to the top
+/// left of the screen, and `camera` is where the top left of the screen currently is, such as the
+/// value returned by this function last frame.
+#[must_use]
+pub fn camera_with_dead_zone(
+    camera: Vector2D<Num<i32, 8>>,
+    target: Vector2D<Num<i32, 8>>,
+    dead_zone: &Rect<i32>,
+    level_bounds: &Rect<i32>,
+) -> Vector2D<Num<i32, 8>> {
+    let follow = |camera: Num<i32, 8>, target: Num<i32, 8>, start: i32, length: i32| {
+        let on_screen = target - camera;
+        if on_screen < start.into() {
+            target - start
+        } else if on_screen > (start + length).into() {
+            target - (start + length)
+        } else {
+            camera
+        }
+    };
+
+    let camera = (
+        follow(camera.x, target.x, dead_zone.position.x, dead_zone.size.x),
+        follow(camera.y, target.y, dead_zone.position.y, dead_zone.size.y),
+    )
+        .into();
+
+    clamp_to_level(camera, level_bounds)
+}
+
+fn clamp_to_level(
+    camera: Vector2D<Num<i32, 8>>,
+    level_bounds: &Rect<i32>,
+) -> Vector2D<Num<i32, 8>> {
+    let clamp = |camera: Num<i32, 8>, start: i32, length: i32, screen_length: i32| {
+        if length <= screen_length {
+            Num::new(start) + Num::new(length - screen_length) / 2
+        } else {
+            camera
+                .max(start.into())
+                .min((start + length - screen_length).into())
+        }
+    };
+
+    (
+        clamp(
+            camera.x,
+            level_bounds.position.x,
+            level_bounds.size.x,
+            display::WIDTH,
+        ),
+        clamp(
+            camera.y,
+            level_bounds.position.y,
+            level_bounds.size.y,
+            display::HEIGHT,
+        ),
+    )
+        .into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixnum::num;
+
+    #[test_case]
+    fn the_camera_centres_on_the_target_inside_the_level(_gba: &mut crate::Gba) {
+        let level = Rect::new((-100, 0).into(), (1000, 500).into());
+
+        assert_eq!(
+            camera_for((num!(300.5), num!(200.)).into(), &level),
+            (num!(180.5), num!(120.)).into()
+        );
+
+        // near the top left and bottom right corners of the level
+        assert_eq!(camera_for((-90, 10).into(), &level), (-100, 0).into());
+        assert_eq!(
+            camera_for((895, 499).into(), &level),
+            (900 - 240, 500 - 160).into()
+        );
+    }
+
+    #[test_case]
+    fn levels_smaller_than_the_screen_are_centred(_gba: &mut crate::Gba) {
+        let level = Rect::new((16, 0).into(), (200, 1000).into());
+
+        assert_eq!(camera_for((50, 400).into(), &level), (16 - 20, 320).into());
+    }
+
+    #[test_case]
+    fn the_camera_only_moves_when_the_target_leaves_the_dead_zone(_gba: &mut crate::Gba) {
+        let level = Rect::new((0, 0).into(), (2000, 2000).into());
+        let dead_zone = Rect::new((100, 60).into(), (40, 40).into());
+
+        let camera: Vector2D<Num<i32, 8>> = (500, 500).into();
+
+        // inside the dead zone
+        assert_eq!(
+            camera_with_dead_zone(camera, (620, 580).into(), &dead_zone, &level),
+            camera
+        );
+
+        // past the right and top edges of the dead zone
+        assert_eq!(
+            camera_with_dead_zone(camera, (num!(645.5), num!(550.)).into(), &dead_zone, &level),
+            (num!(505.5), num!(490.)).into()
+        );
+
+        // and still clamped to the level
+        assert_eq!(
+            camera_with_dead_zone(camera, (10, 1990).into(), &dead_zone, &level),
+            (0, 2000 - 160).into()
+        );
+    }
+}
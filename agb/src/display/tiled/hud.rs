@@ -0,0 +1,200 @@
+use super::{
+    InfiniteScrolledMap, MapLoan, PartialUpdateStatus, RegularMap, TileProvider, TileSet,
+    TileSetting, VRamManager,
+};
+
+use crate::{display, fixnum::Vector2D};
+
+const SCREEN_WIDTH_TILES: u16 = (display::WIDTH / 8) as u16;
+const SCREEN_HEIGHT_TILES: u16 = (display::HEIGHT / 8) as u16;
+
+/// Which edge of the screen the status bar of a [`ScrolledMapWithHud`] is along.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HudEdge {
+    Top,
+    Bottom,
+}
+
+/// A scrolling playfield with a status bar along the top or bottom of the screen which stays
+/// still while the playfield scrolls underneath it.
+///
+/// The status bar is drawn on its own background which is never scrolled, and only the given
+/// number of tile rows along the edge of the screen can be set, so the rest of it stays
+/// transparent and the playfield shows through. The status bar's background should have a higher
+/// priority than the playfield's so that it is drawn on top.
+///
+/// As with [`InfiniteScrolledMap`], you must call [`.clear()`](ScrolledMapWithHud::clear) before
+/// this is dropped or you will leak video RAM.
+pub struct ScrolledMapWithHud<'a, F> {
+    hud: MapLoan<'a, RegularMap>,
+    playfield: InfiniteScrolledMap<'a, F>,
+    edge: HudEdge,
+    rows: u16,
+}
+
+impl<'a, F> ScrolledMapWithHud<'a, F>
+where
+    F: TileProvider<'a>,
+{
+    /// Creates the status bar using `hud`, which takes up `rows` tile rows along `edge` of the
+    /// screen, over the top of `playfield`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rows` is more than the 20 rows of the screen.
+    #[must_use]
+    pub fn new(
+        mut hud: MapLoan<'a, RegularMap>,
+        playfield: InfiniteScrolledMap<'a, F>,
+        edge: HudEdge,
+        rows: u16,
+    ) -> Self {
+        assert!(
+            rows <= SCREEN_HEIGHT_TILES,
+            "the status bar can be at most {} rows tall, got {}",
+            SCREEN_HEIGHT_TILES,
+            rows
+        );
+
+        hud.set_scroll_pos((0, 0).into());
+
+        Self {
+            hud,
+            playfield,
+            edge,
+            rows,
+        }
+    }
+
+    /// Sets a tile of the status bar. `pos` is relative to the top left of the status bar,
+    /// wherever it is on the screen.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pos` is outside of the status bar.
+    pub fn set_hud_tile(
+        &mut self,
+        vram: &mut VRamManager,
+        pos: Vector2D<u16>,
+        tileset: &TileSet<'_>,
+        tile_setting: TileSetting,
+    ) {
+        assert!(
+            pos.x < SCREEN_WIDTH_TILES && pos.y < self.rows,
+            "({}, {}) is outside of the {}x{} status bar",
+            pos.x,
+            pos.y,
+            SCREEN_WIDTH_TILES,
+            self.rows
+        );
+
+        let first_row = match self.edge {
+            HudEdge::Top => 0,
+            HudEdge::Bottom => SCREEN_HEIGHT_TILES - self.rows,
+        };
+
+        self.hud.set_tile(
+            vram,
+            (pos.x, first_row + pos.y).into(),
+            tileset,
+            tile_setting,
+        );
+    }
+
+    /// Scrolls the playfield so that `pos` is at the top left of the screen, leaving the status
+    /// bar where it is. This behaves in the same way as
+    /// [`InfiniteScrolledMap::set_pos`].
+    pub fn set_camera(
+        &mut self,
+        vram: &mut VRamManager,
+        pos: Vector2D<i32>,
+    ) -> PartialUpdateStatus {
+        self.playfield.set_pos(vram, pos)
+    }
+
+    /// The playfield, for example to initialise it or to find out about its tiles.
+    pub fn playfield_mut(&mut self) -> &mut InfiniteScrolledMap<'a, F> {
+        &mut self.playfield
+    }
+
+    /// Makes both the status bar and the playfield visible
+    pub fn show(&mut self) {
+        self.hud.show();
+        self.playfield.show();
+    }
+
+    /// Hides both the status bar and the playfield
+    pub fn hide(&mut self) {
+        self.hud.hide();
+        self.playfield.hide();
+    }
+
+    /// Copies both the status bar and the playfield to vram. Needs to be called during vblank if
+    /// possible
+    pub fn commit(&mut self, vram: &mut VRamManager) {
+        self.hud.commit(vram);
+        self.playfield.commit(vram);
+    }
+
+    /// Clears both the status bar and the playfield, releasing all the tiles they use in video
+    /// ram.
+    pub fn clear(&mut self, vram: &mut VRamManager) {
+        self.hud.clear(vram);
+        self.playfield.clear(vram);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::display::{
+        tiled::{RegularBackgroundSize, TileFormat},
+        Priority,
+    };
+
+    #[test_case]
+    fn the_status_bar_stays_still_while_the_playfield_scrolls(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+
+        let tiles = [0x55u8; 8 * 8 / 2];
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        let playfield = InfiniteScrolledMap::with_tile_provider(
+            gfx.background(Priority::P1, RegularBackgroundSize::Background32x32),
+            |_| (&tileset, TileSetting::new(0, false, false, 0)),
+        );
+        let mut map = ScrolledMapWithHud::new(
+            gfx.background(Priority::P0, RegularBackgroundSize::Background32x32),
+            playfield,
+            HudEdge::Bottom,
+            2,
+        );
+
+        map.playfield_mut()
+            .init(&mut vram, (0, 0).into(), &mut || {});
+        map.set_hud_tile(
+            &mut vram,
+            (3u16, 1u16).into(),
+            &tileset,
+            TileSetting::new(0, false, false, 1),
+        );
+        assert_eq!(
+            map.hud.tile_setting_bits((3u16, 19u16).into()),
+            TileSetting::new(0, false, false, 1).setting()
+        );
+
+        // far enough to wrap around the 256 pixel background more than once
+        let mut camera: Vector2D<i32> = (0, 0).into();
+        for _ in 0..300 {
+            camera += (3, -2).into();
+            while map.set_camera(&mut vram, camera) == PartialUpdateStatus::Continue {}
+            map.commit(&mut vram);
+
+            assert_eq!(map.hud.scroll_pos(), (0, 0).into());
+            assert_eq!(map.playfield_mut().position(), camera);
+        }
+
+        map.clear(&mut vram);
+        map.commit(&mut vram);
+    }
+}
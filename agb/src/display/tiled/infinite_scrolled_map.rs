@@ -1,10 +1,14 @@
-use alloc::boxed::Box;
+use core::{iter, ops::Range};
 
-use super::{BackgroundID, MapLoan, RegularMap, TileSet, TileSetting, VRamManager};
+use alloc::{boxed::Box, vec::Vec};
+
+use super::{
+    BackgroundID, MapLoan, RegularMap, TileMap, TileMapEdge, TileSet, TileSetting, VRamManager,
+};
 
 use crate::{
     display,
-    fixnum::{Rect, Vector2D},
+    fixnum::{Num, Rect, Vector2D},
 };
 
 /// The infinite scrolled map allows you to create a game space larger than a single GBA background.
@@ -14,6 +18,8 @@ use crate::{
 /// onto and a function which takes a Vector2D<i32> position and returns which tile should be rendered there.
 ///
 /// The passed function should handle being out of bounds, as the scrolled map does buffer around the edges slightly.
+/// It may also mutate its own state, for example to cache decompressed level data or to track which areas have been
+/// seen, since it is only ever called from methods which take `&mut self`.
 ///
 /// Note that nothing is copied to video memory until you call [`.commit()`](`InfiniteScrolledMap::commit`), and you
 /// must call [`.clear()`](`InfiniteScrolledMap::clear`) before dropping the infinite scrolled map or you will leak video RAM.
@@ -25,7 +31,7 @@ use crate::{
 /// # #![no_main]
 /// extern crate alloc;
 ///
-/// use alloc::boxed::Box;
+/// use alloc::{boxed::Box, vec::Vec};
 ///
 /// use agb::display::tiled::{
 ///     InfiniteScrolledMap,
@@ -71,16 +77,45 @@ use crate::{
 /// backdrop.show();
 /// # }
 /// ```
-pub struct InfiniteScrolledMap<'a> {
+///
+/// If you would rather avoid the allocation and dynamic dispatch of the boxed function, use
+/// [`InfiniteScrolledMap::with_tile_provider`] which accepts any function directly.
+///
+/// Any size of background can be used. Tiles just off screen are loaded ahead of time into the
+/// part of the background which isn't visible, so a larger background such as a 64x64 one means
+/// tiles are loaded in bigger batches much less often.
+pub struct InfiniteScrolledMap<
+    'a,
+    F = Box<dyn FnMut(Vector2D<i32>) -> (&'a TileSet<'a>, TileSetting) + 'a>,
+> {
     map: MapLoan<'a, RegularMap>,
-    tile: Box<dyn Fn(Vector2D<i32>) -> (&'a TileSet<'a>, TileSetting) + 'a>,
+    tile: F,
 
     current_pos: Vector2D<i32>,
     offset: Vector2D<i32>,
 
+    // the tiles which are currently correct in the background, which is the visible tiles plus
+    // any which were loaded ahead of time
+    loaded_xs: Range<i32>,
+    loaded_ys: Range<i32>,
+
     copied_up_to: i32,
+    tiles_per_partial_update: i32,
+    screen_margin: i32,
+
+    // reused between calls to set_pos to collect the newly visible rows and columns
+    tile_buffer: Vec<(&'a TileSet<'a>, TileSetting)>,
 }
 
+// Roughly 2 rows of the visible area
+const DEFAULT_TILES_PER_PARTIAL_UPDATE: i32 = 64;
+// The size of the largest sprite
+const DEFAULT_SCREEN_MARGIN: i32 = 64;
+
+// The furthest set_pos will scroll in each direction in a single call, to keep the number of tiles
+// it needs to set bounded
+const MAX_SCROLL_PER_UPDATE: i32 = 10 * 8;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PartialUpdateStatus {
     Done,
@@ -97,17 +132,272 @@ impl<'a> InfiniteScrolledMap<'a> {
     #[must_use]
     pub fn new(
         map: MapLoan<'a, RegularMap>,
-        tile: Box<dyn Fn(Vector2D<i32>) -> (&'a TileSet<'a>, TileSetting) + 'a>,
+        tile: Box<dyn FnMut(Vector2D<i32>) -> (&'a TileSet<'a>, TileSetting) + 'a>,
     ) -> Self {
+        Self::with_tile_provider(map, tile)
+    }
+}
+
+/// Decides which tile goes where for an [`InfiniteScrolledMap`]. This is implemented for any
+/// function from a position to a tile, but level data which is faster to decode a row or column at
+/// a time, such as run length encoded data, can implement [`TileProvider::row`] and
+/// [`TileProvider::column`] directly.
+pub trait TileProvider<'a> {
+    /// Returns the tile at `pos`, in tiles.
+    fn tile(&mut self, pos: Vector2D<i32>) -> (&'a TileSet<'a>, TileSetting);
+
+    /// Pushes the tiles of row `y` for each x in `xs` on to `tiles`, in order.
+    fn row(&mut self, y: i32, xs: Range<i32>, tiles: &mut Vec<(&'a TileSet<'a>, TileSetting)>) {
+        tiles.extend(xs.map(|x| self.tile((x, y).into())));
+    }
+
+    /// Pushes the tiles of column `x` for each y in `ys` on to `tiles`, in order.
+    fn column(&mut self, x: i32, ys: Range<i32>, tiles: &mut Vec<(&'a TileSet<'a>, TileSetting)>) {
+        tiles.extend(ys.map(|y| self.tile((x, y).into())));
+    }
+
+    /// Returns the gameplay property of the tile at `pos`, in tiles, such as whether it is solid.
+    /// This is used by [`InfiniteScrolledMap::tile_property`], and is 0 unless implemented.
+    fn property(&mut self, _pos: Vector2D<i32>) -> u8 {
+        0
+    }
+}
+
+/// A [`TileProvider`] which displays a [`TileMap`] using a single tileset, with `edge` deciding
+/// what is beyond the edges of the tile map. The properties of the tile map are available using
+/// [`InfiniteScrolledMap::tile_property`].
+pub struct TileMapProvider<'a> {
+    tile_map: TileMap,
+    tileset: &'a TileSet<'a>,
+    edge: TileMapEdge,
+}
+
+impl<'a> TileMapProvider<'a> {
+    #[must_use]
+    pub fn new(tile_map: TileMap, tileset: &'a TileSet<'a>, edge: TileMapEdge) -> Self {
+        Self {
+            tile_map,
+            tileset,
+            edge,
+        }
+    }
+
+    fn source_position(&self, pos: Vector2D<i32>) -> (u16, u16) {
+        (
+            self.edge.world_position(pos.x, self.tile_map.width()),
+            self.edge.world_position(pos.y, self.tile_map.height()),
+        )
+    }
+}
+
+impl<'a> TileProvider<'a> for TileMapProvider<'a> {
+    fn tile(&mut self, pos: Vector2D<i32>) -> (&'a TileSet<'a>, TileSetting) {
+        let (x, y) = self.source_position(pos);
+        (self.tileset, self.tile_map.get(x, y))
+    }
+
+    fn property(&mut self, pos: Vector2D<i32>) -> u8 {
+        let (x, y) = self.source_position(pos);
+        self.tile_map.property(x, y)
+    }
+}
+
+/// A [`TileProvider`] for a level of a fixed size, which only asks `provider` for tiles within
+/// `bounds` and uses the `outside` tile everywhere else, so `provider` doesn't need to check
+/// whether positions are inside the level. Use [`InfiniteScrolledMap::with_bounds`] to create an
+/// infinite scrolled map using one.
+///
+/// `bounds` is in tiles, and contains the tiles from its position up to but not including its
+/// position plus its size.
+pub struct BoundedTileProvider<'a, P> {
+    provider: P,
+    bounds: Rect<i32>,
+    outside: (&'a TileSet<'a>, TileSetting),
+}
+
+impl<'a, P> BoundedTileProvider<'a, P> {
+    #[must_use]
+    pub fn new(provider: P, bounds: Rect<i32>, outside: (&'a TileSet<'a>, TileSetting)) -> Self {
+        Self {
+            provider,
+            bounds,
+            outside,
+        }
+    }
+
+    #[must_use]
+    pub fn bounds(&self) -> &Rect<i32> {
+        &self.bounds
+    }
+
+    fn xs(&self) -> Range<i32> {
+        self.bounds.position.x..self.bounds.position.x + self.bounds.size.x
+    }
+
+    fn ys(&self) -> Range<i32> {
+        self.bounds.position.y..self.bounds.position.y + self.bounds.size.y
+    }
+
+    fn contains(&self, pos: Vector2D<i32>) -> bool {
+        self.xs().contains(&pos.x) && self.ys().contains(&pos.y)
+    }
+}
+
+// Fills in the tiles of `line` which are outside of `inside` with `outside`, using `fetch` for
+// the rest.
+fn fill_bounded_line<'a>(
+    line: Range<i32>,
+    inside: Option<Range<i32>>,
+    outside: (&'a TileSet<'a>, TileSetting),
+    tiles: &mut Vec<(&'a TileSet<'a>, TileSetting)>,
+    fetch: impl FnOnce(Range<i32>, &mut Vec<(&'a TileSet<'a>, TileSetting)>),
+) {
+    let inside = match inside {
+        Some(inside) => inside,
+        None => {
+            tiles.resize(tiles.len() + line.len(), outside);
+            return;
+        }
+    };
+
+    tiles.resize(tiles.len() + (inside.start - line.start) as usize, outside);
+    fetch(inside.clone(), tiles);
+    tiles.resize(tiles.len() + (line.end - inside.end) as usize, outside);
+}
+
+impl<'a, P> TileProvider<'a> for BoundedTileProvider<'a, P>
+where
+    P: TileProvider<'a>,
+{
+    fn tile(&mut self, pos: Vector2D<i32>) -> (&'a TileSet<'a>, TileSetting) {
+        if self.contains(pos) {
+            self.provider.tile(pos)
+        } else {
+            self.outside
+        }
+    }
+
+    fn row(&mut self, y: i32, xs: Range<i32>, tiles: &mut Vec<(&'a TileSet<'a>, TileSetting)>) {
+        let line = Rect::new((xs.start, y).into(), (xs.len() as i32, 1).into());
+        let inside = line
+            .clip_to(&self.bounds)
+            .map(|inside| inside.position.x..inside.position.x + inside.size.x);
+
+        let provider = &mut self.provider;
+        fill_bounded_line(xs, inside, self.outside, tiles, |xs, tiles| {
+            provider.row(y, xs, tiles);
+        });
+    }
+
+    fn column(&mut self, x: i32, ys: Range<i32>, tiles: &mut Vec<(&'a TileSet<'a>, TileSetting)>) {
+        let line = Rect::new((x, ys.start).into(), (1, ys.len() as i32).into());
+        let inside = line
+            .clip_to(&self.bounds)
+            .map(|inside| inside.position.y..inside.position.y + inside.size.y);
+
+        let provider = &mut self.provider;
+        fill_bounded_line(ys, inside, self.outside, tiles, |ys, tiles| {
+            provider.column(x, ys, tiles);
+        });
+    }
+
+    fn property(&mut self, pos: Vector2D<i32>) -> u8 {
+        if self.contains(pos) {
+            self.provider.property(pos)
+        } else {
+            0
+        }
+    }
+}
+
+impl<'a, F> TileProvider<'a> for F
+where
+    F: FnMut(Vector2D<i32>) -> (&'a TileSet<'a>, TileSetting),
+{
+    fn tile(&mut self, pos: Vector2D<i32>) -> (&'a TileSet<'a>, TileSetting) {
+        self(pos)
+    }
+}
+
+impl<'a, F> InfiniteScrolledMap<'a, F>
+where
+    F: FnMut(Vector2D<i32>) -> (&'a TileSet<'a>, TileSetting),
+{
+    /// Creates a new infinite scrolled map in the same way as [`InfiniteScrolledMap::new`], but
+    /// without needing to box the function which positions tiles. This lets the function borrow
+    /// level data directly and avoids an indirect call for every tile.
+    #[must_use]
+    pub fn with_tile_provider(map: MapLoan<'a, RegularMap>, tile: F) -> Self {
+        Self::with_provider(map, tile)
+    }
+}
+
+impl<'a, P> InfiniteScrolledMap<'a, BoundedTileProvider<'a, P>>
+where
+    P: TileProvider<'a>,
+{
+    /// Creates a new infinite scrolled map for a level which only has tiles within `bounds`, in
+    /// tiles. `provider` is only ever asked for tiles inside `bounds`, and the `outside` tile is
+    /// used everywhere else. See [`BoundedTileProvider`].
+    #[must_use]
+    pub fn with_bounds(
+        map: MapLoan<'a, RegularMap>,
+        provider: P,
+        bounds: Rect<i32>,
+        outside: (&'a TileSet<'a>, TileSetting),
+    ) -> Self {
+        Self::with_provider(map, BoundedTileProvider::new(provider, bounds, outside))
+    }
+
+    /// Changes the area of the level which has tiles, for example when moving to a different
+    /// room of a larger map. If the map has been initialised, every visible tile is set again
+    /// straight away in the same way as [`.teleport()`](`InfiniteScrolledMap::teleport`).
+    pub fn set_bounds(&mut self, vram: &mut VRamManager, bounds: Rect<i32>) {
+        self.tile.bounds = bounds;
+
+        if self.copied_up_to != 0 || !self.loaded_xs.is_empty() {
+            self.teleport(vram, self.current_pos);
+        }
+    }
+}
+
+impl<'a, F> InfiniteScrolledMap<'a, F>
+where
+    F: TileProvider<'a>,
+{
+    /// Creates a new infinite scrolled map which gets its tiles from a [`TileProvider`], so level
+    /// data can be decoded a whole row or column at a time while scrolling.
+    #[must_use]
+    pub fn with_provider(map: MapLoan<'a, RegularMap>, tile: F) -> Self {
         Self {
             map,
             tile,
             current_pos: (0, 0).into(),
             offset: (0, 0).into(),
+            loaded_xs: 0..0,
+            loaded_ys: 0..0,
             copied_up_to: 0,
+            tiles_per_partial_update: DEFAULT_TILES_PER_PARTIAL_UPDATE,
+            screen_margin: DEFAULT_SCREEN_MARGIN,
+            tile_buffer: Vec::new(),
         }
     }
 
+    /// Sets the maximum number of tiles which [`.init_partial()`](`InfiniteScrolledMap::init_partial`)
+    /// will set in a single call. Lower values spread initialisation over more frames. The default
+    /// is 64, which is around 2 rows of the screen.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tiles` is 0, since initialisation would never finish.
+    pub fn set_partial_update_budget(&mut self, tiles: u16) {
+        assert!(
+            tiles > 0,
+            "the partial update budget must be at least 1 tile"
+        );
+        self.tiles_per_partial_update = i32::from(tiles);
+    }
+
     /// Initialises the map and fills it, calling the between_updates occasionally to allow you to ensure that
     /// music keeps playing without interruption.
     ///
@@ -120,7 +410,7 @@ impl<'a> InfiniteScrolledMap<'a> {
     /// # #![no_main]
     /// # extern crate alloc;
     /// #
-    /// # use alloc::boxed::Box;
+    /// # use alloc::{boxed::Box, vec::Vec};
     /// #
     /// # use agb::display::tiled::{
     /// #    InfiniteScrolledMap,
@@ -176,9 +466,11 @@ impl<'a> InfiniteScrolledMap<'a> {
         }
     }
 
-    /// Does a partial initialisation of the background, rendering 2 rows.
+    /// Does a partial initialisation of the background, setting at most the number of tiles given
+    /// by [`.set_partial_update_budget()`](`InfiniteScrolledMap::set_partial_update_budget`).
     /// This is because initialisation can take quite a while, so you will need to call
-    /// this method a few times to ensure that you update the entire frame.
+    /// this method a few times to ensure that you update the entire frame. Calling this with a
+    /// different position part way through restarts the initialisation.
     ///
     /// Returns [`PartialUpdateStatus::Done`] if complete, and [`PartialUpdateStatus::Continue`]
     /// if you need to call this a few more times to fully update the screen.
@@ -194,7 +486,7 @@ impl<'a> InfiniteScrolledMap<'a> {
     /// # #![no_main]
     /// # extern crate alloc;
     /// #
-    /// # use alloc::boxed::Box;
+    /// # use alloc::{boxed::Box, vec::Vec};
     /// #
     /// # use agb::display::tiled::{
     /// #    InfiniteScrolledMap,
@@ -245,6 +537,15 @@ impl<'a> InfiniteScrolledMap<'a> {
         vram: &mut VRamManager,
         pos: Vector2D<i32>,
     ) -> PartialUpdateStatus {
+        if pos != self.current_pos {
+            self.copied_up_to = 0;
+        }
+
+        if self.copied_up_to == 0 {
+            self.loaded_xs = 0..0;
+            self.loaded_ys = 0..0;
+        }
+
         self.current_pos = pos;
 
         let x_start = div_floor(self.current_pos.x, 8);
@@ -254,144 +555,199 @@ impl<'a> InfiniteScrolledMap<'a> {
         let y_end = div_ceil(self.current_pos.y + display::HEIGHT, 8) + 1;
 
         let offset = self.current_pos - (x_start * 8, y_start * 8).into();
-        let offset_scroll = (
-            self.map.size().tile_pos_x(offset.x),
-            self.map.size().tile_pos_y(offset.y),
-        )
-            .into();
 
-        self.map.set_scroll_pos(offset_scroll);
+        self.map.set_scroll_pos(offset);
         self.offset = (x_start, y_start).into();
 
+        let width = x_end - x_start;
+        let total_tiles = width * (y_end - y_start);
+
         let copy_from = self.copied_up_to;
-        const ROWS_TO_COPY: i32 = 2;
+        let copy_to = total_tiles.min(copy_from + self.tiles_per_partial_update);
 
-        for (y_idx, y) in
-            ((y_start + copy_from)..(y_end.min(y_start + copy_from + ROWS_TO_COPY))).enumerate()
-        {
-            for (x_idx, x) in (x_start..x_end).enumerate() {
-                let pos = (x, y).into();
-                let (tileset, tile_setting) = (self.tile)(pos);
-
-                self.map.set_tile(
-                    vram,
-                    (x_idx as u16, (y_idx + copy_from as usize) as u16).into(),
-                    tileset,
-                    tile_setting,
-                );
-            }
+        for tile in copy_from..copy_to {
+            let x_idx = tile % width;
+            let y_idx = tile / width;
+
+            let (tileset, tile_setting) = self.tile.tile((x_start + x_idx, y_start + y_idx).into());
+
+            self.map.set_tile(
+                vram,
+                (x_idx as u16, y_idx as u16).into(),
+                tileset,
+                tile_setting,
+            );
         }
 
-        if copy_from + ROWS_TO_COPY >= y_end - y_start {
+        if copy_to >= total_tiles {
             self.copied_up_to = 0;
+            self.loaded_xs = x_start..x_end;
+            self.loaded_ys = y_start..y_end;
             PartialUpdateStatus::Done
         } else {
-            self.copied_up_to = copy_from + ROWS_TO_COPY;
+            self.copied_up_to = copy_to;
             PartialUpdateStatus::Continue
         }
     }
 
-    /// Set the top left corner of the map. You may need to call this method multiple times if
-    /// [`PartialUpdateStatus::Continue`] is returned.
+    /// Set the top left corner of the map using a fixed point position, such as a camera which
+    /// moves by fractions of a pixel each frame. The map can only be positioned to the nearest
+    /// pixel, so this always rounds down (so -0.5 is treated as -1), which means the map only moves
+    /// when the whole pixel part of the position changes. Otherwise this is the same as
+    /// [`.set_pos()`](`InfiniteScrolledMap::set_pos`).
+    pub fn set_pos_fixed(
+        &mut self,
+        vram: &mut VRamManager,
+        new_pos: Vector2D<Num<i32, 8>>,
+    ) -> PartialUpdateStatus {
+        self.set_pos(vram, new_pos.floor())
+    }
+
+    /// Scrolls the map towards the given top left corner, only setting the tiles which come into
+    /// view. The map moves at most 80 pixels in each direction per call, so if `new_pos` is
+    /// further away than that this returns [`PartialUpdateStatus::Continue`] and you need to call
+    /// it again, for example on the next frame, to finish scrolling. Use
+    /// [`.teleport()`](`InfiniteScrolledMap::teleport`) to jump straight to a far away position.
+    ///
+    /// If the map is part way through being initialised by
+    /// [`.init_partial()`](`InfiniteScrolledMap::init_partial`), this carries on with that
+    /// initialisation instead and won't scroll until it is done.
     pub fn set_pos(
         &mut self,
         vram: &mut VRamManager,
         new_pos: Vector2D<i32>,
     ) -> PartialUpdateStatus {
-        let old_pos = self.current_pos;
+        if self.copied_up_to != 0
+            && self.init_partial(vram, self.current_pos) == PartialUpdateStatus::Continue
+        {
+            return PartialUpdateStatus::Continue;
+        }
 
-        let difference = new_pos - old_pos;
+        let old_pos = self.current_pos;
 
-        if difference.x.abs() > 10 * 8 || difference.y.abs() > 10 * 8 {
-            return self.init_partial(vram, new_pos);
-        }
+        let requested = new_pos - old_pos;
+        let difference: Vector2D<i32> = (
+            requested
+                .x
+                .clamp(-MAX_SCROLL_PER_UPDATE, MAX_SCROLL_PER_UPDATE),
+            requested
+                .y
+                .clamp(-MAX_SCROLL_PER_UPDATE, MAX_SCROLL_PER_UPDATE),
+        )
+            .into();
+        let new_pos = old_pos + difference;
 
         self.current_pos = new_pos;
 
-        let new_tile_x = div_floor(new_pos.x, 8);
-        let new_tile_y = div_floor(new_pos.y, 8);
+        let size = self.map.size();
 
-        let difference_tile_x = div_ceil(difference.x, 8);
-        let difference_tile_y = div_ceil(difference.y, 8);
+        let (visible_xs, visible_ys) = visible_tiles(new_pos);
 
-        let size = self.map.size();
+        let old_xs = self.loaded_xs.clone();
+        let old_ys = self.loaded_ys.clone();
+        let new_xs = tiles_to_load(old_xs.clone(), visible_xs, size.width() as i32);
+        let new_ys = tiles_to_load(old_ys.clone(), visible_ys, size.height() as i32);
 
-        let vertical_rect_to_update: Rect<i32> = if div_floor(old_pos.x, 8) != new_tile_x {
-            // need to update the x line
-            // calculate which direction we need to update
-            let direction = difference.x.signum();
-
-            // either need to update 20 or 21 tiles depending on whether the y coordinate is a perfect multiple
-            let y_tiles_to_update = 22;
-
-            let line_to_update = if direction < 0 {
-                // moving to the left, so need to update the left most position
-                new_tile_x
-            } else {
-                // moving to the right, so need to update the right most position
-                new_tile_x + 30 // TODO is this correct?
-            };
-
-            Rect::new(
-                (line_to_update, new_tile_y - 1).into(),
-                (difference_tile_x, y_tiles_to_update).into(),
-            )
-        } else {
-            Rect::new((0i32, 0).into(), (0i32, 0).into())
-        };
+        self.loaded_xs = new_xs.clone();
+        self.loaded_ys = new_ys.clone();
 
-        let horizontal_rect_to_update: Rect<i32> = if div_floor(old_pos.y, 8) != new_tile_y {
-            // need to update the y line
-            // calculate which direction we need to update
-            let direction = difference.y.signum();
-
-            // either need to update 30 or 31 tiles depending on whether the x coordinate is a perfect multiple
-            let x_tiles_to_update: i32 = 32;
-
-            let line_to_update = if direction < 0 {
-                // moving up so need to update the top
-                new_tile_y
-            } else {
-                // moving down so need to update the bottom
-                new_tile_y + 20 // TODO is this correct?
-            };
-
-            Rect::new(
-                (new_tile_x - 1, line_to_update).into(),
-                (x_tiles_to_update, difference_tile_y).into(),
-            )
-        } else {
-            Rect::new((0i32, 0).into(), (0i32, 0).into())
-        };
+        // Every tile which was loaded before is already correct, so only the columns and rows
+        // which have just been loaded need updating.
+        let new_columns = newly_visible(old_xs, new_xs.clone());
+        let new_rows = newly_visible(old_ys, new_ys.clone());
 
-        for (tile_x, tile_y) in vertical_rect_to_update
-            .iter()
-            .chain(horizontal_rect_to_update.iter())
-        {
-            let (tileset, tile_setting) = (self.tile)((tile_x, tile_y).into());
+        for tile_x in new_columns.flatten() {
+            self.tile_buffer.clear();
+            self.tile
+                .column(tile_x, new_ys.clone(), &mut self.tile_buffer);
 
-            self.map.set_tile(
+            self.map.set_tile_column(
                 vram,
-                (
-                    size.tile_pos_x(tile_x - self.offset.x),
-                    size.tile_pos_y(tile_y - self.offset.y),
-                )
-                    .into(),
-                tileset,
-                tile_setting,
+                size.tile_pos_x(tile_x - self.offset.x),
+                size.tile_pos_y(new_ys.start - self.offset.y),
+                &self.tile_buffer,
             );
         }
 
-        let current_scroll = self.map.scroll_pos();
-        let new_scroll = (
-            size.px_offset_x(i32::from(current_scroll.x) + difference.x),
-            size.px_offset_y(i32::from(current_scroll.y) + difference.y),
-        )
-            .into();
+        for tile_y in new_rows.flatten() {
+            self.tile_buffer.clear();
+            self.tile.row(tile_y, new_xs.clone(), &mut self.tile_buffer);
+
+            self.map.set_tile_row(
+                vram,
+                size.tile_pos_y(tile_y - self.offset.y),
+                size.tile_pos_x(new_xs.start - self.offset.x),
+                &self.tile_buffer,
+            );
+        }
+
+        self.map.scroll_by(difference);
 
-        self.map.set_scroll_pos(new_scroll);
+        if difference == requested {
+            PartialUpdateStatus::Done
+        } else {
+            PartialUpdateStatus::Continue
+        }
+    }
+
+    /// Moves the top left corner of the map straight to `pos`, however far away it is, by
+    /// setting every visible tile again. This abandons any initialisation which was in progress.
+    /// Unlike [`.init()`](`InfiniteScrolledMap::init`) this does all the work at once, so use
+    /// [`.init_partial()`](`InfiniteScrolledMap::init_partial`) if it needs to be spread over
+    /// several frames.
+    pub fn teleport(&mut self, vram: &mut VRamManager, pos: Vector2D<i32>) {
+        self.copied_up_to = 0;
+        self.current_pos = pos;
+
+        while self.init_partial(vram, pos) != PartialUpdateStatus::Done {}
+    }
 
-        PartialUpdateStatus::Done
+    /// The gameplay property of the tile drawn at `world_pos`, in pixels, from
+    /// [`TileProvider::property`]. This is the same position that is passed to
+    /// [`.set_pos()`](`InfiniteScrolledMap::set_pos`), so for example the tile under a sprite
+    /// at `world_pos - map.position()` on screen.
+    pub fn tile_property(&mut self, world_pos: Vector2D<i32>) -> u8 {
+        self.tile
+            .property((div_floor(world_pos.x, 8), div_floor(world_pos.y, 8)).into())
+    }
+
+    /// The top left corner of the map, which is where the map has actually scrolled to and may
+    /// lag behind the position last passed to
+    /// [`.set_pos()`](`InfiniteScrolledMap::set_pos`) if that was a large jump.
+    #[must_use]
+    pub fn position(&self) -> Vector2D<i32> {
+        self.current_pos
+    }
+
+    /// Where `world_pos`, in the same coordinates as
+    /// [`.set_pos()`](`InfiniteScrolledMap::set_pos`), is on the screen, for example to place a
+    /// sprite over the map. This uses the position the map has actually scrolled to, so sprites
+    /// placed using it line up with the background even while the map is catching up with a
+    /// large jump.
+    ///
+    /// Returns `None` if the position is further off screen than the margin set by
+    /// [`.set_screen_margin()`](`InfiniteScrolledMap::set_screen_margin`).
+    #[must_use]
+    pub fn to_screen_space(&self, world_pos: Vector2D<i32>) -> Option<Vector2D<i32>> {
+        let screen_pos = world_pos - self.current_pos;
+        let margin = self.screen_margin;
+
+        let on_screen = (-margin..display::WIDTH + margin).contains(&screen_pos.x)
+            && (-margin..display::HEIGHT + margin).contains(&screen_pos.y);
+
+        if on_screen {
+            Some(screen_pos)
+        } else {
+            None
+        }
+    }
+
+    /// Sets how far off the screen, in pixels, a position can be before
+    /// [`.to_screen_space()`](`InfiniteScrolledMap::to_screen_space`) returns `None`. The
+    /// default is 64, so that a sprite of any size positioned by its top left corner is never
+    /// hidden while part of it is still on screen.
+    pub fn set_screen_margin(&mut self, margin: u16) {
+        self.screen_margin = i32::from(margin);
     }
 
     /// Makes the map visible
@@ -409,10 +765,21 @@ impl<'a> InfiniteScrolledMap<'a> {
         self.map.commit(vram);
     }
 
-    /// Clears the underlying map. You must call this before the scrolled map goes out of scope
-    /// or you will leak VRam.
+    /// Clears the underlying map, releasing all the tiles it uses in video ram. You must call this
+    /// before the scrolled map goes out of scope or you will leak VRam, since dropping it doesn't
+    /// have access to the [`VRamManager`]. The tiles are freed on the next
+    /// [`.commit()`](`InfiniteScrolledMap::commit`) or commit of any other map.
+    ///
+    /// Afterwards the map is empty and must be initialised again using
+    /// [`.init()`](`InfiniteScrolledMap::init`) before it can be scrolled.
     pub fn clear(&mut self, vram: &mut VRamManager) {
         self.map.clear(vram);
+
+        self.current_pos = (0, 0).into();
+        self.offset = (0, 0).into();
+        self.loaded_xs = 0..0;
+        self.loaded_ys = 0..0;
+        self.copied_up_to = 0;
     }
 
     #[must_use]
@@ -421,6 +788,44 @@ impl<'a> InfiniteScrolledMap<'a> {
     }
 }
 
+// The range of tiles in each direction which are at least partly on screen when the top left of
+// the screen is at `pos`
+fn visible_tiles(pos: Vector2D<i32>) -> (Range<i32>, Range<i32>) {
+    (
+        div_floor(pos.x, 8)..div_floor(pos.x + display::WIDTH - 1, 8) + 1,
+        div_floor(pos.y, 8)..div_floor(pos.y + display::HEIGHT - 1, 8) + 1,
+    )
+}
+
+// The range of tiles along one axis which should be loaded once `visible` is on screen, given that
+// `loaded` is already correct and the background is `background_size` tiles long. Nothing new is
+// loaded until part of `visible` isn't loaded, and then half of the spare space in the background
+// is filled with tiles ahead of the direction of travel, so that larger backgrounds need loading
+// less often.
+fn tiles_to_load(loaded: Range<i32>, visible: Range<i32>, background_size: i32) -> Range<i32> {
+    if loaded.start <= visible.start && visible.end <= loaded.end {
+        return loaded;
+    }
+
+    let ahead = (background_size - visible.len() as i32) / 2;
+
+    if visible.end > loaded.end {
+        visible.start..visible.end + ahead
+    } else {
+        visible.start - ahead..visible.end
+    }
+}
+
+// The parts of `new` which aren't in `old`, which is at most one range either side of `old`
+fn newly_visible(old: Range<i32>, new: Range<i32>) -> impl Iterator<Item = Range<i32>> {
+    let before = new.start..new.end.min(old.start);
+    let after = new.start.max(old.end)..new.end;
+
+    iter::once(before)
+        .chain(iter::once(after))
+        .filter(|range| !range.is_empty())
+}
+
 fn div_floor(x: i32, y: i32) -> i32 {
     if x > 0 && y < 0 {
         (x - 1) / y - 1
@@ -440,3 +845,643 @@ fn div_ceil(x: i32, y: i32) -> i32 {
         x / y
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::display::{tiled::TileFormat, Priority};
+
+    #[test_case]
+    fn init_partial_respects_budget_and_blocks_scrolling(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+
+        let tiles = [0x33u8; 8 * 8 / 2];
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        let mut map = InfiniteScrolledMap::new(
+            gfx.background(
+                Priority::P0,
+                super::super::RegularBackgroundSize::Background32x32,
+            ),
+            Box::new(|_| (&tileset, TileSetting::new(0, false, false, 0))),
+        );
+
+        map.set_partial_update_budget(100);
+
+        // 32x22 tiles are needed to cover the screen at this position
+        let mut calls = 1;
+        while map.init_partial(&mut vram, (4, 4).into()) == PartialUpdateStatus::Continue {
+            calls += 1;
+
+            if calls == 2 {
+                let scroll = map.map.scroll_pos();
+                assert_eq!(
+                    map.set_pos(&mut vram, (12, 12).into()),
+                    PartialUpdateStatus::Continue
+                );
+                assert_eq!(map.map.scroll_pos(), scroll);
+                assert_eq!(map.current_pos, (4, 4).into());
+                calls += 1;
+            }
+        }
+
+        assert_eq!(calls, (32 * 22 + 99) / 100);
+        assert_eq!(
+            map.set_pos(&mut vram, (12, 12).into()),
+            PartialUpdateStatus::Done
+        );
+
+        map.clear(&mut vram);
+        map.commit(&mut vram);
+    }
+
+    #[test_case]
+    fn tile_provider_can_mutate_state(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+
+        let tiles = [0x44u8; 8 * 8 / 2];
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        let mut tiles_requested = 0;
+
+        let mut map = InfiniteScrolledMap::with_tile_provider(
+            gfx.background(
+                Priority::P0,
+                super::super::RegularBackgroundSize::Background32x32,
+            ),
+            |_| {
+                tiles_requested += 1;
+                (&tileset, TileSetting::new(0, false, false, 0))
+            },
+        );
+
+        map.init(&mut vram, (0, 0).into(), &mut || {});
+        map.clear(&mut vram);
+        map.commit(&mut vram);
+
+        drop(map);
+        assert_eq!(tiles_requested, 31 * 21);
+    }
+
+    // Encodes which copy of the 32x32 tile map a world tile is in into its palette, so that a tile
+    // left over from 32 tiles away can be told apart from the correct one. It is always flipped so
+    // that it can't be mistaken for an empty tile.
+    fn looping_test_tile(pos: Vector2D<i32>) -> TileSetting {
+        let palette =
+            (pos.x.div_euclid(32).rem_euclid(4) + 4 * pos.y.div_euclid(32).rem_euclid(4)) as u8;
+        TileSetting::new(0, true, false, palette)
+    }
+
+    struct RowDecoder<'a> {
+        tileset: &'a TileSet<'a>,
+        tile_calls: usize,
+        line_calls: usize,
+    }
+
+    impl<'a> TileProvider<'a> for RowDecoder<'a> {
+        fn tile(&mut self, pos: Vector2D<i32>) -> (&'a TileSet<'a>, TileSetting) {
+            self.tile_calls += 1;
+            (self.tileset, looping_test_tile(pos))
+        }
+
+        fn row(&mut self, y: i32, xs: Range<i32>, tiles: &mut Vec<(&'a TileSet<'a>, TileSetting)>) {
+            self.line_calls += 1;
+            let tileset = self.tileset;
+            tiles.extend(xs.map(|x| (tileset, looping_test_tile((x, y).into()))));
+        }
+
+        fn column(
+            &mut self,
+            x: i32,
+            ys: Range<i32>,
+            tiles: &mut Vec<(&'a TileSet<'a>, TileSetting)>,
+        ) {
+            self.line_calls += 1;
+            let tileset = self.tileset;
+            tiles.extend(ys.map(|y| (tileset, looping_test_tile((x, y).into()))));
+        }
+    }
+
+    #[test_case]
+    fn scrolling_fetches_whole_rows_and_columns_from_the_provider(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+
+        let tiles = [0x55u8; 8 * 8 / 2];
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        let mut map = InfiniteScrolledMap::with_provider(
+            gfx.background(
+                Priority::P0,
+                super::super::RegularBackgroundSize::Background32x32,
+            ),
+            RowDecoder {
+                tileset: &tileset,
+                tile_calls: 0,
+                line_calls: 0,
+            },
+        );
+
+        let mut pos: Vector2D<i32> = (0, 0).into();
+        map.init(&mut vram, pos, &mut || {});
+        let tile_calls_from_init = map.tile.tile_calls;
+
+        for _ in 0..100 {
+            pos += (3, 2).into();
+            assert_eq!(map.set_pos(&mut vram, pos), PartialUpdateStatus::Done);
+        }
+
+        assert_eq!(map.tile.tile_calls, tile_calls_from_init);
+        // 300 pixels across is at least 37 columns, and 200 pixels down is at least 25 rows
+        assert!(map.tile.line_calls >= 37 + 25);
+
+        let size = map.map.size();
+        let (xs, ys) = visible_tiles(pos);
+        for y in ys {
+            for x in xs.clone() {
+                let map_pos = (
+                    size.tile_pos_x(x - map.offset.x),
+                    size.tile_pos_y(y - map.offset.y),
+                )
+                    .into();
+                assert_eq!(
+                    map.map.tile_setting_bits(map_pos),
+                    looping_test_tile((x, y).into()).setting()
+                );
+            }
+        }
+
+        map.clear(&mut vram);
+        map.commit(&mut vram);
+    }
+
+    #[test_case]
+    fn scrolling_one_pixel_at_a_time_keeps_every_visible_tile_correct(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+
+        let tiles = [0x55u8; 8 * 8 / 2];
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        let mut map = InfiniteScrolledMap::with_tile_provider(
+            gfx.background(
+                Priority::P0,
+                super::super::RegularBackgroundSize::Background32x32,
+            ),
+            |pos| (&tileset, looping_test_tile(pos)),
+        );
+
+        let mut pos: Vector2D<i32> = (0, 0).into();
+        map.init(&mut vram, pos, &mut || {});
+
+        let directions: [Vector2D<i32>; 4] =
+            [(1, 0).into(), (0, 1).into(), (-1, 0).into(), (0, -1).into()];
+
+        for direction in directions {
+            for _ in 0..512 {
+                pos += direction;
+                assert_eq!(map.set_pos(&mut vram, pos), PartialUpdateStatus::Done);
+                vram.gc();
+
+                assert_visible_tiles_are_correct(&map, pos);
+            }
+        }
+
+        map.clear(&mut vram);
+        map.commit(&mut vram);
+    }
+
+    #[test_case]
+    fn scrolling_diagonally_keeps_the_newly_visible_corners_correct(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+
+        let tiles = [0x55u8; 8 * 8 / 2];
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        let mut map = InfiniteScrolledMap::with_tile_provider(
+            gfx.background(
+                Priority::P0,
+                super::super::RegularBackgroundSize::Background32x32,
+            ),
+            |pos| (&tileset, looping_test_tile(pos)),
+        );
+
+        // start part way through a tile so that x and y cross tile boundaries on different steps
+        // as well as on the same step
+        let mut pos: Vector2D<i32> = (3, 5).into();
+        map.init(&mut vram, pos, &mut || {});
+
+        let directions: [Vector2D<i32>; 8] = [
+            (1, 1).into(),
+            (-1, -1).into(),
+            (1, -1).into(),
+            (-1, 1).into(),
+            (-1, 0).into(),
+            (0, -1).into(),
+            (1, 0).into(),
+            (0, 1).into(),
+        ];
+
+        for direction in directions {
+            for _ in 0..40 {
+                pos += direction;
+                assert_eq!(map.set_pos(&mut vram, pos), PartialUpdateStatus::Done);
+                vram.gc();
+
+                assert_visible_tiles_are_correct(&map, pos);
+            }
+
+            // and the same again a whole tile at a time, crossing both boundaries on every step
+            for _ in 0..5 {
+                pos += direction * 8;
+                assert_eq!(map.set_pos(&mut vram, pos), PartialUpdateStatus::Done);
+                vram.gc();
+
+                assert_visible_tiles_are_correct(&map, pos);
+            }
+        }
+
+        map.clear(&mut vram);
+        map.commit(&mut vram);
+    }
+
+    #[test_case]
+    fn bounded_maps_only_ask_for_tiles_inside_the_level(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+
+        let tiles = [0x55u8; 8 * 8 / 2];
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+        let outside = TileSetting::new(0, false, true, 15);
+
+        let level = Rect::new((0, 0).into(), (40, 30).into());
+        let room = Rect::new((4, 2).into(), (10, 10).into());
+
+        let mut map = InfiniteScrolledMap::with_bounds(
+            gfx.background(
+                Priority::P0,
+                super::super::RegularBackgroundSize::Background32x32,
+            ),
+            |pos: Vector2D<i32>| {
+                assert!(
+                    (0..40).contains(&pos.x) && (0..30).contains(&pos.y),
+                    "asked for tile {}, {} outside of the level",
+                    pos.x,
+                    pos.y
+                );
+                (&tileset, looping_test_tile(pos))
+            },
+            level.clone(),
+            (&tileset, outside),
+        );
+
+        let check = |map: &InfiniteScrolledMap<'_, _>, bounds: &Rect<i32>, pos: Vector2D<i32>| {
+            let size = map.map.size();
+            let (xs, ys) = visible_tiles(pos);
+            for y in ys {
+                for x in xs.clone() {
+                    let expected = if (bounds.position.x..bounds.position.x + bounds.size.x)
+                        .contains(&x)
+                        && (bounds.position.y..bounds.position.y + bounds.size.y).contains(&y)
+                    {
+                        looping_test_tile((x, y).into())
+                    } else {
+                        outside
+                    };
+
+                    let map_pos = (
+                        size.tile_pos_x(x - map.offset.x),
+                        size.tile_pos_y(y - map.offset.y),
+                    )
+                        .into();
+                    assert_eq!(map.map.tile_setting_bits(map_pos), expected.setting());
+                }
+            }
+        };
+
+        let mut pos: Vector2D<i32> = (-100, -60).into();
+        map.init(&mut vram, pos, &mut || {});
+        check(&map, &level, pos);
+
+        // across the whole level and out the other side
+        for _ in 0..70 {
+            pos += (7, 5).into();
+            assert_eq!(map.set_pos(&mut vram, pos), PartialUpdateStatus::Done);
+            vram.gc();
+            check(&map, &level, pos);
+        }
+
+        map.set_bounds(&mut vram, room.clone());
+        check(&map, &room, pos);
+
+        pos = (-8, -8).into();
+        map.teleport(&mut vram, pos);
+        check(&map, &room, pos);
+
+        map.clear(&mut vram);
+        map.commit(&mut vram);
+    }
+
+    fn assert_visible_tiles_are_correct<F>(map: &InfiniteScrolledMap<'_, F>, pos: Vector2D<i32>) {
+        let size = map.map.size();
+        let (xs, ys) = visible_tiles(pos);
+        for y in ys {
+            for x in xs.clone() {
+                let map_pos = (
+                    size.tile_pos_x(x - map.offset.x),
+                    size.tile_pos_y(y - map.offset.y),
+                )
+                    .into();
+
+                assert_eq!(
+                    map.map.tile_setting_bits(map_pos),
+                    looping_test_tile((x, y).into()).setting(),
+                    "tile {}, {} is wrong with the screen at {}, {}",
+                    x,
+                    y,
+                    pos.x,
+                    pos.y
+                );
+            }
+        }
+
+        assert_eq!(
+            map.map.scroll_pos(),
+            (
+                (pos.x - map.offset.x * 8).rem_euclid(size.width() as i32 * 8),
+                (pos.y - map.offset.y * 8).rem_euclid(size.height() as i32 * 8)
+            )
+                .into()
+        );
+    }
+
+    #[test_case]
+    fn tile_properties_match_the_tiles_drawn(gba: &mut crate::Gba) {
+        use crate::display::tiled::{TileMap, TileMapEdge};
+
+        static SETTINGS: [TileSetting; 6] = [
+            TileSetting::new(0, false, false, 0),
+            TileSetting::new(0, false, false, 1),
+            TileSetting::new(0, false, false, 2),
+            TileSetting::new(0, false, false, 3),
+            TileSetting::new(0, false, false, 4),
+            TileSetting::new(0, false, false, 5),
+        ];
+        static PROPERTIES: [u8; 6] = [10, 11, 12, 13, 14, 15];
+        const LEVEL: TileMap = TileMap::new(3, 2, &SETTINGS).with_properties(&PROPERTIES);
+
+        let (gfx, mut vram) = gba.display.video.tiled0();
+
+        let tiles = [0x55u8; 8 * 8 / 2];
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        let mut map = InfiniteScrolledMap::with_provider(
+            gfx.background(
+                Priority::P0,
+                super::super::RegularBackgroundSize::Background32x32,
+            ),
+            TileMapProvider::new(LEVEL, &tileset, TileMapEdge::Clamp),
+        );
+        map.init(&mut vram, (-4, -4).into(), &mut || {});
+
+        assert_eq!(map.tile_property((0, 0).into()), 10);
+        assert_eq!(map.tile_property((7, 7).into()), 10);
+        assert_eq!(map.tile_property((8, 7).into()), 11);
+        assert_eq!(map.tile_property((17, 9).into()), 15);
+        // clamped in the same way as the tiles
+        assert_eq!(map.tile_property((-1, -1).into()), 10);
+        assert_eq!(map.tile_property((100, 100).into()), 15);
+
+        let size = map.map.size();
+        let on_screen = |tile: Vector2D<i32>| {
+            (
+                size.tile_pos_x(tile.x - map.offset.x),
+                size.tile_pos_y(tile.y - map.offset.y),
+            )
+                .into()
+        };
+        for &(world_pos, palette) in &[((-1, -1), 0u8), ((8, 7), 1), ((17, 9), 5)] {
+            let world_pos: Vector2D<i32> = world_pos.into();
+            let tile = (world_pos.x.div_euclid(8), world_pos.y.div_euclid(8)).into();
+            assert_eq!(
+                map.map.tile_setting_bits(on_screen(tile)),
+                TileSetting::new(0, false, false, palette).setting()
+            );
+        }
+
+        map.clear(&mut vram);
+        map.commit(&mut vram);
+    }
+
+    #[test_case]
+    fn larger_backgrounds_load_tiles_less_often(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+
+        let tiles = [0x55u8; 8 * 8 / 2];
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        let mut updates_for_size = |size| {
+            let mut map = InfiniteScrolledMap::with_provider(
+                gfx.background(Priority::P0, size),
+                RowDecoder {
+                    tileset: &tileset,
+                    tile_calls: 0,
+                    line_calls: 0,
+                },
+            );
+
+            let mut pos: Vector2D<i32> = (0, 0).into();
+            map.init(&mut vram, pos, &mut || {});
+
+            let mut updates = 0;
+            for _ in 0..128 {
+                pos += (1, 1).into();
+
+                let line_calls = map.tile.line_calls;
+                assert_eq!(map.set_pos(&mut vram, pos), PartialUpdateStatus::Done);
+                if map.tile.line_calls != line_calls {
+                    updates += 1;
+                }
+
+                assert_visible_tiles_are_correct(&map, pos);
+            }
+
+            map.clear(&mut vram);
+            map.commit(&mut vram);
+
+            updates
+        };
+
+        let small_updates = updates_for_size(super::super::RegularBackgroundSize::Background32x32);
+        let large_updates = updates_for_size(super::super::RegularBackgroundSize::Background64x64);
+
+        // a 32x32 background has no spare columns, so needs a new one every 8 pixels
+        assert!(small_updates >= 15);
+        assert!(
+            large_updates <= 4,
+            "a 64x64 background loaded tiles {} times",
+            large_updates
+        );
+    }
+
+    #[test_case]
+    fn large_jumps_scroll_over_several_calls_unless_teleporting(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+
+        let tiles = [0x55u8; 8 * 8 / 2];
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        let mut map = InfiniteScrolledMap::with_tile_provider(
+            gfx.background(
+                Priority::P0,
+                super::super::RegularBackgroundSize::Background32x32,
+            ),
+            |pos| (&tileset, looping_test_tile(pos)),
+        );
+
+        map.init(&mut vram, (0, 0).into(), &mut || {});
+
+        let target = (200, -100).into();
+        assert_eq!(
+            map.set_pos(&mut vram, target),
+            PartialUpdateStatus::Continue
+        );
+        assert_eq!(map.position(), (80, -80).into());
+        assert_visible_tiles_are_correct(&map, map.position());
+
+        assert_eq!(
+            map.set_pos(&mut vram, target),
+            PartialUpdateStatus::Continue
+        );
+        assert_eq!(map.position(), (160, -100).into());
+
+        assert_eq!(map.set_pos(&mut vram, target), PartialUpdateStatus::Done);
+        assert_eq!(map.position(), target);
+        assert_visible_tiles_are_correct(&map, target);
+
+        let far_away = (-1000, 3000).into();
+        map.teleport(&mut vram, far_away);
+        assert_eq!(map.position(), far_away);
+        assert_visible_tiles_are_correct(&map, far_away);
+
+        vram.gc();
+        map.clear(&mut vram);
+        map.commit(&mut vram);
+    }
+
+    #[test_case]
+    fn clearing_between_levels_does_not_leak_tiles(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+
+        let mut tiles = [0u8; 4 * 8 * 8 / 2];
+        for (i, tile) in tiles.chunks_mut(8 * 8 / 2).enumerate() {
+            tile.fill(0x60 + i as u8);
+        }
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        let initial_stats = vram.stats();
+
+        for level in 0..10 {
+            let mut map = InfiniteScrolledMap::with_tile_provider(
+                gfx.background(
+                    Priority::P0,
+                    super::super::RegularBackgroundSize::Background32x32,
+                ),
+                |pos| {
+                    let tile = (pos.x + pos.y + level).rem_euclid(4) as u16;
+                    (&tileset, TileSetting::new(tile, false, false, 0))
+                },
+            );
+
+            map.init(&mut vram, (level * 100, -level * 50).into(), &mut || {});
+            map.commit(&mut vram);
+            assert_eq!(vram.stats().used_tile_slots, 4);
+
+            map.clear(&mut vram);
+            map.commit(&mut vram);
+
+            assert_eq!(vram.stats(), initial_stats);
+        }
+    }
+
+    #[test_case]
+    fn fixed_point_positions_round_down(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+
+        let tiles = [0x66u8; 8 * 8 / 2];
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        let mut map = InfiniteScrolledMap::with_tile_provider(
+            gfx.background(
+                Priority::P0,
+                super::super::RegularBackgroundSize::Background32x32,
+            ),
+            |_| (&tileset, TileSetting::new(0, false, false, 0)),
+        );
+
+        map.init(&mut vram, (0, 0).into(), &mut || {});
+
+        let mut camera: Vector2D<Num<i32, 8>> = (0, 0).into();
+        let velocity: Vector2D<Num<i32, 8>> = (Num::new(-1) / 4, Num::new(1) / 3).into();
+
+        for _ in 0..20 {
+            camera += velocity;
+            map.set_pos_fixed(&mut vram, camera);
+
+            assert_eq!(map.current_pos, camera.floor());
+        }
+
+        assert_eq!(map.current_pos, (-5, 6).into());
+
+        map.clear(&mut vram);
+        map.commit(&mut vram);
+    }
+
+    #[test_case]
+    fn world_positions_convert_to_where_the_map_has_scrolled_to(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+
+        let tiles = [0x66u8; 8 * 8 / 2];
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        let mut map = InfiniteScrolledMap::with_tile_provider(
+            gfx.background(
+                Priority::P0,
+                super::super::RegularBackgroundSize::Background32x32,
+            ),
+            |_| (&tileset, TileSetting::new(0, false, false, 0)),
+        );
+
+        map.init(&mut vram, (100, -50).into(), &mut || {});
+
+        assert_eq!(
+            map.to_screen_space((110, -40).into()),
+            Some((10, 10).into())
+        );
+        assert_eq!(map.to_screen_space((36, -50).into()), Some((-64, 0).into()));
+        assert_eq!(map.to_screen_space((35, -50).into()), None);
+        assert_eq!(
+            map.to_screen_space((100 + 240 + 63, 110 + 63).into()),
+            Some((303, 223).into())
+        );
+        assert_eq!(map.to_screen_space((100 + 240 + 64, 0).into()), None);
+
+        map.set_screen_margin(0);
+        assert_eq!(map.to_screen_space((99, -40).into()), None);
+
+        // the map only scrolls part of the way towards a far away position
+        assert_eq!(
+            map.set_pos(&mut vram, (1000, -50).into()),
+            PartialUpdateStatus::Continue
+        );
+        assert_eq!(
+            map.to_screen_space((1000, -50).into()),
+            None,
+            "the target is still off screen"
+        );
+        assert_eq!(
+            map.to_screen_space(map.position() + (5, 5).into()),
+            Some((5, 5).into())
+        );
+
+        map.clear(&mut vram);
+        map.commit(&mut vram);
+    }
+}
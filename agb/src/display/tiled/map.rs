@@ -1,31 +1,173 @@
 use core::cell::RefCell;
-use core::ops::{Deref, DerefMut};
+use core::ops::{Deref, DerefMut, Range};
 
 use crate::bitarray::Bitarray;
 use crate::display::{Priority, DISPLAY_CONTROL};
-use crate::dma::dma_copy16;
-use crate::fixnum::Vector2D;
-use crate::memory_mapped::MemoryMapped;
+use crate::dma::{HBlankDma, HblankDmaUser};
+use crate::fixnum::{Rect, Vector2D};
+use crate::hash_map::HashMap;
+use crate::interrupt::free;
+use crate::memory_mapped::{MemoryMapped, MemoryMapped1DArray};
 
-use super::{BackgroundID, RegularBackgroundSize, Tile, TileSet, TileSetting, VRamManager};
+use super::{
+    screenblock_address, BackgroundID, OutOfVideoRam, PreloadedTileset, RegularBackgroundSize,
+    ResolvedTile, Tile, TileFlip, TileIndex, TileMap, TileMapEdge, TileSet, TileSetting,
+    VRamManager,
+};
 
 use alloc::{vec, vec::Vec};
 
+// The screenblocks used by a map, which are next to each other in video ram and big enough for
+// the largest background
+type Screenblocks = MemoryMapped1DArray<Tile, { 64 * 64 }>;
+
+fn screenblocks(first_screenblock: u8) -> Screenblocks {
+    unsafe { MemoryMapped1DArray::new(screenblock_address(first_screenblock as usize)) }
+}
+
 pub struct RegularMap {
     background_id: u8,
 
     screenblock: u8,
+    // the screenblock which isn't being displayed, for double buffered maps
+    back_screenblock: Option<u8>,
     x_scroll: u16,
     y_scroll: u16,
     priority: Priority,
+    mosaic: bool,
+
+    has_committed: bool,
+    show_on_commit: bool,
 
     tiles: Vec<Tile>,
-    tiles_dirty: bool,
+    dirty_rows: Bitarray<4>,
+    // the columns within each dirty row which have changed, so that scrolling one column at a
+    // time doesn't copy whole rows
+    dirty_columns: Range<usize>,
+    // rows which changed in the previous commit of a double buffered map, so are out of date in
+    // what is now the back screenblock
+    stale_back_rows: Bitarray<4>,
+    // the only tiles which are copied to video ram on commit, if set
+    viewport: Option<Rect<u16>>,
+
+    size: RegularBackgroundSize,
+
+    scroll_per_line: Option<ScrollPerLine>,
+}
+
+struct ScrollPerLine {
+    dma: HBlankDma,
+    enabled: bool,
+}
+
+/// Why [`RegularMap::try_set_tile`] couldn't set a tile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SetTileError {
+    /// The tile setting refers to a tile past the end of the tileset.
+    TileOutOfRange {
+        /// The address of the tile data of the tileset, to tell which tileset it was
+        tileset_address: usize,
+        /// The tile which was requested
+        tile: u16,
+        /// The number of tiles in the tileset
+        tile_count: u16,
+    },
+    /// Every background tile slot in video ram is in use, so the tile couldn't be loaded.
+    OutOfVideoRam,
+}
+
+/// A copy of every tile of a [`RegularMap`] made by [`RegularMap::export`], which refers to tiles
+/// by their tileset and tile number rather than where they happen to be in video ram, so it can
+/// be saved and used to rebuild the map later with [`RegularMap::import`].
+#[derive(Clone, Debug)]
+pub struct ExportedMap {
+    size: RegularBackgroundSize,
+    tiles: Vec<ExportedTile>,
+    missing: Vec<Vector2D<u16>>,
+}
+
+/// A single tile of an [`ExportedMap`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExportedTile {
+    /// The index of the tileset in the list of tilesets given to [`RegularMap::export`]. This is
+    /// 0 for empty tiles.
+    pub tileset: usize,
+    /// The tile in that tileset along with its flips and palette, or [`TileSetting::BLANK`]
+    pub setting: TileSetting,
+}
+
+impl ExportedMap {
+    #[must_use]
+    pub fn size(&self) -> RegularBackgroundSize {
+        self.size
+    }
+
+    /// The tile at `pos`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pos` is outside of the map.
+    #[must_use]
+    pub fn tile(&self, pos: Vector2D<u16>) -> ExportedTile {
+        assert!(
+            u32::from(pos.x) < self.size.width() && u32::from(pos.y) < self.size.height(),
+            "({}, {}) is outside of the map",
+            pos.x,
+            pos.y
+        );
+        self.tiles[pos.x as usize + pos.y as usize * self.size.width() as usize]
+    }
 
+    /// Positions of tiles which couldn't be exported, because the tileset they were loaded from
+    /// wasn't given to [`RegularMap::export`] or has been removed from video ram. These are
+    /// empty in the exported map.
+    #[must_use]
+    pub fn missing(&self) -> &[Vector2D<u16>] {
+        &self.missing
+    }
+}
+
+/// The tiles, scroll position and priority of a [`RegularMap`] saved by
+/// [`RegularMap::snapshot`], for example while the background is used for a pause menu.
+///
+/// The snapshot keeps every tile it uses in video ram, so that changing the map in the meantime
+/// doesn't free them. It must be given back to [`RegularMap::restore`] or released with
+/// [`MapSnapshot::release`], otherwise those tiles are leaked. The snapshot can't release the
+/// tiles itself when it is dropped since that needs the [`VRamManager`], so debug builds panic
+/// if a snapshot holding any tiles is dropped instead.
+#[must_use = "the snapshot's tiles are leaked unless it is restored or released"]
+pub struct MapSnapshot {
     size: RegularBackgroundSize,
+    tiles: Vec<Tile>,
+    scroll: Vector2D<i32>,
+    priority: Priority,
+}
+
+impl MapSnapshot {
+    /// Lets go of the tiles this snapshot was keeping in video ram without restoring it.
+    pub fn release(mut self, vram: &mut VRamManager) {
+        for tile in core::mem::take(&mut self.tiles) {
+            if tile != Tile::default() {
+                vram.remove_tile(tile.tile_index());
+            }
+        }
+    }
+}
+
+impl Drop for MapSnapshot {
+    fn drop(&mut self) {
+        debug_assert!(
+            self.tiles.iter().all(|&tile| tile == Tile::default()),
+            "MapSnapshot dropped without being restored or released, leaking its tiles"
+        );
+    }
 }
 
-pub const TRANSPARENT_TILE_INDEX: u16 = (1 << 10) - 1;
+// The number of tiles in a single row of a screenblock. Dirty tracking is done per row of each
+// screenblock, so a 64x64 background needs 128 bits to track, along with the range of columns
+// which changed in any of those rows.
+const ROW_LENGTH: usize = 32;
 
 impl RegularMap {
     pub(crate) fn new(
@@ -34,19 +176,38 @@ impl RegularMap {
         priority: Priority,
         size: RegularBackgroundSize,
     ) -> Self {
-        Self {
+        let mut map = Self {
             background_id,
 
             screenblock,
+            back_screenblock: None,
             x_scroll: 0,
             y_scroll: 0,
             priority,
+            mosaic: false,
+
+            has_committed: false,
+            show_on_commit: false,
 
             tiles: vec![Default::default(); size.num_tiles()],
-            tiles_dirty: true,
+            dirty_rows: Bitarray::new(),
+            dirty_columns: 0..0,
+            viewport: None,
+            stale_back_rows: Bitarray::new(),
 
             size,
-        }
+
+            scroll_per_line: None,
+        };
+
+        map.mark_all_dirty();
+        map
+    }
+
+    // Makes the map draw into `back_screenblock` and switch to displaying it on each commit
+    pub(crate) fn double_buffered(mut self, back_screenblock: u8) -> Self {
+        self.back_screenblock = Some(back_screenblock);
+        self
     }
 
     pub fn set_tile(
@@ -56,6 +217,18 @@ impl RegularMap {
         tileset: &TileSet<'_>,
         tile_setting: TileSetting,
     ) {
+        let new_tile = if tile_setting.is_blank() {
+            Tile::default()
+        } else {
+            let (new_tile_idx, flip) = vram.add_tile(tileset, tile_setting.index());
+            Tile::new(new_tile_idx, tile_setting.flipped(flip))
+        };
+
+        self.put_tile(vram, pos, new_tile);
+    }
+
+    // Replaces the tile at `pos` with `new_tile`, which has already been added to video ram
+    fn put_tile(&mut self, vram: &mut VRamManager, pos: Vector2D<u16>, new_tile: Tile) {
         let pos = self.size.gba_offset(pos);
 
         let old_tile = self.tiles[pos];
@@ -63,22 +236,263 @@ impl RegularMap {
             vram.remove_tile(old_tile.tile_index());
         }
 
-        let tile_index = tile_setting.index();
+        if old_tile == new_tile {
+            // no need to mark as dirty if nothing changes
+            return;
+        }
+
+        self.tiles[pos] = new_tile;
+        self.mark_dirty(pos);
+    }
 
-        let new_tile = if tile_index != TRANSPARENT_TILE_INDEX {
-            let new_tile_idx = vram.add_tile(tileset, tile_index);
-            Tile::new(new_tile_idx, tile_setting)
-        } else {
+    /// Sets the tile at `pos` to a tile of a tileset preloaded using
+    /// [`VRamManager::preload_tileset`]. This is faster than [`set_tile`](RegularMap::set_tile)
+    /// since the tile doesn't need to be looked up or loaded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the tile number of `tile_setting` is past the end of the tileset.
+    pub fn set_preloaded_tile(
+        &mut self,
+        vram: &mut VRamManager,
+        pos: Vector2D<u16>,
+        preloaded: &PreloadedTileset,
+        tile_setting: TileSetting,
+    ) {
+        let pos = self.size.gba_offset(pos);
+
+        let new_tile = if tile_setting.is_blank() {
             Tile::default()
+        } else {
+            let index = preloaded.tile_index(tile_setting.index());
+            vram.add_tile_reference(index);
+            Tile::new(index, tile_setting)
         };
 
+        let old_tile = self.tiles[pos];
+        if old_tile != Tile::default() {
+            vram.remove_tile(old_tile.tile_index());
+        }
+
         if old_tile == new_tile {
-            // no need to mark as dirty if nothing changes
             return;
         }
 
         self.tiles[pos] = new_tile;
-        self.tiles_dirty = true;
+        self.mark_dirty(pos);
+    }
+
+    /// Sets the tile at `pos` to a tile loaded using [`VRamManager::resolve`]. This is faster
+    /// than [`set_tile`](RegularMap::set_tile) since the tile doesn't need to be looked up. Only
+    /// the flips and palette bank of `tile_setting` are used, or if it is
+    /// [`TileSetting::BLANK`] the tile is emptied as it would be by `set_tile`.
+    pub fn set_tile_resolved(
+        &mut self,
+        vram: &mut VRamManager,
+        pos: Vector2D<u16>,
+        resolved: &ResolvedTile,
+        tile_setting: TileSetting,
+    ) {
+        let new_tile = if tile_setting.is_blank() {
+            Tile::default()
+        } else {
+            let index = resolved.tile_index();
+            vram.add_tile_reference(index);
+            Tile::new(index, tile_setting.flipped(resolved.flip()))
+        };
+
+        self.put_tile(vram, pos, new_tile);
+    }
+
+    /// Sets a row of tiles starting at `x_start` and going right, which is faster than calling
+    /// [`set_tile`][RegularMap::set_tile] for each of them. The row wraps around to the left of
+    /// the background if it goes past the right hand edge.
+    pub fn set_tile_row(
+        &mut self,
+        vram: &mut VRamManager,
+        y: u16,
+        x_start: u16,
+        tiles: &[(&TileSet<'_>, TileSetting)],
+    ) {
+        let tiles = (0..).zip(tiles).map(|(i, &(tileset, tile_setting))| {
+            ((x_start.wrapping_add(i), y).into(), tileset, tile_setting)
+        });
+        self.set_tiles(vram, tiles);
+    }
+
+    /// Sets a column of tiles starting at `y_start` and going down, which is faster than calling
+    /// [`set_tile`][RegularMap::set_tile] for each of them. The column wraps around to the top of
+    /// the background if it goes past the bottom edge.
+    pub fn set_tile_column(
+        &mut self,
+        vram: &mut VRamManager,
+        x: u16,
+        y_start: u16,
+        tiles: &[(&TileSet<'_>, TileSetting)],
+    ) {
+        let tiles = (0..).zip(tiles).map(|(i, &(tileset, tile_setting))| {
+            ((x, y_start.wrapping_add(i)).into(), tileset, tile_setting)
+        });
+        self.set_tiles(vram, tiles);
+    }
+
+    /// Copies part of `tile_map` on to this background. The `dest` area of the background is
+    /// filled starting from `src_offset` in the tile map, and `edge` controls what happens where
+    /// that goes past the edge of the tile map. `dest` wraps around the background in the same
+    /// way as [`set_tile`][RegularMap::set_tile].
+    pub fn draw_map(
+        &mut self,
+        vram: &mut VRamManager,
+        tile_map: &TileMap,
+        tileset: &TileSet<'_>,
+        src_offset: Vector2D<u16>,
+        dest: &Rect<u16>,
+        edge: TileMapEdge,
+    ) {
+        let (position, size) = (dest.position, dest.size);
+        let tiles = (0..size.y).flat_map(move |y| {
+            (0..size.x).map(move |x| {
+                let src_x = edge.source_position(src_offset.x + x, tile_map.width());
+                let src_y = edge.source_position(src_offset.y + y, tile_map.height());
+                let pos = (position.x.wrapping_add(x), position.y.wrapping_add(y)).into();
+
+                (pos, tileset, tile_map.get(src_x, src_y))
+            })
+        });
+
+        self.set_tiles(vram, tiles);
+    }
+
+    /// Changes every tile in `region` which uses palette bank `from_bank` to use `to_bank`
+    /// instead, for effects like switching the whole map to a darker palette at night. Only the
+    /// map entries change, so this is much faster than setting the tiles again. `region` wraps
+    /// around the background in the same way as [`set_tile`][RegularMap::set_tile].
+    ///
+    /// # Panics
+    ///
+    /// Panics if either bank is 16 or more.
+    pub fn remap_palette(&mut self, region: &Rect<u16>, from_bank: u8, to_bank: u8) {
+        assert!(
+            from_bank < 16 && to_bank < 16,
+            "palette banks must be between 0 and 15, got {} and {}",
+            from_bank,
+            to_bank
+        );
+
+        if from_bank == to_bank {
+            return;
+        }
+
+        const PALETTE_SHIFT: u16 = 12;
+
+        for y in 0..region.size.y {
+            for x in 0..region.size.x {
+                let pos = self.size.gba_offset(
+                    (
+                        region.position.x.wrapping_add(x),
+                        region.position.y.wrapping_add(y),
+                    )
+                        .into(),
+                );
+
+                let tile = self.tiles[pos];
+                if tile == Tile::default() || tile.0 >> PALETTE_SHIFT != u16::from(from_bank) {
+                    continue;
+                }
+
+                self.tiles[pos] = Tile(
+                    (tile.0 & ((1 << PALETTE_SHIFT) - 1)) | (u16::from(to_bank) << PALETTE_SHIFT),
+                );
+                self.mark_dirty(pos);
+            }
+        }
+    }
+
+    fn set_tiles<'t>(
+        &mut self,
+        vram: &mut VRamManager,
+        tiles: impl Iterator<Item = (Vector2D<u16>, &'t TileSet<'t>, TileSetting)>,
+    ) {
+        // runs of the same tile are common, for example in the sky, and those can skip looking
+        // up the tile in video ram
+        let mut previous: Option<(&TileSet<'_>, u16, TileIndex, TileFlip)> = None;
+
+        for (pos, tileset, tile_setting) in tiles {
+            let pos = self.size.gba_offset(pos);
+
+            let new_tile = if tile_setting.is_blank() {
+                Tile::default()
+            } else {
+                let (tile_index, flip) = match previous {
+                    Some((previous_tileset, previous_tile, tile_index, flip))
+                        if previous_tile == tile_setting.index()
+                            && previous_tileset.same_tiles(tileset) =>
+                    {
+                        vram.add_tile_reference(tile_index);
+                        (tile_index, flip)
+                    }
+                    _ => vram.add_tile(tileset, tile_setting.index()),
+                };
+
+                previous = Some((tileset, tile_setting.index(), tile_index, flip));
+                Tile::new(tile_index, tile_setting.flipped(flip))
+            };
+
+            let old_tile = self.tiles[pos];
+            if old_tile != Tile::default() {
+                vram.remove_tile(old_tile.tile_index());
+            }
+
+            if old_tile != new_tile {
+                self.tiles[pos] = new_tile;
+                self.mark_dirty(pos);
+            }
+        }
+    }
+
+    /// Like [`set_tile`][RegularMap::set_tile], but returns an error rather than panicking if the
+    /// tile can't be set, so that problems with level data can be reported. If it fails, the map
+    /// is left as it was.
+    pub fn try_set_tile(
+        &mut self,
+        vram: &mut VRamManager,
+        pos: Vector2D<u16>,
+        tileset: &TileSet<'_>,
+        tile_setting: TileSetting,
+    ) -> Result<(), SetTileError> {
+        if !tile_setting.is_blank() && tile_setting.index() >= tileset.tile_count() {
+            return Err(SetTileError::TileOutOfRange {
+                tileset_address: tileset.tile_data_address(),
+                tile: tile_setting.index(),
+                tile_count: tileset.tile_count(),
+            });
+        }
+
+        let new_tile = if tile_setting.is_blank() {
+            Tile::default()
+        } else {
+            let (new_tile_idx, flip) = vram
+                .try_add_tile(tileset, tile_setting.index())
+                .map_err(|OutOfVideoRam| SetTileError::OutOfVideoRam)?;
+            Tile::new(new_tile_idx, tile_setting.flipped(flip))
+        };
+
+        self.put_tile(vram, pos, new_tile);
+        Ok(())
+    }
+
+    /// Empties the tile at `pos`, releasing the tile it used.
+    pub(crate) fn clear_tile(&mut self, vram: &mut VRamManager, pos: Vector2D<u16>) {
+        let pos = self.size.gba_offset(pos);
+
+        let old_tile = self.tiles[pos];
+        if old_tile == Tile::default() {
+            return;
+        }
+
+        vram.remove_tile(old_tile.tile_index());
+        self.tiles[pos] = Tile::default();
+        self.mark_dirty(pos);
     }
 
     pub fn clear(&mut self, vram: &mut VRamManager) {
@@ -89,104 +503,600 @@ impl RegularMap {
 
             *tile = Tile::default();
         }
+
+        self.mark_all_dirty();
+    }
+
+    fn mark_dirty(&mut self, pos: usize) {
+        self.dirty_rows.set(pos / ROW_LENGTH, true);
+
+        let column = pos % ROW_LENGTH;
+        self.dirty_columns = if self.dirty_columns.is_empty() {
+            column..column + 1
+        } else {
+            self.dirty_columns.start.min(column)..self.dirty_columns.end.max(column + 1)
+        };
+    }
+
+    fn mark_all_dirty(&mut self) {
+        for row in 0..self.size.num_tiles() / ROW_LENGTH {
+            self.dirty_rows.set(row, true);
+        }
+
+        self.dirty_columns = 0..ROW_LENGTH;
+    }
+
+    /// Copies every tile of this map in a form which can be saved and later loaded with
+    /// [`import`](RegularMap::import). Each tile is recorded as a tile number in one of
+    /// `tilesets`, and any tile which didn't come from one of them is listed in
+    /// [`ExportedMap::missing`].
+    ///
+    /// Tiles which are identical to, or mirror images of, a tile from a tileset earlier in
+    /// `tilesets` may be recorded as that tile instead, since they share video ram, but look the
+    /// same.
+    #[must_use]
+    pub fn export(&self, vram: &VRamManager, tilesets: &[&TileSet<'_>]) -> ExportedMap {
+        let width = self.size.width() as u16;
+        let height = self.size.height() as u16;
+
+        // where each tile slot in video ram came from, preferring the earliest tileset given
+        let mut sources: HashMap<u16, (usize, u16, TileFlip)> = HashMap::new();
+        for (tile_index, tileset, tile, flip) in vram.tile_sources() {
+            let index = match tilesets
+                .iter()
+                .position(|candidate| candidate.is_at(tileset))
+            {
+                Some(index) => index,
+                None => continue,
+            };
+
+            let source = sources
+                .entry(tile_index.index())
+                .or_insert((index, tile, flip));
+            if index < source.0 {
+                *source = (index, tile, flip);
+            }
+        }
+
+        let mut tiles = Vec::with_capacity(self.size.num_tiles());
+        let mut missing = Vec::new();
+
+        for y in 0..height {
+            for x in 0..width {
+                let tile = self.tiles[self.size.gba_offset((x, y).into())];
+                if tile == Tile::default() {
+                    tiles.push(ExportedTile {
+                        tileset: 0,
+                        setting: TileSetting::BLANK,
+                    });
+                    continue;
+                }
+
+                match sources.get(&tile.tile_index().index()) {
+                    // the map entry has the flip to show the slot as the tile which was set,
+                    // and the source's flip shows the slot as the source tile
+                    Some(&(tileset, tile_number, flip)) => tiles.push(ExportedTile {
+                        tileset,
+                        setting: TileSetting::from_raw(
+                            tile_number | ((tile.0 & !((1 << 10) - 1)) ^ flip.bits()),
+                        ),
+                    }),
+                    None => {
+                        missing.push((x, y).into());
+                        tiles.push(ExportedTile {
+                            tileset: 0,
+                            setting: TileSetting::BLANK,
+                        });
+                    }
+                }
+            }
+        }
+
+        ExportedMap {
+            size: self.size,
+            tiles,
+            missing,
+        }
+    }
+
+    /// Sets every tile of this map to the tiles of `map`, which was made by
+    /// [`export`](RegularMap::export) with the same list of tilesets.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `map` is a different size to this map, refers to a tileset which isn't in
+    /// `tilesets`, or `tilesets` is empty.
+    pub fn import(&mut self, vram: &mut VRamManager, map: &ExportedMap, tilesets: &[&TileSet<'_>]) {
+        assert_eq!(
+            map.size, self.size,
+            "can't import a {:?} map into a {:?} background",
+            map.size, self.size
+        );
+
+        let width = self.size.width() as u16;
+        let tiles = (0..).zip(&map.tiles).map(|(i, tile)| {
+            let tileset: &TileSet<'_> = if tile.setting.is_blank() {
+                tilesets[0]
+            } else {
+                tilesets.get(tile.tileset).unwrap_or_else(|| {
+                    panic!(
+                        "exported map uses tileset {} but only {} were given",
+                        tile.tileset,
+                        tilesets.len()
+                    )
+                })
+            };
+
+            ((i % width, i / width).into(), tileset, tile.setting)
+        });
+
+        self.set_tiles(vram, tiles);
+    }
+
+    /// Saves the tiles, scroll position and priority of this map so that they can be put back
+    /// with [`restore`](RegularMap::restore), for example after using the background for a pause
+    /// menu.
+    pub fn snapshot(&self, vram: &mut VRamManager) -> MapSnapshot {
+        for &tile in &self.tiles {
+            if tile != Tile::default() {
+                vram.add_tile_reference(tile.tile_index());
+            }
+        }
+
+        MapSnapshot {
+            size: self.size,
+            tiles: self.tiles.clone(),
+            scroll: self.scroll_pos(),
+            priority: self.priority,
+        }
+    }
+
+    /// Replaces everything on this map with the tiles, scroll position and priority saved in
+    /// `snapshot`. The change is shown on the next call to [`commit`](RegularMap::commit).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the snapshot was taken of a background of a different size.
+    pub fn restore(&mut self, vram: &mut VRamManager, mut snapshot: MapSnapshot) {
+        assert_eq!(
+            snapshot.size, self.size,
+            "can't restore a snapshot of a {:?} map to a {:?} background",
+            snapshot.size, self.size
+        );
+
+        self.clear(vram);
+
+        // the snapshot's references to its tiles now belong to the map
+        self.tiles = core::mem::take(&mut snapshot.tiles);
+        self.set_scroll_pos(snapshot.scroll);
+        self.priority = snapshot.priority;
     }
 
+    /// Makes the background visible. If the background has never been committed, this waits
+    /// until the first call to [`commit`](RegularMap::commit) so that whatever was previously in
+    /// the screenblock is never shown.
     pub fn show(&mut self) {
+        if self.has_committed {
+            self.set_visible();
+        } else {
+            self.show_on_commit = true;
+        }
+    }
+
+    fn set_visible(&self) {
         let mode = DISPLAY_CONTROL.get();
         let new_mode = mode | (1 << (self.background_id + 0x08));
         DISPLAY_CONTROL.set(new_mode);
     }
 
     pub fn hide(&mut self) {
+        self.show_on_commit = false;
+
         let mode = DISPLAY_CONTROL.get();
         let new_mode = mode & !(1 << (self.background_id + 0x08));
         DISPLAY_CONTROL.set(new_mode);
     }
 
+    /// Copies any changes to the map and its settings to the hardware. Every tile of the
+    /// background is kept in video ram, not just the part on screen, so after a commit the whole
+    /// background is valid and changing only the scroll position never reveals stale tiles.
+    ///
+    /// For double buffered maps from
+    /// [`Tiled0::background_double_buffered`](super::Tiled0::background_double_buffered), the
+    /// changes are copied to the screenblock which isn't being displayed, and then the hardware
+    /// switches to displaying it all at once.
     pub fn commit(&mut self, vram: &mut VRamManager) {
-        let new_bg_control_value = (self.priority as u16)
-            | (u16::from(self.screenblock) << 8)
-            | (self.size.size_flag() << 14);
-
-        self.bg_control_register().set(new_bg_control_value);
-        self.bg_h_offset().set(self.x_scroll);
         self.bg_v_offset().set(self.y_scroll);
+        self.commit_scroll_per_line();
 
-        let screenblock_memory = self.screenblock_memory();
-
-        if self.tiles_dirty {
-            unsafe {
-                dma_copy16(
-                    self.tiles.as_ptr() as *const u16,
-                    screenblock_memory,
-                    self.size.num_tiles(),
-                );
-            }
+        match self.back_screenblock {
+            Some(back_screenblock) => self.commit_to_back_screenblock(back_screenblock),
+            None => self.commit_dirty_rows(&screenblocks(self.screenblock)),
         }
 
+        self.bg_control_register().set(self.bg_control_value());
+
         vram.gc();
 
-        self.tiles_dirty = false;
+        self.has_committed = true;
+        if self.show_on_commit {
+            self.show_on_commit = false;
+            self.set_visible();
+        }
     }
 
-    pub fn set_scroll_pos(&mut self, pos: Vector2D<u16>) {
-        self.x_scroll = pos.x;
-        self.y_scroll = pos.y;
-    }
+    /// Sets the horizontal scroll position used for each of the 160 lines of the screen, for
+    /// effects like heat haze or water reflections. While active this overrides the x coordinate
+    /// of [`set_scroll_pos`](RegularMap::set_scroll_pos), and takes effect from the next call to
+    /// `commit`. The table can be updated every frame without tearing.
+    ///
+    /// # Panics
+    ///
+    /// Only one hblank effect can be used at a time, such as a per line scroll, a
+    /// [`BackdropGradient`](crate::display::backdrop::BackdropGradient) or an
+    /// [`HBlankDma`](crate::dma::HBlankDma), and this will panic if a different background or
+    /// effect is already using one.
+    pub fn set_scroll_per_line(&mut self, table: &[u16; 160]) {
+        let background = self.background_id;
+        let h_offset = self.bg_h_offset();
 
-    #[must_use]
-    pub fn scroll_pos(&self) -> Vector2D<u16> {
-        (self.x_scroll, self.y_scroll).into()
-    }
+        let scroll_per_line = self.scroll_per_line.get_or_insert_with(|| ScrollPerLine {
+            dma: HBlankDma::claim(HblankDmaUser::ScrollPerLine { background }),
+            enabled: true,
+        });
 
-    pub(crate) fn size(&self) -> RegularBackgroundSize {
-        self.size
+        unsafe { scroll_per_line.dma.start(h_offset.as_ptr() as usize, table) };
+        scroll_per_line.enabled = true;
     }
 
-    const fn bg_control_register(&self) -> MemoryMapped<u16> {
-        unsafe { MemoryMapped::new(0x0400_0008 + 2 * self.background_id as usize) }
+    /// Stops using the per line scroll set by [`set_scroll_per_line`](RegularMap::set_scroll_per_line)
+    /// from the next call to `commit`, going back to the normal scroll position.
+    pub fn clear_scroll_per_line(&mut self) {
+        if let Some(scroll_per_line) = &mut self.scroll_per_line {
+            scroll_per_line.enabled = false;
+        }
     }
 
-    const fn bg_h_offset(&self) -> MemoryMapped<u16> {
-        unsafe { MemoryMapped::new(0x0400_0010 + 4 * self.background_id as usize) }
-    }
+    fn commit_scroll_per_line(&mut self) {
+        let h_offset = self.bg_h_offset();
 
-    const fn bg_v_offset(&self) -> MemoryMapped<u16> {
-        unsafe { MemoryMapped::new(0x0400_0012 + 4 * self.background_id as usize) }
+        let scroll_per_line = match &mut self.scroll_per_line {
+            Some(scroll_per_line) if scroll_per_line.enabled => scroll_per_line,
+            Some(_) => {
+                self.stop_scroll_per_line();
+                h_offset.set(self.x_scroll);
+                return;
+            }
+            None => {
+                h_offset.set(self.x_scroll);
+                return;
+            }
+        };
+
+        scroll_per_line.dma.commit();
     }
 
-    const fn screenblock_memory(&self) -> *mut u16 {
-        (0x0600_0000 + 0x1000 * self.screenblock as usize / 2) as *mut u16
+    fn stop_scroll_per_line(&mut self) {
+        // dropping the DMA lets other effects use it
+        self.scroll_per_line = None;
     }
-}
 
-pub struct MapLoan<'a, T> {
-    map: T,
-    background_id: u8,
-    screenblock_id: u8,
-    screenblock_length: u8,
-    regular_map_list: &'a RefCell<Bitarray<1>>,
-    screenblock_list: &'a RefCell<Bitarray<1>>,
-}
+    // Copies only the rows which have changed since the last commit. If only some columns
+    // changed, such as after scrolling horizontally, just those columns of each row are copied.
+    // Otherwise adjacent dirty rows are merged into a single DMA transfer.
+    fn commit_dirty_rows(&mut self, screenblocks: &Screenblocks) {
+        let num_rows = self.size.num_tiles() / ROW_LENGTH;
 
-impl<'a, T> Deref for MapLoan<'a, T> {
-    type Target = T;
+        let columns = core::mem::replace(&mut self.dirty_columns, 0..0);
+        if let Some(viewport) = self.viewport.clone() {
+            self.commit_dirty_rows_in_viewport(screenblocks, &viewport, columns);
+            return;
+        }
 
-    fn deref(&self) -> &Self::Target {
-        &self.map
-    }
-}
+        if columns.len() < ROW_LENGTH {
+            for row in 0..num_rows {
+                if self.dirty_rows.get(row) != Some(true) {
+                    continue;
+                }
 
-impl<'a, T> DerefMut for MapLoan<'a, T> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.map
+                self.dirty_rows.set(row, false);
+
+                let offset = row * ROW_LENGTH + columns.start;
+                screenblocks.write_slice(offset, &self.tiles[offset..offset + columns.len()]);
+            }
+
+            return;
+        }
+
+        let mut row = 0;
+        while row < num_rows {
+            if self.dirty_rows.get(row) != Some(true) {
+                row += 1;
+                continue;
+            }
+
+            let first_row = row;
+            while row < num_rows && self.dirty_rows.get(row) == Some(true) {
+                self.dirty_rows.set(row, false);
+                row += 1;
+            }
+
+            screenblocks.write_slice(
+                first_row * ROW_LENGTH,
+                &self.tiles[first_row * ROW_LENGTH..row * ROW_LENGTH],
+            );
+        }
     }
-}
 
-impl<'a, T> MapLoan<'a, T> {
-    pub(crate) fn new(
-        map: T,
-        background_id: u8,
+    // Copies the dirty parts of the rows which pass through the viewport, and forgets about
+    // changes outside of it.
+    fn commit_dirty_rows_in_viewport(
+        &mut self,
+        screenblocks: &Screenblocks,
+        viewport: &Rect<u16>,
+        columns: Range<usize>,
+    ) {
+        let num_rows = self.size.num_tiles() / ROW_LENGTH;
+        let screenblocks_per_row = self.size.width() as usize / ROW_LENGTH;
+
+        for row in 0..num_rows {
+            if self.dirty_rows.get(row) != Some(true) {
+                continue;
+            }
+
+            self.dirty_rows.set(row, false);
+
+            // rows are stored one screenblock at a time, so work out where this one is in the map
+            let screenblock = row / ROW_LENGTH;
+            let y = (screenblock / screenblocks_per_row) * ROW_LENGTH + row % ROW_LENGTH;
+            let first_x = (screenblock % screenblocks_per_row) * ROW_LENGTH;
+
+            let dirty = Rect::new(
+                ((first_x + columns.start) as u16, y as u16).into(),
+                (columns.len() as u16, 1).into(),
+            );
+            let visible = match dirty.clip_to(viewport) {
+                Some(visible) => visible,
+                None => continue,
+            };
+
+            let start = visible.position.x as usize - first_x;
+            let end = start + visible.size.x as usize;
+
+            let offset = row * ROW_LENGTH;
+            screenblocks.write_slice(offset + start, &self.tiles[offset + start..offset + end]);
+        }
+    }
+
+    // Brings the back screenblock up to date and swaps it with the displayed one. The back
+    // screenblock is missing both the changes since the last commit and the changes made in the
+    // last commit, which only went to the other screenblock.
+    fn commit_to_back_screenblock(&mut self, back_screenblock: u8) {
+        let num_rows = self.size.num_tiles() / ROW_LENGTH;
+
+        let mut changed_rows = Bitarray::new();
+        let mut any_changed = false;
+        for row in 0..num_rows {
+            if self.dirty_rows.get(row) == Some(true) {
+                changed_rows.set(row, true);
+                any_changed = true;
+            } else if self.stale_back_rows.get(row) == Some(true) {
+                self.dirty_rows.set(row, true);
+            } else {
+                continue;
+            }
+
+            // the columns changed last time may be different, so copy whole rows
+            self.dirty_columns = 0..ROW_LENGTH;
+        }
+
+        self.commit_dirty_rows(&screenblocks(back_screenblock));
+        self.stale_back_rows = changed_rows;
+
+        if any_changed {
+            self.back_screenblock = Some(self.screenblock);
+            self.screenblock = back_screenblock;
+        }
+    }
+
+    /// Restricts [`commit`](RegularMap::commit) to copying only the tiles within `viewport` to
+    /// video ram, for example for a minimap which only ever covers a small part of the screen.
+    /// `viewport` is in tiles from the top left of the background.
+    ///
+    /// Changes to tiles outside of the viewport are kept in the map but won't be displayed until
+    /// the viewport is changed or removed with [`clear_viewport`](RegularMap::clear_viewport).
+    /// Those tiles can be hidden on screen by using a window.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the viewport is empty or extends past the edge of the background.
+    pub fn set_viewport(&mut self, viewport: &Rect<u16>) {
+        assert!(
+            viewport.size.x > 0 && viewport.size.y > 0,
+            "the viewport can't be empty"
+        );
+        assert!(
+            u32::from(viewport.position.x) + u32::from(viewport.size.x) <= self.size.width()
+                && u32::from(viewport.position.y) + u32::from(viewport.size.y)
+                    <= self.size.height(),
+            "the viewport must be inside the {}x{} background",
+            self.size.width(),
+            self.size.height()
+        );
+
+        self.viewport = Some(viewport.clone());
+        self.mark_all_dirty();
+    }
+
+    /// Goes back to copying every changed tile of the map to video ram on commit.
+    pub fn clear_viewport(&mut self) {
+        if self.viewport.take().is_some() {
+            self.mark_all_dirty();
+        }
+    }
+
+    /// The area set by [`set_viewport`](RegularMap::set_viewport), if any.
+    #[must_use]
+    pub fn viewport(&self) -> Option<&Rect<u16>> {
+        self.viewport.as_ref()
+    }
+
+    /// Sets the position of the top left of the screen within the background in pixels. This wraps
+    /// around the size of the background, so for a 32x32 background (256x256 pixels) both -8 and
+    /// 248 scroll to the same place.
+    pub fn set_scroll_pos(&mut self, pos: Vector2D<i32>) {
+        self.x_scroll = self.size.px_offset_x(pos.x);
+        self.y_scroll = self.size.px_offset_y(pos.y);
+    }
+
+    /// Moves the scroll position by `delta` pixels, wrapping around the size of the background.
+    pub fn scroll_by(&mut self, delta: Vector2D<i32>) {
+        self.set_scroll_pos(self.scroll_pos() + delta);
+    }
+
+    /// Sets whether this background is pixelated by the mosaic effect. The size of the mosaic is
+    /// shared between all backgrounds and is set using [`Mosaic`](crate::display::mosaic::Mosaic).
+    pub fn set_mosaic(&mut self, mosaic: bool) {
+        self.mosaic = mosaic;
+    }
+
+    /// The current scroll position, which is always between 0 and the size of the background in
+    /// pixels.
+    #[must_use]
+    pub fn scroll_pos(&self) -> Vector2D<i32> {
+        (self.x_scroll, self.y_scroll).into()
+    }
+
+    /// The size of this background.
+    #[must_use]
+    pub fn size(&self) -> RegularBackgroundSize {
+        self.size
+    }
+
+    /// Sets the priority of this background, which takes effect from the next call to
+    /// [`commit`](RegularMap::commit). To change the priorities of several backgrounds at once,
+    /// such as swapping two of them, use [`set_priorities`](RegularMap::set_priorities) instead so
+    /// that they never share a priority for a frame.
+    pub fn set_priority(&mut self, priority: Priority) {
+        self.priority = priority;
+    }
+
+    #[must_use]
+    pub fn priority(&self) -> Priority {
+        self.priority
+    }
+
+    /// Changes the priorities of several backgrounds at once, writing all of their control
+    /// registers immediately one after another with interrupts disabled, so that the hardware
+    /// never sees some of the changes without the others. This should be called during vblank.
+    /// Nothing else about the maps is committed.
+    ///
+    /// ```rust,no_run
+    /// # #![no_std]
+    /// # #![no_main]
+    /// # use agb::display::{Priority, tiled::{RegularBackgroundSize, RegularMap}};
+    /// # fn foo(mut gba: agb::Gba) {
+    /// # let (gfx, mut vram) = gba.display.video.tiled0();
+    /// let mut player_layer = gfx.background(Priority::P1, RegularBackgroundSize::Background32x32);
+    /// let mut foreground = gfx.background(Priority::P0, RegularBackgroundSize::Background32x32);
+    ///
+    /// // the player walks behind the foreground
+    /// RegularMap::set_priorities(&mut [
+    ///     (&mut *player_layer, Priority::P0),
+    ///     (&mut *foreground, Priority::P1),
+    /// ]);
+    /// # }
+    /// ```
+    pub fn set_priorities(changes: &mut [(&mut RegularMap, Priority)]) {
+        for (map, priority) in changes.iter_mut() {
+            map.priority = *priority;
+        }
+
+        free(|_| {
+            for (map, _) in changes.iter() {
+                map.bg_control_register().set(map.bg_control_value());
+            }
+        });
+    }
+
+    /// The hardware background (0 to 3) this map is displayed on.
+    #[must_use]
+    pub fn background_id(&self) -> u8 {
+        self.background_id
+    }
+
+    /// The first screenblock used by this map in video ram. For double buffered maps this is the
+    /// screenblock currently being displayed.
+    #[must_use]
+    pub fn screenblock(&self) -> u8 {
+        self.screenblock
+    }
+
+    // The flip and palette bits of the tile at the given position
+    #[cfg(test)]
+    pub(crate) fn tile_setting_bits(&self, pos: Vector2D<u16>) -> u16 {
+        self.tiles[self.size.gba_offset(pos)].0 & !((1 << 10) - 1)
+    }
+
+    // The character base block is always 0, see the layout of video ram in the tiled module
+    fn bg_control_value(&self) -> u16 {
+        (self.priority as u16)
+            | (u16::from(self.mosaic) << 6)
+            | (u16::from(self.screenblock) << 8)
+            | (self.size.size_flag() << 14)
+    }
+
+    const fn bg_control_register(&self) -> MemoryMapped<u16> {
+        unsafe { MemoryMapped::new(0x0400_0008 + 2 * self.background_id as usize) }
+    }
+
+    const fn bg_h_offset(&self) -> MemoryMapped<u16> {
+        unsafe { MemoryMapped::new(0x0400_0010 + 4 * self.background_id as usize) }
+    }
+
+    const fn bg_v_offset(&self) -> MemoryMapped<u16> {
+        unsafe { MemoryMapped::new(0x0400_0012 + 4 * self.background_id as usize) }
+    }
+
+    #[cfg(test)]
+    const fn screenblock_memory(&self) -> *mut u16 {
+        screenblock_address(self.screenblock as usize) as *mut u16
+    }
+}
+
+impl Drop for RegularMap {
+    fn drop(&mut self) {
+        self.stop_scroll_per_line();
+    }
+}
+
+pub struct MapLoan<'a, T> {
+    map: T,
+    background_id: u8,
+    screenblock_id: u8,
+    screenblock_length: u8,
+    regular_map_list: &'a RefCell<Bitarray<1>>,
+    screenblock_list: &'a RefCell<Bitarray<1>>,
+}
+
+impl<'a, T> Deref for MapLoan<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.map
+    }
+}
+
+impl<'a, T> DerefMut for MapLoan<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.map
+    }
+}
+
+impl<'a, T> MapLoan<'a, T> {
+    pub(crate) fn new(
+        map: T,
+        background_id: u8,
         screenblock_id: u8,
         screenblock_length: u8,
         regular_map_list: &'a RefCell<Bitarray<1>>,
@@ -206,6 +1116,12 @@ impl<'a, T> MapLoan<'a, T> {
     pub const fn background(&self) -> BackgroundID {
         BackgroundID(self.background_id)
     }
+
+    /// The hardware background (0 to 3) this loan is for.
+    #[must_use]
+    pub const fn background_id(&self) -> u8 {
+        self.background_id
+    }
 }
 
 impl<'a, T> Drop for MapLoan<'a, T> {
@@ -221,3 +1137,1161 @@ impl<'a, T> Drop for MapLoan<'a, T> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::display::tiled::{RegularBackgroundSize, TileFormat, TileMap, TileMapEdge};
+
+    #[test_case]
+    fn scrolling_across_the_wrap_reveals_committed_tiles(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+        let mut map = gfx.background(Priority::P0, RegularBackgroundSize::Background32x32);
+
+        let tiles = [0u8; 4 * 8 * 8 / 2];
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        // give each column a different palette so the revealed columns can be told apart
+        for y in 0..32u16 {
+            for x in 0..32u16 {
+                map.set_tile(
+                    &mut vram,
+                    (x, y).into(),
+                    &tileset,
+                    TileSetting::new(0, false, false, (x % 16) as u8),
+                );
+            }
+        }
+        map.commit(&mut vram);
+
+        for step in 0..12 {
+            map.set_scroll_pos((200 + step * 8, -40 + step * 8).into());
+            map.commit(&mut vram);
+
+            for y in 0..32u16 {
+                for x in 0..32u16 {
+                    let offset = map.size.gba_offset((x, y).into());
+                    let entry = unsafe { map.screenblock_memory().add(offset).read_volatile() };
+                    assert_eq!(entry, map.tiles[offset].0);
+                    assert_eq!(entry >> 12, x % 16);
+                }
+            }
+        }
+
+        map.clear(&mut vram);
+        vram.gc();
+    }
+
+    #[test_case]
+    fn tile_rows_and_columns_wrap_like_set_tile(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+        let mut map = gfx.background(Priority::P0, RegularBackgroundSize::Background32x32);
+        let mut expected = gfx.background(Priority::P1, RegularBackgroundSize::Background32x32);
+
+        let tiles = [0x55u8; 2 * 8 * 8 / 2];
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        let row: Vec<_> = (0..8)
+            .map(|i| (&tileset, TileSetting::new(i / 4, false, false, i as u8)))
+            .collect();
+
+        map.set_tile_row(&mut vram, 3, 28, &row);
+        map.set_tile_column(&mut vram, 5, 30, &row);
+
+        for (i, &(tileset, tile_setting)) in row.iter().enumerate() {
+            let i = i as u16;
+            expected.set_tile(&mut vram, ((28 + i) % 32, 3).into(), tileset, tile_setting);
+            expected.set_tile(&mut vram, (5, (30 + i) % 32).into(), tileset, tile_setting);
+        }
+
+        for offset in 0..map.tiles.len() {
+            assert_eq!(map.tiles[offset], expected.tiles[offset]);
+        }
+        assert_eq!(vram.stats().used_tile_slots, 1);
+
+        map.clear(&mut vram);
+        expected.clear(&mut vram);
+        vram.gc();
+        assert_eq!(vram.stats().used_tile_slots, 0);
+    }
+
+    #[test_case]
+    fn draw_map_wraps_or_clamps_the_source(gba: &mut crate::Gba) {
+        const fn palette(palette_id: u8) -> TileSetting {
+            TileSetting::new(0, false, false, palette_id)
+        }
+        static SETTINGS: [TileSetting; 6] = [
+            palette(0),
+            palette(1),
+            palette(2),
+            palette(3),
+            palette(4),
+            palette(5),
+        ];
+        const TILE_MAP: TileMap = TileMap::new(3, 2, &SETTINGS);
+
+        let (gfx, mut vram) = gba.display.video.tiled0();
+        let mut map = gfx.background(Priority::P0, RegularBackgroundSize::Background32x32);
+
+        let tiles = [0x66u8; 8 * 8 / 2];
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        let palette_at =
+            |map: &RegularMap, x: u16, y: u16| map.tile_setting_bits((x, y).into()) >> 12;
+
+        map.draw_map(
+            &mut vram,
+            &TILE_MAP,
+            &tileset,
+            (1u16, 0u16).into(),
+            &Rect::new((30u16, 0u16).into(), (4u16, 3u16).into()),
+            TileMapEdge::Wrap,
+        );
+        let wrapped = [[1, 2, 0, 1], [4, 5, 3, 4], [1, 2, 0, 1]];
+        for (y, row) in wrapped.iter().enumerate() {
+            for (x, &expected) in row.iter().enumerate() {
+                let dest_x = (30 + x as u16) % 32;
+                assert_eq!(palette_at(&map, dest_x, y as u16), expected);
+            }
+        }
+
+        map.draw_map(
+            &mut vram,
+            &TILE_MAP,
+            &tileset,
+            (1u16, 0u16).into(),
+            &Rect::new((0u16, 10u16).into(), (4u16, 3u16).into()),
+            TileMapEdge::Clamp,
+        );
+        let clamped = [[1, 2, 2, 2], [4, 5, 5, 5], [4, 5, 5, 5]];
+        for (y, row) in clamped.iter().enumerate() {
+            for (x, &expected) in row.iter().enumerate() {
+                assert_eq!(palette_at(&map, x as u16, 10 + y as u16), expected);
+            }
+        }
+
+        map.clear(&mut vram);
+        vram.gc();
+    }
+
+    #[test_case]
+    fn try_set_tile_reports_tiles_out_of_range(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+        let mut map = gfx.background(Priority::P0, RegularBackgroundSize::Background32x32);
+
+        let tiles = [0x44u8; 3 * 8 * 8 / 2];
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+        assert_eq!(vram.tileset_len(&tileset), 3);
+
+        assert_eq!(
+            map.try_set_tile(
+                &mut vram,
+                (1u16, 1u16).into(),
+                &tileset,
+                TileSetting::new(3, false, false, 0)
+            ),
+            Err(SetTileError::TileOutOfRange {
+                tileset_address: tiles.as_ptr() as usize,
+                tile: 3,
+                tile_count: 3
+            })
+        );
+        assert_eq!(
+            map.tiles[map.size.gba_offset((1u16, 1u16).into())],
+            Tile::default()
+        );
+
+        assert_eq!(
+            map.try_set_tile(
+                &mut vram,
+                (1u16, 1u16).into(),
+                &tileset,
+                TileSetting::new(2, false, false, 0)
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            map.try_set_tile(&mut vram, (1u16, 2u16).into(), &tileset, TileSetting::BLANK),
+            Ok(())
+        );
+
+        map.clear(&mut vram);
+        vram.gc();
+    }
+
+    #[test_case]
+    fn try_set_tile_reports_running_out_of_video_ram(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+        let mut map = gfx.background(Priority::P0, RegularBackgroundSize::Background32x32);
+
+        let mut tiles = [0x55u8; 2 * 8 * 8 / 2];
+        tiles[8 * 8 / 2..].fill(0x66);
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        map.set_tile(
+            &mut vram,
+            (1u16, 1u16).into(),
+            &tileset,
+            TileSetting::new(0, false, false, 0),
+        );
+        let before = map.tiles[map.size.gba_offset((1u16, 1u16).into())];
+
+        let filler: Vec<_> = (0..vram.stats().free_tile_slots)
+            .map(|_| vram.new_dynamic_tile())
+            .collect();
+        let full = vram.stats();
+        assert_eq!(full.free_tile_slots, 0);
+
+        assert_eq!(
+            map.try_set_tile(
+                &mut vram,
+                (1u16, 1u16).into(),
+                &tileset,
+                TileSetting::new(1, false, false, 0)
+            ),
+            Err(SetTileError::OutOfVideoRam)
+        );
+        assert_eq!(map.tiles[map.size.gba_offset((1u16, 1u16).into())], before);
+        assert_eq!(vram.stats(), full);
+
+        // tiles which are already in video ram can still be used
+        assert_eq!(
+            map.try_set_tile(
+                &mut vram,
+                (2u16, 1u16).into(),
+                &tileset,
+                TileSetting::new(0, true, false, 0)
+            ),
+            Ok(())
+        );
+
+        for tile in filler {
+            vram.remove_dynamic_tile(tile);
+        }
+        map.clear(&mut vram);
+        vram.gc();
+        assert_eq!(vram.stats().used_tile_slots, 0);
+    }
+
+    #[test_case]
+    fn tile_zero_keeps_its_settings(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+        let mut map = gfx.background(Priority::P0, RegularBackgroundSize::Background32x32);
+
+        let tiles = [0x11u8; 8 * 8 / 2];
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        map.set_tile(
+            &mut vram,
+            (3u16, 4u16).into(),
+            &tileset,
+            TileSetting::new(0, true, true, 2),
+        );
+
+        let tile = map.tiles[map.size.gba_offset((3u16, 4u16).into())];
+        assert_ne!(tile, Tile::default());
+        assert_eq!(tile.0 & !((1 << 10) - 1), (1 << 10) | (1 << 11) | (2 << 12));
+        assert_eq!(vram.stats().used_tile_slots, 1);
+
+        map.set_tile(&mut vram, (3u16, 4u16).into(), &tileset, TileSetting::BLANK);
+
+        assert_eq!(
+            map.tiles[map.size.gba_offset((3u16, 4u16).into())],
+            Tile::default()
+        );
+
+        vram.gc();
+        assert_eq!(vram.stats().used_tile_slots, 0);
+    }
+
+    #[test_case]
+    fn the_last_tile_index_is_a_real_tile(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+        let mut map = gfx.background(Priority::P0, RegularBackgroundSize::Background32x32);
+
+        const TILES: usize = 1 << 10;
+        let mut tiles = vec![0u8; TILES * 8 * 8 / 2];
+        tiles[(TILES - 1) * 8 * 8 / 2..].fill(0x22);
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        let last = TileSetting::new(TILES as u16 - 1, false, true, 0);
+        assert_ne!(last, TileSetting::BLANK);
+        assert_ne!(TileSetting::from_raw((1 << 10) - 1), TileSetting::BLANK);
+
+        assert_eq!(
+            map.try_set_tile(&mut vram, (1u16, 2u16).into(), &tileset, last),
+            Ok(())
+        );
+
+        let tile = map.tiles[map.size.gba_offset((1u16, 2u16).into())];
+        assert_ne!(tile, Tile::default());
+        assert_eq!(vram.stats().used_tile_slots, 1);
+
+        let slot = (0x0600_0000 + tile.tile_index().index() as usize * 8 * 8 / 2) as *const u8;
+        let data = unsafe { core::slice::from_raw_parts(slot, 8 * 8 / 2) };
+        assert_eq!(data, tileset.tile_data(TILES as u16 - 1));
+
+        map.set_tile(&mut vram, (1u16, 2u16).into(), &tileset, TileSetting::BLANK);
+        vram.gc();
+        assert_eq!(vram.stats().used_tile_slots, 0);
+    }
+
+    #[test_case]
+    fn commit_only_copies_dirty_rows(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+        let mut map = gfx.background(Priority::P0, RegularBackgroundSize::Background32x32);
+
+        let tiles = [0x22u8; 8 * 8 / 2];
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        // the first commit writes every row
+        map.commit(&mut vram);
+
+        // marks every entry in video ram, so that any entry the commit writes can be spotted
+        const SENTINEL: u16 = 0xbeef;
+        let entries = map.size.num_tiles();
+        let fill_with_sentinel = |map: &RegularMap| {
+            for offset in 0..entries {
+                unsafe {
+                    map.screenblock_memory()
+                        .add(offset)
+                        .write_volatile(SENTINEL)
+                };
+            }
+        };
+        let entry = |map: &RegularMap, offset: usize| unsafe {
+            map.screenblock_memory().add(offset).read_volatile()
+        };
+
+        fill_with_sentinel(&map);
+        map.set_tile(
+            &mut vram,
+            (5u16, 7u16).into(),
+            &tileset,
+            TileSetting::new(0, false, false, 0),
+        );
+        map.commit(&mut vram);
+
+        let dirty = map.size.gba_offset((5u16, 7u16).into());
+        assert_eq!(entry(&map, dirty), map.tiles[dirty].0);
+        for offset in (0..entries).filter(|&offset| offset / 32 != dirty / 32) {
+            assert_eq!(
+                entry(&map, offset),
+                SENTINEL,
+                "entry {} of a clean row was rewritten",
+                offset
+            );
+        }
+
+        // changing just the scroll position doesn't rewrite anything
+        fill_with_sentinel(&map);
+        map.set_scroll_pos((12, 3).into());
+        map.commit(&mut vram);
+        assert!((0..entries).all(|offset| entry(&map, offset) == SENTINEL));
+
+        map.clear(&mut vram);
+        vram.gc();
+    }
+
+    #[test_case]
+    fn remap_palette_only_changes_tiles_using_the_bank(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+        let mut map = gfx.background(Priority::P0, RegularBackgroundSize::Background32x32);
+
+        let tiles = [0x22u8; 8 * 8 / 2];
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        map.set_tile(
+            &mut vram,
+            (1u16, 1u16).into(),
+            &tileset,
+            TileSetting::new(0, true, false, 2),
+        );
+        map.set_tile(
+            &mut vram,
+            (2u16, 1u16).into(),
+            &tileset,
+            TileSetting::new(0, false, false, 3),
+        );
+        map.set_tile(
+            &mut vram,
+            (5u16, 5u16).into(),
+            &tileset,
+            TileSetting::new(0, false, false, 2),
+        );
+        map.commit(&mut vram);
+
+        map.remap_palette(&Rect::new((0u16, 0u16).into(), (4u16, 4u16).into()), 2, 7);
+
+        assert_eq!(
+            map.tile_setting_bits((1u16, 1u16).into()),
+            TileSetting::new(0, true, false, 7).setting()
+        );
+        assert_eq!(
+            map.tile_setting_bits((2u16, 1u16).into()),
+            TileSetting::new(0, false, false, 3).setting()
+        );
+        assert_eq!(
+            map.tile_setting_bits((5u16, 5u16).into()),
+            TileSetting::new(0, false, false, 2).setting()
+        );
+
+        map.commit(&mut vram);
+        let pos = map.size.gba_offset((1u16, 1u16).into());
+        let screenblock_entry = unsafe { map.screenblock_memory().add(pos).read_volatile() };
+        assert_eq!(screenblock_entry, map.tiles[pos].0);
+
+        map.clear(&mut vram);
+        map.commit(&mut vram);
+    }
+
+    #[test_case]
+    fn mirrored_tiles_are_flipped_in_the_map(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+        let mut map = gfx.background(Priority::P0, RegularBackgroundSize::Background32x32);
+
+        // the second tile is the first mirrored horizontally
+        let mut tiles = [0u8; 2 * 8 * 8 / 2];
+        tiles[0] = 0x01;
+        tiles[32 + 3] = 0x10;
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        map.set_tile(
+            &mut vram,
+            (0u16, 0u16).into(),
+            &tileset,
+            TileSetting::new(0, false, false, 0),
+        );
+        map.set_tile(
+            &mut vram,
+            (1u16, 0u16).into(),
+            &tileset,
+            TileSetting::new(1, false, false, 0),
+        );
+        // asking for a flipped copy of the mirror image gives the original tile unflipped
+        map.set_tile_row(
+            &mut vram,
+            1,
+            0,
+            &[(&tileset, TileSetting::new(1, true, true, 0))],
+        );
+
+        let tile_at =
+            |map: &RegularMap, x: u16, y: u16| map.tiles[map.size.gba_offset((x, y).into())];
+
+        assert_eq!(
+            tile_at(&map, 0, 0).tile_index().index(),
+            tile_at(&map, 1, 0).tile_index().index()
+        );
+        assert_eq!(
+            map.tile_setting_bits((1u16, 0u16).into()),
+            TileSetting::new(0, true, false, 0).setting()
+        );
+        assert_eq!(
+            map.tile_setting_bits((0u16, 1u16).into()),
+            TileSetting::new(0, false, true, 0).setting()
+        );
+        assert_eq!(vram.stats().used_tile_slots, 1);
+
+        map.clear(&mut vram);
+        map.commit(&mut vram);
+    }
+
+    #[test_case]
+    fn double_buffered_maps_switch_screenblock_on_commit(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+        let mut map =
+            gfx.background_double_buffered(Priority::P0, RegularBackgroundSize::Background32x32);
+
+        let tiles = [0x22u8; 8 * 8 / 2];
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        let entry = |map: &RegularMap, pos: Vector2D<u16>| unsafe {
+            map.screenblock_memory()
+                .add(map.size.gba_offset(pos))
+                .read_volatile()
+        };
+
+        map.commit(&mut vram);
+        let first = map.screenblock();
+        assert_eq!(
+            map.bg_control_register().get() >> 8 & 0x1f,
+            u16::from(first)
+        );
+
+        map.set_tile(
+            &mut vram,
+            (3u16, 4u16).into(),
+            &tileset,
+            TileSetting::new(0, false, false, 1),
+        );
+        // nothing reaches the displayed screenblock until the commit
+        assert_eq!(entry(&map, (3u16, 4u16).into()), 0);
+
+        map.commit(&mut vram);
+        let second = map.screenblock();
+        assert_ne!(first, second);
+        assert_eq!(
+            map.bg_control_register().get() >> 8 & 0x1f,
+            u16::from(second)
+        );
+        assert_eq!(
+            entry(&map, (3u16, 4u16).into()),
+            map.tiles[map.size.gba_offset((3u16, 4u16).into())].0
+        );
+
+        // the next commit brings the other screenblock up to date as well as adding new changes
+        map.set_tile(
+            &mut vram,
+            (10u16, 20u16).into(),
+            &tileset,
+            TileSetting::new(0, false, false, 2),
+        );
+        map.commit(&mut vram);
+        assert_eq!(map.screenblock(), first);
+        assert_eq!(
+            entry(&map, (3u16, 4u16).into()),
+            map.tiles[map.size.gba_offset((3u16, 4u16).into())].0
+        );
+        assert_eq!(
+            entry(&map, (10u16, 20u16).into()),
+            map.tiles[map.size.gba_offset((10u16, 20u16).into())].0
+        );
+
+        map.clear(&mut vram);
+        map.commit(&mut vram);
+    }
+
+    #[test_case]
+    fn priorities_are_swapped_together(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+        let mut back = gfx.background(Priority::P1, RegularBackgroundSize::Background32x32);
+        let mut front = gfx.background(Priority::P0, RegularBackgroundSize::Background32x32);
+        back.commit(&mut vram);
+        front.commit(&mut vram);
+
+        RegularMap::set_priorities(&mut [(&mut *back, Priority::P0), (&mut *front, Priority::P1)]);
+
+        assert_eq!(back.priority(), Priority::P0);
+        assert_eq!(front.priority(), Priority::P1);
+        assert_eq!(back.bg_control_register().get() & 0b11, 0);
+        assert_eq!(front.bg_control_register().get() & 0b11, 1);
+        assert_eq!(back.bg_control_register().get(), back.bg_control_value());
+    }
+
+    #[test_case]
+    fn commit_only_copies_the_dirty_columns(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+        let mut map = gfx.background(Priority::P0, RegularBackgroundSize::Background32x32);
+        map.commit(&mut vram);
+
+        let tiles = [0x22u8; 8 * 8 / 2];
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        let column = [(&tileset, TileSetting::new(0, false, false, 0)); 21];
+        map.set_tile_column(&mut vram, 9, 0, &column);
+
+        // anything outside of the dirty column of each row shouldn't be touched by the commit
+        let untouched = map.size.gba_offset((10u16, 4u16).into());
+        unsafe {
+            map.screenblock_memory()
+                .add(untouched)
+                .write_volatile(0x1234)
+        };
+
+        map.commit(&mut vram);
+
+        for y in 0..21u16 {
+            let pos = map.size.gba_offset((9u16, y).into());
+            let screenblock_entry = unsafe { map.screenblock_memory().add(pos).read_volatile() };
+            assert_eq!(screenblock_entry, map.tiles[pos].0);
+        }
+
+        let untouched_entry = unsafe { map.screenblock_memory().add(untouched).read_volatile() };
+        assert_eq!(untouched_entry, 0x1234);
+
+        map.clear(&mut vram);
+        map.commit(&mut vram);
+    }
+
+    #[test_case]
+    fn commit_only_copies_tiles_in_the_viewport(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+        let mut map = gfx.background(Priority::P0, RegularBackgroundSize::Background64x32);
+        map.commit(&mut vram);
+
+        let tiles = [0x22u8; 8 * 8 / 2];
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        // crosses from the first screenblock into the second
+        let viewport = Rect::new((28u16, 26u16).into(), (8u16, 6u16).into());
+        map.set_viewport(&viewport);
+
+        for y in 0..32u16 {
+            for x in 0..64u16 {
+                map.set_tile(
+                    &mut vram,
+                    (x, y).into(),
+                    &tileset,
+                    TileSetting::new(0, false, false, 1),
+                );
+            }
+        }
+        map.commit(&mut vram);
+
+        for y in 0..32u16 {
+            for x in 0..64u16 {
+                let pos = map.size.gba_offset((x, y).into());
+                let entry = unsafe { map.screenblock_memory().add(pos).read_volatile() };
+                if (28..36).contains(&x) && (26..32).contains(&y) {
+                    assert_eq!(entry, map.tiles[pos].0);
+                } else {
+                    assert_eq!(entry, 0);
+                }
+            }
+        }
+
+        map.clear_viewport();
+        map.commit(&mut vram);
+
+        for pos in 0..map.size.num_tiles() {
+            let entry = unsafe { map.screenblock_memory().add(pos).read_volatile() };
+            assert_eq!(entry, map.tiles[pos].0);
+        }
+
+        map.clear(&mut vram);
+        map.commit(&mut vram);
+    }
+
+    #[test_case]
+    fn scroll_per_line_arms_and_releases_hblank_dma(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+        let mut map = gfx.background(Priority::P0, RegularBackgroundSize::Background32x32);
+
+        let dma0_control: MemoryMapped<u16> = unsafe { MemoryMapped::new(0x0400_00BA) };
+
+        let mut table = [0; 160];
+        for (line, scroll) in table.iter_mut().enumerate() {
+            *scroll = (line % 8) as u16;
+        }
+
+        map.set_scroll_per_line(&table);
+        map.commit(&mut vram);
+
+        assert_ne!(dma0_control.get() & (1 << 15), 0);
+        let scroll_per_line = map.scroll_per_line.as_ref().unwrap();
+        assert_eq!(scroll_per_line.dma.front_table()[..160], table);
+        assert_eq!(scroll_per_line.dma.front_table()[160], table[0]);
+
+        map.clear_scroll_per_line();
+        map.commit(&mut vram);
+
+        assert_eq!(dma0_control.get() & (1 << 15), 0);
+        assert!(map.scroll_per_line.is_none());
+
+        // now that it has been released, a different background can use it
+        let mut other = gfx.background(Priority::P1, RegularBackgroundSize::Background32x32);
+        other.set_scroll_per_line(&table);
+        drop(other);
+
+        assert_eq!(dma0_control.get() & (1 << 15), 0);
+    }
+
+    #[test_case]
+    fn show_waits_for_the_first_commit(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+        let mut map = gfx.background(Priority::P0, RegularBackgroundSize::Background32x32);
+
+        let enabled_bit = 1 << (map.background_id + 0x08);
+
+        map.show();
+        assert_eq!(DISPLAY_CONTROL.get() & enabled_bit, 0);
+
+        map.commit(&mut vram);
+        assert_ne!(DISPLAY_CONTROL.get() & enabled_bit, 0);
+
+        map.hide();
+        assert_eq!(DISPLAY_CONTROL.get() & enabled_bit, 0);
+
+        // once it has been committed, show takes effect straight away
+        map.show();
+        assert_ne!(DISPLAY_CONTROL.get() & enabled_bit, 0);
+
+        map.hide();
+    }
+
+    #[test_case]
+    fn maps_report_where_they_are(gba: &mut crate::Gba) {
+        let (gfx, _vram) = gba.display.video.tiled0();
+
+        let first = gfx.background(Priority::P0, RegularBackgroundSize::Background64x32);
+        let second = gfx.background(Priority::P0, RegularBackgroundSize::Background32x32);
+
+        assert_eq!(first.background_id(), 0);
+        assert_eq!(second.background_id(), 1);
+        assert_eq!(first.size(), RegularBackgroundSize::Background64x32);
+        assert_eq!(first.screenblock(), 16);
+        assert_eq!(second.screenblock(), 18);
+
+        drop(first);
+
+        let third = gfx.background(Priority::P0, RegularBackgroundSize::Background32x32);
+        assert_eq!(third.background_id(), 0);
+        assert_eq!(third.screenblock(), 16);
+        assert_eq!(third.background(), BackgroundID(0));
+    }
+
+    #[test_case]
+    fn scroll_position_wraps_around_the_background(gba: &mut crate::Gba) {
+        let (gfx, _vram) = gba.display.video.tiled0();
+        let mut small = gfx.background(Priority::P0, RegularBackgroundSize::Background32x32);
+        let mut wide = gfx.background(Priority::P0, RegularBackgroundSize::Background64x32);
+
+        small.set_scroll_pos((-8, 256).into());
+        assert_eq!(small.scroll_pos(), (248, 0).into());
+
+        small.scroll_by((8, -1).into());
+        assert_eq!(small.scroll_pos(), (0, 255).into());
+
+        small.scroll_by((-1, 1).into());
+        assert_eq!(small.scroll_pos(), (255, 0).into());
+
+        wide.set_scroll_pos((-8, -8).into());
+        assert_eq!(wide.scroll_pos(), (504, 248).into());
+
+        wide.scroll_by((300, 0).into());
+        assert_eq!(wide.scroll_pos(), (292, 248).into());
+
+        wide.set_scroll_pos((i32::from(u16::MAX), 0).into());
+        assert_eq!(wide.scroll_pos(), (511, 0).into());
+    }
+
+    #[test_case]
+    fn exported_maps_can_be_imported_again(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+        let mut map = gfx.background(Priority::P0, RegularBackgroundSize::Background32x32);
+
+        let ground_tiles = [0x12u8; 2 * 8 * 8 / 2];
+        let ground = TileSet::new(&ground_tiles, TileFormat::FourBpp);
+        let mut sky_tiles = [0x34u8; 8 * 8 / 2];
+        sky_tiles[0] = 0x56;
+        let sky = TileSet::new(&sky_tiles, TileFormat::FourBpp);
+
+        map.set_tile(
+            &mut vram,
+            (3u16, 4u16).into(),
+            &ground,
+            TileSetting::new(1, true, false, 2),
+        );
+        map.set_tile(
+            &mut vram,
+            (31u16, 31u16).into(),
+            &sky,
+            TileSetting::new(0, false, true, 5),
+        );
+
+        let tilesets = [&ground, &sky];
+        let exported = map.export(&vram, &tilesets);
+        assert!(exported.missing().is_empty());
+        assert_eq!(
+            exported.tile((0u16, 0u16).into()).setting,
+            TileSetting::BLANK
+        );
+        assert_eq!(
+            exported.tile((31u16, 31u16).into()),
+            ExportedTile {
+                tileset: 1,
+                setting: TileSetting::new(0, false, true, 5),
+            }
+        );
+
+        map.clear(&mut vram);
+        map.import(&mut vram, &exported, &tilesets);
+        assert_eq!(
+            map.tile_setting_bits((3u16, 4u16).into()),
+            TileSetting::new(1, true, false, 2).setting()
+        );
+        assert_eq!(
+            map.tile_setting_bits((31u16, 31u16).into()),
+            TileSetting::new(0, false, true, 5).setting()
+        );
+        assert_eq!(map.export(&vram, &tilesets).tiles, exported.tiles);
+
+        // tiles from a tileset which has gone can't be exported
+        vram.force_remove_tileset(&sky);
+        let exported = map.export(&vram, &tilesets);
+        assert_eq!(exported.missing(), &[(31u16, 31u16).into()]);
+        assert_eq!(
+            exported.tile((31u16, 31u16).into()).setting,
+            TileSetting::BLANK
+        );
+
+        map.clear(&mut vram);
+        map.commit(&mut vram);
+        vram.gc();
+    }
+
+    #[test_case]
+    fn tiles_shared_with_a_removed_tileset_can_still_be_exported(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+        let mut map = gfx.background(Priority::P0, RegularBackgroundSize::Background32x32);
+
+        let tile_size = 8 * 8 / 2;
+        // the second tileset has the first's tile as its tile 1, and a mirror image of it as
+        // its tile 2
+        let mut first_tiles = [0u8; 8 * 8 / 2];
+        let mut second_tiles = [0x77u8; 3 * 8 * 8 / 2];
+        for row in 0..8 {
+            first_tiles[row * 4] = 0x21;
+            second_tiles[tile_size + row * 4..tile_size + row * 4 + 4]
+                .copy_from_slice(&[0x21, 0, 0, 0]);
+            second_tiles[2 * tile_size + row * 4..2 * tile_size + row * 4 + 4]
+                .copy_from_slice(&[0, 0, 0, 0x12]);
+        }
+        let first = TileSet::new(&first_tiles, TileFormat::FourBpp);
+        let second = TileSet::new(&second_tiles, TileFormat::FourBpp);
+
+        map.set_tile(
+            &mut vram,
+            (0u16, 0u16).into(),
+            &first,
+            TileSetting::new(0, false, false, 0),
+        );
+        map.set_tile(
+            &mut vram,
+            (1u16, 0u16).into(),
+            &second,
+            TileSetting::new(1, false, true, 0),
+        );
+        map.set_tile(
+            &mut vram,
+            (2u16, 0u16).into(),
+            &second,
+            TileSetting::new(2, false, false, 0),
+        );
+
+        // all three tiles share a slot, which now only the second tileset uses
+        vram.force_remove_tileset(&first);
+
+        let exported = map.export(&vram, &[&second]);
+        assert!(exported.missing().is_empty());
+        assert_eq!(
+            exported.tile((0u16, 0u16).into()).setting,
+            TileSetting::new(1, false, false, 0)
+        );
+        assert_eq!(
+            exported.tile((1u16, 0u16).into()).setting,
+            TileSetting::new(1, false, true, 0)
+        );
+        assert_eq!(
+            exported.tile((2u16, 0u16).into()).setting,
+            TileSetting::new(2, false, false, 0)
+        );
+
+        map.clear(&mut vram);
+        map.commit(&mut vram);
+        vram.gc();
+    }
+
+    #[test_case]
+    fn commit_handles_scroll_positions_either_side_of_the_u16_wrap(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+        let mut map = gfx.background(Priority::P0, RegularBackgroundSize::Background64x32);
+
+        let tiles = [0u8; 8 * 8 / 2];
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        for y in 0..32u16 {
+            for x in 0..64u16 {
+                map.set_tile(
+                    &mut vram,
+                    (x, y).into(),
+                    &tileset,
+                    TileSetting::new(0, false, false, ((x + y) % 16) as u8),
+                );
+            }
+        }
+
+        let width = 64 * 8;
+        let height = 32 * 8;
+        for &(scroll, expected) in &[
+            ((0xfff8, 0xffff), (width - 8, height - 1)),
+            ((-8, -1), (width - 8, height - 1)),
+            ((0x1_0000 + 3, 0x1_0000 + 1), (3, 1)),
+            ((1, 3), (1, 3)),
+        ] {
+            map.set_scroll_pos(scroll.into());
+            assert_eq!(map.scroll_pos(), expected.into());
+
+            map.commit(&mut vram);
+
+            for y in 0..32u16 {
+                for x in 0..64u16 {
+                    let offset = map.size.gba_offset((x, y).into());
+                    let entry = unsafe { map.screenblock_memory().add(offset).read_volatile() };
+                    assert_eq!(entry, map.tiles[offset].0);
+                }
+            }
+        }
+
+        map.clear(&mut vram);
+        map.commit(&mut vram);
+        vram.gc();
+    }
+
+    #[test_case]
+    fn snapshots_keep_their_tiles_and_can_be_restored(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+        let mut map = gfx.background(Priority::P2, RegularBackgroundSize::Background32x32);
+
+        let level_tiles = [0x45u8; 8 * 8 / 2];
+        let level = TileSet::new(&level_tiles, TileFormat::FourBpp);
+        let menu_tiles = [0x67u8; 8 * 8 / 2];
+        let menu = TileSet::new(&menu_tiles, TileFormat::FourBpp);
+
+        for x in 0..32u16 {
+            map.set_tile(
+                &mut vram,
+                (x, 7u16).into(),
+                &level,
+                TileSetting::new(0, false, true, 3),
+            );
+        }
+        map.set_scroll_pos((17, 40).into());
+        map.commit(&mut vram);
+
+        let snapshot = map.snapshot(&mut vram);
+
+        // using the background for something else doesn't free the level's tiles
+        map.clear(&mut vram);
+        map.set_tile(
+            &mut vram,
+            (7u16, 7u16).into(),
+            &menu,
+            TileSetting::new(0, false, false, 0),
+        );
+        map.set_scroll_pos((0, 0).into());
+        map.set_priority(Priority::P0);
+        map.commit(&mut vram);
+        assert_eq!(vram.resident_tiles(&level), 1);
+
+        map.restore(&mut vram, snapshot);
+        map.commit(&mut vram);
+
+        assert_eq!(vram.resident_tiles(&menu), 0);
+        assert_eq!(map.scroll_pos(), (17, 40).into());
+        assert_eq!(map.priority(), Priority::P2);
+        for x in 0..32u16 {
+            assert_eq!(
+                map.tile_setting_bits((x, 7u16).into()),
+                TileSetting::new(0, false, true, 3).setting()
+            );
+        }
+
+        // and releasing a snapshot instead gives back its references
+        let snapshot = map.snapshot(&mut vram);
+        snapshot.release(&mut vram);
+
+        map.clear(&mut vram);
+        map.commit(&mut vram);
+        assert_eq!(vram.resident_tiles(&level), 0);
+    }
+
+    #[test_case]
+    fn preloaded_tilesets_are_copied_in_one_go(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+        let mut map = gfx.background(Priority::P0, RegularBackgroundSize::Background32x32);
+
+        let mut tiles = [0u8; 3 * 8 * 8 / 2];
+        for (i, byte) in tiles.iter_mut().enumerate() {
+            *byte = 0x70 + (i / (8 * 8 / 2)) as u8;
+        }
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        let preloaded = vram.preload_tileset(&tileset);
+        assert_eq!(preloaded.len(), 3);
+        assert_eq!(vram.resident_tiles(&tileset), 3);
+        assert_eq!(vram.stats().used_tile_slots, 3);
+
+        for tile in 0..3 {
+            let index = preloaded.tile_index(tile);
+            assert_eq!(index.index(), preloaded.tile_index(0).index() + tile);
+
+            let slot = (0x0600_0000 + index.index() as usize * 8 * 8 / 2) as *const u8;
+            let data = unsafe { core::slice::from_raw_parts(slot, 8 * 8 / 2) };
+            assert_eq!(data, tileset.tile_data(tile));
+        }
+
+        map.set_preloaded_tile(
+            &mut vram,
+            (4u16, 5u16).into(),
+            &preloaded,
+            TileSetting::new(2, true, false, 1),
+        );
+        assert_eq!(
+            map.tiles[map.size.gba_offset((4u16, 5u16).into())].0,
+            preloaded.tile_index(2).index() | TileSetting::new(0, true, false, 1).setting()
+        );
+
+        // setting the same tile the normal way finds the preloaded one
+        map.set_tile(
+            &mut vram,
+            (6u16, 5u16).into(),
+            &tileset,
+            TileSetting::new(1, false, false, 0),
+        );
+        assert_eq!(
+            map.tiles[map.size.gba_offset((6u16, 5u16).into())]
+                .tile_index()
+                .index(),
+            preloaded.tile_index(1).index()
+        );
+        assert_eq!(vram.stats().used_tile_slots, 3);
+
+        // the tiles stay until both the map and the preloaded tileset let go of them
+        vram.remove_preloaded_tileset(preloaded);
+        vram.gc();
+        assert_eq!(vram.stats().used_tile_slots, 2);
+
+        map.clear(&mut vram);
+        map.commit(&mut vram);
+        assert_eq!(vram.stats().used_tile_slots, 0);
+        assert_eq!(vram.resident_tiles(&tileset), 0);
+    }
+
+    #[test_case]
+    fn tiles_in_both_character_blocks_can_be_used(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+        let mut map = gfx.background(Priority::P0, RegularBackgroundSize::Background64x32);
+
+        // more tiles than fit in a single character block
+        const TILES: u16 = 700;
+        let mut tiles = vec![0u8; TILES as usize * 8 * 8 / 2];
+        for (i, tile) in tiles.chunks_mut(8 * 8 / 2).enumerate() {
+            for pair in tile.chunks_mut(2) {
+                pair.copy_from_slice(&(i as u16).to_le_bytes());
+            }
+        }
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        for tile in 0..TILES {
+            map.set_tile(
+                &mut vram,
+                (tile % 64, tile / 64).into(),
+                &tileset,
+                TileSetting::new(tile, false, false, 0),
+            );
+        }
+        map.commit(&mut vram);
+
+        assert_eq!(map.bg_control_register().get() & (0b11 << 2), 0);
+
+        let mut highest_index = 0;
+        for tile in 0..TILES {
+            let index = map.tiles[map.size.gba_offset((tile % 64, tile / 64).into())]
+                .tile_index()
+                .index();
+            highest_index = highest_index.max(index);
+
+            let slot = (0x0600_0000 + index as usize * 8 * 8 / 2) as *const u8;
+            let data = unsafe { core::slice::from_raw_parts(slot, 8 * 8 / 2) };
+            assert_eq!(data, tileset.tile_data(tile));
+        }
+        assert!(highest_index >= 512);
+
+        map.clear(&mut vram);
+        map.commit(&mut vram);
+        vram.gc();
+        assert_eq!(vram.stats().used_tile_slots, 0);
+    }
+
+    #[test_case]
+    fn resolved_tiles_keep_their_references_balanced(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+        let mut map = gfx.background(Priority::P0, RegularBackgroundSize::Background32x32);
+
+        // the second tile is the first one mirrored left to right, so they share a slot
+        let mut tiles = [0u8; 2 * 8 * 8 / 2];
+        tiles[0] = 0x21;
+        tiles[8 * 8 / 2 + 3] = 0x12;
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        let tile = vram.resolve(&tileset, 0);
+        let mirrored = vram.resolve(&tileset, 1);
+        assert_eq!(tile.tile_index().index(), mirrored.tile_index().index());
+
+        for x in 0..3u16 {
+            map.set_tile_resolved(
+                &mut vram,
+                (x, 0u16).into(),
+                &tile,
+                TileSetting::new(0, false, false, 2),
+            );
+        }
+        // replacing a resolved tile with another one
+        map.set_tile_resolved(
+            &mut vram,
+            (1u16, 0u16).into(),
+            &mirrored,
+            TileSetting::new(0, false, true, 2),
+        );
+
+        let index = tile.tile_index().index();
+        assert_eq!(
+            map.tiles[map.size.gba_offset((0u16, 0u16).into())].0,
+            index | TileSetting::new(0, false, false, 2).setting()
+        );
+        assert_eq!(
+            map.tiles[map.size.gba_offset((1u16, 0u16).into())].0,
+            index | TileSetting::new(0, true, true, 2).setting()
+        );
+
+        // the map keeps the tile after the resolved tiles are released
+        vram.release_resolved(tile);
+        vram.release_resolved(mirrored);
+        vram.gc();
+        assert_eq!(vram.stats().used_tile_slots, 1);
+
+        map.clear(&mut vram);
+        map.commit(&mut vram);
+        assert_eq!(vram.stats().used_tile_slots, 0);
+    }
+
+    #[test_case]
+    fn blank_resolved_tiles_empty_the_tile(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+        let mut map = gfx.background(Priority::P0, RegularBackgroundSize::Background32x32);
+
+        let tiles = [0x33u8; 8 * 8 / 2];
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        let tile = vram.resolve(&tileset, 0);
+        map.set_tile_resolved(
+            &mut vram,
+            (4u16, 2u16).into(),
+            &tile,
+            TileSetting::new(0, true, false, 1),
+        );
+        map.commit(&mut vram);
+
+        map.set_tile_resolved(&mut vram, (4u16, 2u16).into(), &tile, TileSetting::BLANK);
+        // setting an empty tile to blank again changes nothing
+        map.set_tile_resolved(&mut vram, (5u16, 2u16).into(), &tile, TileSetting::BLANK);
+
+        for x in 4..6u16 {
+            assert_eq!(
+                map.tiles[map.size.gba_offset((x, 2u16).into())],
+                Tile::default()
+            );
+        }
+
+        // only the resolved tile keeps it in video ram
+        assert!(vram
+            .tile_reference_counts()
+            .any(|(index, count)| index == tile.tile_index().index() && count == 1));
+
+        vram.release_resolved(tile);
+        map.commit(&mut vram);
+        assert_eq!(vram.stats().used_tile_slots, 0);
+    }
+}
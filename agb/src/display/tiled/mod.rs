@@ -1,13 +1,45 @@
+mod animated_tiles;
+mod camera;
+mod hud;
 mod infinite_scrolled_map;
 mod map;
+mod parallax;
 mod tiled0;
-mod vram_manager;
+pub(crate) mod vram_manager;
 
 use agb_fixnum::Vector2D;
-pub use infinite_scrolled_map::{InfiniteScrolledMap, PartialUpdateStatus};
-pub use map::{MapLoan, RegularMap};
+pub use animated_tiles::AnimatedTiles;
+pub use camera::{camera_for, camera_with_dead_zone};
+pub use hud::{HudEdge, ScrolledMapWithHud};
+pub use infinite_scrolled_map::{
+    BoundedTileProvider, InfiniteScrolledMap, PartialUpdateStatus, TileMapProvider, TileProvider,
+};
+pub use map::{ExportedMap, ExportedTile, MapLoan, MapSnapshot, RegularMap, SetTileError};
+pub use parallax::Parallax;
 pub use tiled0::Tiled0;
-pub use vram_manager::{DynamicTile, TileFormat, TileIndex, TileSet, VRamManager};
+pub use vram_manager::{
+    DynamicTile, PaletteBank, PreloadedTileset, ResolvedTile, TileFormat, TileIndex, TileSet,
+    TileSlot, TilesStillInUse, VRamManager, VRamStats,
+};
+use vram_manager::{OutOfVideoRam, TileFlip};
+
+// Layout of background video ram. Tile data starts at the beginning of video ram and grows
+// upwards, while maps use screenblocks from FIRST_MAP_SCREENBLOCK onwards. Tile data must never
+// reach the first screenblock used for maps.
+//
+// The tile data fills character blocks 0 and 1, which is exactly the 1024 4bpp tiles that the 10
+// bit tile index of a screen entry can reach, so every background uses character base block 0.
+// Character blocks 2 and 3 are the map screenblocks, so a different base wouldn't give any more
+// room for tiles.
+const VRAM_START: usize = 0x0600_0000;
+const SCREENBLOCK_SIZE: usize = 0x800;
+const FIRST_MAP_SCREENBLOCK: usize = 16;
+const MAP_SCREENBLOCKS: usize = 16;
+const TILE_DATA_END: usize = VRAM_START + FIRST_MAP_SCREENBLOCK * SCREENBLOCK_SIZE;
+
+const fn screenblock_address(screenblock: usize) -> usize {
+    VRAM_START + screenblock * SCREENBLOCK_SIZE
+}
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum RegularBackgroundSize {
@@ -98,31 +130,180 @@ impl Tile {
     }
 }
 
-#[derive(Clone, Copy, Debug, Default)]
-pub struct TileSetting(u16);
+// The bits below this are laid out the same as a map entry, so blank is kept above them
+const BLANK_TILE_SETTING: u32 = 1 << 16;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TileSetting(u32);
 
 impl TileSetting {
+    /// A setting which leaves the tile empty, showing whatever is behind this background. Every
+    /// tile index, including 0 and 1023, refers to a real tile in the tileset.
+    pub const BLANK: Self = Self(BLANK_TILE_SETTING);
+
     #[must_use]
     pub const fn new(tile_id: u16, hflip: bool, vflip: bool, palette_id: u8) -> Self {
         Self(
-            (tile_id & ((1 << 10) - 1))
-                | ((hflip as u16) << 10)
-                | ((vflip as u16) << 11)
-                | ((palette_id as u16) << 12),
+            (tile_id as u32 & ((1 << 10) - 1))
+                | ((hflip as u32) << 10)
+                | ((vflip as u32) << 11)
+                | ((palette_id as u32 & 0b1111) << 12),
         )
     }
 
+    /// Creates a setting from a map entry, as stored in video ram. Every raw value refers to a
+    /// tile, so use [`TileSetting::BLANK`] for empty tiles.
     #[must_use]
     pub const fn from_raw(raw: u16) -> Self {
-        Self(raw)
+        Self(raw as u32)
+    }
+
+    /// Returns this setting using the palette bank allocated by [`VRamManager::palette`].
+    #[must_use]
+    pub fn with_palette(self, palette: &PaletteBank) -> Self {
+        Self((self.0 & !(0b1111 << 12)) | (u32::from(palette.bank()) << 12))
     }
 
     fn index(self) -> u16 {
-        self.0 & ((1 << 10) - 1)
+        (self.0 & ((1 << 10) - 1)) as u16
+    }
+
+    // Applies the flip needed to display a tile which shares its slot with a mirror image of it
+    fn flipped(self, flip: TileFlip) -> Self {
+        Self(self.0 ^ u32::from(flip.bits()))
+    }
+
+    const fn is_blank(self) -> bool {
+        self.0 & BLANK_TILE_SETTING != 0
     }
 
     fn setting(self) -> u16 {
-        self.0 & !((1 << 10) - 1)
+        (self.0 & !((1 << 10) - 1)) as u16
+    }
+}
+
+/// A rectangular map of tile settings, such as a level produced by an importer, which can be
+/// drawn on to a background using [`RegularMap::draw_map`].
+///
+/// Each tile can also have a byte of gameplay properties, such as whether it is solid or water,
+/// added using [`TileMap::with_properties`]. What the values mean is up to the game.
+#[derive(Clone, Copy, Debug)]
+pub struct TileMap {
+    width: u16,
+    height: u16,
+    settings: &'static [TileSetting],
+    properties: Option<&'static [u8]>,
+}
+
+impl TileMap {
+    /// Creates a tile map from settings stored in rows, starting at the top left.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of settings isn't `width * height`, or either is 0.
+    #[must_use]
+    pub const fn new(width: u16, height: u16, settings: &'static [TileSetting]) -> Self {
+        assert!(width > 0 && height > 0, "tile maps can't be empty");
+        assert!(
+            settings.len() == width as usize * height as usize,
+            "tile map has the wrong number of settings for its size"
+        );
+
+        Self {
+            width,
+            height,
+            settings,
+            properties: None,
+        }
+    }
+
+    /// Adds a property byte for each tile, stored in rows in the same order as the settings.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of properties isn't `width * height`.
+    #[must_use]
+    pub const fn with_properties(self, properties: &'static [u8]) -> Self {
+        assert!(
+            properties.len() == self.width as usize * self.height as usize,
+            "tile map has the wrong number of properties for its size"
+        );
+
+        Self {
+            properties: Some(properties),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub const fn width(&self) -> u16 {
+        self.width
+    }
+
+    #[must_use]
+    pub const fn height(&self) -> u16 {
+        self.height
+    }
+
+    /// Returns the setting at the given position.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is outside of the map.
+    #[must_use]
+    pub fn get(&self, x: u16, y: u16) -> TileSetting {
+        assert!(
+            x < self.width && y < self.height,
+            "({}, {}) is outside of the tile map",
+            x,
+            y
+        );
+        self.settings[x as usize + y as usize * self.width as usize]
+    }
+
+    /// Returns the property of the tile at the given position, or 0 if this map doesn't have
+    /// any properties.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is outside of the map.
+    #[must_use]
+    pub fn property(&self, x: u16, y: u16) -> u8 {
+        assert!(
+            x < self.width && y < self.height,
+            "({}, {}) is outside of the tile map",
+            x,
+            y
+        );
+        self.properties.map_or(0, |properties| {
+            properties[x as usize + y as usize * self.width as usize]
+        })
+    }
+}
+
+/// What [`RegularMap::draw_map`] does when asked for tiles beyond the edge of a [`TileMap`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TileMapEdge {
+    /// Continue from the other side of the tile map, so it repeats
+    Wrap,
+    /// Repeat the tiles along the edge of the tile map
+    Clamp,
+}
+
+impl TileMapEdge {
+    fn source_position(self, pos: u16, length: u16) -> u16 {
+        match self {
+            TileMapEdge::Wrap => pos % length,
+            TileMapEdge::Clamp => pos.min(length - 1),
+        }
+    }
+
+    // Like source_position, but for positions which can be before the start of the tile map
+    fn world_position(self, pos: i32, length: u16) -> u16 {
+        match self {
+            TileMapEdge::Wrap => pos.rem_euclid(i32::from(length)) as u16,
+            TileMapEdge::Clamp => pos.clamp(0, i32::from(length) - 1) as u16,
+        }
     }
 }
 
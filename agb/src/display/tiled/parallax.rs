@@ -0,0 +1,198 @@
+use alloc::{boxed::Box, vec::Vec};
+
+use super::{
+    InfiniteScrolledMap, PartialUpdateStatus, TileProvider, TileSet, TileSetting, VRamManager,
+};
+
+use crate::fixnum::{Num, Vector2D};
+
+struct ParallaxLayer<'a, F> {
+    map: InfiniteScrolledMap<'a, F>,
+    factor: Num<i32, 8>,
+}
+
+/// Several [`InfiniteScrolledMap`]s which all follow a single camera, each scrolling at its own
+/// fraction of the camera's speed, such as a distant background which scrolls at half speed
+/// behind the level.
+///
+/// The position of each layer is worked out from the camera every time it moves, rather than by
+/// adding up how far the camera has moved, so layers never drift relative to each other however
+/// far the camera travels.
+///
+/// As with [`InfiniteScrolledMap`], you must call [`.clear()`](Parallax::clear) before this is
+/// dropped or you will leak video RAM.
+pub struct Parallax<'a, F = Box<dyn FnMut(Vector2D<i32>) -> (&'a TileSet<'a>, TileSetting) + 'a>> {
+    layers: Vec<ParallaxLayer<'a, F>>,
+}
+
+impl<'a, F> Parallax<'a, F>
+where
+    F: TileProvider<'a>,
+{
+    /// Creates a parallax with no layers
+    #[must_use]
+    pub fn new() -> Self {
+        Self { layers: Vec::new() }
+    }
+
+    /// Adds a layer which scrolls by `factor` times as much as the camera, so 1 moves with the
+    /// camera and 0.5 moves at half speed. Returns the index of the layer for use with
+    /// [`.layer_mut()`](Parallax::layer_mut).
+    pub fn add_layer(&mut self, map: InfiniteScrolledMap<'a, F>, factor: Num<i32, 8>) -> usize {
+        self.layers.push(ParallaxLayer { map, factor });
+        self.layers.len() - 1
+    }
+
+    /// The layer with the given index, for example to find out about its tiles.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no layer with that index.
+    pub fn layer_mut(&mut self, index: usize) -> &mut InfiniteScrolledMap<'a, F> {
+        &mut self.layers[index].map
+    }
+
+    /// Initialises every layer for the camera being at `camera`. `between_updates` is called
+    /// between each partial update in the same way as [`InfiniteScrolledMap::init`].
+    pub fn init(
+        &mut self,
+        vram: &mut VRamManager,
+        camera: Vector2D<Num<i32, 8>>,
+        between_updates: &mut impl FnMut(),
+    ) {
+        for layer in &mut self.layers {
+            let pos = layer_position(camera, layer.factor);
+            layer.map.init(vram, pos, between_updates);
+        }
+    }
+
+    /// Scrolls every layer to follow the camera being at `camera`. This behaves in the same way
+    /// as [`InfiniteScrolledMap::set_pos`], returning [`PartialUpdateStatus::Continue`] until
+    /// every layer has reached its position.
+    pub fn set_camera(
+        &mut self,
+        vram: &mut VRamManager,
+        camera: Vector2D<Num<i32, 8>>,
+    ) -> PartialUpdateStatus {
+        let mut status = PartialUpdateStatus::Done;
+
+        for layer in &mut self.layers {
+            let pos = layer_position(camera, layer.factor);
+            if layer.map.set_pos(vram, pos) == PartialUpdateStatus::Continue {
+                status = PartialUpdateStatus::Continue;
+            }
+        }
+
+        status
+    }
+
+    /// Makes every layer visible
+    pub fn show(&mut self) {
+        for layer in &mut self.layers {
+            layer.map.show();
+        }
+    }
+
+    /// Hides every layer
+    pub fn hide(&mut self) {
+        for layer in &mut self.layers {
+            layer.map.hide();
+        }
+    }
+
+    /// Copies every layer to vram. Needs to be called during vblank if possible
+    pub fn commit(&mut self, vram: &mut VRamManager) {
+        for layer in &mut self.layers {
+            layer.map.commit(vram);
+        }
+    }
+
+    /// Clears every layer, releasing all the tiles they use in video ram.
+    pub fn clear(&mut self, vram: &mut VRamManager) {
+        for layer in &mut self.layers {
+            layer.map.clear(vram);
+        }
+    }
+}
+
+impl<'a, F> Default for Parallax<'a, F>
+where
+    F: TileProvider<'a>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The top left of a layer which moves `factor` times as far as the camera, rounded down to the
+// pixel. This is worked out with 64 bits so that it doesn't overflow for far away cameras.
+fn layer_position(camera: Vector2D<Num<i32, 8>>, factor: Num<i32, 8>) -> Vector2D<i32> {
+    let scale = |coordinate: Num<i32, 8>| {
+        ((i64::from(coordinate.to_raw()) * i64::from(factor.to_raw())) >> 16) as i32
+    };
+
+    (scale(camera.x), scale(camera.y)).into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        display::{
+            tiled::{RegularBackgroundSize, TileFormat},
+            Priority,
+        },
+        fixnum::num,
+    };
+
+    #[test_case]
+    fn layers_follow_the_camera_at_their_own_speed(gba: &mut crate::Gba) {
+        let (gfx, mut vram) = gba.display.video.tiled0();
+
+        let tiles = [0x55u8; 8 * 8 / 2];
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        let mut parallax: Parallax = Parallax::new();
+        for &factor in &[num!(1.), num!(0.5), num!(0.25)] {
+            let map = InfiniteScrolledMap::new(
+                gfx.background(Priority::P0, RegularBackgroundSize::Background32x32),
+                Box::new(|_| (&tileset, TileSetting::new(0, false, false, 0))),
+            );
+            parallax.add_layer(map, factor);
+        }
+
+        let mut camera: Vector2D<Num<i32, 8>> = (0, 0).into();
+        parallax.init(&mut vram, camera, &mut || {});
+
+        // a step which isn't a whole number of pixels, so rounding errors would build up if the
+        // layers kept track of how far they had moved
+        let step: Vector2D<Num<i32, 8>> = (num!(1.75), num!(-0.625)).into();
+        for _ in 0..1000 {
+            camera += step;
+            while parallax.set_camera(&mut vram, camera) == PartialUpdateStatus::Continue {}
+
+            let front = parallax.layer_mut(0).position();
+            assert_eq!(front, camera.floor());
+            assert_eq!(parallax.layer_mut(1).position(), (camera / 2).floor());
+            assert_eq!(parallax.layer_mut(2).position(), (camera / 4).floor());
+        }
+
+        parallax.clear(&mut vram);
+        parallax.commit(&mut vram);
+    }
+
+    #[test_case]
+    fn layer_positions_work_for_far_away_cameras(_gba: &mut crate::Gba) {
+        let far: Vector2D<Num<i32, 8>> = (Num::from_raw(i32::MAX), Num::from_raw(i32::MIN)).into();
+
+        assert_eq!(layer_position(far, num!(1.)), far.floor());
+        assert_eq!(
+            layer_position(far, num!(0.5)),
+            (i32::MAX / 512, i32::MIN / 512).into()
+        );
+        assert_eq!(
+            layer_position(far, num!(-0.25)),
+            (-(1 << 21), 1 << 21).into()
+        );
+    }
+}
@@ -1,11 +1,13 @@
 use core::cell::RefCell;
 
+use alloc::vec::Vec;
+
 use crate::{
     bitarray::Bitarray,
     display::{set_graphics_mode, DisplayMode, Priority},
 };
 
-use super::{MapLoan, RegularBackgroundSize, RegularMap};
+use super::{MapLoan, RegularBackgroundSize, RegularMap, FIRST_MAP_SCREENBLOCK, MAP_SCREENBLOCKS};
 
 pub struct Tiled0 {
     regular: RefCell<Bitarray<1>>,
@@ -22,44 +24,160 @@ impl Tiled0 {
         }
     }
 
-    pub fn background(
+    /// Gets a background of the given size, or `None` if all 4 backgrounds are in use or there
+    /// isn't enough space left for a map of this size.
+    pub fn try_background(
+        &self,
+        priority: Priority,
+        size: RegularBackgroundSize,
+    ) -> Option<MapLoan<'_, RegularMap>> {
+        let new_background = self
+            .regular
+            .borrow()
+            .first_zero()
+            .filter(|&background| background < 4)?;
+
+        self.background_at(new_background, priority, size)
+    }
+
+    /// Gets a specific hardware background, which is useful when other effects need to refer to
+    /// that background by index. Returns `None` if that background is already in use or there
+    /// isn't enough space left for a map of this size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` isn't one of the 4 backgrounds, 0 to 3.
+    pub fn background_at(
+        &self,
+        index: usize,
+        priority: Priority,
+        size: RegularBackgroundSize,
+    ) -> Option<MapLoan<'_, RegularMap>> {
+        assert!(
+            index < 4,
+            "background {} doesn't exist, there are only 4",
+            index
+        );
+
+        self.allocate_background(index, priority, size, false)
+    }
+
+    /// Gets a background which uses twice the usual map space, so that changes are drawn to a
+    /// copy of the map which isn't being displayed and then all appear at once when the map is
+    /// [committed](RegularMap::commit). This is useful for large changes which take more than a
+    /// frame to make, such as drawing a full screen menu, since the player never sees them
+    /// partly done.
+    ///
+    /// # Panics
+    ///
+    /// Panics if all 4 backgrounds are in use or there isn't enough space left for two maps of
+    /// this size.
+    pub fn background_double_buffered(
         &self,
         priority: Priority,
         size: RegularBackgroundSize,
     ) -> MapLoan<'_, RegularMap> {
+        let new_background = self
+            .regular
+            .borrow()
+            .first_zero()
+            .filter(|&background| background < 4)
+            .expect("can only have 4 active backgrounds, and all of them are in use");
+
+        match self.allocate_background(new_background, priority, size, true) {
+            Some(background) => background,
+            None => panic!(
+                "not enough free map space for a double buffered {:?} background",
+                size
+            ),
+        }
+    }
+
+    fn allocate_background(
+        &self,
+        index: usize,
+        priority: Priority,
+        size: RegularBackgroundSize,
+        double_buffered: bool,
+    ) -> Option<MapLoan<'_, RegularMap>> {
         let mut regular = self.regular.borrow_mut();
-        let new_background = regular.first_zero().unwrap();
-        if new_background >= 4 {
-            panic!("can only have 4 active backgrounds");
+        if regular.get(index) == Some(true) {
+            return None;
         }
+        let new_background = index;
 
-        let num_screenblocks = size.num_screen_blocks();
+        let map_screenblocks = size.num_screen_blocks();
+        let num_screenblocks = if double_buffered {
+            map_screenblocks * 2
+        } else {
+            map_screenblocks
+        };
         let mut screenblocks = self.screenblocks.borrow_mut();
 
-        let screenblock = find_screenblock_gap(&screenblocks, num_screenblocks);
+        let screenblock = find_screenblock_gap(&screenblocks, num_screenblocks)?;
         for id in screenblock..(screenblock + num_screenblocks) {
             screenblocks.set(id, true);
         }
 
-        let bg = RegularMap::new(new_background as u8, screenblock as u8 + 16, priority, size);
+        let mut bg = RegularMap::new(
+            new_background as u8,
+            (screenblock + FIRST_MAP_SCREENBLOCK) as u8,
+            priority,
+            size,
+        );
+
+        if double_buffered {
+            bg = bg.double_buffered((screenblock + map_screenblocks + FIRST_MAP_SCREENBLOCK) as u8);
+        }
 
         regular.set(new_background, true);
 
-        MapLoan::new(
+        Some(MapLoan::new(
             bg,
             new_background as u8,
             screenblock as u8,
             num_screenblocks as u8,
             &self.regular,
             &self.screenblocks,
-        )
+        ))
+    }
+
+    /// Gets a background of the given size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if all 4 backgrounds are in use or there isn't enough space left for a map of this
+    /// size. Use [`Tiled0::try_background`] to handle this instead.
+    pub fn background(
+        &self,
+        priority: Priority,
+        size: RegularBackgroundSize,
+    ) -> MapLoan<'_, RegularMap> {
+        match self.try_background(priority, size) {
+            Some(background) => background,
+            None => {
+                let regular = self.regular.borrow();
+                let in_use: Vec<usize> = (0..4)
+                    .filter(|&background| regular.get(background) == Some(true))
+                    .collect();
+
+                if in_use.len() == 4 {
+                    panic!("can only have 4 active backgrounds, and all of them are in use");
+                } else {
+                    panic!(
+                        "not enough free map space for a {:?} background, backgrounds {:?} are in use",
+                        size, in_use
+                    );
+                }
+            }
+        }
     }
 }
 
-fn find_screenblock_gap(screenblocks: &Bitarray<1>, gap: usize) -> usize {
+fn find_screenblock_gap(screenblocks: &Bitarray<1>, gap: usize) -> Option<usize> {
     let mut candidate = 0;
 
-    'outer: while candidate < 16 - gap {
+    'outer: while candidate <= MAP_SCREENBLOCKS - gap {
         let starting_point = candidate;
         for attempt in starting_point..(starting_point + gap) {
             if screenblocks.get(attempt) == Some(true) {
@@ -68,11 +186,62 @@ fn find_screenblock_gap(screenblocks: &Bitarray<1>, gap: usize) -> usize {
             }
         }
 
-        return candidate;
+        return Some(candidate);
     }
 
-    panic!(
-        "Failed to find screenblock gap of at least {} elements",
-        gap
-    );
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_case]
+    fn try_background_runs_out_gracefully(gba: &mut crate::Gba) {
+        let (gfx, _vram) = gba.display.video.tiled0();
+
+        // 4 of the largest backgrounds exactly fill the space for maps
+        let backgrounds: Vec<_> = (0..4)
+            .map(|_| gfx.try_background(Priority::P0, RegularBackgroundSize::Background64x64))
+            .collect();
+        assert!(backgrounds.iter().all(Option::is_some));
+
+        assert!(gfx
+            .try_background(Priority::P0, RegularBackgroundSize::Background32x32)
+            .is_none());
+
+        drop(backgrounds);
+
+        let first = gfx.try_background(Priority::P0, RegularBackgroundSize::Background64x64);
+        assert!(first.is_some());
+    }
+
+    #[test_case]
+    fn specific_backgrounds_can_be_requested(gba: &mut crate::Gba) {
+        let (gfx, _vram) = gba.display.video.tiled0();
+
+        let second = gfx
+            .background_at(2, Priority::P1, RegularBackgroundSize::Background32x32)
+            .unwrap();
+        assert_eq!(second.background_id(), 2);
+        assert!(gfx
+            .background_at(2, Priority::P0, RegularBackgroundSize::Background32x32)
+            .is_none());
+
+        // first free allocation skips over the background which was asked for
+        let others: Vec<_> = (0..3)
+            .map(|_| gfx.background(Priority::P0, RegularBackgroundSize::Background32x32))
+            .collect();
+        let ids: Vec<_> = others.iter().map(|map| map.background_id()).collect();
+        assert_eq!(ids, [0, 1, 3]);
+
+        let mut screenblocks: Vec<_> = others
+            .iter()
+            .chain(core::iter::once(&second))
+            .map(|map| map.screenblock())
+            .collect();
+        screenblocks.sort_unstable();
+        screenblocks.dedup();
+        assert_eq!(screenblocks.len(), 4);
+    }
 }
@@ -1,16 +1,30 @@
-use core::{alloc::Layout, ptr::NonNull};
+use core::{
+    alloc::Layout,
+    hash::{Hash, Hasher},
+    ops::Range,
+    ptr::NonNull,
+};
 
 use alloc::{slice, vec::Vec};
 
 use crate::{
-    agb_alloc::{block_allocator::BlockAllocator, bump_allocator::StartEnd},
-    display::palette16,
-    dma::dma_copy16,
+    agb_alloc::{block_allocator::BlockAllocator, bump_allocator::StartEnd, AllocatorStats},
+    display::{
+        palette16,
+        palette_manager::{self, PaletteKind},
+    },
+    dma::{dma_copy16, dma_copy32},
+    fixnum::Num,
     hash_map::HashMap,
+    mem,
     memory_mapped::MemoryMapped1DArray,
 };
 
-const TILE_RAM_START: usize = 0x0600_0000;
+use rustc_hash::FxHasher;
+
+use super::{TileSetting, TILE_DATA_END, VRAM_START};
+
+const TILE_RAM_START: usize = VRAM_START;
 
 const PALETTE_BACKGROUND: MemoryMapped1DArray<u16, 256> =
     unsafe { MemoryMapped1DArray::new(0x0500_0000) };
@@ -18,10 +32,47 @@ const PALETTE_BACKGROUND: MemoryMapped1DArray<u16, 256> =
 static TILE_ALLOCATOR: BlockAllocator = unsafe {
     BlockAllocator::new(StartEnd {
         start: || TILE_RAM_START + 8 * 8,
-        end: || TILE_RAM_START + 0x8000,
+        end: || TILE_DATA_END,
     })
 };
 
+pub(crate) fn tile_vram_stats() -> AllocatorStats {
+    TILE_ALLOCATOR.stats()
+}
+
+const PALETTE_BANKS: usize = 16;
+
+/// A background palette bank allocated by [`VRamManager::palette`]. The bank stays allocated to
+/// its palette until every clone of this has been dropped. Use [`PaletteBank::bank`] or
+/// [`TileSetting::with_palette`](super::TileSetting::with_palette) to refer to it when setting tiles.
+pub struct PaletteBank {
+    bank: u8,
+}
+
+impl PaletteBank {
+    /// The index of the palette bank, between 0 and 15.
+    #[must_use]
+    pub fn bank(&self) -> u8 {
+        self.bank
+    }
+}
+
+impl Clone for PaletteBank {
+    fn clone(&self) -> Self {
+        palette_manager::add_reference(PaletteKind::Background, self.bank);
+
+        Self { bank: self.bank }
+    }
+}
+
+impl Drop for PaletteBank {
+    fn drop(&mut self) {
+        palette_manager::release(PaletteKind::Background, self.bank);
+    }
+}
+
+const TILE_SLOTS: usize = (TILE_DATA_END - TILE_RAM_START - 8 * 8) / (8 * 8 / 2);
+
 const TILE_LAYOUT: Layout = unsafe { Layout::from_size_align_unchecked(8 * 8 / 2, 8 * 8 / 2) };
 
 #[derive(Clone, Copy, Debug)]
@@ -52,6 +103,40 @@ impl<'a> TileSet<'a> {
     fn reference(&self) -> NonNull<[u8]> {
         self.tiles.into()
     }
+
+    pub(crate) fn is_at(&self, reference: NonNull<[u8]>) -> bool {
+        self.tiles.as_ptr() == reference.as_ptr().cast::<u8>()
+            && self.tiles.len() == reference.len()
+    }
+
+    pub(crate) fn same_tiles(&self, other: &TileSet<'_>) -> bool {
+        self.tiles.as_ptr() == other.tiles.as_ptr() && self.tiles.len() == other.tiles.len()
+    }
+
+    pub(crate) fn tile_data_address(&self) -> usize {
+        self.tiles.as_ptr() as usize
+    }
+
+    pub(crate) fn tile_count(&self) -> u16 {
+        (self.tiles.len() / self.format.tile_size()) as u16
+    }
+
+    pub(crate) fn tile_data(&self, tile: u16) -> &[u8] {
+        let tile_count = self.tile_count();
+        assert!(
+            tile < tile_count,
+            "tile {} is past the end of the tileset at {:p}, which has {} tiles so the largest valid tile is {}",
+            tile,
+            self.tiles.as_ptr(),
+            tile_count,
+            // an empty tileset has no valid tiles
+            i32::from(tile_count) - 1
+        );
+
+        let tile_size = self.format.tile_size();
+        let tile_offset = (tile as usize) * tile_size;
+        &self.tiles[tile_offset..(tile_offset + tile_size)]
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -67,9 +152,137 @@ impl TileIndex {
     }
 }
 
+/// A whole tileset copied into consecutive tile slots by [`VRamManager::preload_tileset`]. Its
+/// tiles can be set using [`RegularMap::set_preloaded_tile`](super::RegularMap::set_preloaded_tile)
+/// without looking anything up, since where each tile is in video ram can be worked out from its
+/// tile number.
+///
+/// The tiles stay in video ram until this is given back to
+/// [`VRamManager::remove_preloaded_tileset`] and no maps use any of them any more.
+#[must_use]
+#[derive(Debug)]
+pub struct PreloadedTileset {
+    first: u16,
+    count: u16,
+    // what the tiles were allocated with, since they are freed together
+    layout: Layout,
+}
+
+impl PreloadedTileset {
+    /// Where `tile` of the tileset is in video ram.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tile` is past the end of the tileset.
+    #[must_use]
+    pub fn tile_index(&self, tile: u16) -> TileIndex {
+        assert!(
+            tile < self.count,
+            "tile {} is past the end of the preloaded tileset of {} tiles",
+            tile,
+            self.count
+        );
+
+        TileIndex(self.first + tile)
+    }
+
+    /// The number of tiles in the tileset
+    #[must_use]
+    pub fn len(&self) -> u16 {
+        self.count
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+}
+
+/// A tile which has been loaded into video ram by [`VRamManager::resolve`]. Setting it using
+/// [`RegularMap::set_tile_resolved`](super::RegularMap::set_tile_resolved) skips looking the
+/// tile up, so tiles which are set often, such as those of a scrolling level, can be resolved
+/// once up front.
+///
+/// The tile stays in video ram until this is given back to [`VRamManager::release_resolved`]
+/// and no maps use it any more.
+#[must_use]
+#[derive(Debug)]
+pub struct ResolvedTile {
+    index: TileIndex,
+    flip: TileFlip,
+}
+
+impl ResolvedTile {
+    /// Where the tile is in video ram. This may be shared with other tiles with the same
+    /// content, including mirror images of it.
+    #[must_use]
+    pub fn tile_index(&self) -> TileIndex {
+        self.index
+    }
+
+    pub(crate) fn flip(&self) -> TileFlip {
+        self.flip
+    }
+}
+
+/// The flips which need to be applied to a tile in video ram to display the tile which was asked
+/// for, since tiles which are mirror images of each other share a slot. These are the horizontal
+/// and vertical flip bits of a map entry.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct TileFlip(u16);
+
+impl TileFlip {
+    pub(crate) const NONE: Self = Self(0);
+    const HORIZONTAL: Self = Self(1 << 10);
+    const VERTICAL: Self = Self(1 << 11);
+    const BOTH: Self = Self((1 << 10) | (1 << 11));
+
+    pub(crate) const fn bits(self) -> u16 {
+        self.0
+    }
+
+    // Writes `tile_data` with this flip applied into `flipped`. In 4bpp tiles each row is 4 bytes
+    // and the left pixel of each pair is in the low nibble.
+    fn apply(self, tile_data: &[u8], flipped: &mut [u8; 8 * 8 / 2]) {
+        let horizontal = self.0 & Self::HORIZONTAL.0 != 0;
+        let vertical = self.0 & Self::VERTICAL.0 != 0;
+
+        for y in 0..8 {
+            let source_y = if vertical { 7 - y } else { y };
+
+            for x in 0..4 {
+                flipped[y * 4 + x] = if horizontal {
+                    tile_data[source_y * 4 + 3 - x].rotate_left(4)
+                } else {
+                    tile_data[source_y * 4 + x]
+                };
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct TileReference(NonNull<u32>);
 
+impl TileReference {
+    fn tile_data(self) -> &'static [u8] {
+        unsafe { slice::from_raw_parts(self.0.as_ptr().cast(), TILE_LAYOUT.size()) }
+    }
+}
+
+/// A hash of the pixel data of a tile, used to find tiles with identical content
+/// which are already in video ram regardless of which tileset they came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct TileContentHash(u32);
+
+impl TileContentHash {
+    fn new(tile_data: &[u8]) -> Self {
+        let mut hasher = FxHasher::default();
+        tile_data.hash(&mut hasher);
+        Self(hasher.finish() as u32)
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Hash)]
 struct TileInTileSetReference {
     tileset: NonNull<[u8]>,
@@ -88,7 +301,13 @@ impl TileInTileSetReference {
 #[derive(Clone, Default)]
 struct TileReferenceCount {
     reference_count: u16,
+    // one of the tiles in tilesets which use this slot, used to describe the slot
     tile_in_tile_set: Option<TileInTileSetReference>,
+    // the number of tiles in tilesets which are looked up to this slot, which is more than one
+    // when tiles with the same content share it
+    sources: u16,
+    // set if every tileset this was loaded from was forcibly removed while it was still in use
+    orphaned: bool,
 }
 
 impl TileReferenceCount {
@@ -96,9 +315,15 @@ impl TileReferenceCount {
         Self {
             reference_count: 1,
             tile_in_tile_set: Some(tile_in_tile_set),
+            sources: 1,
+            orphaned: false,
         }
     }
 
+    fn is_shared(&self) -> bool {
+        self.sources > 1
+    }
+
     fn increment_reference_count(&mut self) {
         self.reference_count += 1;
     }
@@ -116,6 +341,8 @@ impl TileReferenceCount {
     fn clear(&mut self) {
         self.reference_count = 0;
         self.tile_in_tile_set = None;
+        self.sources = 0;
+        self.orphaned = false;
     }
 
     fn current_count(&self) -> u16 {
@@ -163,22 +390,329 @@ impl DynamicTile<'_> {
     }
 }
 
+/// A snapshot of how background tile video ram is being used, returned by [`VRamManager::stats`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VRamStats {
+    /// Number of tile slots which are currently referenced by at least one tile
+    pub used_tile_slots: usize,
+    /// Number of tile slots which are no longer referenced but won't be freed until the next commit
+    pub tile_slots_awaiting_gc: usize,
+    /// Number of tile slots which are available for new tiles
+    pub free_tile_slots: usize,
+    /// Number of distinct tilesets which have at least one tile in video ram
+    pub tile_sets: usize,
+    /// Number of bytes of video ram taken up by used and awaiting collection tile slots
+    pub used_bytes: usize,
+}
+
+/// Returned by [`VRamManager::remove_tileset`] when tiles from the tileset are still in use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TilesStillInUse {
+    /// Number of tiles from the tileset which are still used by a map
+    pub count: usize,
+}
+
+/// What is stored in an occupied background tile slot, returned by [`VRamManager::tile_slots`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TileSlot {
+    /// The index of the slot in video ram, which is what maps refer to the tile by
+    pub index: u16,
+    /// The address of the tile data of a tileset the tile was loaded from. Tiles with the same
+    /// content from several tilesets share a slot, in which case this is one of them.
+    pub tileset_address: usize,
+    /// The tile within that tileset
+    pub tile: u16,
+    /// The number of places currently using the tile. Slots with a count of 0 are waiting to be
+    /// freed.
+    pub reference_count: u16,
+    /// Whether the tileset was removed using [`VRamManager::force_remove_tileset`] while this
+    /// tile was still in use
+    pub orphaned: bool,
+}
+
+// The palette banks given to a tileset by VRamManager::set_tileset_palettes, and which of them
+// each tile uses
+struct TilesetPalettes {
+    banks: Vec<PaletteBank>,
+    assignments: &'static [u8],
+}
+
 pub struct VRamManager {
-    tile_set_to_vram: HashMap<TileInTileSetReference, TileReference>,
+    // where each tile is in video ram, and the flip to apply to the slot to show it
+    tile_set_to_vram: HashMap<TileInTileSetReference, (TileReference, TileFlip)>,
+    content_to_vram: HashMap<TileContentHash, TileReference>,
     reference_counts: Vec<TileReferenceCount>,
+    tileset_palettes: HashMap<NonNull<[u8]>, TilesetPalettes>,
+    // what was last copied into each background palette bank, so that cycling colours always
+    // starts from the original palette
+    background_palettes: [[u16; 16]; PALETTE_BANKS],
 
     indices_to_gc: Vec<TileIndex>,
+    // the runs of tile slots allocated by preload_tileset, which are freed in one go once none of
+    // their tiles are used
+    preloaded_runs: Vec<PreloadedRun>,
+}
+
+struct PreloadedRun {
+    slots: Range<usize>,
+    layout: Layout,
+    // set once the PreloadedTileset has been given back
+    released: bool,
 }
 
 impl VRamManager {
     pub(crate) fn new() -> Self {
-        let tile_set_to_vram: HashMap<TileInTileSetReference, TileReference> =
+        let tile_set_to_vram: HashMap<TileInTileSetReference, (TileReference, TileFlip)> =
             HashMap::with_capacity(256);
 
         Self {
             tile_set_to_vram,
+            content_to_vram: HashMap::with_capacity(256),
             reference_counts: Default::default(),
+            tileset_palettes: Default::default(),
+            background_palettes: [[0; 16]; PALETTE_BANKS],
             indices_to_gc: Default::default(),
+            preloaded_runs: Vec::new(),
+        }
+    }
+
+    /// Makes sure that `additional` more distinct tiles can be added without the maps tracking
+    /// them having to grow, so that a loading screen can do the allocation rather than it
+    /// happening part way through a level.
+    pub fn reserve_tiles(&mut self, additional: usize) {
+        let additional = additional.min(TILE_SLOTS);
+
+        self.tile_set_to_vram.reserve(additional);
+        self.content_to_vram.reserve(additional);
+        self.reference_counts.reserve(additional);
+    }
+
+    /// Returns a summary of how many background tile slots are in use.
+    #[must_use]
+    pub fn stats(&self) -> VRamStats {
+        let used_tile_slots = self
+            .reference_counts
+            .iter()
+            .filter(|count| count.current_count() > 0)
+            .count();
+        let tile_slots_awaiting_gc = self
+            .reference_counts
+            .iter()
+            .filter(|count| count.current_count() == 0 && count.tile_in_tile_set.is_some())
+            .count();
+
+        let mut tile_sets: Vec<NonNull<[u8]>> = self
+            .tile_set_to_vram
+            .keys()
+            .map(|tile_in_tile_set| tile_in_tile_set.tileset)
+            .collect();
+        tile_sets.sort_unstable_by_key(|tileset| tileset.as_ptr() as *const u8 as usize);
+        tile_sets.dedup();
+
+        let occupied_slots = used_tile_slots + tile_slots_awaiting_gc;
+
+        VRamStats {
+            used_tile_slots,
+            tile_slots_awaiting_gc,
+            free_tile_slots: TILE_SLOTS - occupied_slots,
+            tile_sets: tile_sets.len(),
+            used_bytes: occupied_slots * TILE_LAYOUT.size(),
+        }
+    }
+
+    /// Iterates over every occupied tile slot, giving its index in video ram along with the number
+    /// of times it is currently referenced. Slots with a count of 0 are waiting to be freed.
+    pub fn tile_reference_counts(&self) -> impl Iterator<Item = (u16, u16)> + '_ {
+        self.reference_counts
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| count.tile_in_tile_set.is_some())
+            .map(|(index, count)| (index as u16, count.current_count()))
+    }
+
+    /// Iterates over every occupied tile slot, giving which tile it holds and how many times it is
+    /// referenced. Useful for tracking down which tileset is leaking tiles.
+    pub fn tile_slots(&self) -> impl Iterator<Item = TileSlot> + '_ {
+        self.reference_counts
+            .iter()
+            .enumerate()
+            .filter_map(|(index, count)| {
+                let tile_in_tile_set = count.tile_in_tile_set.as_ref()?;
+
+                Some(TileSlot {
+                    index: index as u16,
+                    tileset_address: tile_in_tile_set.tileset.as_ptr() as *const u8 as usize,
+                    tile: tile_in_tile_set.tile,
+                    reference_count: count.current_count(),
+                    orphaned: count.orphaned,
+                })
+            })
+    }
+
+    /// Prints every tile slot which is referenced more than `threshold` times, along with any
+    /// slots whose tileset has been removed while they were still in use, to the mgba debug
+    /// output. Does nothing outside of mgba.
+    pub fn debug_dump(&self, threshold: u16) {
+        let stats = self.stats();
+        crate::println!(
+            "{} background tile slots in use, {} awaiting gc, {} free",
+            stats.used_tile_slots,
+            stats.tile_slots_awaiting_gc,
+            stats.free_tile_slots
+        );
+
+        for slot in self
+            .tile_slots()
+            .filter(|slot| slot.reference_count > threshold || slot.orphaned)
+        {
+            crate::println!(
+                "slot {}: tile {} of tileset {:#010x}, {} references{}",
+                slot.index,
+                slot.tile,
+                slot.tileset_address,
+                slot.reference_count,
+                if slot.orphaned { ", orphaned" } else { "" }
+            );
+        }
+    }
+
+    /// Returns how many tiles of `tile_set` are currently in video ram. Tiles which share their slot
+    /// with identical tiles from other tilesets are counted for every tileset using the slot.
+    #[must_use]
+    pub fn resident_tiles(&self, tile_set: &TileSet<'_>) -> usize {
+        let reference = tile_set.reference();
+        self.tile_set_to_vram
+            .keys()
+            .filter(|tile_in_tile_set| {
+                tile_in_tile_set.tileset.cast::<u8>() == reference.cast::<u8>()
+            })
+            .count()
+    }
+
+    /// The number of tiles in `tile_set`, for example to check that a map only uses tiles which
+    /// exist when it is loaded. Tile numbers from 0 up to but not including this are valid.
+    #[must_use]
+    pub fn tileset_len(&self, tile_set: &TileSet<'_>) -> u16 {
+        tile_set.tile_count()
+    }
+
+    /// Forgets about `tile_set`, freeing any of its tiles which are waiting to be collected. This
+    /// should be called before the memory holding a tileset which isn't `'static` is freed or
+    /// reused, as tiles are looked up by the address of their tileset and a different tileset in
+    /// the same memory would otherwise be given the old tiles.
+    ///
+    /// Returns an error and does nothing if any of the tileset's tiles are still used by a map.
+    ///
+    /// # Panics
+    ///
+    /// With the `vram_leak_check` feature enabled, this panics listing the tiles still in use
+    /// rather than returning an error.
+    pub fn remove_tileset(&mut self, tile_set: &TileSet<'_>) -> Result<(), TilesStillInUse> {
+        // a slot which is shared with a tile from another tileset stays valid for that tileset,
+        // so only slots which this tileset is the only source of count as still in use
+        let mut slots = self.tileset_slots(tile_set);
+        slots.sort_unstable();
+
+        let mut count = 0;
+        for tiles in slots.chunk_by(|a, b| a == b) {
+            let reference_count = &self.reference_counts[tiles[0]];
+            if reference_count.current_count() > 0
+                && usize::from(reference_count.sources) == tiles.len()
+            {
+                count += tiles.len();
+            }
+        }
+
+        if count > 0 {
+            #[cfg(feature = "vram_leak_check")]
+            self.report_tiles_still_in_use(tile_set, count);
+
+            return Err(TilesStillInUse { count });
+        }
+
+        self.gc();
+        self.force_remove_tileset(tile_set);
+
+        Ok(())
+    }
+
+    // The slot of each tile of `tile_set` which is in video ram
+    fn tileset_slots(&self, tile_set: &TileSet<'_>) -> Vec<usize> {
+        let reference = tile_set.reference();
+        self.tile_set_to_vram
+            .iter()
+            .filter(|(tile_in_tile_set, _)| {
+                tile_in_tile_set.tileset.cast::<u8>() == reference.cast::<u8>()
+            })
+            .map(|(_, &(tile_reference, _))| Self::index_from_reference(tile_reference))
+            .collect()
+    }
+
+    #[cfg(feature = "vram_leak_check")]
+    fn report_tiles_still_in_use(&self, tile_set: &TileSet<'_>, count: usize) {
+        let tileset_address = tile_set.reference().as_ptr() as *const u8 as usize;
+        for slot in self
+            .tile_slots()
+            .filter(|slot| slot.tileset_address == tileset_address && slot.reference_count > 0)
+        {
+            crate::println!("{:?}", slot);
+        }
+
+        panic!(
+            "removing tileset {:#010x} which still has {} tiles in use",
+            tileset_address, count
+        );
+    }
+
+    /// Forgets about `tile_set` even if some of its tiles are still used by maps. Those tiles stay
+    /// in video ram until they are no longer used, but loading a tile from a different tileset at
+    /// the same address will never reuse them.
+    pub fn force_remove_tileset(&mut self, tile_set: &TileSet<'_>) {
+        let reference = tile_set.reference();
+        let tiles_in_tile_set: Vec<_> = self
+            .tile_set_to_vram
+            .keys()
+            .filter(|tile_in_tile_set| {
+                tile_in_tile_set.tileset.cast::<u8>() == reference.cast::<u8>()
+            })
+            .cloned()
+            .collect();
+
+        let mut needs_new_description = Vec::new();
+        for tile_in_tile_set in &tiles_in_tile_set {
+            if let Some((tile_reference, _)) = self.tile_set_to_vram.remove(tile_in_tile_set) {
+                let index = Self::index_from_reference(tile_reference);
+                let count = &mut self.reference_counts[index];
+                count.sources -= 1;
+
+                if count.sources == 0 {
+                    if count.current_count() > 0 {
+                        count.orphaned = true;
+                    }
+                } else if count.tile_in_tile_set.as_ref() == Some(tile_in_tile_set) {
+                    needs_new_description.push(index);
+                }
+            }
+        }
+
+        // slots still used by other tilesets are described by one of those instead
+        for (tile_in_tile_set, &(tile_reference, _)) in self.tile_set_to_vram.iter() {
+            let index = Self::index_from_reference(tile_reference);
+            if let Some(position) = needs_new_description.iter().position(|&i| i == index) {
+                self.reference_counts[index].tile_in_tile_set = Some(tile_in_tile_set.clone());
+                needs_new_description.swap_remove(position);
+            }
+        }
+    }
+
+    fn allocate_tile_slot() -> NonNull<u32> {
+        Self::try_allocate_tile_slot().unwrap_or_else(|OutOfVideoRam| out_of_video_ram())
+    }
+
+    fn try_allocate_tile_slot() -> Result<NonNull<u32>, OutOfVideoRam> {
+        match unsafe { TILE_ALLOCATOR.alloc(TILE_LAYOUT) } {
+            Some(reference) => Ok(reference.cast()),
+            None => Err(OutOfVideoRam),
         }
     }
 
@@ -192,11 +726,111 @@ impl VRamManager {
         TileReference(NonNull::new(ptr as *mut _).unwrap())
     }
 
+    /// Copies every tile of `tile_set` into consecutive tile slots at once. This is faster than
+    /// loading the tiles one at a time as they are set for tilesets which fit in video ram, such
+    /// as the tileset of a level which doesn't change. Setting tiles from `tile_set` using
+    /// [`RegularMap::set_tile`](super::RegularMap::set_tile) also uses the preloaded tiles.
+    ///
+    /// Unlike tiles which are loaded as they are needed, preloaded tiles aren't shared with
+    /// identical tiles from other tilesets.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the tileset is empty, or there isn't a long enough run of free tile slots for it.
+    pub fn preload_tileset(&mut self, tile_set: &TileSet<'_>) -> PreloadedTileset {
+        let count = tile_set.tile_count();
+        assert!(count > 0, "can't preload an empty tileset");
+
+        let layout =
+            Layout::from_size_align(count as usize * TILE_LAYOUT.size(), TILE_LAYOUT.align())
+                .unwrap();
+
+        let start = match unsafe { TILE_ALLOCATOR.alloc(layout) } {
+            Some(start) => start,
+            None => panic!(
+                "Ran out of video ram for background tiles, there is no run of {} free tile slots to preload the tileset into",
+                count
+            ),
+        };
+
+        copy_tiles(
+            &tile_set.tiles[..count as usize * TILE_LAYOUT.size()],
+            start.as_ptr().cast(),
+        );
+
+        let first = Self::index_from_reference(TileReference(start.cast()));
+        self.reference_counts.resize(
+            self.reference_counts.len().max(first + count as usize),
+            Default::default(),
+        );
+
+        for tile in 0..count {
+            let index = first + tile as usize;
+            let tile_in_tile_set = TileInTileSetReference::new(tile_set, tile);
+
+            self.tile_set_to_vram.insert(
+                tile_in_tile_set.clone(),
+                (
+                    Self::reference_from_index(TileIndex::new(index)),
+                    TileFlip::NONE,
+                ),
+            );
+            // the reference held by the PreloadedTileset
+            self.reference_counts[index] = TileReferenceCount::new(tile_in_tile_set);
+        }
+
+        self.preloaded_runs.push(PreloadedRun {
+            slots: first..first + count as usize,
+            layout,
+            released: false,
+        });
+
+        PreloadedTileset {
+            first: first as u16,
+            count,
+            layout,
+        }
+    }
+
+    /// Lets go of the tiles of a tileset loaded by [`preload_tileset`](VRamManager::preload_tileset).
+    /// They were allocated together, so they are all freed together once no maps use any of them.
+    // This takes ownership of the preloaded tileset because it will no longer be valid after this call
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn remove_preloaded_tileset(&mut self, preloaded: PreloadedTileset) {
+        let first = preloaded.first as usize;
+        let run = self
+            .preloaded_runs
+            .iter_mut()
+            .find(|run| run.slots.start == first && !run.released)
+            .expect("preloaded tileset was not preloaded by this manager");
+        debug_assert_eq!(run.layout, preloaded.layout);
+        run.released = true;
+
+        for tile in 0..preloaded.count {
+            self.remove_tile(preloaded.tile_index(tile));
+        }
+    }
+
+    /// Loads `tile` from `tile_set` into video ram in the same way as
+    /// [`RegularMap::set_tile`](super::RegularMap::set_tile), and keeps it there until the
+    /// returned tile is given back to [`release_resolved`](VRamManager::release_resolved).
+    pub fn resolve(&mut self, tile_set: &TileSet<'_>, tile: u16) -> ResolvedTile {
+        let (index, flip) = self.add_tile(tile_set, tile);
+        ResolvedTile { index, flip }
+    }
+
+    /// Lets go of a tile loaded by [`resolve`](VRamManager::resolve). It is freed once no maps
+    /// use it.
+    // This takes ownership of the resolved tile because it will no longer be valid after this call
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn release_resolved(&mut self, resolved: ResolvedTile) {
+        self.remove_tile(resolved.index);
+    }
+
     #[must_use]
     pub fn new_dynamic_tile<'a>(&mut self) -> DynamicTile<'a> {
         let tile_format = TileFormat::FourBpp;
-        let new_reference: NonNull<u32> =
-            unsafe { TILE_ALLOCATOR.alloc(TILE_LAYOUT) }.unwrap().cast();
+        let new_reference = Self::allocate_tile_slot();
         let tile_reference = TileReference(new_reference);
 
         let index = Self::index_from_reference(tile_reference);
@@ -209,7 +843,7 @@ impl VRamManager {
 
         self.tile_set_to_vram.insert(
             TileInTileSetReference::new(&tile_set, index as u16),
-            tile_reference,
+            (tile_reference, TileFlip::NONE),
         );
 
         self.reference_counts.resize(
@@ -242,19 +876,56 @@ impl VRamManager {
         self.remove_tile(TileIndex::new(tile_index));
     }
 
-    pub(crate) fn add_tile(&mut self, tile_set: &TileSet<'_>, tile: u16) -> TileIndex {
-        let reference = self
-            .tile_set_to_vram
-            .get(&TileInTileSetReference::new(tile_set, tile));
+    /// Adds a reference to the tile, loading it into video ram if needed. The tile may share a
+    /// slot with a mirror image of itself, in which case the returned flip must be applied to the
+    /// map entry using it.
+    pub(crate) fn add_tile(&mut self, tile_set: &TileSet<'_>, tile: u16) -> (TileIndex, TileFlip) {
+        self.try_add_tile(tile_set, tile)
+            .unwrap_or_else(|OutOfVideoRam| out_of_video_ram())
+    }
 
-        if let Some(reference) = reference {
-            let index = Self::index_from_reference(*reference);
+    /// Like [`add_tile`](VRamManager::add_tile), but returns an error rather than panicking if
+    /// every tile slot is in use.
+    pub(crate) fn try_add_tile(
+        &mut self,
+        tile_set: &TileSet<'_>,
+        tile: u16,
+    ) -> Result<(TileIndex, TileFlip), OutOfVideoRam> {
+        let tile_in_tile_set = TileInTileSetReference::new(tile_set, tile);
+
+        if let Some(&(reference, flip)) = self.tile_set_to_vram.get(&tile_in_tile_set) {
+            let index = Self::index_from_reference(reference);
             self.reference_counts[index].increment_reference_count();
-            return TileIndex::new(index);
+            return Ok((TileIndex::new(index), flip));
+        }
+
+        // A tile with the same content may already be in vram from a different tileset (or
+        // a different position in this one), in which case share that slot. Failing that, a
+        // mirror image of it can be shared, since the hardware can flip tiles for free.
+        let tile_data = tile_set.tile_data(tile);
+        let content_hash = TileContentHash::new(tile_data);
+
+        if let Some(index) =
+            self.share_tile_with_content(content_hash, tile_data, &tile_in_tile_set, TileFlip::NONE)
+        {
+            return Ok((index, TileFlip::NONE));
+        }
+
+        let mut flipped = [0; 8 * 8 / 2];
+        for &flip in &[TileFlip::HORIZONTAL, TileFlip::VERTICAL, TileFlip::BOTH] {
+            flip.apply(tile_data, &mut flipped);
+
+            if let Some(index) = self.share_tile_with_content(
+                TileContentHash::new(&flipped),
+                &flipped,
+                &tile_in_tile_set,
+                flip,
+            ) {
+                return Ok((index, flip));
+            }
         }
 
-        let new_reference: NonNull<u32> =
-            unsafe { TILE_ALLOCATOR.alloc(TILE_LAYOUT) }.unwrap().cast();
+        let new_reference = Self::try_allocate_tile_slot()?;
         let tile_reference = TileReference(new_reference);
 
         self.copy_tile_to_location(tile_set, tile, tile_reference);
@@ -262,17 +933,55 @@ impl VRamManager {
         let index = Self::index_from_reference(tile_reference);
 
         self.tile_set_to_vram
-            .insert(TileInTileSetReference::new(tile_set, tile), tile_reference);
+            .insert(tile_in_tile_set.clone(), (tile_reference, TileFlip::NONE));
+        // on a hash collision the first tile keeps the entry and this one just doesn't get shared
+        self.content_to_vram
+            .entry(content_hash)
+            .or_insert(tile_reference);
 
         self.reference_counts.resize(
             self.reference_counts.len().max(index + 1),
             Default::default(),
         );
 
-        self.reference_counts[index] =
-            TileReferenceCount::new(TileInTileSetReference::new(tile_set, tile));
+        self.reference_counts[index] = TileReferenceCount::new(tile_in_tile_set);
+
+        Ok((TileIndex::new(index), TileFlip::NONE))
+    }
+
+    // Shares the slot holding `tile_data` if there is one, recording that `tile_in_tile_set` is
+    // shown by the slot with `flip` applied
+    fn share_tile_with_content(
+        &mut self,
+        content_hash: TileContentHash,
+        tile_data: &[u8],
+        tile_in_tile_set: &TileInTileSetReference,
+        flip: TileFlip,
+    ) -> Option<TileIndex> {
+        let &reference = self.content_to_vram.get(&content_hash)?;
+        if reference.tile_data() != tile_data {
+            return None;
+        }
+
+        let index = Self::index_from_reference(reference);
+        let count = &mut self.reference_counts[index];
+        count.increment_reference_count();
+        if count.sources == 0 {
+            // every tileset which used this slot was forcibly removed, so this takes it over
+            count.tile_in_tile_set = Some(tile_in_tile_set.clone());
+            count.orphaned = false;
+        }
+        count.sources += 1;
+
+        self.tile_set_to_vram
+            .insert(tile_in_tile_set.clone(), (reference, flip));
+
+        Some(TileIndex::new(index))
+    }
 
-        TileIndex::new(index)
+    /// Adds another reference to a tile which is already in video ram.
+    pub(crate) fn add_tile_reference(&mut self, tile_index: TileIndex) {
+        self.reference_counts[tile_index.index() as usize].increment_reference_count();
     }
 
     pub(crate) fn remove_tile(&mut self, tile_index: TileIndex) {
@@ -288,82 +997,1171 @@ impl VRamManager {
     }
 
     pub(crate) fn gc(&mut self) {
-        for tile_index in self.indices_to_gc.drain(..) {
+        // taken out so the sources of each slot can be forgotten, and put back to keep its space
+        let mut indices_to_gc = core::mem::take(&mut self.indices_to_gc);
+        for tile_index in indices_to_gc.drain(..) {
             let index = tile_index.index() as usize;
             if self.reference_counts[index].current_count() > 0 {
                 continue; // it has since been added back
             }
 
+            if self
+                .preloaded_runs
+                .iter()
+                .any(|run| run.slots.contains(&index))
+            {
+                continue; // freed along with the rest of its run
+            }
+
+            self.forget_slot(index);
+
             let tile_reference = Self::reference_from_index(tile_index);
             unsafe {
                 TILE_ALLOCATOR.dealloc_no_normalise(tile_reference.0.cast().as_ptr(), TILE_LAYOUT);
             }
+        }
+        self.indices_to_gc = indices_to_gc;
 
-            let tile_ref = self.reference_counts[index]
-                .tile_in_tile_set
-                .as_ref()
-                .unwrap();
+        self.gc_preloaded_runs();
+    }
+
+    // Frees each run of preloaded tiles which has been given back and none of whose tiles are used
+    fn gc_preloaded_runs(&mut self) {
+        let mut i = 0;
+        while i < self.preloaded_runs.len() {
+            let run = &self.preloaded_runs[i];
+            let unused = run.released
+                && self.reference_counts[run.slots.clone()]
+                    .iter()
+                    .all(|count| count.current_count() == 0);
+            if !unused {
+                i += 1;
+                continue;
+            }
 
-            self.tile_set_to_vram.remove(tile_ref);
-            self.reference_counts[index].clear();
+            let run = self.preloaded_runs.swap_remove(i);
+            for index in run.slots.clone() {
+                self.forget_slot(index);
+            }
+
+            let start = Self::reference_from_index(TileIndex::new(run.slots.start));
+            unsafe {
+                TILE_ALLOCATOR.dealloc_no_normalise(start.0.cast().as_ptr(), run.layout);
+            }
         }
     }
 
+    // Clears everything known about the slot at `index`, before it is freed
+    fn forget_slot(&mut self, index: usize) {
+        let tile_reference = Self::reference_from_index(TileIndex::new(index));
+        Self::remove_content_reference(&mut self.content_to_vram, tile_reference);
+
+        self.forget_sources(index);
+        self.reference_counts[index].clear();
+    }
+
+    // Removes every tile in a tileset which is looked up to the slot at `index`
+    fn forget_sources(&mut self, index: usize) {
+        let count = &self.reference_counts[index];
+        let tile_reference = Self::reference_from_index(TileIndex::new(index));
+
+        match count.sources {
+            0 => {}
+            1 => {
+                // the slot may have been orphaned and the key reused by a tileset loaded into
+                // the same memory since, in which case the entry belongs to that one instead
+                let tile_in_tile_set = count.tile_in_tile_set.as_ref().unwrap();
+                if self
+                    .tile_set_to_vram
+                    .get(tile_in_tile_set)
+                    .map(|&(reference, _)| reference)
+                    == Some(tile_reference)
+                {
+                    self.tile_set_to_vram.remove(tile_in_tile_set);
+                }
+            }
+            _ => {
+                let sharing: Vec<_> = self
+                    .tile_set_to_vram
+                    .iter()
+                    .filter(|(_, &(reference, _))| reference == tile_reference)
+                    .map(|(tile_in_tile_set, _)| tile_in_tile_set.clone())
+                    .collect();
+
+                for tile_in_tile_set in &sharing {
+                    self.tile_set_to_vram.remove(tile_in_tile_set);
+                }
+            }
+        }
+    }
+
+    /// Replaces the pixel data of the tile `source_tile` from `source_tile_set` which is currently in
+    /// video ram with the data of `target_tile` from `target_tile_set`. Every position on every map
+    /// using that tile will change at once without any calls to `set_tile`, which makes this ideal
+    /// for animating things like water. Reference counts and maps are left untouched.
+    ///
+    /// Returns `false` and does nothing if the source tile is not currently in video ram, or if its
+    /// video ram slot is shared with an identical tile from somewhere else (since tiles are
+    /// deduplicated by content), as replacing it would change that tile too. Once every other
+    /// tile sharing the slot has been removed, the tile can be replaced again.
     pub fn replace_tile(
         &mut self,
         source_tile_set: &TileSet<'_>,
         source_tile: u16,
         target_tile_set: &TileSet<'_>,
         target_tile: u16,
-    ) {
-        if let Some(&reference) = self
+    ) -> bool {
+        let (reference, flip) = match self
             .tile_set_to_vram
             .get(&TileInTileSetReference::new(source_tile_set, source_tile))
         {
+            Some(&location) => location,
+            None => return false,
+        };
+
+        if self.reference_counts[Self::index_from_reference(reference)].is_shared() {
+            return false;
+        }
+
+        // the content of this slot is about to change, so it can no longer be shared by content
+        Self::remove_content_reference(&mut self.content_to_vram, reference);
+
+        if flip == TileFlip::NONE {
             self.copy_tile_to_location(target_tile_set, target_tile, reference);
+        } else {
+            // the slot holds a mirror image of the source tile, so the maps using it flip it back
+            let mut flipped = [0; 8 * 8 / 2];
+            flip.apply(target_tile_set.tile_data(target_tile), &mut flipped);
+            copy_tiles(&flipped, reference.0.as_ptr());
         }
+
+        true
     }
 
-    fn copy_tile_to_location(
+    /// Every tile in a tileset which is in video ram, as the slot it is in, its tileset and tile
+    /// number, and the flip which needs to be applied to the slot to show it. A slot shared by
+    /// several tiles appears once for each of them, and slots whose tilesets have all been
+    /// removed don't appear at all.
+    pub(crate) fn tile_sources(
+        &self,
+    ) -> impl Iterator<Item = (TileIndex, NonNull<[u8]>, u16, TileFlip)> + '_ {
+        self.tile_set_to_vram
+            .iter()
+            .map(|(tile_in_tile_set, &(reference, flip))| {
+                (
+                    TileIndex::new(Self::index_from_reference(reference)),
+                    tile_in_tile_set.tileset,
+                    tile_in_tile_set.tile,
+                    flip,
+                )
+            })
+    }
+
+    /// The pixel data currently in video ram for `tile` from `tile_set`, or `None` if it isn't
+    /// resident, shares its slot with an identical tile from somewhere else, or is stored as a
+    /// mirror image of itself.
+    pub(crate) fn resident_tile_data(
         &self,
         tile_set: &TileSet<'_>,
-        tile_id: u16,
+        tile: u16,
+    ) -> Option<&'static [u8]> {
+        let &(reference, flip) = self
+            .tile_set_to_vram
+            .get(&TileInTileSetReference::new(tile_set, tile))?;
+
+        if flip != TileFlip::NONE
+            || self.reference_counts[Self::index_from_reference(reference)].is_shared()
+        {
+            return None;
+        }
+
+        Some(reference.tile_data())
+    }
+
+    fn remove_content_reference(
+        content_to_vram: &mut HashMap<TileContentHash, TileReference>,
         tile_reference: TileReference,
     ) {
-        let tile_size = tile_set.format.tile_size();
-        let tile_offset = (tile_id as usize) * tile_size;
-        let tile_slice = &tile_set.tiles[tile_offset..(tile_offset + tile_size)];
+        let content_hash = TileContentHash::new(tile_reference.tile_data());
 
-        let tile_size_in_half_words = tile_slice.len() / 2;
-
-        let target_location = tile_reference.0.as_ptr() as *mut _;
+        if content_to_vram.get(&content_hash) == Some(&tile_reference) {
+            content_to_vram.remove(&content_hash);
+        }
+    }
 
-        unsafe {
-            dma_copy16(
-                tile_slice.as_ptr() as *const u16,
-                target_location,
-                tile_size_in_half_words,
-            );
-        };
+    fn copy_tile_to_location(
+        &self,
+        tile_set: &TileSet<'_>,
+        tile_id: u16,
+        tile_reference: TileReference,
+    ) {
+        copy_tiles(tile_set.tile_data(tile_id), tile_reference.0.as_ptr());
     }
 
     /// Copies raw palettes to the background palette without any checks.
     pub fn set_background_palette_raw(&mut self, palette: &[u16]) {
-        unsafe {
-            dma_copy16(palette.as_ptr(), PALETTE_BACKGROUND.as_ptr(), palette.len());
+        for (bank, colours) in self.background_palettes.iter_mut().zip(palette.chunks(16)) {
+            bank[..colours.len()].copy_from_slice(colours);
         }
+
+        PALETTE_BACKGROUND.write_slice(0, palette);
+    }
+
+    /// Copies a palette into background palette bank `pal_index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pal_index` isn't a valid bank, i.e. it is 16 or more.
+    pub fn set_background_palette(&mut self, pal_index: u8, palette: &palette16::Palette16) {
+        assert!(
+            (pal_index as usize) < PALETTE_BANKS,
+            "background palette bank {} out of range, there are only {} banks",
+            pal_index,
+            PALETTE_BANKS
+        );
+
+        PALETTE_BACKGROUND.write_slice(16 * pal_index as usize, &palette.colours);
+
+        self.background_palettes[pal_index as usize] = palette.colours;
     }
 
-    fn set_background_palette(&mut self, pal_index: u8, palette: &palette16::Palette16) {
-        for (colour_index, &colour) in palette.colours.iter().enumerate() {
-            PALETTE_BACKGROUND.set(colour_index + 16 * pal_index as usize, colour);
+    /// Rotates the colours in `range` of background palette bank `bank` by `frame` places, in
+    /// the same way as [`Palette16::cycled`](palette16::Palette16::cycled), to animate large
+    /// areas such as water without changing any tiles. To move the colours on every few frames,
+    /// pass the frame number divided by the number of frames each step should last.
+    ///
+    /// The colours are always rotated starting from the palette last copied into the bank by
+    /// this [`VRamManager`], so repeated calls never build up errors.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bank` is 16 or more, or `range` goes past the end of the palette.
+    pub fn cycle_background_palette(&mut self, bank: u8, range: Range<usize>, frame: usize) {
+        assert!(
+            (bank as usize) < PALETTE_BANKS,
+            "background palette bank {} out of range, there are only {} banks",
+            bank,
+            PALETTE_BANKS
+        );
+
+        let original = palette16::Palette16::new(self.background_palettes[bank as usize]);
+        let cycled = original.cycled(range.clone(), frame);
+
+        PALETTE_BACKGROUND.write_slice(16 * bank as usize + range.start, &cycled.colours[range]);
+    }
+
+    /// Allocates a background palette bank for `palette` and copies it to palette ram. If a
+    /// palette with the same colours already has a bank then that bank is shared. Returns `None`
+    /// if all 16 banks are in use. The bank is released once the returned [`PaletteBank`] and all
+    /// its clones are dropped. Banks are allocated by the
+    /// [`palette_manager`](crate::display::palette_manager), so palettes recoloured there are
+    /// copied with their new colours.
+    ///
+    /// Banks written using [`set_background_palettes`](VRamManager::set_background_palettes) or
+    /// [`set_background_palette_raw`](VRamManager::set_background_palette_raw) aren't tracked,
+    /// so avoid mixing the two unless you know which banks are free.
+    pub fn palette(&mut self, palette: &'static palette16::Palette16) -> Option<PaletteBank> {
+        let (bank, colours) = palette_manager::allocate(PaletteKind::Background, palette)?;
+
+        if let Some(colours) = colours {
+            self.set_background_palette(bank, &palette16::Palette16::new(colours));
         }
+
+        Some(PaletteBank { bank })
+    }
+
+    /// Gives `tile_set` a palette bank for each of `palettes`, allocated in the same way as
+    /// [`palette`](VRamManager::palette), so that
+    /// [`tile_setting_for`](VRamManager::tile_setting_for) can fill in the right bank for each
+    /// tile. `palette_assignments` is the index in `palettes` of the palette used by each tile,
+    /// as in [`TileData`](crate::display::tile_data::TileData). This replaces any palettes
+    /// the tileset already had.
+    ///
+    /// The banks stay allocated until
+    /// [`remove_tileset_palettes`](VRamManager::remove_tileset_palettes) is called.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there aren't enough free palette banks, if `palette_assignments` is shorter
+    /// than the tileset, or if it refers to a palette past the end of `palettes`.
+    pub fn set_tileset_palettes(
+        &mut self,
+        tile_set: &TileSet<'_>,
+        palettes: &'static [palette16::Palette16],
+        palette_assignments: &'static [u8],
+    ) {
+        assert!(
+            palette_assignments.len() >= tile_set.tile_count() as usize,
+            "the tileset has {} tiles but only {} palette assignments",
+            tile_set.tile_count(),
+            palette_assignments.len()
+        );
+        assert!(
+            palette_assignments
+                .iter()
+                .all(|&palette| (palette as usize) < palettes.len()),
+            "palette assignments refer to palettes past the {} given",
+            palettes.len()
+        );
+
+        let banks = palettes
+            .iter()
+            .map(|palette| match self.palette(palette) {
+                Some(bank) => bank,
+                None => panic!(
+                    "Ran out of background palette banks for the {} palettes of a tileset",
+                    palettes.len()
+                ),
+            })
+            .collect();
+
+        self.tileset_palettes.insert(
+            tile_set.reference(),
+            TilesetPalettes {
+                banks,
+                assignments: palette_assignments,
+            },
+        );
+    }
+
+    /// Releases the palette banks given to `tile_set` by
+    /// [`set_tileset_palettes`](VRamManager::set_tileset_palettes). Each bank is freed once
+    /// nothing else uses it.
+    pub fn remove_tileset_palettes(&mut self, tile_set: &TileSet<'_>) {
+        self.tileset_palettes.remove(&tile_set.reference());
+    }
+
+    /// The setting for `tile` of `tile_set` using the palette bank given to it by
+    /// [`set_tileset_palettes`](VRamManager::set_tileset_palettes). To choose the bank
+    /// yourself, use [`TileSetting::new`] instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tile_set` has no palettes, or `tile` is past the end of its palette
+    /// assignments.
+    #[must_use]
+    pub fn tile_setting_for(
+        &self,
+        tile_set: &TileSet<'_>,
+        tile: u16,
+        hflip: bool,
+        vflip: bool,
+    ) -> TileSetting {
+        let palettes = self
+            .tileset_palettes
+            .get(&tile_set.reference())
+            .expect("the tileset has no palettes, give it some with set_tileset_palettes");
+        let bank = &palettes.banks[palettes.assignments[tile as usize] as usize];
+
+        TileSetting::new(tile, hflip, vflip, 0).with_palette(bank)
     }
 
     /// Copies palettes to the background palettes without any checks.
     pub fn set_background_palettes(&mut self, palettes: &[palette16::Palette16]) {
+        self.set_background_palettes_at(0, palettes);
+    }
+
+    /// Copies palettes to consecutive background palette banks starting at `first_bank`, leaving
+    /// the other banks untouched.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the palettes would end up past the last bank.
+    pub fn set_background_palettes_at(
+        &mut self,
+        first_bank: u8,
+        palettes: &[palette16::Palette16],
+    ) {
+        assert!(
+            first_bank as usize + palettes.len() <= PALETTE_BANKS,
+            "{} background palettes starting at bank {} don't fit in the {} banks",
+            palettes.len(),
+            first_bank,
+            PALETTE_BANKS
+        );
+
         for (palette_index, entry) in palettes.iter().enumerate() {
-            self.set_background_palette(palette_index as u8, entry);
+            self.set_background_palette(first_bank + palette_index as u8, entry);
+        }
+    }
+
+    /// Copies the blend `t` of the way from each of `from` to the palette at the same position
+    /// in `to`, as given by [`Palette16::blend`](palette16::Palette16::blend), to consecutive
+    /// background palette banks starting at `first_bank`. This can be called every frame as `t`
+    /// changes to tint the whole scene, such as fading between day and night.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `from` and `to` have different lengths, or any of the palettes would end up
+    /// past the last bank.
+    pub fn blend_background_palettes(
+        &mut self,
+        first_bank: u8,
+        from: &[palette16::Palette16],
+        to: &[palette16::Palette16],
+        t: Num<i32, 8>,
+    ) {
+        assert_eq!(
+            from.len(),
+            to.len(),
+            "there must be the same number of palettes to blend from and to"
+        );
+        assert!(
+            first_bank as usize + from.len() <= PALETTE_BANKS,
+            "{} background palettes starting at bank {} don't fit in the {} banks",
+            from.len(),
+            first_bank,
+            PALETTE_BANKS
+        );
+
+        for (palette_index, (from, to)) in from.iter().zip(to).enumerate() {
+            let blended = palette16::Palette16::new(palette16::Palette16::blend(from, to, t));
+            self.set_background_palette(first_bank + palette_index as u8, &blended);
+        }
+    }
+}
+
+// Every background tile slot is in use
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct OutOfVideoRam;
+
+fn out_of_video_ram() -> ! {
+    panic!(
+        "Ran out of video ram for background tiles, all {} tile slots are in use",
+        TILE_SLOTS
+    )
+}
+
+// Copies tile data into video ram at `target_location`.
+// Aligned copies of up to 4 4bpp tiles use the cpu rather than DMA
+const SMALL_TILE_COPY: usize = 4 * 32;
+
+fn copy_tiles(tile_data: &[u8], target_location: *mut u32) {
+    debug_assert!(
+        target_location as usize + tile_data.len() <= TILE_DATA_END,
+        "Tile copy to {:p} would overwrite the screenblocks used by maps",
+        target_location
+    );
+
+    // Tilesets are byte slices so may not be aligned. DMA ignores the low bits of the source
+    // address, so only use it when the data is aligned, and otherwise build up each half word
+    // from the bytes since video ram can't be written a byte at a time. Copying a few tiles is
+    // quicker without the DMA setup.
+    let source = tile_data.as_ptr();
+    unsafe {
+        if source as usize & 0b11 == 0 && tile_data.len() <= SMALL_TILE_COPY {
+            mem::copy_words(source.cast(), target_location, tile_data.len() / 4);
+        } else if source as usize & 0b11 == 0 {
+            dma_copy32(source.cast(), target_location, tile_data.len() / 4);
+        } else if source as usize & 0b1 == 0 {
+            dma_copy16(source.cast(), target_location.cast(), tile_data.len() / 2);
+        } else {
+            let target_location: *mut u16 = target_location.cast();
+            for i in 0..tile_data.len() / 2 {
+                let half_word = u16::from_le_bytes([tile_data[2 * i], tile_data[2 * i + 1]]);
+                target_location.add(i).write_volatile(half_word);
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec;
+
+    fn used_tile_slots(vram: &VRamManager) -> usize {
+        vram.reference_counts
+            .iter()
+            .filter(|count| count.current_count() > 0)
+            .count()
+    }
+
+    #[test_case]
+    fn tiles_with_identical_content_share_a_slot(gba: &mut crate::Gba) {
+        let (_gfx, mut vram) = gba.display.video.tiled0();
+
+        let tile_size = TileFormat::FourBpp.tile_size();
+
+        // the first 10 tiles of both tilesets are the same, the last 10 are different
+        let mut first_tiles = vec![0u8; 20 * tile_size];
+        let mut second_tiles = vec![0u8; 20 * tile_size];
+        for tile in 0..20 {
+            let range = (tile * tile_size)..((tile + 1) * tile_size);
+            // chosen so that no tile is a mirror image of a different one
+            first_tiles[range.clone()].fill(tile as u8 + 0x40);
+            second_tiles[range].fill(if tile < 10 { tile + 0x40 } else { tile + 100 } as u8);
+        }
+
+        let first_tileset = TileSet::new(&first_tiles, TileFormat::FourBpp);
+        let second_tileset = TileSet::new(&second_tiles, TileFormat::FourBpp);
+
+        let mut indices = vec![];
+        for tile in 0..20 {
+            indices.push(vram.add_tile(&first_tileset, tile).0);
+            indices.push(vram.add_tile(&second_tileset, tile).0);
+        }
+
+        assert_eq!(used_tile_slots(&vram), 30);
+
+        for tile in 0..10 {
+            assert_eq!(
+                indices[tile * 2].index(),
+                indices[tile * 2 + 1].index(),
+                "tile {} should be shared between the tilesets",
+                tile
+            );
+        }
+
+        // removing the first tileset's tiles must not free the shared slots
+        for &index in indices.iter().step_by(2) {
+            vram.remove_tile(index);
+        }
+        vram.gc();
+
+        assert_eq!(used_tile_slots(&vram), 20);
+        for tile in 0..10 {
+            assert_eq!(
+                indices[tile * 2 + 1].index(),
+                vram.add_tile(&second_tileset, tile as u16).0.index()
+            );
+            vram.remove_tile(indices[tile * 2 + 1]);
+        }
+
+        for &index in indices.iter().skip(1).step_by(2) {
+            vram.remove_tile(index);
+        }
+        vram.gc();
+
+        assert_eq!(used_tile_slots(&vram), 0);
+    }
+
+    #[test_case]
+    fn reserving_tiles_means_adding_them_never_resizes(gba: &mut crate::Gba) {
+        let (_gfx, mut vram) = gba.display.video.tiled0();
+
+        let tile_size = TileFormat::FourBpp.tile_size();
+        let tile_count = 400;
+
+        // more tiles than the maps start with space for, each with different content
+        let mut tiles = vec![0u8; tile_count * tile_size];
+        for (tile, data) in tiles.chunks_mut(tile_size).enumerate() {
+            data[0] = tile as u8;
+            data[1] = (tile >> 8) as u8;
+        }
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        vram.reserve_tiles(tile_count);
+        let capacities = |vram: &VRamManager| {
+            (
+                vram.tile_set_to_vram.capacity(),
+                vram.content_to_vram.capacity(),
+                vram.reference_counts.capacity(),
+            )
+        };
+        let reserved = capacities(&vram);
+
+        let indices: Vec<_> = (0..tile_count as u16)
+            .map(|tile| vram.add_tile(&tileset, tile).0)
+            .collect();
+
+        assert_eq!(used_tile_slots(&vram), tile_count);
+        assert_eq!(capacities(&vram), reserved, "a map was resized");
+
+        for index in indices {
+            vram.remove_tile(index);
+        }
+        vram.gc();
+    }
+
+    #[test_case]
+    fn mirror_images_share_a_slot(gba: &mut crate::Gba) {
+        let (_gfx, mut vram) = gba.display.video.tiled0();
+
+        let tile_size = TileFormat::FourBpp.tile_size();
+
+        // a slope going up to the right, its horizontal and vertical mirror images, and a tile
+        // which is symmetric in both directions
+        let mut tiles = vec![0u8; 4 * tile_size];
+        for y in 0..8 {
+            for x in 0..8 {
+                let set = |tiles: &mut [u8], tile: usize, x: usize, y: usize, colour: u8| {
+                    tiles[tile * tile_size + y * 4 + x / 2] |= colour << ((x & 1) * 4);
+                };
+
+                if x + y >= 7 {
+                    set(&mut tiles, 0, x, y, 3);
+                    set(&mut tiles, 1, 7 - x, y, 3);
+                    set(&mut tiles, 2, x, 7 - y, 3);
+                }
+                if x == 0 || y == 0 || x == 7 || y == 7 {
+                    set(&mut tiles, 3, x, y, 5);
+                }
+            }
+        }
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        let (slope, slope_flip) = vram.add_tile(&tileset, 0);
+        let (left_slope, left_flip) = vram.add_tile(&tileset, 1);
+        let (upside_down_slope, upside_down_flip) = vram.add_tile(&tileset, 2);
+        let (symmetric, symmetric_flip) = vram.add_tile(&tileset, 3);
+
+        assert_eq!(used_tile_slots(&vram), 2);
+        assert_eq!(slope_flip, TileFlip::NONE);
+        assert_eq!(left_slope.index(), slope.index());
+        assert_eq!(left_flip, TileFlip::HORIZONTAL);
+        assert_eq!(upside_down_slope.index(), slope.index());
+        assert_eq!(upside_down_flip, TileFlip::VERTICAL);
+        assert_eq!(symmetric_flip, TileFlip::NONE);
+
+        let mut flipped = [0; 8 * 8 / 2];
+        left_flip.apply(
+            VRamManager::reference_from_index(left_slope).tile_data(),
+            &mut flipped,
+        );
+        assert_eq!(&flipped[..], tileset.tile_data(1));
+
+        // a tile which is its own mirror image matches itself before any flips are tried
+        let (symmetric_again, symmetric_again_flip) = vram.add_tile(&tileset, 3);
+        assert_eq!(symmetric_again.index(), symmetric.index());
+        assert_eq!(symmetric_again_flip, TileFlip::NONE);
+
+        for index in [
+            slope,
+            left_slope,
+            upside_down_slope,
+            symmetric,
+            symmetric_again,
+        ] {
+            vram.remove_tile(index);
+        }
+        vram.gc();
+
+        assert_eq!(used_tile_slots(&vram), 0);
+    }
+
+    #[test_case]
+    fn tiles_are_copied_correctly_whatever_their_alignment(gba: &mut crate::Gba) {
+        let (_gfx, mut vram) = gba.display.video.tiled0();
+
+        let tile_size = TileFormat::FourBpp.tile_size();
+
+        // a word aligned buffer with room to start the tileset at any of the 4 byte offsets
+        let mut buffer = vec![0u32; (tile_size + 4) / 4];
+        let bytes = unsafe {
+            slice::from_raw_parts_mut(buffer.as_mut_ptr().cast::<u8>(), buffer.len() * 4)
+        };
+
+        let mut timers = gba.timers.timers();
+        let timer = &mut timers.timer2;
+        timer.set_divider(crate::timer::Divider::Divider1);
+
+        let mut times = [0; 4];
+        for (offset, time) in times.iter_mut().enumerate() {
+            for (i, byte) in bytes[offset..offset + tile_size].iter_mut().enumerate() {
+                *byte = (i as u8).wrapping_mul(37).wrapping_add(offset as u8);
+            }
+
+            let tileset = TileSet::new(&bytes[offset..offset + tile_size], TileFormat::FourBpp);
+            let reference = VRamManager::reference_from_index(vram.add_tile(&tileset, 0).0);
+
+            timer.set_enabled(false).set_overflow_amount(0xFFFF);
+            timer.set_enabled(true);
+            let start = timer.value();
+            vram.copy_tile_to_location(&tileset, 0, reference);
+            let end = timer.value();
+            timer.set_enabled(false);
+            *time = end.wrapping_sub(start);
+
+            assert_eq!(
+                reference.tile_data(),
+                tileset.tile_data(0),
+                "tile starting at offset {} was copied incorrectly",
+                offset
+            );
+
+            vram.remove_tile(TileIndex::new(VRamManager::index_from_reference(reference)));
+            vram.force_remove_tileset(&tileset);
+            vram.gc();
+        }
+
+        // how long the copy takes depends on the emulator, so this is only reported to compare
+        // the aligned and unaligned copies rather than checked
+        crate::println!(
+            "cycles to copy a tile starting at each byte offset: {:?}",
+            times
+        );
+    }
+
+    #[test_case]
+    fn replace_tile_refuses_tiles_shared_by_content(gba: &mut crate::Gba) {
+        let (_gfx, mut vram) = gba.display.video.tiled0();
+
+        let tile_size = TileFormat::FourBpp.tile_size();
+
+        let mut animated_tiles = vec![0u8; 3 * tile_size];
+        animated_tiles[tile_size..2 * tile_size].fill(1);
+        animated_tiles[2 * tile_size..].fill(2);
+        let other_tiles = vec![1u8; tile_size];
+
+        let animated_tileset = TileSet::new(&animated_tiles, TileFormat::FourBpp);
+        let other_tileset = TileSet::new(&other_tiles, TileFormat::FourBpp);
+
+        let animated = vram.add_tile(&animated_tileset, 0).0;
+        let shared = vram.add_tile(&animated_tileset, 1).0;
+        let shared_other = vram.add_tile(&other_tileset, 0).0;
+        assert_eq!(shared.index(), shared_other.index());
+
+        assert!(vram.replace_tile(&animated_tileset, 0, &animated_tileset, 2));
+        assert_eq!(
+            VRamManager::reference_from_index(animated).tile_data(),
+            animated_tileset.tile_data(2)
+        );
+
+        assert!(!vram.replace_tile(&animated_tileset, 1, &animated_tileset, 2));
+        assert_eq!(
+            VRamManager::reference_from_index(shared).tile_data(),
+            other_tileset.tile_data(0)
+        );
+
+        // the replaced tile must not be picked up by identical content any more
+        let third = vram.add_tile(&animated_tileset, 2).0;
+        assert_ne!(third.index(), animated.index());
+
+        for index in [animated, shared, shared_other, third] {
+            vram.remove_tile(index);
+        }
+        vram.gc();
+
+        assert_eq!(used_tile_slots(&vram), 0);
+    }
+
+    #[test_case]
+    #[cfg(not(feature = "vram_leak_check"))]
+    fn tiles_shared_by_content_belong_to_every_tileset_using_them(gba: &mut crate::Gba) {
+        let (_gfx, mut vram) = gba.display.video.tiled0();
+
+        let tile_size = TileFormat::FourBpp.tile_size();
+
+        let first_tiles = vec![0x55u8; tile_size];
+        let mut second_tiles = vec![0x66u8; 2 * tile_size];
+        second_tiles[tile_size..].fill(0x55);
+
+        let first_tileset = TileSet::new(&first_tiles, TileFormat::FourBpp);
+        let second_tileset = TileSet::new(&second_tiles, TileFormat::FourBpp);
+
+        let first = vram.add_tile(&first_tileset, 0).0;
+        let second = vram.add_tile(&second_tileset, 1).0;
+        assert_eq!(first.index(), second.index());
+        assert_eq!(vram.resident_tiles(&first_tileset), 1);
+        assert_eq!(vram.resident_tiles(&second_tileset), 1);
+        assert!(!vram.replace_tile(&second_tileset, 1, &second_tileset, 0));
+
+        // the slot is still used through the second tileset, so the first can go
+        vram.remove_tile(first);
+        assert_eq!(vram.remove_tileset(&first_tileset), Ok(()));
+        assert_eq!(vram.resident_tiles(&first_tileset), 0);
+        assert_eq!(vram.resident_tiles(&second_tileset), 1);
+
+        let slot = vram
+            .tile_slots()
+            .find(|slot| slot.index == second.index())
+            .unwrap();
+        assert_eq!(slot.tileset_address, second_tiles.as_ptr() as usize);
+        assert_eq!(slot.tile, 1);
+        assert!(!slot.orphaned);
+
+        // and with no one else using the slot the second tileset can animate it
+        assert_eq!(
+            vram.resident_tile_data(&second_tileset, 1),
+            Some(second_tileset.tile_data(1))
+        );
+        assert!(vram.replace_tile(&second_tileset, 1, &second_tileset, 0));
+        assert_eq!(
+            VRamManager::reference_from_index(second).tile_data(),
+            second_tileset.tile_data(0)
+        );
+
+        vram.remove_tile(second);
+        vram.gc();
+        assert_eq!(used_tile_slots(&vram), 0);
+        assert_eq!(vram.resident_tiles(&second_tileset), 0);
+    }
+
+    #[test_case]
+    fn force_removing_one_tileset_sharing_a_slot_leaves_it_to_the_others(gba: &mut crate::Gba) {
+        let (_gfx, mut vram) = gba.display.video.tiled0();
+
+        let tile_size = TileFormat::FourBpp.tile_size();
+
+        // a tile and its horizontal mirror image
+        let mut first_tiles = vec![0u8; tile_size];
+        let mut second_tiles = vec![0u8; tile_size];
+        for row in 0..8 {
+            first_tiles[row * 4] = 0x21;
+            second_tiles[row * 4 + 3] = 0x12;
+        }
+
+        let first_tileset = TileSet::new(&first_tiles, TileFormat::FourBpp);
+        let second_tileset = TileSet::new(&second_tiles, TileFormat::FourBpp);
+
+        let (first, _) = vram.add_tile(&first_tileset, 0);
+        let (second, flip) = vram.add_tile(&second_tileset, 0);
+        assert_eq!(first.index(), second.index());
+        assert_eq!(flip, TileFlip::HORIZONTAL);
+
+        // the first tileset goes while its tile is still in use
+        vram.force_remove_tileset(&first_tileset);
+        assert!(
+            !vram
+                .tile_slots()
+                .find(|slot| slot.index == first.index())
+                .unwrap()
+                .orphaned
+        );
+
+        // adding the second tile again finds it with the same flip
+        let (again, again_flip) = vram.add_tile(&second_tileset, 0);
+        assert_eq!(again.index(), second.index());
+        assert_eq!(again_flip, TileFlip::HORIZONTAL);
+
+        // replacing it through the second tileset keeps it the right way round
+        let mut replacement_tiles = vec![0u8; tile_size];
+        replacement_tiles[0] = 0x03;
+        let replacement = TileSet::new(&replacement_tiles, TileFormat::FourBpp);
+        assert!(vram.replace_tile(&second_tileset, 0, &replacement, 0));
+
+        let mut shown = [0; 8 * 8 / 2];
+        again_flip.apply(
+            VRamManager::reference_from_index(again).tile_data(),
+            &mut shown,
+        );
+        assert_eq!(&shown[..], replacement.tile_data(0));
+
+        for index in [first, second, again] {
+            vram.remove_tile(index);
+        }
+        vram.gc();
+        assert_eq!(used_tile_slots(&vram), 0);
+    }
+
+    #[test_case]
+    fn stats_track_tile_usage(gba: &mut crate::Gba) {
+        let (_gfx, mut vram) = gba.display.video.tiled0();
+
+        let tile_size = TileFormat::FourBpp.tile_size();
+
+        let mut tiles = vec![0u8; 4 * tile_size];
+        for (i, tile) in tiles.chunks_mut(tile_size).enumerate() {
+            tile.fill(i as u8 + 0x50);
+        }
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        let initial = vram.stats();
+        assert_eq!(initial.used_tile_slots, 0);
+        assert_eq!(initial.tile_sets, 0);
+        assert_eq!(initial.free_tile_slots, TILE_SLOTS);
+
+        let first = vram.add_tile(&tileset, 0).0;
+        let first_again = vram.add_tile(&tileset, 0).0;
+        let second = vram.add_tile(&tileset, 1).0;
+
+        let stats = vram.stats();
+        assert_eq!(stats.used_tile_slots, 2);
+        assert_eq!(stats.free_tile_slots, TILE_SLOTS - 2);
+        assert_eq!(stats.tile_sets, 1);
+        assert_eq!(stats.used_bytes, 2 * tile_size);
+        assert_eq!(vram.resident_tiles(&tileset), 2);
+        assert!(vram
+            .tile_reference_counts()
+            .any(|(index, count)| index == first.index() && count == 2));
+
+        vram.remove_tile(second);
+        let stats = vram.stats();
+        assert_eq!(stats.used_tile_slots, 1);
+        assert_eq!(stats.tile_slots_awaiting_gc, 1);
+        assert_eq!(stats.free_tile_slots, TILE_SLOTS - 2);
+
+        vram.remove_tile(first);
+        vram.remove_tile(first_again);
+        vram.gc();
+
+        assert_eq!(vram.stats(), initial);
+        assert_eq!(vram.resident_tiles(&tileset), 0);
+    }
+
+    #[test_case]
+    fn removing_a_preloaded_tileset_frees_all_its_video_ram(gba: &mut crate::Gba) {
+        let (_gfx, mut vram) = gba.display.video.tiled0();
+
+        let tile_size = TileFormat::FourBpp.tile_size();
+
+        let mut tiles = vec![0u8; 5 * tile_size];
+        for (i, tile) in tiles.chunks_mut(tile_size).enumerate() {
+            tile.fill(i as u8 + 0x60);
+        }
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        let initial = vram.stats();
+        let initial_allocator = tile_vram_stats();
+
+        let preloaded = vram.preload_tileset(&tileset);
+        assert_eq!(vram.stats().used_tile_slots, 5);
+
+        // a tile still in use keeps the whole run allocated
+        let resolved = vram.resolve(&tileset, 3);
+        vram.remove_preloaded_tileset(preloaded);
+        vram.gc();
+        assert_eq!(vram.stats().used_tile_slots, 1);
+        assert_eq!(vram.stats().tile_slots_awaiting_gc, 4);
+        assert_eq!(
+            tile_vram_stats().bytes_allocated,
+            initial_allocator.bytes_allocated + 5 * tile_size
+        );
+
+        vram.release_resolved(resolved);
+        vram.gc();
+
+        assert_eq!(vram.stats(), initial);
+        assert_eq!(tile_vram_stats(), initial_allocator);
+        assert_eq!(vram.resident_tiles(&tileset), 0);
+    }
+
+    const fn bank_test_palettes() -> [palette16::Palette16; PALETTE_BANKS + 1] {
+        const BLANK: palette16::Palette16 = palette16::Palette16::new([0; 16]);
+
+        let mut palettes = [BLANK; PALETTE_BANKS + 1];
+        let mut i = 0;
+        while i < palettes.len() {
+            palettes[i] = palette16::Palette16::new([0x1000 + i as u16; 16]);
+            i += 1;
+        }
+
+        palettes
+    }
+
+    static BANK_TEST_PALETTES: [palette16::Palette16; PALETTE_BANKS + 1] = bank_test_palettes();
+
+    #[test_case]
+    fn tilesets_are_given_their_own_palette_banks(gba: &mut crate::Gba) {
+        let (_gfx, mut vram) = gba.display.video.tiled0();
+
+        let tiles = [0u8; 3 * 8 * 8 / 2];
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        // something else is already using the second palette, so its bank is shared
+        let palettes = &BANK_TEST_PALETTES[..2];
+        let second = vram.palette(&palettes[1]).unwrap();
+
+        vram.set_tileset_palettes(&tileset, palettes, &[1, 0, 1]);
+
+        assert_eq!(
+            vram.tile_setting_for(&tileset, 0, true, false),
+            TileSetting::new(0, true, false, second.bank())
+        );
+
+        let first = vram.tile_setting_for(&tileset, 1, false, true);
+        let first_bank = first.setting() >> 12;
+        assert_eq!(first, TileSetting::new(1, false, true, first_bank as u8));
+        assert_ne!(first_bank, u16::from(second.bank()));
+        assert_eq!(PALETTE_BACKGROUND.get(16 * first_bank as usize), 0x1000);
+
+        vram.remove_tileset_palettes(&tileset);
+        drop(second);
+
+        assert_eq!(palette_manager::banks_in_use(PaletteKind::Background), 0);
+    }
+
+    #[test_case]
+    fn cycling_always_starts_from_the_uploaded_palette(gba: &mut crate::Gba) {
+        let (_gfx, mut vram) = gba.display.video.tiled0();
+
+        let mut colours = [0; 16];
+        for (i, colour) in colours.iter_mut().enumerate() {
+            *colour = 0x100 + i as u16;
         }
+        vram.set_background_palette(3, &palette16::Palette16::new(colours));
+
+        let bank_colours = || (0..16).map(|i| PALETTE_BACKGROUND.get(16 * 3 + i));
+
+        vram.cycle_background_palette(3, 1..4, 1);
+        assert!(bank_colours()
+            .take(5)
+            .eq([0x100, 0x103, 0x101, 0x102, 0x104]));
+
+        vram.cycle_background_palette(3, 1..4, 2);
+        assert!(bank_colours()
+            .take(5)
+            .eq([0x100, 0x102, 0x103, 0x101, 0x104]));
+
+        vram.cycle_background_palette(3, 1..4, 3);
+        assert!(bank_colours().eq(colours));
+    }
+
+    #[test_case]
+    fn palette_banks_are_shared_and_released(gba: &mut crate::Gba) {
+        let (_gfx, mut vram) = gba.display.video.tiled0();
+
+        let a = vram.palette(&BANK_TEST_PALETTES[0]).unwrap();
+        let a_again = vram.palette(&BANK_TEST_PALETTES[0]).unwrap();
+        let b = vram.palette(&BANK_TEST_PALETTES[1]).unwrap();
+
+        assert_eq!(a.bank(), a_again.bank());
+        assert_ne!(a.bank(), b.bank());
+        assert_eq!(PALETTE_BACKGROUND.get(16 * b.bank() as usize + 3), 0x1001);
+
+        let setting = super::super::TileSetting::new(5, true, false, 0).with_palette(&b);
+        assert_eq!(setting.setting() >> 12, u16::from(b.bank()));
+        assert_eq!(setting.index(), 5);
+
+        drop(a);
+        drop(a_again);
+        drop(b);
+
+        let mut banks: Vec<_> = BANK_TEST_PALETTES[..PALETTE_BANKS]
+            .iter()
+            .map(|palette| vram.palette(palette).unwrap())
+            .collect();
+
+        assert!(vram.palette(&BANK_TEST_PALETTES[PALETTE_BANKS]).is_none());
+
+        let released_bank = banks.swap_remove(3).bank();
+        let last = vram.palette(&BANK_TEST_PALETTES[PALETTE_BANKS]).unwrap();
+        assert_eq!(last.bank(), released_bank);
+    }
+
+    #[test_case]
+    fn set_background_palettes_at_offsets_banks(gba: &mut crate::Gba) {
+        let (_gfx, mut vram) = gba.display.video.tiled0();
+
+        vram.set_background_palettes_at(14, &BANK_TEST_PALETTES[..2]);
+
+        let palette_ram = PALETTE_BACKGROUND.as_ptr() as usize;
+        let bank_15 = unsafe { ((palette_ram + 0x1E0) as *const u16).read_volatile() };
+
+        assert_eq!(PALETTE_BACKGROUND.get(14 * 16), 0x1000);
+        assert_eq!(bank_15, 0x1001);
+    }
+
+    #[test_case]
+    fn blending_background_palettes_writes_each_bank(gba: &mut crate::Gba) {
+        let (_gfx, mut vram) = gba.display.video.tiled0();
+
+        vram.blend_background_palettes(
+            14,
+            &BANK_TEST_PALETTES[..2],
+            &BANK_TEST_PALETTES[2..4],
+            crate::fixnum::num!(0.5),
+        );
+
+        // halfway between 0x1000 and 0x1002, and 0x1001 and 0x1003
+        assert_eq!(PALETTE_BACKGROUND.get(14 * 16), 0x1001);
+        assert_eq!(PALETTE_BACKGROUND.get(15 * 16 + 3), 0x1002);
+    }
+
+    #[test_case]
+    fn every_tile_slot_fits_below_the_map_screenblocks(gba: &mut crate::Gba) {
+        let (_gfx, mut vram) = gba.display.video.tiled0();
+
+        let tile_size = TileFormat::FourBpp.tile_size();
+        let mut tiles = vec![0u8; TILE_SLOTS * tile_size];
+        for (i, tile) in tiles.chunks_mut(tile_size).enumerate() {
+            tile[..2].copy_from_slice(&(i as u16).to_le_bytes());
+            tile[2] = 0xAA;
+        }
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        let indices: Vec<_> = (0..TILE_SLOTS as u16)
+            .map(|tile| vram.add_tile(&tileset, tile).0)
+            .collect();
+
+        assert_eq!(vram.stats().free_tile_slots, 0);
+        for index in &indices {
+            let end = VRamManager::reference_from_index(*index).0.as_ptr() as usize + tile_size;
+            assert!(end <= TILE_DATA_END);
+        }
+
+        for index in indices {
+            vram.remove_tile(index);
+        }
+        vram.gc();
+
+        assert_eq!(used_tile_slots(&vram), 0);
+    }
+
+    #[test_case]
+    #[cfg(not(feature = "vram_leak_check"))]
+    fn tilesets_can_only_be_removed_once_unused(gba: &mut crate::Gba) {
+        let (_gfx, mut vram) = gba.display.video.tiled0();
+
+        let tiles = vec![0x11u8; 4 * TileFormat::FourBpp.tile_size()];
+        let tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+
+        let first = vram.add_tile(&tileset, 0).0;
+        let second = vram.add_tile(&tileset, 0).0;
+
+        assert_eq!(
+            vram.remove_tileset(&tileset),
+            Err(TilesStillInUse { count: 1 })
+        );
+
+        vram.remove_tile(first);
+        vram.remove_tile(second);
+        assert_eq!(vram.remove_tileset(&tileset), Ok(()));
+        assert_eq!(vram.resident_tiles(&tileset), 0);
+        assert_eq!(vram.stats().tile_slots_awaiting_gc, 0);
+
+        // adding it again after removal loads it afresh
+        let again = vram.add_tile(&tileset, 0).0;
+        assert_eq!(vram.resident_tiles(&tileset), 1);
+        vram.remove_tile(again);
+        vram.gc();
+    }
+
+    #[test_case]
+    fn forcibly_removed_tilesets_can_have_their_memory_reused(gba: &mut crate::Gba) {
+        let (_gfx, mut vram) = gba.display.video.tiled0();
+
+        let mut tiles = vec![0x22u8; TileFormat::FourBpp.tile_size()];
+
+        let old_tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+        let old_tile = vram.add_tile(&old_tileset, 0).0;
+        vram.force_remove_tileset(&old_tileset);
+
+        let old_slot = vram
+            .tile_slots()
+            .find(|slot| slot.index == old_tile.index())
+            .unwrap();
+        assert_eq!(old_slot.tileset_address, tiles.as_ptr() as usize);
+        assert_eq!(old_slot.tile, 0);
+        assert_eq!(old_slot.reference_count, 1);
+        assert!(old_slot.orphaned);
+
+        // a different tileset in the same memory as the old one
+        tiles.fill(0x33);
+        let new_tileset = TileSet::new(&tiles, TileFormat::FourBpp);
+        let new_tile = vram.add_tile(&new_tileset, 0).0;
+
+        assert_ne!(old_tile.index(), new_tile.index());
+        assert_eq!(
+            VRamManager::reference_from_index(new_tile).tile_data()[0],
+            0x33
+        );
+        assert!(
+            !vram
+                .tile_slots()
+                .find(|slot| slot.index == new_tile.index())
+                .unwrap()
+                .orphaned
+        );
+
+        // collecting the old tile leaves the new one alone
+        vram.remove_tile(old_tile);
+        vram.gc();
+        assert_eq!(vram.resident_tiles(&new_tileset), 1);
+
+        vram.remove_tile(new_tile);
+        vram.gc();
+        assert_eq!(used_tile_slots(&vram), 0);
     }
 }
@@ -0,0 +1,224 @@
+use super::draw::fill_run16;
+
+/// Byte access to a region of video ram.
+///
+/// Video ram ignores 8 bit writes, and instead writes the byte to both halves of the halfword
+/// containing it, so writing through a `*mut u8` changes the neighbouring byte too. `VramBytes`
+/// writes single bytes by reading the halfword containing them and writing it back with only that
+/// byte changed, and writes runs of bytes two at a time.
+///
+/// This is what to use for 8 bit per pixel graphics, such as drawing to
+/// [`Bitmap4`](super::bitmap4::Bitmap4) or to 256 colour tiles.
+pub struct VramBytes {
+    start: *mut u16,
+    len: usize,
+}
+
+impl VramBytes {
+    /// Byte access to the `len` bytes of video ram starting at `address`.
+    ///
+    /// # Safety
+    ///
+    /// The region must be in video ram, and nothing else may write to it while this exists.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `address` isn't 2 byte aligned.
+    #[must_use]
+    pub unsafe fn new(address: usize, len: usize) -> Self {
+        assert_eq!(
+            address & 1,
+            0,
+            "video ram byte access must start at a 2 byte aligned address"
+        );
+
+        VramBytes {
+            start: address as *mut u16,
+            len,
+        }
+    }
+
+    /// The number of bytes in the region
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the region has no bytes in it
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Reads the byte at `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is outside of the region.
+    #[must_use]
+    pub fn byte(&self, index: usize) -> u8 {
+        self.assert_in_bounds(index, 1);
+
+        let pair = unsafe { self.start.add(index / 2).read_volatile() };
+        if index & 1 != 0 {
+            (pair >> 8) as u8
+        } else {
+            pair as u8
+        }
+    }
+
+    /// Sets the byte at `index`, leaving the other byte in the same halfword unchanged.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is outside of the region.
+    pub fn set_byte(&mut self, index: usize, value: u8) {
+        self.assert_in_bounds(index, 1);
+
+        unsafe {
+            let pair = self.start.add(index / 2);
+            let current = pair.read_volatile();
+            let updated = if index & 1 != 0 {
+                (current & 0x00FF) | (u16::from(value) << 8)
+            } else {
+                (current & 0xFF00) | u16::from(value)
+            };
+            pair.write_volatile(updated);
+        }
+    }
+
+    /// Copies `bytes` into the region starting at `offset`. Only the bytes at either end which
+    /// share a halfword with a byte outside of the run need a read, the rest are written two at a
+    /// time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bytes would go past the end of the region.
+    pub fn write_bytes(&mut self, offset: usize, bytes: &[u8]) {
+        self.assert_in_bounds(offset, bytes.len());
+
+        let mut offset = offset;
+        let mut bytes = bytes;
+
+        if !bytes.is_empty() && offset & 1 != 0 {
+            self.set_byte(offset, bytes[0]);
+            offset += 1;
+            bytes = &bytes[1..];
+        }
+
+        for i in 0..bytes.len() / 2 {
+            let pair = u16::from(bytes[2 * i]) | (u16::from(bytes[2 * i + 1]) << 8);
+            unsafe { self.start.add(offset / 2 + i).write_volatile(pair) };
+        }
+
+        if bytes.len() & 1 != 0 {
+            self.set_byte(offset + bytes.len() - 1, bytes[bytes.len() - 1]);
+        }
+    }
+
+    /// Sets `len` bytes starting at `offset` to `value`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bytes would go past the end of the region.
+    pub fn fill_bytes(&mut self, offset: usize, len: usize, value: u8) {
+        self.assert_in_bounds(offset, len);
+
+        let mut offset = offset;
+        let mut len = len;
+
+        if len > 0 && offset & 1 != 0 {
+            self.set_byte(offset, value);
+            offset += 1;
+            len -= 1;
+        }
+
+        let doubled = u16::from(value) | (u16::from(value) << 8);
+        unsafe { fill_run16(self.start.add(offset / 2), len / 2, doubled) };
+
+        if len & 1 != 0 {
+            self.set_byte(offset + len - 1, value);
+        }
+    }
+
+    fn assert_in_bounds(&self, offset: usize, len: usize) {
+        assert!(
+            offset.checked_add(len).is_some_and(|end| end <= self.len),
+            "{} bytes from offset {} go past the end of the {} bytes of video ram",
+            len,
+            offset,
+            self.len
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // the first page of bitmap mode 4, which the tests claim by entering the mode
+    const PAGE: usize = 0x0600_0000;
+
+    #[test_case]
+    fn setting_a_byte_keeps_the_other_byte_in_the_halfword(gba: &mut crate::Gba) {
+        let _bitmap = gba.display.video.bitmap4();
+        let mut bytes = unsafe { VramBytes::new(PAGE, 16) };
+
+        bytes.write_bytes(0, &[0x11; 16]);
+        bytes.set_byte(4, 0xAB);
+        bytes.set_byte(7, 0xCD);
+
+        let halfwords = PAGE as *const u16;
+        unsafe {
+            assert_eq!(halfwords.add(2).read_volatile(), 0x11AB);
+            assert_eq!(halfwords.add(3).read_volatile(), 0xCD11);
+        }
+        assert_eq!((bytes.byte(3), bytes.byte(4)), (0x11, 0xAB));
+        assert_eq!((bytes.byte(7), bytes.byte(8)), (0xCD, 0x11));
+    }
+
+    #[test_case]
+    fn runs_of_bytes_keep_the_bytes_either_side(gba: &mut crate::Gba) {
+        let _bitmap = gba.display.video.bitmap4();
+        let mut bytes = unsafe { VramBytes::new(PAGE, 32) };
+
+        // every combination of even and odd start and end
+        for (offset, len) in [(2, 6), (3, 6), (2, 7), (3, 7), (5, 1), (5, 0)] {
+            bytes.fill_bytes(0, 32, 0xEE);
+
+            let run: [u8; 7] = [1, 2, 3, 4, 5, 6, 7];
+            bytes.write_bytes(offset, &run[..len]);
+
+            for i in 0..32 {
+                let expected = if (offset..offset + len).contains(&i) {
+                    run[i - offset]
+                } else {
+                    0xEE
+                };
+                assert_eq!(
+                    bytes.byte(i),
+                    expected,
+                    "byte {} after writing {:?}",
+                    i,
+                    (offset, len)
+                );
+            }
+
+            bytes.fill_bytes(offset, len, 0x42);
+            for i in 0..32 {
+                let expected = if (offset..offset + len).contains(&i) {
+                    0x42
+                } else {
+                    0xEE
+                };
+                assert_eq!(
+                    bytes.byte(i),
+                    expected,
+                    "byte {} after filling {:?}",
+                    i,
+                    (offset, len)
+                );
+            }
+        }
+    }
+}
@@ -226,11 +226,7 @@ impl MovableWindow {
     fn commit(&self, id: usize) {
         self.inner.commit(id);
 
-        let left_right =
-            (self.rect.position.x as u16) << 8 | (self.rect.position.x + self.rect.size.x) as u16;
-
-        let top_bottom =
-            (self.rect.position.y as u16) << 8 | (self.rect.position.y + self.rect.size.y) as u16;
+        let (left_right, top_bottom) = window_registers(&self.rect);
         unsafe {
             REG_HORIZONTAL_BASE.add(id).write_volatile(left_right);
             REG_VERTICAL_BASE.add(id).write_volatile(top_bottom);
@@ -247,17 +243,67 @@ impl MovableWindow {
         self
     }
 
-    /// Sets the position of the area that is inside the window.
+    /// Sets the position of the area that is inside the window. Any part of
+    /// the rectangle which is off screen is clipped, so a rectangle which is
+    /// entirely off screen results in an empty window.
     #[inline(always)]
     pub fn set_position(&mut self, rect: &Rect<i32>) -> &mut Self {
-        let new_rect = Rect::new(
-            (
-                rect.position.x.clamp(0, WIDTH) as u8,
-                rect.position.y.clamp(0, HEIGHT) as u8,
-            )
-                .into(),
-            (rect.size.x as u8, rect.size.y as u8).into(),
+        self.set_position_u8(clip_to_screen(rect))
+    }
+}
+
+fn clip_to_screen(rect: &Rect<i32>) -> Rect<u8> {
+    let left = rect.position.x.clamp(0, WIDTH);
+    let top = rect.position.y.clamp(0, HEIGHT);
+    let right = (rect.position.x + rect.size.x).clamp(left, WIDTH);
+    let bottom = (rect.position.y + rect.size.y).clamp(top, HEIGHT);
+
+    Rect::new(
+        (left as u8, top as u8).into(),
+        ((right - left) as u8, (bottom - top) as u8).into(),
+    )
+}
+
+// The right and bottom edges are exclusive. The hardware treats a right or
+// bottom edge which is before the left or top edge as if it were at the edge of
+// the screen, so the far edges are clamped to the screen to prevent a window
+// which goes past the edge of the screen wrapping around.
+fn window_registers(rect: &Rect<u8>) -> (u16, u16) {
+    let left = rect.position.x as u16;
+    let top = rect.position.y as u16;
+    let right = (left + rect.size.x as u16).min(WIDTH as u16);
+    let bottom = (top + rect.size.y as u16).min(HEIGHT as u16);
+
+    (left << 8 | right, top << 8 | bottom)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_case]
+    fn window_registers_are_exclusive_and_clamped(_gba: &mut crate::Gba) {
+        let rect = Rect::new((10, 20).into(), (30, 40).into());
+        assert_eq!(window_registers(&rect), (10 << 8 | 40, 20 << 8 | 60));
+
+        // would wrap past 255 without clamping
+        let rect = Rect::new((200, 150).into(), (100, 100).into());
+        assert_eq!(window_registers(&rect), (200 << 8 | 240, 150 << 8 | 160));
+    }
+
+    #[test_case]
+    fn off_screen_rects_are_clipped(_gba: &mut crate::Gba) {
+        let partially_off = clip_to_screen(&Rect::new((-10, -20).into(), (30, 40).into()));
+        assert_eq!(partially_off, Rect::new((0, 0).into(), (20, 20).into()));
+
+        let past_bottom_right = clip_to_screen(&Rect::new((230, 150).into(), (64, 64).into()));
+        assert_eq!(
+            past_bottom_right,
+            Rect::new((230, 150).into(), (10, 10).into())
         );
-        self.set_position_u8(new_rect)
+
+        let entirely_off = clip_to_screen(&Rect::new((-100, 300).into(), (64, 64).into()));
+        assert_eq!(entirely_off.size, (0, 0).into());
+        assert_eq!(window_registers(&entirely_off), (0, 160 << 8 | 160));
     }
 }
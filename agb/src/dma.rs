@@ -1,4 +1,9 @@
-use crate::memory_mapped::MemoryMapped;
+use core::cell::RefCell;
+use core::marker::PhantomData;
+
+use bare_metal::Mutex;
+
+use crate::{interrupt::free, memory_mapped::MemoryMapped};
 
 const fn dma_source_addr(dma: usize) -> usize {
     0x0400_00b0 + 0x0c * dma
@@ -12,15 +17,765 @@ const fn dma_control_addr(dma: usize) -> usize {
     0x0400_00b8 + 0x0c * dma
 }
 
-const DMA3_SOURCE_ADDR: MemoryMapped<u32> = unsafe { MemoryMapped::new(dma_source_addr(3)) };
-const DMA3_DEST_ADDR: MemoryMapped<u32> = unsafe { MemoryMapped::new(dma_dest_addr(3)) };
-const DMA3_CONTROL: MemoryMapped<u32> = unsafe { MemoryMapped::new(dma_control_addr(3)) };
+// The channel agb uses for copies, and the default for games
+const GENERAL_CHANNEL: usize = 3;
+
+const WORD_TRANSFER: u32 = 1 << 26;
+const DMA_SOURCE_FIXED: u32 = 2 << 23;
+const DMA_START_VBLANK: u32 = 1 << 28;
+const DMA_ENABLE: u32 = 1 << 31;
+
+// DMA3 can transfer up to 0x10000 units at a time, but the other channels only 0x4000
+const fn max_count(channel: usize) -> usize {
+    if channel == 3 {
+        u16::MAX as usize - 1
+    } else {
+        0x4000
+    }
+}
+
+// Starts a transfer on `channel`. Immediate transfers stop the CPU until they have finished. If
+// an asynchronous copy is still waiting to start on the channel, this waits for it first.
+unsafe fn transfer(channel: usize, src: u32, dest: u32, count: usize, control: u32) {
+    assert!(count <= max_count(channel));
+    wait_for(channel);
+
+    let alignment = if control & WORD_TRANSFER != 0 { 4 } else { 2 };
+    check_alignment(src, dest, alignment);
+
+    let source_addr: MemoryMapped<u32> = MemoryMapped::new(dma_source_addr(channel));
+    let dest_addr: MemoryMapped<u32> = MemoryMapped::new(dma_dest_addr(channel));
+    let control_register: MemoryMapped<u32> = MemoryMapped::new(dma_control_addr(channel));
+
+    source_addr.set(src);
+    dest_addr.set(dest);
+
+    control_register.set(count as u32 | control | DMA_ENABLE);
+}
 
 pub(crate) unsafe fn dma_copy16(src: *const u16, dest: *mut u16, count: usize) {
-    assert!(count < u16::MAX as usize);
+    transfer(GENERAL_CHANNEL, src as u32, dest as u32, count, 0);
+}
+
+/// Copies `count` words from `src` to `dest`, both of which must be 4 byte aligned.
+pub(crate) unsafe fn dma_copy32(src: *const u32, dest: *mut u32, count: usize) {
+    transfer(
+        GENERAL_CHANNEL,
+        src as u32,
+        dest as u32,
+        count,
+        WORD_TRANSFER,
+    );
+}
+
+/// Writes `value` to `count` halfwords starting at `dest`.
+pub(crate) unsafe fn dma_fill16(value: u16, dest: *mut u16, count: usize) {
+    fill16_on(GENERAL_CHANNEL, value, dest, count);
+}
+
+/// Writes `value` to `count` words starting at `dest`, which must be 4 byte aligned.
+pub(crate) unsafe fn dma_fill32(value: u32, dest: *mut u32, count: usize) {
+    fill32_on(GENERAL_CHANNEL, value, dest, count);
+}
+
+unsafe fn fill16_on(channel: usize, value: u16, dest: *mut u16, count: usize) {
+    // The CPU is stopped until the transfer has finished, so `value` stays on the stack, which is
+    // in iwram where DMA can read it, for the whole of the transfer.
+    transfer(
+        channel,
+        &value as *const u16 as u32,
+        dest as u32,
+        count,
+        DMA_SOURCE_FIXED,
+    );
+}
+
+unsafe fn fill32_on(channel: usize, value: u32, dest: *mut u32, count: usize) {
+    transfer(
+        channel,
+        &value as *const u32 as u32,
+        dest as u32,
+        count,
+        DMA_SOURCE_FIXED | WORD_TRANSFER,
+    );
+}
+
+/// Copies `src` into `dest` using DMA, which is much faster than copying in a loop. The CPU waits
+/// until the copy is finished. Slices of `u16` are always aligned correctly for DMA.
+///
+/// This uses DMA3, which is the channel agb uses for copies. Use a [`Dma`] to choose the channel.
+///
+/// `dest` can be in video ram or palette ram, which only allow 16 and 32 bit writes, by making a
+/// slice of it with [`core::slice::from_raw_parts_mut`] as long as nothing else is using that
+/// memory at the time.
+///
+/// # Panics
+///
+/// Panics if `src` and `dest` have different lengths, or they are empty or 65535 halfwords or
+/// longer. An empty copy would be treated by the hardware as the longest possible copy.
+pub fn copy16(src: &[u16], dest: &mut [u16]) {
+    check_copy(src.len(), dest.len());
+
+    unsafe { dma_copy16(src.as_ptr(), dest.as_mut_ptr(), src.len()) };
+}
+
+/// Copies `src` into `dest` using DMA a word at a time, which is twice as fast as [`copy16`] for
+/// the same number of bytes. The CPU waits until the copy is finished.
+///
+/// # Panics
+///
+/// Panics if `src` and `dest` have different lengths, or they are empty or 65535 words or
+/// longer.
+pub fn copy32(src: &[u32], dest: &mut [u32]) {
+    check_copy(src.len(), dest.len());
+
+    unsafe { dma_copy32(src.as_ptr(), dest.as_mut_ptr(), src.len()) };
+}
+
+/// Sets every halfword of `dest` to `value` using DMA, for example to clear a screenblock or a
+/// bitmap. Like [`copy16`], `dest` can be in video ram.
+///
+/// # Panics
+///
+/// Panics if `dest` is empty or 65535 halfwords or longer.
+pub fn fill16(value: u16, dest: &mut [u16]) {
+    check_length(dest.len());
+
+    unsafe { dma_fill16(value, dest.as_mut_ptr(), dest.len()) };
+}
+
+/// Sets every word of `dest` to `value` using DMA.
+///
+/// # Panics
+///
+/// Panics if `dest` is empty or 65535 words or longer.
+pub fn fill32(value: u32, dest: &mut [u32]) {
+    check_length(dest.len());
+
+    unsafe { dma_fill32(value, dest.as_mut_ptr(), dest.len()) };
+}
+
+fn check_copy(src_len: usize, dest_len: usize) {
+    assert_eq!(
+        src_len, dest_len,
+        "the source and destination of a DMA copy must be the same length"
+    );
+    check_length(src_len);
+}
+
+fn is_enabled(channel: usize) -> bool {
+    let control: MemoryMapped<u32> = unsafe { MemoryMapped::new(dma_control_addr(channel)) };
+    control.get() & DMA_ENABLE != 0
+}
+
+fn wait_for(channel: usize) {
+    while is_enabled(channel) {}
+}
+
+fn check_length(len: usize) {
+    assert!(len > 0, "a DMA transfer can't be empty");
+}
+
+// DMA ignores the low bits of misaligned addresses, which silently copies the wrong data
+fn check_alignment(src: u32, dest: u32, alignment: u32) {
+    debug_assert!(
+        src & (alignment - 1) == 0,
+        "DMA source {:#010X} isn't aligned to {} bytes",
+        src,
+        alignment
+    );
+    debug_assert!(
+        dest & (alignment - 1) == 0,
+        "DMA destination {:#010X} isn't aligned to {} bytes",
+        dest,
+        alignment
+    );
+}
+
+/// Manages access to the Game Boy Advance's 4 DMA channels.
+#[non_exhaustive]
+pub struct DmaController {}
+
+impl DmaController {
+    pub(crate) const fn new() -> Self {
+        Self {}
+    }
+
+    /// The 4 DMA channels
+    pub fn dma(&mut self) -> Dmas {
+        unsafe { Dmas::new() }
+    }
+}
+
+/// The 4 DMA channels.
+///
+/// Each channel has a priority, with DMA0 the highest and DMA3 the lowest. If more than one
+/// transfer is due at the same time the highest priority one goes first, and a transfer started
+/// on a higher priority channel pauses any lower priority one until it is done.
+///
+/// agb uses the channels as follows, so avoid starting transfers on them while they are in use:
+/// * DMA0 for effects at every hblank, such as [`HBlankDma`]
+/// * DMA1 and DMA2 for the sound [mixer](crate::sound::mixer)
+/// * DMA3 for copies, such as copying tiles into video ram and the free functions in this module
+///
+/// DMA0 can't read from the cartridge, and DMA0 to DMA2 can only transfer 0x4000 halfwords or
+/// words at a time.
+#[non_exhaustive]
+pub struct Dmas {
+    /// The highest priority channel, used for hblank effects
+    pub dma0: Dma,
+    /// Used by the sound mixer for the first sound channel
+    pub dma1: Dma,
+    /// Used by the sound mixer for the second sound channel
+    pub dma2: Dma,
+    /// The lowest priority channel, used for copies
+    pub dma3: Dma,
+}
+
+impl Dmas {
+    pub(crate) unsafe fn new() -> Self {
+        Self {
+            dma0: Dma { channel: 0 },
+            dma1: Dma { channel: 1 },
+            dma2: Dma { channel: 2 },
+            dma3: Dma { channel: 3 },
+        }
+    }
+}
+
+/// Returned when trying to start a transfer on a DMA channel which is already being used, such as
+/// by a repeating transfer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DmaBusy;
+
+/// One of the DMA channels, for copying and filling memory using a particular channel. The CPU
+/// waits until each transfer is finished.
+#[non_exhaustive]
+pub struct Dma {
+    channel: usize,
+}
+
+impl Dma {
+    /// Whether a transfer is set up on this channel, for example the repeating transfers used by
+    /// hblank effects and the sound mixer.
+    #[must_use]
+    pub fn is_busy(&self) -> bool {
+        is_enabled(self.channel)
+    }
+
+    /// Copies `src` into `dest` like [`copy16`], or returns an error without copying anything if
+    /// the channel [is busy](Dma::is_busy).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `src` and `dest` have different lengths, or they are empty or longer than the
+    /// channel can transfer at once.
+    pub fn copy16(&mut self, src: &[u16], dest: &mut [u16]) -> Result<(), DmaBusy> {
+        check_copy(src.len(), dest.len());
+        self.check_not_busy()?;
+
+        unsafe {
+            transfer(
+                self.channel,
+                src.as_ptr() as u32,
+                dest.as_mut_ptr() as u32,
+                src.len(),
+                0,
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Copies `src` into `dest` a word at a time like [`copy32`], or returns an error without
+    /// copying anything if the channel [is busy](Dma::is_busy).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `src` and `dest` have different lengths, or they are empty or longer than the
+    /// channel can transfer at once.
+    pub fn copy32(&mut self, src: &[u32], dest: &mut [u32]) -> Result<(), DmaBusy> {
+        check_copy(src.len(), dest.len());
+        self.check_not_busy()?;
+
+        unsafe {
+            transfer(
+                self.channel,
+                src.as_ptr() as u32,
+                dest.as_mut_ptr() as u32,
+                src.len(),
+                WORD_TRANSFER,
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Sets every halfword of `dest` to `value` like [`fill16`], or returns an error without
+    /// writing anything if the channel [is busy](Dma::is_busy).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `dest` is empty or longer than the channel can transfer at once.
+    pub fn fill16(&mut self, value: u16, dest: &mut [u16]) -> Result<(), DmaBusy> {
+        check_length(dest.len());
+        self.check_not_busy()?;
+
+        unsafe { fill16_on(self.channel, value, dest.as_mut_ptr(), dest.len()) };
+
+        Ok(())
+    }
+
+    /// Sets every word of `dest` to `value` like [`fill32`], or returns an error without writing
+    /// anything if the channel [is busy](Dma::is_busy).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `dest` is empty or longer than the channel can transfer at once.
+    pub fn fill32(&mut self, value: u32, dest: &mut [u32]) -> Result<(), DmaBusy> {
+        check_length(dest.len());
+        self.check_not_busy()?;
+
+        unsafe { fill32_on(self.channel, value, dest.as_mut_ptr(), dest.len()) };
+
+        Ok(())
+    }
+
+    /// Starts copying `src` into `dest` at the start of the next vblank, and returns straight
+    /// away so the CPU can carry on with something else, such as decompressing the next part of a
+    /// level, until then. The CPU is paused while any DMA transfer runs, so this is how to overlap
+    /// a big copy with other work, and it also makes sure copies to video ram happen during
+    /// vblank. Returns an error without copying anything if the channel [is busy](Dma::is_busy).
+    ///
+    /// The returned [`DmaInFlight`] borrows `src` and `dest` until the copy is done. Dropping it
+    /// waits for the copy to finish.
+    ///
+    /// # Safety
+    ///
+    /// The returned [`DmaInFlight`] must be dropped or waited on rather than leaked with
+    /// [`core::mem::forget`], otherwise the copy could happen after `src` or `dest` have been
+    /// reused.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `src` and `dest` have different lengths, or they are empty or longer than the
+    /// channel can transfer at once.
+    pub unsafe fn copy16_async<'a>(
+        &'a mut self,
+        src: &'a [u16],
+        dest: &'a mut [u16],
+    ) -> Result<DmaInFlight<'a>, DmaBusy> {
+        check_copy(src.len(), dest.len());
+        self.check_not_busy()?;
+
+        transfer(
+            self.channel,
+            src.as_ptr() as u32,
+            dest.as_mut_ptr() as u32,
+            src.len(),
+            DMA_START_VBLANK,
+        );
+
+        Ok(DmaInFlight::new(self.channel))
+    }
+
+    /// Starts copying `src` into `dest` a word at a time at the start of the next vblank, like
+    /// [`copy16_async`](Dma::copy16_async).
+    ///
+    /// # Safety
+    ///
+    /// The returned [`DmaInFlight`] must not be leaked, as for
+    /// [`copy16_async`](Dma::copy16_async).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `src` and `dest` have different lengths, or they are empty or longer than the
+    /// channel can transfer at once.
+    pub unsafe fn copy32_async<'a>(
+        &'a mut self,
+        src: &'a [u32],
+        dest: &'a mut [u32],
+    ) -> Result<DmaInFlight<'a>, DmaBusy> {
+        check_copy(src.len(), dest.len());
+        self.check_not_busy()?;
+
+        transfer(
+            self.channel,
+            src.as_ptr() as u32,
+            dest.as_mut_ptr() as u32,
+            src.len(),
+            WORD_TRANSFER | DMA_START_VBLANK,
+        );
+
+        Ok(DmaInFlight::new(self.channel))
+    }
+
+    fn check_not_busy(&self) -> Result<(), DmaBusy> {
+        if self.is_busy() {
+            Err(DmaBusy)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// A copy started by [`Dma::copy16_async`] or [`Dma::copy32_async`] which may not have finished
+/// yet. The source and destination of the copy stay borrowed until this is dropped, which waits
+/// for the copy to finish.
+pub struct DmaInFlight<'a> {
+    channel: usize,
+    _borrows: PhantomData<&'a mut ()>,
+}
+
+impl DmaInFlight<'_> {
+    fn new(channel: usize) -> Self {
+        Self {
+            channel,
+            _borrows: PhantomData,
+        }
+    }
+
+    /// Whether the copy has finished.
+    #[must_use]
+    pub fn is_done(&self) -> bool {
+        !is_enabled(self.channel)
+    }
+
+    /// Waits until the copy has finished.
+    pub fn wait(self) {}
+}
+
+impl Drop for DmaInFlight<'_> {
+    fn drop(&mut self) {
+        wait_for(self.channel);
+    }
+}
+
+const DMA0_SOURCE_ADDR: MemoryMapped<u32> = unsafe { MemoryMapped::new(dma_source_addr(0)) };
+const DMA0_DEST_ADDR: MemoryMapped<u32> = unsafe { MemoryMapped::new(dma_dest_addr(0)) };
+const DMA0_CONTROL: MemoryMapped<u32> = unsafe { MemoryMapped::new(dma_control_addr(0)) };
+
+const DMA_CONTROL_SETTING_FOR_HBLANK: u32 = {
+    let dest_fixed: u32 = 2 << 21; // dest addr control = fixed
+    let repeat: u32 = 1 << 25;
+    let dma_start_timing: u32 = 2 << 28; // hblank timing
+    let enable: u32 = 1 << 31;
+
+    dest_fixed | repeat | dma_start_timing | enable
+};
+
+/// Starts DMA0 copying a single halfword from `src` to `dest` at every hblank, moving on to the
+/// next halfword of `src` each time. This keeps going until [`dma0_stop`] is called, so `src` must
+/// remain valid until then and be long enough for every hblank which happens before that.
+pub(crate) unsafe fn dma0_hblank_repeat16(src: *const u16, dest: *mut u16) {
+    check_alignment(src as u32, dest as u32, 2);
+
+    DMA0_CONTROL.set(0);
+
+    DMA0_SOURCE_ADDR.set(src as u32);
+    DMA0_DEST_ADDR.set(dest as u32);
+
+    DMA0_CONTROL.set(1 | DMA_CONTROL_SETTING_FOR_HBLANK);
+}
+
+pub(crate) fn dma0_stop() {
+    DMA0_CONTROL.set(0);
+}
+
+/// The effects which can use DMA0 to change a register at every hblank
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum HblankDmaUser {
+    ScrollPerLine { background: u8 },
+    BackdropGradient,
+    HBlankDma,
+}
+
+// There is only one DMA channel available for hblank effects, so only one of them can be used at
+// a time. This holds whichever is using it.
+static HBLANK_DMA_USER: Mutex<RefCell<Option<HblankDmaUser>>> = Mutex::new(RefCell::new(None));
+
+// Claims DMA0 for an hblank effect, or returns the effect which is already using it
+fn try_claim_hblank_dma(user: HblankDmaUser) -> Result<(), HblankDmaUser> {
+    free(|cs| {
+        let mut current = HBLANK_DMA_USER.borrow(cs).borrow_mut();
+        match *current {
+            Some(current) => Err(current),
+            None => {
+                *current = Some(user);
+                Ok(())
+            }
+        }
+    })
+}
+
+// Stops DMA0 and lets other effects use it, if it is being used by `user`
+fn release_hblank_dma(user: HblankDmaUser) {
+    free(|cs| {
+        let mut current = HBLANK_DMA_USER.borrow(cs).borrow_mut();
+        if *current == Some(user) {
+            dma0_stop();
+            *current = None;
+        }
+    });
+}
+
+const SCREEN_HEIGHT: usize = 160;
+
+type HBlankTables = [[u16; SCREEN_HEIGHT + 1]; 2];
+
+// Only one HBlankDma can exist at a time, so it can have these to itself. They are in iwram so that
+// the DMA at each hblank reads from them without any wait states.
+#[crate::iwram]
+static mut HBLANK_TABLES: HBlankTables = [[0; SCREEN_HEIGHT + 1]; 2];
+
+/// Writes a different value from a table to a register at the start of every line of the screen,
+/// using DMA0 at each hblank. This is how effects such as wavy backgrounds, where the scroll
+/// position changes on each line, are made.
+///
+/// There is only one DMA channel which can be used like this, so only one `HBlankDma` can exist at
+/// a time, and it can't be used at the same time as the effects in agb which use it such as
+/// [`RegularMap::set_scroll_per_line`](crate::display::tiled::RegularMap::set_scroll_per_line).
+///
+/// The table is double buffered, so the table for the next frame can be set while the current
+/// frame is being drawn. Call [`commit`](HBlankDma::commit) during every vblank to start using
+/// the latest table, and to write the first entry which is needed before the first line is drawn.
+pub struct HBlankDma {
+    user: HblankDmaUser,
+    // The hblank DMA copies one entry after each line is drawn, so entry n + 1 is written after
+    // line n. The final entry is written after the last line and is a copy of the first so that
+    // the next frame starts correctly even if commit happens late.
+    tables: &'static mut HBlankTables,
+    front: usize,
+    back_updated: bool,
+    register: Option<*mut u16>,
+}
+
+impl HBlankDma {
+    /// Claims the hblank DMA channel, or returns `None` if it is already being used.
+    #[must_use]
+    pub fn new() -> Option<Self> {
+        Self::try_claim(HblankDmaUser::HBlankDma).ok()
+    }
+
+    /// Claims the hblank DMA channel for one of agb's own effects.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a different effect is already using it.
+    pub(crate) fn claim(user: HblankDmaUser) -> Self {
+        match Self::try_claim(user) {
+            Ok(dma) => dma,
+            Err(current) => panic!(
+                "the hblank DMA is already being used by {:?}, only one hblank effect can be used at a time",
+                current
+            ),
+        }
+    }
+
+    fn try_claim(user: HblankDmaUser) -> Result<Self, HblankDmaUser> {
+        try_claim_hblank_dma(user)?;
+
+        // Safety: the tables are only used by the HBlankDma which has claimed the channel, and the
+        // DMA from any previous one was stopped when it was released
+        let tables = unsafe { &mut *core::ptr::addr_of_mut!(HBLANK_TABLES) };
+        *tables = [[0; SCREEN_HEIGHT + 1]; 2];
+
+        Ok(HBlankDma {
+            user,
+            tables,
+            front: 0,
+            back_updated: false,
+            register: None,
+        })
+    }
+
+    /// Writes entry `n` of `table` to the 16 bit register at `register` before line `n` of the
+    /// screen is drawn, from the next call to [`commit`](HBlankDma::commit).
+    ///
+    /// # Safety
+    ///
+    /// `register` must be the address of a register, or of memory which can be written at any
+    /// time such as palette ram, and it must be safe to write any value in `table` to it.
+    pub unsafe fn start(&mut self, register: usize, table: &[u16; 160]) {
+        self.register = Some(register as *mut u16);
+        self.set_table(table);
+    }
+
+    /// Changes the table used from the next call to [`commit`](HBlankDma::commit). This can be
+    /// called every frame without tearing.
+    pub fn set_table(&mut self, table: &[u16; 160]) {
+        let back = &mut self.tables[1 - self.front];
+        back[..SCREEN_HEIGHT].copy_from_slice(table);
+        back[SCREEN_HEIGHT] = table[0];
+
+        self.back_updated = true;
+    }
+
+    /// Starts using the latest table. This should be called during every vblank while the table
+    /// is being used, and does nothing if it hasn't been started.
+    pub fn commit(&mut self) {
+        let register = match self.register {
+            Some(register) => register,
+            None => return,
+        };
+
+        if self.back_updated {
+            self.front = 1 - self.front;
+            self.back_updated = false;
+        }
+
+        let table = &self.tables[self.front];
+
+        unsafe {
+            register.write_volatile(table[0]);
+            dma0_hblank_repeat16(table[1..].as_ptr(), register);
+        }
+    }
+
+    /// Stops writing to the register. The channel is kept until this is dropped, so it can be
+    /// started again.
+    pub fn stop(&mut self) {
+        dma0_stop();
+        self.register = None;
+    }
+
+    /// The table which is currently being used
+    #[cfg(test)]
+    pub(crate) fn front_table(&self) -> &[u16; SCREEN_HEIGHT + 1] {
+        &self.tables[self.front]
+    }
+}
+
+impl Drop for HBlankDma {
+    fn drop(&mut self) {
+        release_hblank_dma(self.user);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec;
+
+    #[test_case]
+    fn copies_into_the_heap(_gba: &mut crate::Gba) {
+        let src: alloc::vec::Vec<u16> = (0..1000).collect();
+        let mut dest = vec![0; 1000];
+
+        copy16(&src, &mut dest);
+        assert_eq!(src, dest);
+
+        let src: alloc::vec::Vec<u32> = (0..1000).map(|i| i * 0x0001_0001).collect();
+        let mut dest = vec![0; 1000];
+
+        copy32(&src, &mut dest);
+        assert_eq!(src, dest);
+    }
+
+    #[test_case]
+    fn copies_into_video_ram(gba: &mut crate::Gba) {
+        let (_gfx, _vram) = gba.display.video.tiled0();
+
+        // the last screenblock, which no background is using
+        let screenblock =
+            unsafe { core::slice::from_raw_parts_mut(0x0600_f800 as *mut u16, 32 * 32) };
+
+        let src: alloc::vec::Vec<u16> = (0..32 * 32).collect();
+        copy16(&src, screenblock);
+        assert_eq!(screenblock, &src[..]);
+
+        let zeroes = vec![0; 32 * 32];
+        copy16(&zeroes, screenblock);
+        assert!(screenblock.iter().all(|&entry| entry == 0));
+    }
+
+    #[test_case]
+    fn fills_only_the_destination(_gba: &mut crate::Gba) {
+        let mut dest = vec![0u16; 100];
+        fill16(0x1234, &mut dest[1..99]);
+
+        assert_eq!(dest[0], 0);
+        assert!(dest[1..99].iter().all(|&value| value == 0x1234));
+        assert_eq!(dest[99], 0);
+
+        let mut dest = vec![0u32; 100];
+        fill32(0x1234_5678, &mut dest[..50]);
+
+        assert!(dest[..50].iter().all(|&value| value == 0x1234_5678));
+        assert!(dest[50..].iter().all(|&value| value == 0));
+    }
+
+    #[test_case]
+    fn hblank_dma_can_only_be_used_once_at_a_time(_gba: &mut crate::Gba) {
+        let dma0_control: MemoryMapped<u16> = unsafe { MemoryMapped::new(0x0400_00BA) };
+
+        let mut dma = HBlankDma::new().unwrap();
+        assert!(HBlankDma::new().is_none());
+
+        let mut table = [0; 160];
+        for (line, entry) in table.iter_mut().enumerate() {
+            *entry = line as u16;
+        }
+
+        // the first scroll register, which nothing else is using
+        unsafe { dma.start(0x0400_0010, &table) };
+        dma.commit();
+
+        assert_ne!(dma0_control.get() & (1 << 15), 0);
+        assert_eq!(dma.front_table()[..160], table);
+        assert_eq!(dma.front_table()[160], 0);
+        assert!((0x0300_0000..0x0300_8000).contains(&(dma.front_table().as_ptr() as usize)));
+
+        dma.stop();
+        assert_eq!(dma0_control.get() & (1 << 15), 0);
+        assert!(HBlankDma::new().is_none());
+
+        drop(dma);
+        assert!(HBlankDma::new().is_some());
+    }
+
+    #[test_case]
+    fn asynchronous_copies_finish_at_vblank(gba: &mut crate::Gba) {
+        let mut dmas = gba.dma.dma();
+
+        let src: alloc::vec::Vec<u32> = (0..1000).collect();
+        let mut dest = vec![0; 1000];
+
+        let copy = unsafe { dmas.dma3.copy32_async(&src, &mut dest) }.unwrap();
+        copy.wait();
+        assert_eq!(src, dest);
+
+        let src: alloc::vec::Vec<u16> = (0..1000).collect();
+        let mut dest = vec![0; 1000];
+
+        let copy = unsafe { dmas.dma1.copy16_async(&src, &mut dest) }.unwrap();
+        while !copy.is_done() {}
+        drop(copy);
+        assert_eq!(src, dest);
+    }
+
+    #[test_case]
+    fn transfers_on_busy_channels_are_refused(gba: &mut crate::Gba) {
+        let mut dmas = gba.dma.dma();
+
+        let src: alloc::vec::Vec<u16> = (0..100).collect();
+        let mut dest = vec![0; 100];
+
+        dmas.dma0.copy16(&src, &mut dest).unwrap();
+        assert_eq!(src, dest);
+
+        dmas.dma3.fill16(5, &mut dest).unwrap();
+        assert!(dest.iter().all(|&value| value == 5));
+
+        let mut hblank = HBlankDma::new().unwrap();
+        unsafe { hblank.start(0x0400_0010, &[0; 160]) };
+        hblank.commit();
 
-    DMA3_SOURCE_ADDR.set(src as u32);
-    DMA3_DEST_ADDR.set(dest as u32);
+        assert!(dmas.dma0.is_busy());
+        assert_eq!(dmas.dma0.fill16(7, &mut dest), Err(DmaBusy));
+        assert!(dest.iter().all(|&value| value == 5));
 
-    DMA3_CONTROL.set(count as u32 | (1 << 31));
+        drop(hblank);
+        assert!(!dmas.dma0.is_busy());
+    }
 }
@@ -140,17 +140,20 @@ impl<K, V, ALLOCATOR: ClonableAllocator> HashMap<K, V, ALLOCATOR> {
     /// internal size may be larger as it must be a power of 2
     #[must_use]
     pub fn with_capacity_in(capacity: usize, alloc: ALLOCATOR) -> Self {
-        for i in 0..32 {
-            let attempted_size = 1usize << i;
-            if number_before_resize(attempted_size) > capacity {
-                return Self::with_size_in(attempted_size, alloc);
-            }
-        }
+        Self::with_size_in(size_for_capacity(capacity), alloc)
+    }
 
-        panic!(
-            "Failed to come up with a size which satisfies capacity {}",
-            capacity
-        );
+    /// Makes sure that at least `additional` more elements can be inserted without resizing, so
+    /// that the time to resize is spent now rather than during a later insert.
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = self
+            .len()
+            .checked_add(additional)
+            .expect("Capacity overflow when reserving space in a hash map");
+
+        if needed > self.capacity() {
+            self.resize(size_for_capacity(needed));
+        }
     }
 
     /// Returns the number of elements in the map
@@ -645,6 +648,21 @@ const fn number_before_resize(capacity: usize) -> usize {
     capacity * 85 / 100
 }
 
+// The smallest backing size which can hold `capacity` elements without resizing
+fn size_for_capacity(capacity: usize) -> usize {
+    for i in 0..32 {
+        let attempted_size = 1usize << i;
+        if number_before_resize(attempted_size) > capacity {
+            return attempted_size;
+        }
+    }
+
+    panic!(
+        "Failed to come up with a size which satisfies capacity {}",
+        capacity
+    );
+}
+
 struct NodeStorage<K, V, ALLOCATOR: Allocator = Global> {
     nodes: Vec<Node<K, V>, ALLOCATOR>,
     max_distance_to_initial_bucket: i32,
@@ -945,6 +963,29 @@ mod test {
         }
     }
 
+    #[test_case]
+    fn reserving_avoids_resizing_later(_gba: &mut Gba) {
+        let mut map = HashMap::new();
+        map.insert(-1, -1);
+
+        map.reserve(100);
+        assert!(map.capacity() >= 101);
+
+        let nodes = map.nodes.nodes.as_ptr();
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+
+        assert_eq!(map.nodes.nodes.as_ptr(), nodes, "the map was resized");
+        assert_eq!(map.len(), 101);
+        assert_eq!(map.get(&50), Some(&50));
+
+        // reserving less than there is space for already does nothing
+        let capacity = map.capacity();
+        map.reserve(capacity - map.len());
+        assert_eq!(map.nodes.nodes.as_ptr(), nodes);
+    }
+
     #[test_case]
     fn can_get_the_length(_gba: &mut Gba) {
         let mut map = HashMap::new();
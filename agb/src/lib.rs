@@ -144,20 +144,70 @@ macro_rules! include_font {
 /// ```
 pub use agb_macros::entry;
 
+/// Places a static or a function in IWRAM, the GBA's small but fast 32 bit internal work ram.
+///
+/// Functions placed here are compiled as ARM code and are never inlined, which makes this the
+/// right place for hot code such as interrupt handlers or the inner loops of a mixer. Statics here
+/// can be read and written without any wait states, and are copied in from the rom on start up.
+/// There is only 32kB of IWRAM, which is shared with the stack and the
+/// [`InternalAllocator`](crate::InternalAllocator), so keep what you put here small. Statics keep
+/// the alignment of their type.
+///
+/// # Examples
+/// ```no_run,rust
+/// #![no_std]
+/// #![no_main]
+///
+/// #[agb::iwram]
+/// static mut SCROLL_OFFSETS: [u16; 160] = [0; 160];
+///
+/// #[agb::iwram]
+/// fn mix(buffer: &mut [i16], sample: &[i8]) {
+///     for (out, &sample) in buffer.iter_mut().zip(sample) {
+///         *out = out.saturating_add(sample as i16);
+///     }
+/// }
+/// # #[agb::entry]
+/// # fn main(_gba: agb::Gba) -> ! { loop {} }
+/// ```
+pub use agb_macros::iwram;
+
+/// Places a static in EWRAM, the GBA's 256kB of external work ram.
+///
+/// Zero initialised statics would otherwise end up in IWRAM, so large buffers and lookup tables
+/// which are written to at run time should be put here instead. The initial value is copied in
+/// from the rom on start up, and the static keeps the alignment of its type.
+///
+/// # Examples
+/// ```no_run,rust
+/// #![no_std]
+/// #![no_main]
+///
+/// #[agb::ewram]
+/// static mut LEVEL_COLLISION: [u8; 64 * 1024] = [0; 64 * 1024];
+/// # #[agb::entry]
+/// # fn main(_gba: agb::Gba) -> ! { loop {} }
+/// ```
+pub use agb_macros::ewram;
+
 pub use agb_sound_converter::include_wav;
 
 extern crate alloc;
-mod agb_alloc;
+/// The heap allocators, and how much of each is in use.
+pub mod agb_alloc;
 
 mod agbabi;
 mod bitarray;
 /// Implements everything relating to things that are displayed on screen.
 pub mod display;
-mod dma;
+/// Copying memory quickly using the DMA hardware.
+pub mod dma;
 /// Button inputs to the system.
 pub mod input;
 /// Interacting with the GBA interrupts
 pub mod interrupt;
+/// Fast copying and filling of memory a word at a time.
+pub mod mem;
 mod memory_mapped;
 /// Implements logging to the mgba emulator.
 pub mod mgba;
@@ -222,6 +272,8 @@ pub struct Gba {
     pub mixer: sound::mixer::MixerController,
     /// Manages access to the Game Boy Advance's 4 timers.
     pub timers: timer::TimerController,
+    /// Manages access to the Game Boy Advance's 4 DMA channels.
+    pub dma: dma::DmaController,
 }
 
 impl Gba {
@@ -237,6 +289,7 @@ impl Gba {
             sound: sound::dmg::Sound::new(),
             mixer: sound::mixer::MixerController::new(),
             timers: timer::TimerController::new(),
+            dma: dma::DmaController::new(),
         }
     }
 }
@@ -463,6 +516,72 @@ mod test {
             assert_eq!(c, u32::MAX, "expected content to be {}", u32::MAX);
         }
     }
+
+    #[crate::iwram]
+    static mut IWRAM_ATTRIBUTE: [u32; 4] = [1, 2, 3, 4];
+    #[crate::iwram]
+    static mut IWRAM_ZEROED: [u16; 32] = [0; 32];
+    #[crate::ewram]
+    static mut EWRAM_ATTRIBUTE: [u32; 4] = [5, 6, 7, 8];
+    // without the attribute, this would be in the bss which is in iwram
+    #[crate::ewram]
+    static mut EWRAM_ZEROED: [u8; 1024] = [0; 1024];
+
+    #[crate::iwram]
+    fn add_in_iwram(a: u32, b: u32) -> u32 {
+        a.wrapping_add(b)
+    }
+
+    const IWRAM: core::ops::Range<usize> = 0x0300_0000..0x0300_8000;
+    const EWRAM: core::ops::Range<usize> = 0x0200_0000..0x0204_0000;
+
+    #[test_case]
+    fn attributes_place_statics_in_the_right_ram(_gba: &mut Gba) {
+        let placements = unsafe {
+            [
+                (IWRAM_ATTRIBUTE.as_ptr() as usize, IWRAM),
+                (IWRAM_ZEROED.as_ptr() as usize, IWRAM),
+                (EWRAM_ATTRIBUTE.as_ptr() as usize, EWRAM),
+                (EWRAM_ZEROED.as_ptr() as usize, EWRAM),
+            ]
+        };
+
+        for (address, range) in placements {
+            assert!(
+                range.contains(&address),
+                "expected static to be between {:#010X} and {:#010X}, but was at {:#010X}",
+                range.start,
+                range.end,
+                address
+            );
+        }
+
+        unsafe {
+            assert_eq!(IWRAM_ATTRIBUTE, [1, 2, 3, 4]);
+            assert!(IWRAM_ZEROED.iter().all(|&x| x == 0));
+            assert_eq!(EWRAM_ATTRIBUTE, [5, 6, 7, 8]);
+            assert!(EWRAM_ZEROED.iter().all(|&x| x == 0));
+
+            IWRAM_ZEROED[3] = 42;
+            EWRAM_ZEROED[1000] = 42;
+            assert_eq!((IWRAM_ZEROED[3], EWRAM_ZEROED[1000]), (42, 42));
+        }
+    }
+
+    #[test_case]
+    fn iwram_attribute_places_functions_in_iwram(_gba: &mut Gba) {
+        let add: fn(u32, u32) -> u32 = add_in_iwram;
+        let address = add as usize;
+
+        assert!(
+            IWRAM.contains(&address),
+            "expected function to be in iwram, but was at {:#010X}",
+            address
+        );
+        // ARM functions have the bottom bit of their address clear
+        assert_eq!(address & 1, 0, "expected the function to be ARM code");
+        assert_eq!(add(u32::MAX, 3), 2);
+    }
 }
 
 #[inline(never)]
@@ -0,0 +1,100 @@
+//! The routines here are in IWRAM and copy 8 words at a time, so are faster than a loop in Rust,
+//! and for short copies such as a single tile are faster than DMA since there is no transfer to
+//! set up. They also work while the DMA channels are busy.
+
+extern "C" {
+    fn agb_rs__copy32(dest: *mut u32, src: *const u32, count: usize);
+    fn agb_rs__set32(dest: *mut u32, value: u32, count: usize);
+}
+
+/// Copies `src` into `dest` a word at a time. Like [`dma::copy32`](crate::dma::copy32) `dest`
+/// can be in video ram, but this is faster for short copies.
+///
+/// # Panics
+///
+/// Panics if `src` and `dest` have different lengths.
+pub fn copy32(src: &[u32], dest: &mut [u32]) {
+    assert_eq!(
+        src.len(),
+        dest.len(),
+        "the source and destination of a copy must be the same length"
+    );
+
+    unsafe { copy_words(src.as_ptr(), dest.as_mut_ptr(), src.len()) };
+}
+
+/// Sets every word of `dest` to `value`.
+pub fn set32(value: u32, dest: &mut [u32]) {
+    unsafe { agb_rs__set32(dest.as_mut_ptr(), value, dest.len()) };
+}
+
+/// Copies `count` words from `src` to `dest`, both of which must be 4 byte aligned.
+pub(crate) unsafe fn copy_words(src: *const u32, dest: *mut u32, count: usize) {
+    debug_assert!(
+        (src as usize | dest as usize) & 0b11 == 0,
+        "word copies must be between 4 byte aligned addresses"
+    );
+
+    agb_rs__copy32(dest, src, count);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::timer::Divider;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    #[test_case]
+    fn copies_and_sets_any_number_of_words(_gba: &mut crate::Gba) {
+        let src: Vec<u32> = (0..40).map(|i| i * 0x0101_0101).collect();
+
+        // both the 8 word blocks and the words after them
+        for len in [0, 1, 7, 8, 9, 16, 35] {
+            let mut dest = vec![0xffff_ffff; 40];
+            copy32(&src[..len], &mut dest[..len]);
+
+            assert_eq!(&dest[..len], &src[..len]);
+            assert!(dest[len..].iter().all(|&word| word == 0xffff_ffff));
+
+            set32(0x1234_5678, &mut dest[..len]);
+            assert!(dest[..len].iter().all(|&word| word == 0x1234_5678));
+            assert!(dest[len..].iter().all(|&word| word == 0xffff_ffff));
+        }
+    }
+
+    #[test_case]
+    fn copying_is_faster_than_a_loop(gba: &mut crate::Gba) {
+        let src: Vec<u32> = (0..256).collect();
+        let mut dest = vec![0; 256];
+
+        let mut timers = gba.timers.timers();
+        let timer = &mut timers.timer2;
+        timer
+            .set_divider(Divider::Divider1)
+            .set_overflow_amount(0xFFFF)
+            .set_enabled(true);
+
+        let start = timer.value();
+        for (dest, src) in dest.iter_mut().zip(&src) {
+            unsafe { (dest as *mut u32).write_volatile(*src) };
+        }
+        let loop_cycles = timer.value().wrapping_sub(start);
+
+        dest.fill(0);
+
+        let start = timer.value();
+        copy32(&src, &mut dest);
+        let copy_cycles = timer.value().wrapping_sub(start);
+
+        timer.set_enabled(false);
+
+        assert_eq!(src, dest);
+        assert!(
+            copy_cycles < loop_cycles,
+            "copying 256 words took {} cycles, but the loop took {}",
+            copy_cycles,
+            loop_cycles
+        );
+    }
+}
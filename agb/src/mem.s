@@ -0,0 +1,65 @@
+.include "src/asm_include.s"
+
+@ r0: destination, r1: source, r2: number of words. Both addresses must be word aligned.
+agb_arm_func agb_rs__copy32
+    push {r4-r9}
+
+    @ copy 8 words at a time
+    subs r2, r2, #8
+    blt .Lcopy32_remaining
+
+.Lcopy32_blocks:
+    ldmia r1!, {r3-r9, r12}
+    stmia r0!, {r3-r9, r12}
+    subs r2, r2, #8
+    bge .Lcopy32_blocks
+
+.Lcopy32_remaining:
+    adds r2, r2, #8
+    beq .Lcopy32_done
+
+.Lcopy32_words:
+    ldr r3, [r1], #4
+    str r3, [r0], #4
+    subs r2, r2, #1
+    bne .Lcopy32_words
+
+.Lcopy32_done:
+    pop {r4-r9}
+    bx lr
+agb_arm_end agb_rs__copy32
+
+@ r0: destination, r1: value, r2: number of words. The destination must be word aligned.
+agb_arm_func agb_rs__set32
+    push {r4-r9}
+
+    mov r3, r1
+    mov r4, r1
+    mov r5, r1
+    mov r6, r1
+    mov r7, r1
+    mov r8, r1
+    mov r9, r1
+
+    @ write 8 words at a time
+    subs r2, r2, #8
+    blt .Lset32_remaining
+
+.Lset32_blocks:
+    stmia r0!, {r1, r3-r9}
+    subs r2, r2, #8
+    bge .Lset32_blocks
+
+.Lset32_remaining:
+    adds r2, r2, #8
+    beq .Lset32_done
+
+.Lset32_words:
+    str r1, [r0], #4
+    subs r2, r2, #1
+    bne .Lset32_words
+
+.Lset32_done:
+    pop {r4-r9}
+    bx lr
+agb_arm_end agb_rs__set32
@@ -1,4 +1,6 @@
-use core::ops;
+use core::{mem, ops};
+
+use crate::dma::{dma_copy16, dma_copy32};
 
 pub struct MemoryMapped<T> {
     address: *mut T,
@@ -18,6 +20,10 @@ impl<T> MemoryMapped<T> {
     pub fn set(&self, val: T) {
         unsafe { self.address.write_volatile(val) }
     }
+
+    pub fn as_ptr(&self) -> *mut T {
+        self.address
+    }
 }
 
 impl<T> MemoryMapped<T>
@@ -78,6 +84,70 @@ impl<T, const N: usize> MemoryMapped1DArray<T, N> {
     }
 }
 
+#[allow(dead_code)]
+impl<T: Copy, const N: usize> MemoryMapped1DArray<T, N> {
+    /// Copies `values` into the array starting at element `offset`. Panics if this would go
+    /// past the end of the array.
+    pub fn write_slice(&self, offset: usize, values: &[T]) {
+        Self::check_bounds(offset, values.len());
+        unsafe { copy_volatile(values.as_ptr(), self.as_ptr().add(offset), values.len()) }
+    }
+
+    /// Fills `values` from the array starting at element `offset`. Panics if this would go past
+    /// the end of the array.
+    pub fn read_slice(&self, offset: usize, values: &mut [T]) {
+        Self::check_bounds(offset, values.len());
+        unsafe { copy_volatile(self.as_ptr().add(offset), values.as_mut_ptr(), values.len()) }
+    }
+
+    fn check_bounds(offset: usize, len: usize) {
+        assert!(
+            offset.checked_add(len).is_some_and(|end| end <= N),
+            "{} elements from offset {} go past the end of the array of length {}",
+            len,
+            offset,
+            N
+        );
+    }
+}
+
+// Copies which are at least this many bytes are done with DMA
+const DMA_THRESHOLD: usize = 64;
+
+/// Copies `count` elements from `src` to `dest`, where either may be in video or palette ram.
+/// Those can't be written a byte at a time, so this copies in the biggest units which the
+/// alignment of both pointers allows, using DMA for longer copies. The loops for shorter copies
+/// run from iwram.
+#[crate::iwram]
+unsafe fn copy_volatile<T: Copy>(src: *const T, dest: *mut T, count: usize) {
+    let bytes = count * mem::size_of::<T>();
+    let alignment = src as usize | dest as usize | bytes;
+
+    if alignment & 0b11 == 0 {
+        if bytes >= DMA_THRESHOLD {
+            dma_copy32(src.cast(), dest.cast(), bytes / 4);
+        } else {
+            let (src, dest) = (src.cast::<u32>(), dest.cast::<u32>());
+            for i in 0..bytes / 4 {
+                dest.add(i).write_volatile(src.add(i).read_volatile());
+            }
+        }
+    } else if alignment & 0b1 == 0 {
+        if bytes >= DMA_THRESHOLD {
+            dma_copy16(src.cast(), dest.cast(), bytes / 2);
+        } else {
+            let (src, dest) = (src.cast::<u16>(), dest.cast::<u16>());
+            for i in 0..bytes / 2 {
+                dest.add(i).write_volatile(src.add(i).read_volatile());
+            }
+        }
+    } else {
+        for i in 0..count {
+            dest.add(i).write_volatile(src.add(i).read_volatile());
+        }
+    }
+}
+
 pub struct MemoryMapped2DArray<T, const X: usize, const Y: usize> {
     array: *mut [[T; X]; Y],
 }
@@ -94,4 +164,38 @@ impl<T, const X: usize, const Y: usize> MemoryMapped2DArray<T, X, Y> {
     pub fn set(&self, x: usize, y: usize, val: T) {
         unsafe { (&mut (*self.array)[y][x] as *mut T).write_volatile(val) }
     }
+
+    pub fn as_ptr(&self) -> *mut T {
+        self.array.cast()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_case]
+    fn slices_can_be_written_and_read_back(_gba: &mut crate::Gba) {
+        let palette: MemoryMapped1DArray<u16, 256> =
+            unsafe { MemoryMapped1DArray::new(0x0500_0000) };
+
+        let mut previous = [0; 256];
+        palette.read_slice(0, &mut previous);
+
+        // short and long copies, both aligned and unaligned to words
+        for &(offset, len) in &[(4, 3), (5, 2), (16, 100), (17, 100)] {
+            let colours: alloc::vec::Vec<u16> = (0..len).map(|i| 0x1000 + i).collect();
+            palette.write_slice(offset, &colours);
+
+            let mut read = alloc::vec![0; len as usize];
+            palette.read_slice(offset, &mut read);
+            assert_eq!(read, colours);
+            assert_eq!(
+                palette.get(offset + len as usize),
+                previous[offset + len as usize]
+            );
+        }
+
+        palette.write_slice(0, &previous);
+    }
 }
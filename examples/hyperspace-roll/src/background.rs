@@ -42,12 +42,11 @@ fn create_background_map(map: &mut RegularMap, vram: &mut VRamManager, stars_til
         for y in 0..32u16 {
             let blank = rng::gen().rem_euclid(32) < 30;
 
-            let tile_id = if blank {
-                (1 << 10) - 1
+            let tile_setting = if blank {
+                TileSetting::BLANK
             } else {
-                rng::gen().rem_euclid(64) as u16
+                TileSetting::new(rng::gen().rem_euclid(64) as u16, false, false, 0)
             };
-            let tile_setting = TileSetting::new(tile_id, false, false, 0);
 
             map.set_tile(vram, (x, y).into(), stars_tileset, tile_setting);
         }